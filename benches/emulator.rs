@@ -0,0 +1,92 @@
+//! `cargo bench` compares running instructions one at a time with running them in batches,
+//! and times the other hot paths of a frame.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use chip_8::assembler::assemble;
+use chip_8::batch::NullInput;
+use chip_8::{Display, DynEmulator, Emulator, FramebufferDisplay, Opcode, Quirks};
+
+/// Enough instructions for a frame of a turbo mode.
+const CYCLES: u32 = 100_000;
+/// Instructions between timer ticks, a frame at 60000Hz.
+const CYCLES_PER_TICK: u32 = 1000;
+
+fn pong() -> Emulator<FramebufferDisplay> {
+    with_pong(FramebufferDisplay::default())
+}
+
+fn with_pong<D: Display>(display: D) -> Emulator<D> {
+    let rom = assemble(include_str!("../tests/roms/pong.asm")).expect("Pong assembles");
+
+    Emulator::new(display, rom, Quirks::default()).unwrap()
+}
+
+fn run_cycles<D: Display>(mut emulator: Emulator<D>) -> Emulator<D> {
+    for _ in 0..CYCLES / CYCLES_PER_TICK {
+        emulator.run_cycles(CYCLES_PER_TICK, &NullInput).unwrap();
+        emulator.tick_timers();
+    }
+
+    emulator
+}
+
+fn cycles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cycles");
+    group.throughput(Throughput::Elements(u64::from(CYCLES)));
+    group.bench_function("cycle", |b| {
+        b.iter_batched(
+            pong,
+            |mut emulator| {
+                for _ in 0..CYCLES / CYCLES_PER_TICK {
+                    for _ in 0..CYCLES_PER_TICK {
+                        emulator.cycle(&NullInput).unwrap();
+                    }
+                    emulator.tick_timers();
+                }
+
+                emulator
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("run_cycles", |b| {
+        b.iter_batched(pong, run_cycles, BatchSize::LargeInput)
+    });
+    // The same with the display behind a trait object
+    group.bench_function("run_cycles dyn", |b| {
+        b.iter_batched(
+            || -> DynEmulator { with_pong(Box::new(FramebufferDisplay::default())) },
+            run_cycles,
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(0x10000));
+    group.bench_function("every opcode", |b| {
+        b.iter(|| {
+            for opcode in 0..=u16::MAX {
+                black_box(Opcode::decode(black_box(opcode), 0));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn frame(c: &mut Criterion) {
+    c.bench_function("run_frame", |b| {
+        let mut emulator = pong();
+        b.iter(|| emulator.run_frame(&NullInput).unwrap())
+    });
+    c.bench_function("screenshot png", |b| {
+        let mut emulator = pong();
+        emulator.run_cycles(CYCLES, &NullInput).unwrap();
+        b.iter(|| emulator.screenshot().scaled(10).to_png())
+    });
+}
+
+criterion_group!(benches, cycles, decode, frame);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/chip-8/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An emulator, opaque to C.
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create an emulator running the `length` bytes of ROM at `rom`. Returns null if the ROM
+// doesn't fit in memory. The emulator must be freed with [`chip8_free`].
+//
+// # Safety
+//
+// `rom` must point to `length` readable bytes, it's only read during the call.
+Chip8 *chip8_new(const uint8_t *rom, uintptr_t length);
+
+// Free an emulator from [`chip8_new`], does nothing for null.
+//
+// # Safety
+//
+// `chip8` must be null or from [`chip8_new`] and not freed already.
+void chip8_free(Chip8 *chip8);
+
+// Execute a single instruction. Returns 0, or -1 if the ROM did something invalid, after
+// which the emulator shouldn't be run any further.
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed.
+int chip8_cycle(Chip8 *chip8);
+
+// Tick the delay and sound timers, 60 times a second.
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed.
+void chip8_tick_timers(Chip8 *chip8);
+
+// Run 1/60th of a second worth of instructions and tick the timers. Returns 1 if the display
+// changed, 0 if it didn't, or -1 like [`chip8_cycle`].
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed.
+int chip8_run_frame(Chip8 *chip8);
+
+// The display as `width * height` XRGB pixels, row by row, with the size written to `width`
+// and `height` unless they're null. The pixels stay valid until the next call to a function
+// taking the emulator.
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed, `width` and `height` must be null or
+// writable.
+const uint32_t *chip8_framebuffer(Chip8 *chip8, uintptr_t *width, uintptr_t *height);
+
+// Press `key`, 0 to F, if `pressed` is non-zero, otherwise release it. Other keys are
+// ignored.
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed.
+void chip8_key_event(Chip8 *chip8, uint8_t key, int pressed);
+
+// 1 if the tone should be playing, otherwise 0.
+//
+// # Safety
+//
+// `chip8` must be from [`chip8_new`] and not freed.
+int chip8_is_sound_active(const Chip8 *chip8);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // CHIP8_H
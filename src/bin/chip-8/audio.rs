@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chip_8::{AudioBuffer, Sound};
+use rodio::{Sink, Source};
+
+const SAMPLE_RATE: u32 = 44_100;
+const FREQUENCY: u32 = 440;
+const VOLUME: f32 = 0.2;
+
+/// A square wave at [`FREQUENCY`] that never ends, or the XO-CHIP audio pattern in a loop once
+/// a ROM has loaded one.
+struct Tone {
+    sample: u32,
+    buffer: Arc<Mutex<Option<AudioBuffer>>>,
+    /// Where in the pattern playback is, in samples of the pattern.
+    position: f64,
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let is_high = match *self.buffer.lock().unwrap() {
+            Some(buffer) => {
+                self.position += buffer.playback_rate() / f64::from(SAMPLE_RATE);
+                self.position %= 128.0;
+
+                buffer.sample(self.position as usize)
+            }
+            None => {
+                self.sample = (self.sample + 1) % SAMPLE_RATE;
+
+                (self.sample * FREQUENCY * 2 / SAMPLE_RATE).is_multiple_of(2)
+            }
+        };
+
+        Some(if is_high { 1.0 } else { -1.0 })
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Beeps on the default output device by pausing and resuming an endless tone.
+pub struct Beeper {
+    sink: Sink,
+    buffer: Arc<Mutex<Option<AudioBuffer>>>,
+}
+
+impl Beeper {
+    pub fn new() -> Result<Self, &'static str> {
+        let device = rodio::default_output_device().ok_or("No audio output device")?;
+        let sink = Sink::new(&device);
+        sink.set_volume(VOLUME);
+        let buffer = Arc::new(Mutex::new(None));
+        sink.append(Tone {
+            sample: 0,
+            buffer: Arc::clone(&buffer),
+            position: 0.0,
+        });
+        sink.pause();
+
+        Ok(Self { sink, buffer })
+    }
+}
+
+impl Sound for Beeper {
+    fn beep_active(&mut self, active: bool) {
+        if active {
+            self.sink.play();
+        } else {
+            self.sink.pause();
+        }
+    }
+
+    fn set_audio_buffer(&mut self, buffer: Option<AudioBuffer>) {
+        *self.buffer.lock().unwrap() = buffer;
+    }
+}
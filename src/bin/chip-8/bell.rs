@@ -0,0 +1,66 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chip_8::Sound;
+
+/// How the frontend lets the sound timer be noticed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bell {
+    /// Beep on the default output device, flashing instead when there's none.
+    Audio,
+    /// Invert the window while the sound timer is active.
+    Visual,
+    Both,
+    Off,
+}
+
+impl Bell {
+    pub fn is_audible(self) -> bool {
+        matches!(self, Bell::Audio | Bell::Both)
+    }
+
+    pub fn is_visual(self) -> bool {
+        matches!(self, Bell::Visual | Bell::Both)
+    }
+}
+
+impl FromStr for Bell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "audio" => Ok(Bell::Audio),
+            "visual" => Ok(Bell::Visual),
+            "both" => Ok(Bell::Both),
+            "off" => Ok(Bell::Off),
+            _ => Err(format!(
+                "Unknown bell `{}`, expected audio, visual, both, or off",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether the window should be flashing, set by the emulation thread and read by the UI
+/// thread.
+#[derive(Debug, Clone, Default)]
+pub struct VisualBell {
+    active: Arc<AtomicBool>,
+}
+
+impl VisualBell {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+impl Sound for VisualBell {
+    fn beep_active(&mut self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,87 @@
+//! A second window that disassembles the code around the PC as the ROM runs.
+use chip_8::disassembler::Instruction;
+use chip_8::overlay::{self, Line};
+use minifb::{Scale, Window, WindowOptions};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+/// Instructions shown before the one at the PC, so jumps back are easy to follow.
+const CONTEXT: usize = 4;
+
+/// Shows the instructions around the PC in the memory last passed to [`CodeWindow::show`], with
+/// the one at the PC highlighted.
+///
+/// Code is decoded from `CONTEXT` words before the PC, the instructions before the PC can be
+/// off when the ROM mixes in data or 4 byte instructions.
+pub struct CodeWindow {
+    window: Window,
+    memory: Vec<u8>,
+    pc: u16,
+}
+
+impl CodeWindow {
+    pub fn new() -> minifb::Result<Self> {
+        let opts = WindowOptions {
+            scale: Scale::X2,
+            ..WindowOptions::default()
+        };
+
+        Ok(Self {
+            window: Window::new("Code", WIDTH, HEIGHT, opts)?,
+            memory: Vec::new(),
+            pc: 0,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn is_active(&mut self) -> bool {
+        self.window.is_active()
+    }
+
+    pub fn show(&mut self, memory: Vec<u8>, pc: u16) {
+        self.memory = memory;
+        self.pc = pc;
+    }
+
+    pub fn update(&mut self) -> minifb::Result<()> {
+        let mut pixels = vec![0; WIDTH * HEIGHT];
+        overlay::draw_lines(&mut pixels, WIDTH, HEIGHT, &self.lines());
+
+        self.window.update_with_buffer(&pixels, WIDTH, HEIGHT)
+    }
+
+    /// An instruction per line, e.g. `0200  LD I, 0x22A`.
+    fn lines(&self) -> Vec<Line> {
+        let (_, rows) = overlay::text_capacity(WIDTH, HEIGHT);
+        let pc = self.pc as usize;
+        let word = |address: usize| {
+            self.memory
+                .get(address..address + 2)
+                .map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+        };
+
+        let mut lines = Vec::with_capacity(rows);
+        let mut address = pc.saturating_sub(CONTEXT * 2);
+        while lines.len() < rows {
+            let opcode = match word(address) {
+                Some(opcode) => opcode,
+                None => break,
+            };
+            let instruction = Instruction::decode(opcode, word(address + 2).unwrap_or(0));
+            let text = format!("{:04X}  {}", address, instruction);
+            lines.push(if address == pc {
+                Line::highlighted(text)
+            } else {
+                Line::from(text)
+            });
+            let next = address + instruction.size() as usize;
+            // Don't step over the PC when the code before it isn't aligned with it
+            address = if address < pc && next > pc { pc } else { next };
+        }
+
+        lines
+    }
+}
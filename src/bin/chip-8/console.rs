@@ -0,0 +1,114 @@
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use chip_8::debugger;
+use chip_8::Poke;
+
+use crate::emulation::Command;
+
+const HELP: &str = "Commands:
+  poke ADDR:BYTES  Write hex bytes into memory, e.g. poke 0x300:A2F0
+  load FILE        Switch to the ROM in FILE and start it, with the cheats in FILE.cheats
+  cheats           List the cheats and whether they're on
+  cheat N on|off   Turn cheat N on or off
+  continue         Resume after stopping at a breakpoint
+  step [N]         Execute N instructions, 1 by default
+  back [N]         Undo the last N instructions with --debug, 1 by default
+  break ADDR       Stop at ADDR, actions can follow a `:` as with --break
+  delete ADDR      Remove the breakpoint at ADDR
+  print OPERAND    Print V0-VF, I, PC, or [ADDR]
+  set OPERAND VAL  Change V0-VF, I, PC, or [ADDR], e.g. set v3 0x10 or set mem 0x300 0xAB
+  dump mem ADDR N  Print N bytes of memory starting at ADDR
+  hexdump [ADDR N] Print memory and its ASCII, 16 bytes at I by default
+  regs             Print all registers
+  backtrace        Print the subroutine calls that led here
+  sprites [ADDR]   List the sprites the ROM draws, or draw the 8 rows at ADDR
+  profile on|off   Count how often every instruction runs, or stop counting
+  profile          Print the most executed instructions
+  help             Show this message";
+
+/// Read commands from stdin on a background thread and forward them to the emulation thread.
+///
+/// The thread is never joined, it ends with the process or when stdin is closed.
+pub fn spawn(commands: Sender<Command>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
+            let mut words = line.trim().splitn(2, ' ');
+
+            match (words.next(), words.next()) {
+                (Some(""), None) => {}
+                (Some("poke"), Some(poke)) => match poke.parse::<Poke>() {
+                    Ok(poke) => {
+                        if commands.send(Command::Poke(poke)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(error) => eprintln!("{}", error),
+                },
+                (Some("load"), Some(path)) => match std::fs::read(path.trim()) {
+                    Ok(rom) => {
+                        let name = crate::emulation::rom_name(Path::new(path.trim()));
+                        if commands.send(Command::LoadRom { name, rom }).is_err() {
+                            return;
+                        }
+                        crate::switch_cheats(&commands, Path::new(path.trim()));
+                    }
+                    Err(error) => eprintln!("Can't read `{}`: {}", path.trim(), error),
+                },
+                (Some("cheats"), None) => {
+                    if commands.send(Command::ListCheats).is_err() {
+                        return;
+                    }
+                }
+                (Some("cheat"), Some(arguments)) => match parse_cheat_toggle(arguments) {
+                    Some((index, enabled)) => {
+                        if commands
+                            .send(Command::SetCheatEnabled { index, enabled })
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    None => eprintln!("Expected `cheat N on` or `cheat N off`"),
+                },
+                (Some("continue"), None) => {
+                    if commands.send(Command::Continue).is_err() {
+                        return;
+                    }
+                }
+                (Some("help"), None) => eprintln!("{}", HELP),
+                _ => match line.parse::<debugger::Command>() {
+                    Ok(command) => {
+                        if commands.send(Command::Debug(command)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(error) => eprintln!("{}\n{}", error, HELP),
+                },
+            }
+        }
+    });
+}
+
+/// The index and state in `N on` or `N off`, with cheats numbered from 1 as they're listed.
+fn parse_cheat_toggle(arguments: &str) -> Option<(usize, bool)> {
+    let mut words = arguments.split_whitespace();
+    let index = words.next()?.parse::<usize>().ok()?.checked_sub(1)?;
+    let enabled = match words.next()? {
+        "on" => true,
+        "off" => false,
+        _ => return None,
+    };
+
+    match words.next() {
+        Some(_) => None,
+        None => Some((index, enabled)),
+    }
+}
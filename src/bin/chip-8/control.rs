@@ -0,0 +1,67 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use chip_8::control::{Request, Response};
+
+use crate::emulation::Command;
+
+/// Listen for control connections on `address` on a background thread, every connection gets
+/// its own thread that forwards its requests to the emulation thread and writes the responses.
+///
+/// The threads are never joined, they end with the process.
+pub fn spawn(address: &str, commands: Sender<Command>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!(
+        "Listening for control connections on {}",
+        listener.local_addr()?
+    );
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    eprintln!("Control connection failed: {}", error);
+                    continue;
+                }
+            };
+            let commands = commands.clone();
+            thread::spawn(move || {
+                if let Err(error) = serve(stream, &commands) {
+                    eprintln!("Control connection failed: {}", error);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer the requests on `stream` one line at a time until it's closed.
+fn serve(stream: TcpStream, commands: &Sender<Command>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match line.parse::<Request>() {
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if commands.send(Command::Control(request, reply)).is_err() {
+                    return Ok(());
+                }
+                response
+                    .recv()
+                    .unwrap_or_else(|_| Response::Error("The emulator stopped".to_owned()))
+            }
+            Err(error) => Response::Error(error),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,663 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+#[cfg(feature = "control")]
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Receiver, SyncSender, TryRecvError, TrySendError};
+use std::time::Duration;
+
+use chip_8::breakpoint::Hit;
+use chip_8::cheats::Cheats;
+use chip_8::clock::SystemClock;
+#[cfg(feature = "control")]
+use chip_8::control::{Request, Response};
+use chip_8::debugger::{self, Debugger};
+use chip_8::replay::{InputRecorder, InputReplayer};
+use chip_8::runner::{FRAMES_PER_SECOND, MAX_CATCH_UP_FRAMES};
+#[cfg(feature = "scripting")]
+use chip_8::script::Script;
+#[cfg(feature = "control")]
+use chip_8::Screenshot;
+use chip_8::{
+    overlay, Clock, CpuState, Display, FramePacer, FramebufferDisplay, Ghosting, Input, Palette,
+    Poke, Quirks, RomError, Stats,
+};
+
+use crate::input::MiniFBInput;
+
+/// The slowest and fastest speeds the speed hotkeys go to, in instructions per frame.
+const MIN_CYCLES_PER_FRAME: u32 = 1;
+pub const MAX_CYCLES_PER_FRAME: u32 = 2000;
+/// How often the memory goes along with a frame for the debug windows while running, copying
+/// it every frame would be wasted on windows nobody reads that fast.
+const MEMORY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Messages from the UI thread to the emulation thread.
+pub enum Command {
+    /// The latest keypad state read from the window.
+    Input(MiniFBInput),
+    /// Reset the emulator and restart the current ROM.
+    Reset,
+    /// Switch to another ROM and start it.
+    LoadRom { name: String, rom: Vec<u8> },
+    /// Pause because the window lost focus.
+    Pause,
+    /// Resume after the window regained focus, unless stopped at a breakpoint.
+    Resume,
+    /// Resume after stopping at a breakpoint.
+    Continue,
+    /// Stop, or continue when stopped.
+    TogglePause,
+    /// Run a single frame while stopped.
+    StepFrame,
+    /// Write bytes into memory.
+    Poke(Poke),
+    /// Draw in different colors.
+    SetPalette(Palette),
+    /// Interpret the ambiguous instructions differently.
+    SetQuirks(Quirks),
+    /// Run 25% more instructions per frame, at least one more.
+    Faster,
+    /// Run 20% fewer instructions per frame, about undoing [`Command::Faster`].
+    Slower,
+    /// Fast forward while the multiplier is above 1, see
+    /// [`Emulator::set_speed_multiplier`](chip_8::Emulator::set_speed_multiplier).
+    SetSpeedMultiplier(f32),
+    /// Show or hide the CPU state over the picture.
+    ToggleOverlay,
+    /// Show or hide the oscilloscope of the sound over the picture.
+    ToggleScope,
+    /// Start or stop sending the memory along with the frames, for the debug windows.
+    SendMemory(bool),
+    /// Step, inspect, or change breakpoints, printing the result.
+    Debug(debugger::Command),
+    /// Apply `cheats` after every frame instead of the earlier ones.
+    SetCheats(Cheats),
+    /// Turn the cheat at an index in the list on or off.
+    SetCheatEnabled { index: usize, enabled: bool },
+    /// Print the cheats and whether they're on.
+    ListCheats,
+    /// Run the callbacks of a Lua script after every frame, instead of any earlier script.
+    /// Scripts are loaded here as they can't move between threads.
+    #[cfg(feature = "scripting")]
+    LoadScript { name: String, source: String },
+    /// A request from the control channel, answered over the sender.
+    #[cfg(feature = "control")]
+    Control(Request, Sender<Response>),
+}
+
+/// A picture of the display in the window's XRGB format.
+pub struct Frame {
+    pub pixels: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+    /// A copy of the memory after the frame while [`Command::SendMemory`] is on, at most every
+    /// [`MEMORY_INTERVAL`] unless paused.
+    pub memory: Option<Vec<u8>>,
+    pub pc: u16,
+    pub i: u16,
+    /// Frames per second over the last second.
+    pub fps: u32,
+    /// Instructions per second over the last second.
+    pub ips: u32,
+    /// Instructions per frame at normal speed, and the quirks, to show in the pause menu.
+    pub cycles_per_frame: u32,
+    pub quirks: Quirks,
+    /// The name of the ROM in the database, or else its file name, for the title bar.
+    pub rom_title: String,
+    pub is_paused: bool,
+    pub speed_multiplier: f32,
+}
+
+/// Frames and instructions run over the last second, for the overlay and the title bar.
+struct Rates {
+    /// When `start` was taken, by the emulation thread's clock.
+    since: Duration,
+    start: Stats,
+    fps: u32,
+    ips: u32,
+}
+
+impl Rates {
+    fn new(stats: Stats, now: Duration) -> Self {
+        Self {
+            since: now,
+            start: stats,
+            fps: 0,
+            ips: 0,
+        }
+    }
+
+    /// Work out the rates once a second from the emulator's `stats`, returns whether they were.
+    fn update(&mut self, stats: Stats, now: Duration) -> bool {
+        let elapsed = now - self.since;
+        if elapsed < Duration::from_secs(1) {
+            return false;
+        }
+
+        // A reset starts the counters over, the rates are only off for a second
+        self.fps = stats.fps_since(&self.start, elapsed);
+        self.ips = stats.ips_since(&self.start, elapsed);
+        self.since = now;
+        self.start = stats;
+
+        true
+    }
+}
+
+/// Where the keypad state comes from.
+pub enum Session {
+    /// The keyboard.
+    Live,
+    /// The keyboard, recording every press and release.
+    Record(InputRecorder<BufWriter<File>>),
+    /// A recording, the keyboard is ignored.
+    Replay(InputReplayer),
+}
+
+impl Session {
+    /// The keypad state to run from `cycle` on, given the keyboard's `input`.
+    fn input<'a>(&'a mut self, input: &'a MiniFBInput, cycle: u64) -> &'a dyn Input {
+        match self {
+            Session::Live => input,
+            Session::Record(recorder) => {
+                if let Err(error) = recorder.record(cycle, input) {
+                    eprintln!("Recording failed: {}", error);
+                }
+                recorder
+            }
+            Session::Replay(replayer) => {
+                replayer.advance(cycle);
+                replayer
+            }
+        }
+    }
+}
+
+/// Run the debugger's emulator at its own cadence until the UI thread hangs up, then hand the
+/// debugger back. When `start_stopped` is set nothing runs until a `continue` command.
+/// `file_name` is the file name of the running ROM, see [`rom_name`].
+///
+/// Frames are delivered over `frames` in the window's XRGB format. When the UI thread is
+/// still busy with the previous frame the new one is held back instead of blocking
+/// emulation. The UI thread sends the pixels of the frames it's done with back over
+/// `recycled`, to draw the next frames into without allocating.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    mut debugger: Debugger<FramebufferDisplay>,
+    file_name: String,
+    start_stopped: bool,
+    mut session: Session,
+    mut ghosting: Option<Ghosting>,
+    commands: Receiver<Command>,
+    frames: SyncSender<Frame>,
+    recycled: Receiver<Vec<u32>>,
+) -> Debugger<FramebufferDisplay> {
+    let mut is_stopped = start_stopped;
+    if start_stopped {
+        debugger.emulator_mut().pause();
+    }
+    let mut rom_title = title_for(&debugger, file_name);
+    let mut input = MiniFBInput::new();
+    let clock = SystemClock::new();
+    let mut pacer = FramePacer::new(FRAMES_PER_SECOND);
+    let mut last_update = clock.now();
+    let mut frame_pending = false;
+    let mut show_overlay = false;
+    let mut show_scope = false;
+    // The state before the last frame that ran any instructions, the overlay highlights what
+    // changed since
+    let mut previous_state = None;
+    // The pixels of a frame the UI thread had no room for, to draw the next one into
+    let mut spare_pixels = None;
+    let mut send_memory = false;
+    // When the memory last went along with a frame
+    let mut memory_sent: Option<Duration> = None;
+    let mut rates = Rates::new(debugger.emulator().stats(), clock.now());
+    let mut cheats = Cheats::default();
+    #[cfg(feature = "scripting")]
+    let mut script: Option<Script> = None;
+    // Keys pressed over the control channel and the frames they're held for
+    #[cfg(feature = "control")]
+    let mut held_keys: Vec<(u8, u32)> = Vec::new();
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(Command::Input(new_input)) => input = new_input,
+                Ok(Command::Reset) => {
+                    if !debugger.emulator().is_initial_state() {
+                        match &mut session {
+                            Session::Live => {}
+                            Session::Record(_) => eprintln!(
+                                "Reset while recording, the recording replays up to the reset"
+                            ),
+                            Session::Replay(replayer) => replayer.rewind(),
+                        }
+                        debugger = debugger.reset();
+                        is_stopped = false;
+                        pacer.reset();
+                        last_update = clock.now();
+                    }
+                }
+                Ok(Command::LoadRom { name, rom }) => match load_rom(&mut debugger, rom) {
+                    Ok(()) => {
+                        rom_title = title_for(&debugger, name);
+                        if !matches!(session, Session::Live) {
+                            eprintln!("The recorded input doesn't match the new ROM");
+                        }
+                        if let Some(info) = debugger.emulator().rom_info() {
+                            println!(
+                                "Running {} with the settings from the ROM database",
+                                info.name
+                            );
+                        }
+                        if let Some(ghosting) = &mut ghosting {
+                            ghosting.reset();
+                        }
+                        is_stopped = false;
+                        frame_pending = true;
+                        pacer.reset();
+                        last_update = clock.now();
+                    }
+                    Err(error) => eprintln!("Can't load the ROM: {}", error),
+                },
+                Ok(Command::Pause) => debugger.emulator_mut().pause(),
+                Ok(Command::Resume) => {
+                    if !is_stopped {
+                        debugger.emulator_mut().resume();
+                    }
+                }
+                Ok(Command::Continue) => {
+                    is_stopped = false;
+                    debugger.emulator_mut().resume();
+                }
+                Ok(Command::TogglePause) => {
+                    is_stopped = !is_stopped;
+                    // For the title bar
+                    frame_pending = true;
+                    if is_stopped {
+                        debugger.emulator_mut().pause();
+                        println!("Paused, press Space to resume or N to advance a frame");
+                    } else {
+                        debugger.emulator_mut().resume();
+                        println!("Resumed");
+                    }
+                }
+                Ok(Command::StepFrame) if is_stopped => {
+                    #[cfg(feature = "control")]
+                    let input = hold_keys(&input, &mut held_keys);
+                    let frame_input = session.input(&input, debugger.emulator().cycle_count());
+                    let before = overlay_state(&debugger, show_overlay);
+                    match debugger.step_frame(frame_input, |hit| print_hit(&hit)) {
+                        Ok(frame) => {
+                            apply_cheats(&mut cheats, &mut debugger);
+                            frame_pending |= frame.is_dirty || show_overlay || show_scope;
+                            if frame.cycles > 0 {
+                                previous_state = before;
+                            }
+                        }
+                        Err(error) => eprintln!("Stopped: {}\nPress F1 to reset", error),
+                    }
+                }
+                Ok(Command::StepFrame) => {}
+                Ok(Command::Poke(poke)) => {
+                    if let Err(error) = debugger.emulator_mut().poke(&poke) {
+                        eprintln!("{}", error);
+                    }
+                }
+                Ok(Command::SetPalette(palette)) => {
+                    debugger.emulator_mut().set_palette(palette);
+                    frame_pending = true;
+                }
+                Ok(Command::SetQuirks(quirks)) => {
+                    debugger.emulator_mut().set_quirks(quirks);
+                    frame_pending = true;
+                }
+                Ok(Command::Faster) => {
+                    change_speed(&mut debugger, |n| (n * 5 / 4).max(n + 1));
+                    frame_pending = true;
+                }
+                Ok(Command::Slower) => {
+                    change_speed(&mut debugger, |n| n * 4 / 5);
+                    frame_pending = true;
+                }
+                Ok(Command::SetSpeedMultiplier(multiplier)) => {
+                    debugger.emulator_mut().set_speed_multiplier(multiplier);
+                    frame_pending = true;
+                }
+                Ok(Command::ToggleOverlay) => {
+                    show_overlay = !show_overlay;
+                    frame_pending = true;
+                }
+                Ok(Command::ToggleScope) => {
+                    show_scope = !show_scope;
+                    frame_pending = true;
+                }
+                Ok(Command::SendMemory(send)) => {
+                    send_memory = send;
+                    frame_pending = true;
+                }
+                Ok(Command::Debug(command)) => {
+                    println!("{}", debugger.execute(&command, &input));
+                    frame_pending |= debugger.emulator().display().is_dirty();
+                }
+                Ok(Command::SetCheats(new_cheats)) => {
+                    if !new_cheats.is_empty() {
+                        println!(
+                            "Loaded {} cheats, type `cheats` to list them",
+                            new_cheats.len()
+                        );
+                    }
+                    cheats = new_cheats;
+                    apply_cheats(&mut cheats, &mut debugger);
+                }
+                Ok(Command::SetCheatEnabled { index, enabled }) => {
+                    if cheats.set_enabled(index, enabled) {
+                        apply_cheats(&mut cheats, &mut debugger);
+                    } else {
+                        eprintln!("There's no cheat {}, type `cheats` to list them", index + 1);
+                    }
+                }
+                Ok(Command::ListCheats) if cheats.is_empty() => println!("There are no cheats"),
+                Ok(Command::ListCheats) => print!("{}", cheats),
+                #[cfg(feature = "scripting")]
+                Ok(Command::LoadScript { name, source }) => match Script::new(&source, &name) {
+                    Ok(new_script) => script = Some(new_script),
+                    Err(error) => eprintln!("Can't load the script: {}", error),
+                },
+                #[cfg(feature = "control")]
+                Ok(Command::Control(request, reply)) => {
+                    let response = match request {
+                        Request::LoadRom { path } => match std::fs::read(&path) {
+                            Ok(rom) => match load_rom(&mut debugger, rom) {
+                                Ok(()) => {
+                                    rom_title = title_for(&debugger, rom_name(&path));
+                                    if let Some(ghosting) = &mut ghosting {
+                                        ghosting.reset();
+                                    }
+                                    is_stopped = false;
+                                    frame_pending = true;
+                                    pacer.reset();
+                                    last_update = clock.now();
+                                    Response::Done
+                                }
+                                Err(error) => Response::Error(error.to_string()),
+                            },
+                            Err(error) => Response::Error(format!(
+                                "Can't read `{}`: {}",
+                                path.display(),
+                                error
+                            )),
+                        },
+                        Request::Pause => {
+                            is_stopped = true;
+                            debugger.emulator_mut().pause();
+                            Response::Done
+                        }
+                        Request::Resume => {
+                            is_stopped = false;
+                            debugger.emulator_mut().resume();
+                            Response::Done
+                        }
+                        Request::Step { frames } => {
+                            is_stopped = true;
+                            debugger.emulator_mut().pause();
+                            frame_pending = true;
+                            let mut result = Ok(());
+                            for _ in 0..frames {
+                                let input = hold_keys(&input, &mut held_keys);
+                                let frame_input =
+                                    session.input(&input, debugger.emulator().cycle_count());
+                                result = debugger
+                                    .step_frame(frame_input, |hit| print_hit(&hit))
+                                    .map(|_| apply_cheats(&mut cheats, &mut debugger));
+                                if result.is_err() {
+                                    break;
+                                }
+                            }
+                            match result {
+                                Ok(()) => Response::Stepped {
+                                    pc: debugger.pc(),
+                                    cycles: debugger.emulator().cycle_count(),
+                                },
+                                Err(error) => Response::Error(error.to_string()),
+                            }
+                        }
+                        Request::Screenshot { path } => {
+                            let display = debugger.emulator().display();
+                            let png = Screenshot::new(
+                                display.rgba_framebuffer(),
+                                display.width(),
+                                display.height(),
+                            )
+                            .to_png();
+                            match std::fs::write(&path, png) {
+                                Ok(()) => Response::Done,
+                                Err(error) => Response::Error(format!(
+                                    "Can't write `{}`: {}",
+                                    path.display(),
+                                    error
+                                )),
+                            }
+                        }
+                        Request::ReadMemory { address, length } => {
+                            match debugger.emulator().peek_slice(address, length) {
+                                Ok(bytes) => Response::Memory {
+                                    address,
+                                    bytes: bytes.to_vec(),
+                                },
+                                Err(error) => Response::Error(error.to_string()),
+                            }
+                        }
+                        Request::PressKey { key, frames } => {
+                            if frames > 0 {
+                                held_keys.push((key, frames));
+                            }
+                            Response::Done
+                        }
+                    };
+                    let _ = reply.send(response);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return debugger,
+            }
+        }
+
+        let now = clock.now();
+        let due_frames = pacer.advance(now - last_update);
+        last_update = now;
+
+        for _ in 0..due_frames.min(MAX_CATCH_UP_FRAMES) {
+            if debugger.emulator().is_paused() {
+                break;
+            }
+
+            #[cfg(feature = "control")]
+            let input = hold_keys(&input, &mut held_keys);
+            let frame_input = session.input(&input, debugger.emulator().cycle_count());
+            let before = overlay_state(&debugger, show_overlay);
+
+            let frame = debugger.run_frame(frame_input, |hit| {
+                print_hit(&hit);
+                is_stopped |= hit.should_stop;
+            });
+
+            match frame {
+                Ok(frame) => {
+                    apply_cheats(&mut cheats, &mut debugger);
+                    // The overlay, the scope, and the memory change with every instruction, not
+                    // just when the display does
+                    frame_pending |= frame.is_dirty || show_overlay || show_scope || send_memory;
+                    if frame.cycles > 0 {
+                        previous_state = before;
+                    }
+                    #[cfg(feature = "scripting")]
+                    {
+                        let result = match &script {
+                            Some(script) => script.run_frame(debugger.emulator_mut()),
+                            None => Ok(()),
+                        };
+                        if let Err(error) = result {
+                            eprintln!("The script failed and won't run again: {}", error);
+                            script = None;
+                        }
+                    }
+                }
+                Err(error) => {
+                    eprintln!("Stopped: {}\nPress F1 to reset", error);
+                    debugger.emulator_mut().pause();
+                    is_stopped = true;
+                    frame_pending = true;
+                }
+            }
+        }
+
+        frame_pending |= rates.update(debugger.emulator().stats(), now);
+
+        let is_fading = matches!(&ghosting, Some(ghosting) if ghosting.is_fading());
+        if due_frames > 0 && (frame_pending || is_fading) {
+            let emulator = debugger.emulator();
+            let display = emulator.display();
+            let mut pixels = spare_pixels
+                .take()
+                .or_else(|| recycled.try_recv().ok())
+                .unwrap_or_default();
+            display.write_rgba(&mut pixels);
+            if let Some(ghosting) = &mut ghosting {
+                ghosting.apply_in_place(&mut pixels, display.palette().background());
+            }
+            let (width, height) = (display.width(), display.height());
+            let (pixels, width, height) = if show_overlay || show_scope {
+                let (mut scaled, width, height) = overlay::scale(&pixels, width, height);
+                if show_overlay {
+                    let lines =
+                        overlay::lines(emulator, rates.fps, rates.ips, previous_state.as_ref());
+                    overlay::draw_panel(&mut scaled, width, height, &lines);
+                }
+                if show_scope {
+                    overlay::draw_scope(
+                        &mut scaled,
+                        width,
+                        height,
+                        emulator.audio_buffer().as_ref(),
+                        emulator.state().sound_timer,
+                    );
+                }
+                (scaled, width, height)
+            } else {
+                (pixels, width, height)
+            };
+            let is_memory_due = match memory_sent {
+                Some(sent) => now - sent >= MEMORY_INTERVAL || emulator.is_paused(),
+                None => true,
+            };
+            let memory = if send_memory && is_memory_due {
+                memory_sent = Some(now);
+                Some(emulator.dump_memory(..).to_vec())
+            } else {
+                None
+            };
+            let frame = Frame {
+                pixels,
+                width,
+                height,
+                memory,
+                pc: emulator.pc(),
+                i: emulator.i(),
+                fps: rates.fps,
+                ips: rates.ips,
+                cycles_per_frame: emulator.cycles_per_frame(),
+                quirks: emulator.quirks(),
+                rom_title: rom_title.clone(),
+                is_paused: emulator.is_paused(),
+                speed_multiplier: emulator.speed_multiplier(),
+            };
+            match frames.try_send(frame) {
+                Ok(()) => frame_pending = false,
+                Err(TrySendError::Full(frame)) => spare_pixels = Some(frame.pixels),
+                Err(TrySendError::Disconnected(_)) => return debugger,
+            }
+        }
+
+        clock.sleep(pacer.time_until_next_frame());
+    }
+}
+
+/// The keyboard's `input` with the keys held over the control channel pressed too, counting
+/// down the frames they're held for.
+#[cfg(feature = "control")]
+fn hold_keys(input: &MiniFBInput, held_keys: &mut Vec<(u8, u32)>) -> MiniFBInput {
+    let mut input = *input;
+    for (key, frames) in held_keys.iter_mut() {
+        input.press(*key);
+        *frames -= 1;
+    }
+    held_keys.retain(|&(_, frames)| frames > 0);
+
+    input
+}
+
+/// Write the enabled cheats into memory, dropping them all if one doesn't fit.
+fn apply_cheats(cheats: &mut Cheats, debugger: &mut Debugger<FramebufferDisplay>) {
+    if let Err(error) = cheats.apply(debugger.emulator_mut()) {
+        eprintln!("The cheats don't fit this ROM and are off: {}", error);
+        *cheats = Cheats::default();
+    }
+}
+
+fn print_hit(hit: &Hit) {
+    for line in &hit.log {
+        println!("{}", line);
+    }
+
+    if hit.should_stop {
+        println!("Stopped at {:#05x}, type `continue` to resume", hit.address);
+    }
+}
+
+/// The file name of the ROM at `path`, to show until the ROM database knows better.
+pub fn rom_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The name of the running ROM in the database, or `file_name` if it isn't in there.
+fn title_for(debugger: &Debugger<FramebufferDisplay>, file_name: String) -> String {
+    match debugger.emulator().rom_info() {
+        Some(info) => info.name.clone(),
+        None => file_name,
+    }
+}
+
+/// Switch to `rom` after checking it, printing anything odd about it.
+fn load_rom(debugger: &mut Debugger<FramebufferDisplay>, rom: Vec<u8>) -> Result<(), RomError> {
+    for warning in debugger.emulator().variant().validate_rom(&rom)? {
+        eprintln!("Warning: {}", warning);
+    }
+
+    debugger.load_rom(rom)
+}
+
+/// The state to compare the next frame's with, only needed while the overlay shows.
+fn overlay_state(debugger: &Debugger<FramebufferDisplay>, show_overlay: bool) -> Option<CpuState> {
+    if show_overlay {
+        Some(debugger.emulator().state())
+    } else {
+        None
+    }
+}
+
+fn change_speed(debugger: &mut Debugger<FramebufferDisplay>, change: impl Fn(u32) -> u32) {
+    let emulator = debugger.emulator_mut();
+    let cycles =
+        change(emulator.cycles_per_frame()).clamp(MIN_CYCLES_PER_FRAME, MAX_CYCLES_PER_FRAME);
+    emulator.set_cycles_per_frame(cycles);
+    println!(
+        "Running {} instructions per frame, {}Hz",
+        cycles,
+        emulator.cycles_per_second()
+    );
+}
@@ -0,0 +1,208 @@
+//! Effects drawn over the scaled picture to make it look more like an old screen.
+use std::str::FromStr;
+
+/// How much of a pixel's brightness the dark part of a scanline or the grid keeps, out of 256.
+const SCANLINE_BRIGHTNESS: u32 = 128;
+const GRID_BRIGHTNESS: u32 = 192;
+/// How far the corners of the picture bend inwards with curvature.
+const CURVATURE: f32 = 0.08;
+/// How much darker the corners are than the center with the vignette.
+const VIGNETTE: f32 = 0.25;
+
+/// Where the picture ended up in the window after scaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The size of the picture before scaling.
+    pub source_width: usize,
+    pub source_height: usize,
+}
+
+impl Viewport {
+    /// Whether every pixel of the picture is at least `factor` window pixels in both
+    /// directions, filters that draw within pixels need some room to do so.
+    fn is_scaled_by(&self, factor: usize) -> bool {
+        self.width >= self.source_width * factor && self.height >= self.source_height * factor
+    }
+}
+
+/// An effect applied to the window, after scaling and before the window is updated.
+pub trait Filter {
+    /// Change `pixels`, the window `window_width` pixels wide, where the picture is at
+    /// `viewport`.
+    fn apply(&self, pixels: &mut [u32], window_width: usize, viewport: &Viewport);
+}
+
+/// Darkens the lower half of every row of the picture.
+pub struct Scanlines;
+
+impl Filter for Scanlines {
+    fn apply(&self, pixels: &mut [u32], window_width: usize, viewport: &Viewport) {
+        if !viewport.is_scaled_by(2) {
+            return;
+        }
+
+        for y in 0..viewport.height {
+            if (y * viewport.source_height * 2 / viewport.height).is_multiple_of(2) {
+                continue;
+            }
+
+            let start = (viewport.y + y) * window_width + viewport.x;
+            for pixel in &mut pixels[start..start + viewport.width] {
+                *pixel = dim(*pixel, SCANLINE_BRIGHTNESS);
+            }
+        }
+    }
+}
+
+/// Darkens the edges between the pixels of the picture, like an LCD.
+pub struct PixelGrid;
+
+impl Filter for PixelGrid {
+    fn apply(&self, pixels: &mut [u32], window_width: usize, viewport: &Viewport) {
+        if !viewport.is_scaled_by(3) {
+            return;
+        }
+
+        let is_edge = |position: usize, size: usize, source_size: usize| {
+            (position + 1) * source_size / size != position * source_size / size
+        };
+        for y in 0..viewport.height {
+            let is_edge_row = is_edge(y, viewport.height, viewport.source_height);
+            let start = (viewport.y + y) * window_width + viewport.x;
+            let row = &mut pixels[start..start + viewport.width];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if is_edge_row || is_edge(x, viewport.width, viewport.source_width) {
+                    *pixel = dim(*pixel, GRID_BRIGHTNESS);
+                }
+            }
+        }
+    }
+}
+
+/// Bends the picture like the glass of a CRT and darkens the corners.
+pub struct Crt;
+
+impl Filter for Crt {
+    fn apply(&self, pixels: &mut [u32], window_width: usize, viewport: &Viewport) {
+        if viewport.width < 2 || viewport.height < 2 {
+            return;
+        }
+
+        let source: Vec<u32> = (0..viewport.height)
+            .flat_map(|y| {
+                let start = (viewport.y + y) * window_width + viewport.x;
+                pixels[start..start + viewport.width].to_vec()
+            })
+            .collect();
+        let (width, height) = (viewport.width as f32, viewport.height as f32);
+
+        for y in 0..viewport.height {
+            let start = (viewport.y + y) * window_width + viewport.x;
+            let row = &mut pixels[start..start + viewport.width];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                // -1.0 to 1.0 from the left or top edge to the right or bottom edge
+                let u = x as f32 * 2.0 / (width - 1.0) - 1.0;
+                let v = y as f32 * 2.0 / (height - 1.0) - 1.0;
+                let distance = u * u + v * v;
+                let bend = 1.0 + CURVATURE * distance;
+                let (u, v) = (u * bend, v * bend);
+                if u.abs() > 1.0 || v.abs() > 1.0 {
+                    *pixel = 0;
+                    continue;
+                }
+
+                let source_x = ((u + 1.0) / 2.0 * (width - 1.0)).round() as usize;
+                let source_y = ((v + 1.0) / 2.0 * (height - 1.0)).round() as usize;
+                let brightness = (1.0 - VIGNETTE * distance / 2.0) * 256.0;
+                *pixel = dim(
+                    source[source_y * viewport.width + source_x],
+                    brightness as u32,
+                );
+            }
+        }
+    }
+}
+
+/// The filters chosen on the command line, which can be turned on and off together.
+pub struct Filters {
+    filters: Vec<Box<dyn Filter>>,
+    is_enabled: bool,
+}
+
+impl Filters {
+    pub fn new(filters: Vec<Box<dyn Filter>>) -> Self {
+        Self {
+            is_enabled: !filters.is_empty(),
+            filters,
+        }
+    }
+
+    /// Turn the filters on or off, returns whether they're now on. With no filters chosen
+    /// turning them on uses scanlines.
+    pub fn toggle(&mut self) -> bool {
+        if self.filters.is_empty() {
+            self.filters.push(Box::new(Scanlines));
+        }
+        self.is_enabled = !self.is_enabled;
+
+        self.is_enabled
+    }
+
+    pub fn apply(&self, pixels: &mut [u32], window_width: usize, viewport: &Viewport) {
+        if !self.is_enabled {
+            return;
+        }
+
+        for filter in &self.filters {
+            filter.apply(pixels, window_width, viewport);
+        }
+    }
+}
+
+/// The name of a filter on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterName {
+    Scanlines,
+    Grid,
+    Crt,
+}
+
+impl FilterName {
+    pub fn create(self) -> Box<dyn Filter> {
+        match self {
+            FilterName::Scanlines => Box::new(Scanlines),
+            FilterName::Grid => Box::new(PixelGrid),
+            FilterName::Crt => Box::new(Crt),
+        }
+    }
+}
+
+impl FromStr for FilterName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scanlines" => Ok(FilterName::Scanlines),
+            "grid" => Ok(FilterName::Grid),
+            "crt" => Ok(FilterName::Crt),
+            _ => Err(format!(
+                "Unknown filter `{}`, expected scanlines, grid, or crt",
+                s
+            )),
+        }
+    }
+}
+
+/// `color` with every channel scaled by `brightness` out of 256.
+fn dim(color: u32, brightness: u32) -> u32 {
+    (0..3).fold(0, |dimmed, channel| {
+        let shift = channel * 8;
+        let value = ((color >> shift) & 0xFF) * brightness / 256;
+
+        dimmed | (value.min(0xFF) << shift)
+    })
+}
@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use chip_8::controller::{Button, ButtonMap};
+use gilrs::{EventType, Gilrs};
+
+use crate::input::MiniFBInput;
+
+/// Presses the CHIP-8 keys mapped to the buttons held on any connected game controller.
+pub struct Gamepad {
+    gilrs: Gilrs,
+    map: ButtonMap,
+    held: HashSet<Button>,
+}
+
+impl Gamepad {
+    pub fn new(map: ButtonMap) -> Result<Self, gilrs::Error> {
+        Ok(Self {
+            gilrs: Gilrs::new()?,
+            map,
+            held: HashSet::new(),
+        })
+    }
+
+    /// Process pending controller events and press the mapped keys in `input`.
+    pub fn update(&mut self, input: &mut MiniFBInput) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        self.held.insert(button);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        self.held.remove(&button);
+                    }
+                }
+                EventType::Disconnected => self.held.clear(),
+                _ => {}
+            }
+        }
+
+        for &button in &self.held {
+            if let Some(key) = self.map.key_for(button) {
+                input.press(key);
+            }
+        }
+    }
+}
+
+fn map_button(button: gilrs::Button) -> Option<Button> {
+    match button {
+        gilrs::Button::South => Some(Button::South),
+        gilrs::Button::East => Some(Button::East),
+        gilrs::Button::North => Some(Button::North),
+        gilrs::Button::West => Some(Button::West),
+        gilrs::Button::DPadUp => Some(Button::DPadUp),
+        gilrs::Button::DPadDown => Some(Button::DPadDown),
+        gilrs::Button::DPadLeft => Some(Button::DPadLeft),
+        gilrs::Button::DPadRight => Some(Button::DPadRight),
+        gilrs::Button::LeftTrigger => Some(Button::LeftTrigger),
+        gilrs::Button::RightTrigger => Some(Button::RightTrigger),
+        gilrs::Button::Select => Some(Button::Select),
+        gilrs::Button::Start => Some(Button::Start),
+        _ => None,
+    }
+}
@@ -0,0 +1,121 @@
+use chip_8::keymap::KeyMap;
+use chip_8::rom_db::Keymap;
+use chip_8::Input;
+use minifb::{Key, Window};
+
+/// A snapshot of the CHIP-8 keypad as read from a minifb [`Window`].
+///
+/// The snapshot is taken on the UI thread and sent to the emulation thread, hence `Copy`.
+#[derive(Debug, Clone, Copy)]
+pub struct MiniFBInput {
+    key_states: [bool; 16],
+}
+
+impl MiniFBInput {
+    pub fn new() -> Self {
+        Self {
+            key_states: [false; 16],
+        }
+    }
+
+    /// Read the keypad from the keyboard keys in `keys`, see [`keyboard_keys`].
+    pub fn update_key_state(&mut self, window: &Window, keys: &[(Key, u8)]) {
+        self.key_states = [false; 16];
+        for &(key_enum, key) in keys {
+            if window.is_key_down(key_enum) {
+                self.press(key);
+            }
+        }
+    }
+
+    /// Press the keys `keymap` maps the arrow keys and enter to, if they are held.
+    pub fn press_arrow_keys(&mut self, window: &Window, keymap: &Keymap) {
+        let keys = [
+            (Key::Up, keymap.up),
+            (Key::Down, keymap.down),
+            (Key::Left, keymap.left),
+            (Key::Right, keymap.right),
+            (Key::Enter, keymap.action),
+        ];
+
+        for &(key_enum, key) in &keys {
+            if window.is_key_down(key_enum) {
+                self.press(key);
+            }
+        }
+    }
+
+    /// Mark `key` as held in addition to what was read from the window, e.g. from a gamepad.
+    pub fn press(&mut self, key: u8) {
+        self.key_states[key as usize] = true;
+    }
+
+    /// The same snapshot with every key passed through `remap`, e.g. [`Rotation::remap_key`].
+    ///
+    /// [`Rotation::remap_key`]: chip_8::Rotation::remap_key
+    pub fn remapped(&self, remap: impl Fn(u8) -> u8) -> Self {
+        let mut remapped = Self::new();
+        for key in 0..16 {
+            if self.key_states[key as usize] {
+                remapped.key_states[remap(key) as usize] = true;
+            }
+        }
+
+        remapped
+    }
+}
+
+/// The minifb keys for the keyboard keys in `map` and the CHIP-8 keys they press.
+pub fn keyboard_keys(map: &KeyMap) -> Vec<(Key, u8)> {
+    map.keys()
+        .filter_map(|(keyboard_key, key)| map_key(keyboard_key).map(|key_enum| (key_enum, key)))
+        .collect()
+}
+
+fn map_key(keyboard_key: char) -> Option<Key> {
+    match keyboard_key {
+        '0' => Some(Key::Key0),
+        '1' => Some(Key::Key1),
+        '2' => Some(Key::Key2),
+        '3' => Some(Key::Key3),
+        '4' => Some(Key::Key4),
+        '5' => Some(Key::Key5),
+        '6' => Some(Key::Key6),
+        '7' => Some(Key::Key7),
+        '8' => Some(Key::Key8),
+        '9' => Some(Key::Key9),
+        'A' => Some(Key::A),
+        'B' => Some(Key::B),
+        'C' => Some(Key::C),
+        'D' => Some(Key::D),
+        'E' => Some(Key::E),
+        'F' => Some(Key::F),
+        'G' => Some(Key::G),
+        'H' => Some(Key::H),
+        'I' => Some(Key::I),
+        'J' => Some(Key::J),
+        'K' => Some(Key::K),
+        'L' => Some(Key::L),
+        'M' => Some(Key::M),
+        'N' => Some(Key::N),
+        'O' => Some(Key::O),
+        'P' => Some(Key::P),
+        'Q' => Some(Key::Q),
+        'R' => Some(Key::R),
+        'S' => Some(Key::S),
+        'T' => Some(Key::T),
+        'U' => Some(Key::U),
+        'V' => Some(Key::V),
+        'W' => Some(Key::W),
+        'X' => Some(Key::X),
+        'Y' => Some(Key::Y),
+        'Z' => Some(Key::Z),
+        _ => None,
+    }
+}
+
+impl Input for MiniFBInput {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.key_states[key as usize]
+    }
+}
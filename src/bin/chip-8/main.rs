@@ -0,0 +1,1622 @@
+mod audio;
+mod bell;
+mod code_window;
+mod console;
+#[cfg(feature = "control")]
+mod control;
+mod emulation;
+mod filter;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod input;
+mod memory_window;
+mod pause_menu;
+mod picker;
+mod recorder;
+mod repl;
+mod sprite_window;
+
+use chip_8::assembler::{assemble, assemble_octo};
+use chip_8::batch::{self, Job};
+use chip_8::breakpoint::{Breakpoint, Breakpoints};
+use chip_8::cheats::{self, Cheats};
+use chip_8::config::{self, Config, Settings};
+use chip_8::debugger::{Debugger, UNDO_LIMIT};
+use chip_8::disassembler::{symbolize, Line};
+use chip_8::keymap::{KeyMap, KeyMaps};
+use chip_8::overlay;
+use chip_8::recording::RecordingFormat;
+use chip_8::replay::{InputRecorder, InputReplayer};
+use chip_8::rng::Seeded;
+use chip_8::rom_db::{self, Keymap, RomDatabase};
+#[cfg(feature = "scripting")]
+use chip_8::script::{Script, ScriptError};
+use chip_8::settings::History;
+use chip_8::storage::FileStorage;
+use chip_8::{
+    Display, Emulator, EmulatorObserver, FramebufferDisplay, Ghosting, Palette, Poke, Profile,
+    Quirks, Rotation, ScaleMode, Screenshot, SoundSinks, Variant,
+};
+use clap::{crate_authors, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bell::{Bell, VisualBell};
+use code_window::CodeWindow;
+use emulation::{Command, Frame, Session};
+use filter::{FilterName, Filters, Viewport};
+use input::MiniFBInput;
+use memory_window::MemoryWindow;
+use pause_menu::{Action, PauseMenu};
+use recorder::Recorder;
+use sprite_window::SpriteWindow;
+
+/// How long the UI thread waits for a new frame before servicing window events anyway.
+const UI_FRAME_TIMEOUT: Duration = Duration::from_micros(1_000_000 / 60);
+/// The largest --scale, 32 times 64x32 already fills a 4K screen.
+const MAX_SCALE: usize = 32;
+const WINDOW_TITLE: &str = "CHIP-8";
+/// The range of --turbo, faster than 16x the frontend can't keep up with most speeds.
+const MIN_TURBO: f32 = 2.0;
+const MAX_TURBO: f32 = 16.0;
+/// Arguments that pick what to run without a ROM file, without any of them or a ROM file the ROM
+/// is picked in a window.
+#[cfg(feature = "builtin-roms")]
+const ROM_ALTERNATIVES: &[&str] = &["builtin", "list-builtin", "recent"];
+#[cfg(not(feature = "builtin-roms"))]
+const ROM_ALTERNATIVES: &[&str] = &["recent"];
+
+/// Reports failing to save the flags, which would otherwise go unnoticed until the next run.
+struct FlagErrorReporter;
+
+impl EmulatorObserver for FlagErrorReporter {
+    fn on_storage_error(&mut self, error: &std::io::Error) {
+        eprintln!("Saving the flags failed: {}", error);
+    }
+}
+
+fn load_rom(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// The path and contents of the ROM to run, a file or, with `--builtin`, a built-in ROM.
+fn load_rom_arg(
+    matches: &ArgMatches,
+    history: &History,
+) -> Result<(PathBuf, Vec<u8>), Box<dyn std::error::Error>> {
+    #[cfg(feature = "builtin-roms")]
+    {
+        if let Some(name) = matches.value_of("builtin") {
+            let builtin = chip_8::builtin::find(name)
+                .ok_or_else(|| format!("Unknown built-in ROM `{}`, see --list-builtin", name))?;
+
+            return Ok((PathBuf::from(name), builtin.rom()));
+        }
+    }
+
+    let rom_path = match matches.value_of("ROM") {
+        _ if matches.is_present("recent") => pick_recent(history, matches.value_of("recent"))?,
+        Some(path) => PathBuf::from(path),
+        None => return Err("No ROM given, see --help".into()),
+    };
+    let rom = load_rom(&rom_path)?;
+
+    Ok((rom_path, rom))
+}
+
+/// The ROM for `--recent`, the `choice`th most recent or the one picked from a list of them.
+fn pick_recent(
+    history: &History,
+    choice: Option<&str>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if history.recent.is_empty() {
+        return Err("No ROMs have been run yet".into());
+    }
+
+    let choice = match choice {
+        Some(choice) => choice.to_owned(),
+        None => {
+            for (index, path) in history.recent.iter().enumerate() {
+                println!("{:>2}  {}", index + 1, path);
+            }
+            print!("Run which? ");
+            std::io::stdout().flush()?;
+
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim().to_owned()
+        }
+    };
+
+    match choice.parse::<usize>() {
+        Ok(index) if index >= 1 && index <= history.recent.len() => {
+            Ok(PathBuf::from(&history.recent[index - 1]))
+        }
+        _ => Err(format!(
+            "Invalid choice `{}`, expected 1 to {}",
+            choice,
+            history.recent.len()
+        )
+        .into()),
+    }
+}
+
+/// The storage for the settings and ROMs that are remembered between runs.
+fn history_storage() -> Option<FileStorage> {
+    user_dir("XDG_STATE_HOME", ".local/state").map(FileStorage::new)
+}
+
+/// The config file at `path`, or in the config directory if there is one there.
+fn load_config(path: Option<&str>) -> Result<Config, Box<dyn std::error::Error>> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => match default_config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        },
+    };
+
+    Ok(Config::load(&path)?)
+}
+
+/// `chip8.toml` in `$XDG_CONFIG_HOME/chip-8`, or in `~/.config/chip-8` when that isn't set.
+fn default_config_path() -> Option<PathBuf> {
+    user_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join(config::FILE_NAME))
+}
+
+/// `chip-8` in the directory in the environment variable `variable`, or in `fallback` in the
+/// home directory when that isn't set.
+fn user_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    let dir = std::env::var_os(variable)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(fallback)))?;
+
+    Some(dir.join("chip-8"))
+}
+
+/// The command line arguments, with the config file filling in the settings they leave out.
+struct Arguments<'a> {
+    matches: &'a ArgMatches<'a>,
+    settings: Settings,
+}
+
+impl Arguments<'_> {
+    /// Whether `name` was given on the command line or in the config file.
+    fn is_present(&self, name: &str) -> bool {
+        self.matches.occurrences_of(name) > 0 || self.settings.value(name).is_some()
+    }
+
+    /// The value of `name` from the command line, the config file, or its default value.
+    fn value_of(&self, name: &str) -> Option<String> {
+        if self.matches.occurrences_of(name) > 0 {
+            return self.matches.value_of(name).map(str::to_owned);
+        }
+
+        self.settings
+            .value(name)
+            .or_else(|| self.matches.value_of(name).map(str::to_owned))
+    }
+
+    /// The settings given on the command line that are remembered for the ROM.
+    fn given(&self) -> Settings {
+        let given = |name| match self.matches.occurrences_of(name) {
+            0 => None,
+            _ => self.matches.value_of(name).map(str::to_owned),
+        };
+
+        Settings {
+            palette: given("palette"),
+            keymap: None,
+            scale: given("scale").and_then(|scale| scale.parse().ok()),
+            quirks: given("quirks"),
+            speed: given("speed").and_then(|speed| speed.parse().ok()),
+        }
+    }
+}
+
+/// The ROM and how it's running, e.g. `Pong - CHIP-8 - 2x, 120 FPS, 1320 IPS`. The rates are
+/// left out until they've been measured, and while paused.
+fn window_title(frame: &Frame) -> String {
+    let mut title = format!("{} - {}", frame.rom_title, WINDOW_TITLE);
+    if frame.is_paused {
+        title.push_str(" - Paused");
+        return title;
+    }
+
+    let mut details = Vec::new();
+    if frame.speed_multiplier != 1.0 {
+        details.push(format!("{}x", frame.speed_multiplier));
+    }
+    if frame.fps > 0 {
+        details.push(format!("{} FPS, {} IPS", frame.fps, frame.ips));
+    }
+    if !details.is_empty() {
+        title.push_str(" - ");
+        title.push_str(&details.join(", "));
+    }
+
+    title
+}
+
+fn create_window(
+    display: &dyn Display,
+    rotation: Rotation,
+    scale: usize,
+) -> Result<Window, Box<dyn std::error::Error>> {
+    let opts = WindowOptions {
+        resize: true,
+        ..WindowOptions::default()
+    };
+    let (width, height) = rotation.dimensions(display.width(), display.height());
+    let window = Window::new(WINDOW_TITLE, width * scale, height * scale, opts)?;
+
+    Ok(window)
+}
+
+#[cfg(feature = "gamepad")]
+fn create_gamepad(
+    profiles_path: Option<&str>,
+    rom: &[u8],
+) -> Result<Option<gamepad::Gamepad>, Box<dyn std::error::Error>> {
+    let profiles = match profiles_path {
+        Some(path) => {
+            chip_8::controller::ControllerProfiles::parse(&std::fs::read_to_string(path)?)?
+        }
+        None => Default::default(),
+    };
+
+    match gamepad::Gamepad::new(profiles.for_rom(rom)) {
+        Ok(gamepad) => Ok(Some(gamepad)),
+        Err(error) => {
+            eprintln!("Game controllers are unavailable: {}", error);
+            Ok(None)
+        }
+    }
+}
+
+/// The keymap for `rom` from `keymap`, a built-in layout or the path of a keymap file.
+fn load_keymap(keymap: Option<&str>, rom: &[u8]) -> Result<KeyMap, Box<dyn std::error::Error>> {
+    let keymaps = match keymap {
+        Some(keymap) => match KeyMap::layout(keymap) {
+            Some(layout) => KeyMaps::from(layout),
+            None => KeyMaps::parse(&std::fs::read_to_string(keymap)?)?,
+        },
+        None => KeyMaps::default(),
+    };
+
+    Ok(keymaps.for_rom(rom))
+}
+
+/// The cheats in the cheat file at `path`, `None` if there's no such file.
+fn load_cheats(path: &Path) -> Result<Option<Cheats>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => Cheats::parse(&source)
+            .map(Some)
+            .map_err(|error| format!("Invalid cheat file `{}`: {}", path.display(), error)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(format!("Can't read `{}`: {}", path.display(), error)),
+    }
+}
+
+/// Switch to the cheats in the cheat file next to the ROM at `rom_path`, or to none when
+/// there's no such file or it's invalid.
+fn switch_cheats(command_sender: &mpsc::Sender<Command>, rom_path: &Path) {
+    let cheats = load_cheats(&cheats::path_for(rom_path)).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        None
+    });
+    let _ = command_sender.send(Command::SetCheats(cheats.unwrap_or_default()));
+}
+
+/// The sinks for `bell`, and the visual bell among them if any. Without an audio device the
+/// audio bell falls back to the visual one.
+fn create_sound(bell: Bell) -> (SoundSinks, Option<VisualBell>) {
+    let mut sinks = SoundSinks::new();
+    let mut is_visual = bell.is_visual();
+    if bell.is_audible() {
+        match audio::Beeper::new() {
+            Ok(beeper) => sinks.push(Box::new(beeper)),
+            Err(error) if bell == Bell::Audio => {
+                eprintln!(
+                    "Sound is unavailable: {}, flashing the window instead",
+                    error
+                );
+                is_visual = true;
+            }
+            Err(error) => eprintln!("Sound is unavailable: {}", error),
+        }
+    }
+
+    let visual_bell = if is_visual {
+        let visual_bell = VisualBell::new();
+        sinks.push(Box::new(visual_bell.clone()));
+
+        Some(visual_bell)
+    } else {
+        None
+    };
+
+    (sinks, visual_bell)
+}
+
+/// Rotate `frame` and scale it to fill the window, the rest of the window is `background`.
+/// `filters` are drawn over the picture, and the whole window is inverted while `flash` is
+/// set, for the visual bell. `menu` is drawn over everything in the top left corner.
+#[allow(clippy::too_many_arguments)]
+fn present(
+    window: &mut Window,
+    frame: &Frame,
+    rotation: Rotation,
+    scale_mode: ScaleMode,
+    background: u32,
+    filters: &Filters,
+    flash: bool,
+    menu: &[overlay::Line],
+) -> minifb::Result<()> {
+    let (width, height) = rotation.dimensions(frame.width, frame.height);
+    let rotated = rotation.apply(&frame.pixels, frame.width, frame.height);
+    let (window_width, window_height) = window.get_size();
+    let mut scaled = scale_mode.apply(
+        &rotated,
+        width,
+        height,
+        window_width,
+        window_height,
+        background,
+    );
+    if window_width > 0 && window_height > 0 {
+        let (scaled_width, scaled_height) =
+            scale_mode.dimensions(width, height, window_width, window_height);
+        let viewport = Viewport {
+            x: (window_width - scaled_width) / 2,
+            y: (window_height - scaled_height) / 2,
+            width: scaled_width,
+            height: scaled_height,
+            source_width: width,
+            source_height: height,
+        };
+        filters.apply(&mut scaled, window_width, &viewport);
+    }
+    if flash {
+        for pixel in &mut scaled {
+            *pixel ^= 0x00ff_ffff;
+        }
+    }
+    if !menu.is_empty() {
+        overlay::draw_panel(&mut scaled, window_width, window_height, menu);
+    }
+
+    window.update_with_buffer(&scaled, window_width, window_height)
+}
+
+/// Reset the emulator and apply the --poke patches to the reloaded ROM again.
+fn reset(command_sender: &mpsc::Sender<Command>, pokes: &[Poke]) {
+    let _ = command_sender.send(Command::Reset);
+    for poke in pokes {
+        let _ = command_sender.send(Command::Poke(poke.clone()));
+    }
+}
+
+/// Save `frame` as shown, rotated and `scale` times as large, as a PNG in the current directory.
+fn save_screenshot(frame: &Frame, rotation: Rotation, scale: usize) -> std::io::Result<PathBuf> {
+    let (width, height) = rotation.dimensions(frame.width, frame.height);
+    let pixels = rotation.apply(&frame.pixels, frame.width, frame.height);
+    let png = Screenshot::new(pixels, width, height)
+        .scaled(scale)
+        .to_png();
+    let path = timestamped_path("png");
+    std::fs::write(&path, png)?;
+
+    Ok(path)
+}
+
+/// A file name in the current directory for screenshots and recordings started by hotkeys.
+fn timestamped_path(extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis());
+
+    PathBuf::from(format!("chip-8-{}.{}", timestamp, extension))
+}
+
+/// Write the recording of `recorder`, reporting where it went.
+fn finish_recording(recorder: Recorder) {
+    match recorder.finish() {
+        Ok(path) => println!("Saved recording to {}", path.display()),
+        Err(error) => eprintln!("Saving the recording failed: {}", error),
+    }
+}
+
+/// Write all of `emulator`'s memory to `path` for --dump-memory-on-exit.
+fn dump_memory<D: Display>(emulator: &Emulator<D>, path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, emulator.dump_memory(..))?;
+    println!("Saved memory to {}", path.display());
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let app = App::new("CHIP-8")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("A CHIP-8 emulator")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(
+            SubCommand::with_name("repl").about("Execute opcodes interactively on a blank machine"),
+        )
+        .subcommand(
+            SubCommand::with_name("assemble")
+                .about("Assemble a source file into a ROM")
+                .arg(
+                    Arg::with_name("SOURCE")
+                        .help("The assembly source to read, Octo syntax if it ends in .8o")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Where to write the ROM, SOURCE with a .ch8 extension by default"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about("Scan a ROM without running it and report what it needs")
+                .arg(
+                    Arg::with_name("ROM")
+                        .help("The CHIP-8 ROM to analyze")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .arg(
+            Arg::with_name("ROM")
+                .help("The CHIP-8 ROM to run")
+                .multiple(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .requires("ROM")
+                .help("Run all ROMs headlessly in parallel and print a summary report"),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .long("headless")
+                .conflicts_with_all(&["batch", "disassemble"])
+                .help(
+                    "Run the ROM without a window for --cycles cycles and print the final screen",
+                ),
+        )
+        .arg(
+            Arg::with_name("dump")
+                .long("dump")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["hash", "pbm"])
+                .default_value("hash")
+                .help("How --headless prints the final screen, a hash or a PBM image"),
+        )
+        .arg(
+            Arg::with_name("hot-spots")
+                .long("hot-spots")
+                .requires("headless")
+                .help("Print the most executed instructions after a --headless run"),
+        )
+        .arg(
+            Arg::with_name("disassemble")
+                .long("disassemble")
+                .conflicts_with("batch")
+                .help("Print the labelled instructions and data of the ROM instead of running it"),
+        )
+        .arg(
+            Arg::with_name("cycles")
+                .long("cycles")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10000")
+                .help("Number of cycles to run each ROM for in batch and headless mode"),
+        )
+        .arg(
+            Arg::with_name("no-auto-pause")
+                .long("no-auto-pause")
+                .help("Keep running when the window loses focus"),
+        )
+        .arg(
+            Arg::with_name("poke")
+                .long("poke")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("ADDR:BYTES")
+                .help("Write hex bytes into memory before starting, e.g. 0x300:A2F0"),
+        )
+        .arg(
+            Arg::with_name("cheats")
+                .long("cheats")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Cheats for the ROM, read from ROM.cheats next to the ROM by default"),
+        )
+        .arg(
+            Arg::with_name("break")
+                .long("break")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("ADDR[: ACTIONS]")
+                .help(
+                    "Stop at ADDR or run `;` separated actions there, \
+                     e.g. \"0x2F0: log V0; set V1 0x10; poke 0x300:AB; continue\"",
+                ),
+        )
+        .arg(
+            Arg::with_name("debug")
+                .long("debug")
+                .help("Start paused and step, inspect, and set breakpoints from the terminal"),
+        )
+        .arg(
+            Arg::with_name("dump-memory-on-exit")
+                .long("dump-memory-on-exit")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write all of memory to FILE when the emulator exits"),
+        )
+        .arg(
+            Arg::with_name("palette")
+                .long("palette")
+                .takes_value(true)
+                .value_name("PALETTE")
+                .help(
+                    "The colors to draw in, a preset (mono, classic, lcd, amber, gameboy) or \
+                     2 or 4 comma separated hex colors starting with the background. \
+                     F2 cycles through the presets",
+                ),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILTER")
+                .possible_values(&["scanlines", "grid", "crt"])
+                .help("Draw scanlines, a pixel grid, or a curved CRT over the picture, F4 toggles"),
+        )
+        .arg(
+            Arg::with_name("ghosting")
+                .long("ghosting")
+                .takes_value(true)
+                .value_name("DECAY")
+                .help(
+                    "Fade pixels out over a few frames to reduce flicker, DECAY from 0.0 up to \
+                     1.0 is how much of a pixel is left after each frame",
+                ),
+        )
+        .arg(
+            Arg::with_name("keymap")
+                .long("keymap")
+                .takes_value(true)
+                .value_name("LAYOUT|FILE")
+                .help(
+                    "The keyboard keys for the keypad, a layout (qwerty, azerty, qwertz) or \
+                     a keymap file, optionally per ROM",
+                ),
+        )
+        .arg(
+            Arg::with_name("rom-info")
+                .long("rom-info")
+                .help("Print the SHA-1 of the ROM and what the ROM database knows about it"),
+        )
+        .arg(
+            Arg::with_name("speed")
+                .long("speed")
+                .takes_value(true)
+                .value_name("HZ")
+                .help("Instructions per second up to 120000, rounded to instructions per frame"),
+        )
+        .arg(
+            Arg::with_name("ipf")
+                .long("ipf")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with("speed")
+                .help(
+                    "Instructions per 60Hz frame, 11 by default. + and - change it while running",
+                ),
+        )
+        .arg(
+            Arg::with_name("slow")
+                .long("slow")
+                .takes_value(true)
+                .value_name("IPS")
+                .conflicts_with_all(&["speed", "ipf"])
+                .help(
+                    "Run as few as 1 instruction per second with the CPU state shown, changed \
+                     values are highlighted",
+                ),
+        )
+        .arg(
+            Arg::with_name("turbo")
+                .long("turbo")
+                .takes_value(true)
+                .value_name("MULTIPLIER")
+                .default_value("4")
+                .help("How many times faster to run while Tab is held, from 2 to 16"),
+        )
+        .arg(
+            Arg::with_name("mute")
+                .long("mute")
+                .conflicts_with("bell")
+                .help("Don't beep while the sound timer is active, the same as --bell off"),
+        )
+        .arg(
+            Arg::with_name("bell")
+                .long("bell")
+                .takes_value(true)
+                .value_name("BELL")
+                .possible_values(&["audio", "visual", "both", "off"])
+                .default_value("audio")
+                .help("Beep, invert the window, or both while the sound timer is active"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Log every executed instruction and the registers it changed to FILE"),
+        )
+        .arg(
+            Arg::with_name("flags-dir")
+                .long("flags-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help(
+                    "Where to keep the flags XO-CHIP games save with FX75, e.g. high scores. \
+                     The directory of the ROM by default",
+                ),
+        )
+        .arg(
+            Arg::with_name("recent")
+                .long("recent")
+                .takes_value(true)
+                .min_values(0)
+                .value_name("N")
+                .conflicts_with_all(&["ROM", "batch"])
+                .help("Pick one of the ROMs that were run recently, or run the Nth most recent"),
+        )
+        .arg(
+            Arg::with_name("rom-dir")
+                .long("rom-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help(
+                    "Where to pick a ROM from when none is given, the current directory by default",
+                ),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .help(
+                    "Settings to use when they aren't given on the command line, \
+                     ~/.config/chip-8/chip8.toml by default",
+                ),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .value_name("SEED")
+                .help("Make random numbers the same on every run with the same SEED"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("replay")
+                .help("Record every key press and release to FILE"),
+        )
+        .arg(
+            Arg::with_name("record-video")
+                .long("record-video")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Record what's shown to FILE, an animated GIF or PNG by its extension"),
+        )
+        .arg(
+            Arg::with_name("record-seconds")
+                .long("record-seconds")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .help("Stop recording video after SECONDS, with --record-video or F10"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Play back the key presses recorded with --record instead of the keyboard"),
+        )
+        .arg(
+            Arg::with_name("variant")
+                .long("variant")
+                .takes_value(true)
+                .value_name("VARIANT")
+                .possible_values(&["chip-8", "eti-660", "hires", "xo-chip"])
+                .default_value("chip-8")
+                .help("The interpreter to emulate"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .value_name("PROFILE")
+                .possible_values(&["vip", "chip48", "schip", "xochip", "hires"])
+                .conflicts_with("variant")
+                .help(
+                    "Emulate an interpreter's variant, quirks, and speed all at once. --quirks, \
+                     --speed, and --ipf override it",
+                ),
+        )
+        .arg(
+            Arg::with_name("quirks")
+                .long("quirks")
+                .takes_value(true)
+                .value_name("QUIRKS")
+                .default_value("none")
+                .help(
+                    "How to run ambiguous instructions, a comma separated list of presets \
+                     (vip, schip, xochip) and quirks (shift-vy, load-store-i, jump-vx, clip, \
+                     vf-reset, display-wait, wrap-memory)",
+                ),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .long("scale")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("16")
+                .help("Start with a window N times the size of the display"),
+        )
+        .arg(
+            Arg::with_name("scale-mode")
+                .long("scale-mode")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(&["fit", "integer"])
+                .default_value("fit")
+                .help(
+                    "How to fill a resized window, as large as fits or only by whole multiples \
+                     so every pixel is the same size",
+                ),
+        )
+        .arg(
+            Arg::with_name("rotate")
+                .long("rotate")
+                .takes_value(true)
+                .value_name("DEGREES")
+                .possible_values(&["0", "90", "180", "270"])
+                .default_value("0")
+                .help("Rotate the picture clockwise, e.g. for portrait screens"),
+        )
+        .arg(
+            Arg::with_name("rotate-keypad")
+                .long("rotate-keypad")
+                .help("Turn the directional keys 2, 4, 6, and 8 along with the picture"),
+        );
+    #[cfg(feature = "gamepad")]
+    let app = app.arg(
+        Arg::with_name("controller-profiles")
+            .long("controller-profiles")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Game controller button mappings, optionally per ROM"),
+    );
+    #[cfg(feature = "scripting")]
+    let app = app.arg(
+        Arg::with_name("script")
+            .long("script")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Run the on_frame callbacks of a Lua script after every frame"),
+    );
+    #[cfg(feature = "control")]
+    let app = app.arg(
+        Arg::with_name("control")
+            .long("control")
+            .takes_value(true)
+            .value_name("ADDRESS")
+            .conflicts_with("headless")
+            .help("Accept JSON commands over TCP on ADDRESS, e.g. 127.0.0.1:4000"),
+    );
+    #[cfg(feature = "builtin-roms")]
+    let app = app
+        .arg(
+            Arg::with_name("builtin")
+                .long("builtin")
+                .takes_value(true)
+                .value_name("NAME")
+                .conflicts_with_all(&["ROM", "batch"])
+                .help("Run one of the ROMs built into the emulator instead of a file"),
+        )
+        .arg(
+            Arg::with_name("list-builtin")
+                .long("list-builtin")
+                .help("List the ROMs built into the emulator"),
+        );
+    let matches = app.get_matches();
+
+    if matches.subcommand_matches("repl").is_some() {
+        return repl::run();
+    }
+
+    if let Some(matches) = matches.subcommand_matches("assemble") {
+        let source_path = Path::new(matches.value_of("SOURCE").unwrap());
+        let output_path = matches
+            .value_of("output")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| source_path.with_extension("ch8"));
+        let source = std::fs::read_to_string(source_path)?;
+        let rom = if matches!(source_path.extension(), Some(extension) if extension == "8o") {
+            assemble_octo(&source)?
+        } else {
+            assemble(&source)?
+        };
+        std::fs::write(&output_path, &rom)?;
+        println!("Wrote {} bytes to {}", rom.len(), output_path.display());
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("analyze") {
+        let rom = load_rom(Path::new(matches.value_of("ROM").unwrap()))?;
+        print!("{}", chip_8::analyzer::analyze(&rom));
+
+        return Ok(());
+    }
+
+    #[cfg(feature = "builtin-roms")]
+    {
+        if matches.is_present("list-builtin") {
+            for builtin in chip_8::builtin_roms() {
+                println!("{:<8}  {}", builtin.name, builtin.description);
+            }
+
+            return Ok(());
+        }
+    }
+
+    let history_storage = history_storage();
+    let mut history = match &history_storage {
+        Some(storage) => History::load(storage).unwrap_or_else(|error| {
+            eprintln!("Warning: Can't read the remembered settings: {}", error);
+            History::default()
+        }),
+        None => History::default(),
+    };
+
+    if matches.is_present("disassemble") {
+        let (_, rom) = load_rom_arg(&matches, &history)?;
+
+        for line in symbolize(&rom) {
+            match line {
+                Line::Label(_) => println!("{}", line),
+                _ => println!("    {:<24}; {:#05x}", line.to_string(), line.address()),
+            }
+        }
+
+        return Ok(());
+    }
+
+    if matches.is_present("rom-info") {
+        let (_, rom) = load_rom_arg(&matches, &history)?;
+
+        println!("SHA-1: {}", rom_db::rom_id(&rom));
+        match RomDatabase::embedded().lookup(&rom) {
+            Some(info) => println!("{:#?}", info),
+            None => println!("Not in the ROM database"),
+        }
+
+        return Ok(());
+    }
+
+    if matches.is_present("batch") {
+        let cycles = matches.value_of("cycles").unwrap().parse::<usize>()?;
+        let jobs = matches
+            .values_of("ROM")
+            .unwrap()
+            .map(|path| Ok(Job::new(path, load_rom(Path::new(path))?, cycles)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let report = batch::run(jobs);
+
+        println!("{}", report);
+        if report.failed() > 0 {
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    let config = load_config(matches.value_of("config"))?;
+    let is_rom_given =
+        matches.is_present("ROM") || ROM_ALTERNATIVES.iter().any(|name| matches.is_present(name));
+    let rom_dir = matches
+        .value_of("rom-dir")
+        .or(config.rom_dir.as_deref())
+        .map(PathBuf::from);
+    let (rom_path, rom) = if is_rom_given || matches.is_present("headless") {
+        load_rom_arg(&matches, &history)?
+    } else {
+        match picker::pick(rom_dir.as_deref().unwrap_or_else(|| Path::new(".")))? {
+            Some(path) => {
+                let rom = load_rom(&path)?;
+                (path, rom)
+            }
+            None => return Ok(()),
+        }
+    };
+    let file_name = rom_path.file_name().and_then(|name| name.to_str());
+    let mut settings = config.settings_for(file_name.unwrap_or_default(), &rom);
+    // Headless runs should be reproducible, don't let earlier runs change them
+    if !matches.is_present("headless") {
+        settings.apply(&history.settings_for(&rom));
+    }
+    let arguments = Arguments {
+        matches: &matches,
+        settings,
+    };
+    history.remember(&rom, &arguments.given());
+    if !matches.is_present("builtin") {
+        if let Some(path) = std::fs::canonicalize(&rom_path)
+            .ok()
+            .and_then(|path| path.to_str().map(str::to_owned))
+        {
+            history.add_recent(&path);
+        }
+    }
+
+    let rotation = matches.value_of("rotate").unwrap().parse::<Rotation>()?;
+    let rotate_keypad = matches.is_present("rotate-keypad");
+    let profile = matches
+        .value_of("profile")
+        .map(str::parse::<Profile>)
+        .transpose()?;
+    let variant = match (profile, Variant::detect(&rom)) {
+        (Some(profile), _) => profile.variant(),
+        (None, Some(detected)) if matches.occurrences_of("variant") == 0 => {
+            println!("Running as {}, detected from the ROM", detected);
+            detected
+        }
+        (None, _) => matches.value_of("variant").unwrap().parse::<Variant>()?,
+    };
+    let quirks = arguments.value_of("quirks").unwrap().parse::<Quirks>()?;
+    let display = FramebufferDisplay::for_variant(variant);
+    for warning in variant
+        .validate_rom(&rom)
+        .map_err(|error| format!("Can't load `{}`: {}", rom_path.display(), error))?
+    {
+        eprintln!("Warning: {}", warning);
+    }
+
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = if matches.is_present("headless") {
+        None
+    } else {
+        create_gamepad(matches.value_of("controller-profiles"), &rom)?
+    };
+    let keyboard_keys =
+        input::keyboard_keys(&load_keymap(arguments.value_of("keymap").as_deref(), &rom)?);
+    let mut emulator = match profile {
+        Some(profile) => Emulator::with_profile(display, rom, profile)?,
+        None if arguments.is_present("quirks") => {
+            Emulator::with_variant(display, rom, variant, quirks)?
+        }
+        None => Emulator::for_rom(display, rom, variant)?,
+    };
+    if let Some(info) = emulator.rom_info() {
+        println!(
+            "Running {} with the settings from the ROM database",
+            info.name
+        );
+    }
+    // --quirks overrides the profile too
+    if profile.is_some() && arguments.is_present("quirks") {
+        emulator.set_quirks(quirks);
+    }
+
+    if let Some(speed) = arguments.value_of("speed") {
+        // The same range the speed hotkeys go through
+        let max_hz = emulation::MAX_CYCLES_PER_FRAME * 60;
+        match speed.parse::<u32>() {
+            Ok(hz) if hz > 0 && hz <= max_hz => emulator.set_cycles_per_second(hz),
+            _ => return Err(format!("Invalid speed `{}`, expected 1 to {}", speed, max_hz).into()),
+        }
+    }
+    if let Some(ipf) = matches.value_of("ipf") {
+        match ipf.parse::<u32>() {
+            Ok(cycles) if cycles > 0 => emulator.set_cycles_per_frame(cycles),
+            _ => return Err(format!("Invalid instructions per frame `{}`", ipf).into()),
+        }
+    }
+    let is_slow = match matches.value_of("slow") {
+        Some(ips) => match ips.parse::<u32>() {
+            // Slower than one instruction per frame, slow time down as a whole
+            Ok(ips) if ips > 0 && ips < 60 => {
+                emulator.set_cycles_per_frame(1);
+                emulator.set_speed_multiplier(ips as f32 / 60.0);
+                true
+            }
+            Ok(ips) if ips > 0 => {
+                emulator.set_cycles_per_second(ips);
+                true
+            }
+            _ => return Err(format!("Invalid instructions per second `{}`", ips).into()),
+        },
+        None => false,
+    };
+    // What releasing the turbo key goes back to
+    let normal_speed = emulator.speed_multiplier();
+
+    if let Some(seed) = matches.value_of("seed") {
+        let seed = seed
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid seed `{}`", seed))?;
+        emulator.set_rng(Box::new(Seeded::new(seed)));
+    }
+
+    if let Some(path) = matches.value_of("trace") {
+        let mut log = BufWriter::new(File::create(path)?);
+        emulator.set_trace(Box::new(move |event| {
+            let _ = writeln!(log, "{}", event);
+        }));
+    }
+
+    // Headless runs should be reproducible, don't let them save or load flags
+    if !matches.is_present("headless") {
+        let flags_dir = match matches.value_of("flags-dir") {
+            Some(dir) => PathBuf::from(dir),
+            None => rom_path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        emulator.set_flag_storage(Box::new(FileStorage::new(flags_dir)))?;
+        emulator.set_observer(Box::new(FlagErrorReporter));
+    }
+
+    let pokes = matches
+        .values_of("poke")
+        .map(|pokes| pokes.map(str::parse).collect::<Result<Vec<Poke>, _>>())
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+    for poke in &pokes {
+        emulator.poke(poke)?;
+    }
+    #[cfg(feature = "scripting")]
+    let script = match matches.value_of("script") {
+        Some(path) => Some((path.to_string(), std::fs::read_to_string(path)?)),
+        None => None,
+    };
+
+    if matches.is_present("headless") {
+        let cycles = matches.value_of("cycles").unwrap().parse::<usize>()?;
+        emulator.set_profiling(matches.is_present("hot-spots"));
+        #[cfg(feature = "scripting")]
+        let result = match &script {
+            Some((path, source)) => {
+                Script::new(source, path)?.run_cycles(&mut emulator, cycles as u64)
+            }
+            None => batch::run_cycles(&mut emulator, cycles).map_err(ScriptError::from),
+        };
+        #[cfg(not(feature = "scripting"))]
+        let result = batch::run_cycles(&mut emulator, cycles);
+        if let Some(path) = matches.value_of("dump-memory-on-exit") {
+            dump_memory(&emulator, Path::new(path))?;
+        }
+        if let Err(error) = result {
+            eprintln!("Stopped: {}", error);
+            std::process::exit(1);
+        }
+
+        match matches.value_of("dump").unwrap() {
+            "pbm" => print!("{}", batch::framebuffer_pbm(emulator.display())),
+            _ => println!("{:016x}", batch::framebuffer_hash(emulator.display())),
+        }
+        if let Some(report) = emulator.profile_report() {
+            eprint!("{}", report);
+        }
+
+        return Ok(());
+    }
+
+    let bell = if matches.is_present("mute") {
+        Bell::Off
+    } else {
+        matches.value_of("bell").unwrap().parse::<Bell>()?
+    };
+    let (sound, visual_bell) = create_sound(bell);
+    emulator.set_sound(Box::new(sound));
+
+    let mut palette = match arguments.value_of("palette") {
+        Some(palette) => palette.parse::<Palette>()?,
+        None => emulator
+            .rom_info()
+            .and_then(|info| info.palette)
+            .unwrap_or(Palette::CLASSIC),
+    };
+    emulator.set_palette(palette);
+    let ghosting = matches
+        .value_of("ghosting")
+        .map(|decay| match decay.parse::<f32>() {
+            Ok(decay) if (0.0..1.0).contains(&decay) => Ok(Ghosting::new(decay)),
+            _ => Err(format!(
+                "Invalid ghosting `{}`, expected 0.0 up to 1.0",
+                decay
+            )),
+        })
+        .transpose()?;
+    let keymap = emulator
+        .rom_info()
+        .map_or_else(Keymap::default, |info| info.keymap);
+
+    let scale = arguments.value_of("scale").unwrap();
+    let scale = match scale.parse::<usize>() {
+        Ok(scale) if (1..=MAX_SCALE).contains(&scale) => scale,
+        _ => return Err(format!("Invalid scale `{}`, expected 1 to {}", scale, MAX_SCALE).into()),
+    };
+    if let Some(mut storage) = history_storage {
+        if let Err(error) = history.save(&mut storage) {
+            eprintln!("Warning: Can't remember the settings: {}", error);
+        }
+    }
+    let scale_mode = matches
+        .value_of("scale-mode")
+        .unwrap()
+        .parse::<ScaleMode>()?;
+    let mut filters = Filters::new(
+        matches
+            .values_of("filter")
+            .into_iter()
+            .flatten()
+            .map(|name| Ok(name.parse::<FilterName>()?.create()))
+            .collect::<Result<Vec<_>, String>>()?,
+    );
+    let record_seconds = matches
+        .value_of("record-seconds")
+        .map(|seconds| match seconds.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+            _ => Err(format!("Invalid number of seconds `{}`", seconds)),
+        })
+        .transpose()?;
+    let turbo = matches.value_of("turbo").unwrap();
+    let turbo = match turbo.parse::<f32>() {
+        Ok(multiplier) if (MIN_TURBO..=MAX_TURBO).contains(&multiplier) => multiplier,
+        _ => return Err(format!("Invalid turbo multiplier `{}`", turbo).into()),
+    };
+    let mut is_turbo = false;
+    let mut recorder = match matches.value_of("record-video") {
+        Some(path) => {
+            let format = RecordingFormat::from_path(Path::new(path))
+                .ok_or_else(|| format!("Can't record to `{}`, use a .gif or .png file", path))?;
+            Some(Recorder::new(
+                PathBuf::from(path),
+                format,
+                rotation,
+                scale,
+                record_seconds,
+            ))
+        }
+        None => None,
+    };
+    let mut window = create_window(emulator.display(), rotation, scale)?;
+    let mut last_frame: Option<Frame> = None;
+    let mut last_window_size = window.get_size();
+    let mut title = String::from(WINDOW_TITLE);
+    let mut is_flashing = false;
+    let mut needs_redraw = false;
+    let mut input = MiniFBInput::new();
+    let mut sprite_window: Option<SpriteWindow> = None;
+    let mut memory_window: Option<MemoryWindow> = None;
+    let mut code_window: Option<CodeWindow> = None;
+    let mut pause_menu: Option<PauseMenu> = None;
+    // Load ROM in the pause menu picks from the directory of the ROM without a --rom-dir
+    let menu_rom_dir = rom_dir.unwrap_or_else(|| match rom_path.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    });
+
+    let (command_sender, command_receiver) = mpsc::channel();
+    let (frame_sender, frame_receiver) = mpsc::sync_channel(1);
+    let (recycle_sender, recycle_receiver) = mpsc::channel();
+    let mut breakpoints = Breakpoints::new();
+    for breakpoint in matches.values_of("break").into_iter().flatten() {
+        breakpoints.add(breakpoint.parse::<Breakpoint>()?);
+    }
+
+    let mut debugger = Debugger::with_breakpoints(emulator, breakpoints);
+    let start_stopped = matches.is_present("debug");
+    if start_stopped {
+        debugger.emulator_mut().set_undo_limit(UNDO_LIMIT);
+        println!("Paused at {:#05x}, type `help` for commands", debugger.pc());
+    }
+
+    let cheats = match matches.value_of("cheats") {
+        Some(path) => Some(
+            load_cheats(Path::new(path))?.ok_or_else(|| format!("No cheat file at `{}`", path))?,
+        ),
+        None => load_cheats(&cheats::path_for(&rom_path))?,
+    };
+
+    let session = if let Some(path) = matches.value_of("record") {
+        Session::Record(InputRecorder::new(BufWriter::new(File::create(path)?))?)
+    } else if let Some(path) = matches.value_of("replay") {
+        Session::Replay(InputReplayer::parse(&std::fs::read_to_string(path)?)?)
+    } else {
+        Session::Live
+    };
+
+    console::spawn(command_sender.clone());
+    #[cfg(feature = "control")]
+    {
+        if let Some(address) = matches.value_of("control") {
+            control::spawn(address, command_sender.clone())?;
+        }
+    }
+    let rom_name = emulation::rom_name(&rom_path);
+    let emulation_thread = thread::spawn(move || {
+        emulation::run(
+            debugger,
+            rom_name,
+            start_stopped,
+            session,
+            ghosting,
+            command_receiver,
+            frame_sender,
+            recycle_receiver,
+        )
+    });
+    if is_slow {
+        let _ = command_sender.send(Command::ToggleOverlay);
+    }
+    if let Some(cheats) = cheats {
+        let _ = command_sender.send(Command::SetCheats(cheats));
+    }
+    #[cfg(feature = "scripting")]
+    {
+        if let Some((name, source)) = script {
+            let _ = command_sender.send(Command::LoadScript { name, source });
+        }
+    }
+
+    let auto_pause = !matches.is_present("no-auto-pause");
+    let mut is_focused = true;
+
+    while window.is_open() {
+        // Looking at the debug windows doesn't count as leaving the game
+        let is_active = window.is_active()
+            || sprite_window.iter_mut().any(|sprites| sprites.is_active())
+            || memory_window.iter_mut().any(|memory| memory.is_active())
+            || code_window.iter_mut().any(|code| code.is_active());
+        if auto_pause && is_active != is_focused {
+            is_focused = !is_focused;
+            let command = if is_focused {
+                Command::Resume
+            } else {
+                Command::Pause
+            };
+            // The pause menu keeps the game paused until it's closed
+            if !is_focused || pause_menu.is_none() {
+                let _ = command_sender.send(command);
+            }
+        }
+
+        if let Some(menu) = pause_menu.as_mut() {
+            let quirks = last_frame
+                .as_ref()
+                .map_or_else(Quirks::default, |frame| frame.quirks);
+            let mut is_closing = false;
+            let mut is_quitting = false;
+            for key in window.get_keys_pressed(KeyRepeat::Yes).unwrap_or_default() {
+                match menu.handle(key, palette, quirks) {
+                    None => {}
+                    Some(Action::Resume) => is_closing = true,
+                    Some(Action::Reset) => {
+                        reset(&command_sender, &pokes);
+                        is_closing = true;
+                    }
+                    Some(Action::LoadRom) => {
+                        match picker::pick(&menu_rom_dir) {
+                            Ok(Some(path)) => match load_rom(&path) {
+                                Ok(rom) => {
+                                    let name = emulation::rom_name(&path);
+                                    let _ = command_sender.send(Command::LoadRom { name, rom });
+                                    switch_cheats(&command_sender, &path);
+                                }
+                                Err(error) => {
+                                    eprintln!("Can't load `{}`: {}", path.display(), error)
+                                }
+                            },
+                            Ok(None) => {}
+                            Err(error) => eprintln!("{}", error),
+                        }
+                        is_closing = true;
+                    }
+                    Some(Action::SetPalette(new_palette)) => {
+                        palette = new_palette;
+                        let _ = command_sender.send(Command::SetPalette(palette));
+                    }
+                    Some(Action::Faster) => {
+                        let _ = command_sender.send(Command::Faster);
+                    }
+                    Some(Action::Slower) => {
+                        let _ = command_sender.send(Command::Slower);
+                    }
+                    Some(Action::SetQuirks(quirks)) => {
+                        let _ = command_sender.send(Command::SetQuirks(quirks));
+                    }
+                    Some(Action::Quit) => is_quitting = true,
+                }
+                needs_redraw = true;
+            }
+
+            if is_quitting {
+                break;
+            }
+            if is_closing {
+                pause_menu = None;
+                let _ = command_sender.send(Command::Resume);
+            }
+        } else if window.is_key_pressed(Key::Escape, KeyRepeat::No) {
+            pause_menu = Some(PauseMenu::new());
+            let _ = command_sender.send(Command::Pause);
+            needs_redraw = true;
+        }
+
+        if window.is_key_pressed(Key::F1, KeyRepeat::No) {
+            reset(&command_sender, &pokes);
+        }
+
+        if pause_menu.is_none() && window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            let _ = command_sender.send(Command::TogglePause);
+        }
+        if pause_menu.is_none() && window.is_key_pressed(Key::N, KeyRepeat::Yes) {
+            let _ = command_sender.send(Command::StepFrame);
+        }
+
+        if window.is_key_pressed(Key::F2, KeyRepeat::No) {
+            palette = palette.next_preset();
+            println!("Palette: {}", palette);
+            let _ = command_sender.send(Command::SetPalette(palette));
+        }
+
+        if window.is_key_pressed(Key::F3, KeyRepeat::No) {
+            let _ = command_sender.send(Command::ToggleOverlay);
+        }
+        if window.is_key_pressed(Key::F4, KeyRepeat::No) {
+            let is_enabled = filters.toggle();
+            println!("Filters {}", if is_enabled { "on" } else { "off" });
+            needs_redraw = true;
+        }
+        let had_debug_windows =
+            sprite_window.is_some() || memory_window.is_some() || code_window.is_some();
+        if window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            sprite_window = match sprite_window.take() {
+                Some(_) => None,
+                None => Some(SpriteWindow::new()?),
+            };
+        }
+        if window.is_key_pressed(Key::F6, KeyRepeat::No) {
+            let _ = command_sender.send(Command::ToggleScope);
+        }
+        if window.is_key_pressed(Key::F7, KeyRepeat::No) {
+            memory_window = match memory_window.take() {
+                Some(_) => None,
+                None => Some(MemoryWindow::new()?),
+            };
+        }
+        if window.is_key_pressed(Key::F8, KeyRepeat::No) {
+            code_window = match code_window.take() {
+                Some(_) => None,
+                None => Some(CodeWindow::new()?),
+            };
+        }
+        if let Some(sprites) = sprite_window.as_mut() {
+            if sprites.is_open() {
+                sprites.update(&palette)?;
+            } else {
+                sprite_window = None;
+            }
+        }
+        if let Some(memory) = memory_window.as_mut() {
+            if memory.is_open() {
+                memory.update()?;
+            } else {
+                memory_window = None;
+            }
+        }
+        if let Some(code) = code_window.as_mut() {
+            if code.is_open() {
+                code.update()?;
+            } else {
+                code_window = None;
+            }
+        }
+        // The debug windows draw from the memory sent along with the frames
+        let has_debug_windows =
+            sprite_window.is_some() || memory_window.is_some() || code_window.is_some();
+        if has_debug_windows != had_debug_windows {
+            let _ = command_sender.send(Command::SendMemory(has_debug_windows));
+        }
+        if matches!(recorder.as_ref(), Some(recorder) if recorder.is_done()) {
+            finish_recording(recorder.take().unwrap());
+        }
+        if window.is_key_pressed(Key::F10, KeyRepeat::No) {
+            match recorder.take() {
+                Some(recorder) => finish_recording(recorder),
+                None => {
+                    let mut new_recorder = Recorder::new(
+                        timestamped_path(RecordingFormat::Gif.extension()),
+                        RecordingFormat::Gif,
+                        rotation,
+                        scale,
+                        record_seconds,
+                    );
+                    if let Some(frame) = last_frame.as_ref() {
+                        new_recorder.show(frame);
+                    }
+                    println!("Recording, press F10 again to stop");
+                    recorder = Some(new_recorder);
+                }
+            }
+        }
+        if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            if let Some(frame) = last_frame.as_ref() {
+                match save_screenshot(frame, rotation, scale) {
+                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                    Err(error) => eprintln!("Saving the screenshot failed: {}", error),
+                }
+            }
+        }
+        if window.is_key_pressed(Key::Equal, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadPlus, KeyRepeat::Yes)
+        {
+            let _ = command_sender.send(Command::Faster);
+        }
+        if window.is_key_pressed(Key::Minus, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadMinus, KeyRepeat::Yes)
+        {
+            let _ = command_sender.send(Command::Slower);
+        }
+        if window.is_key_down(Key::Tab) != is_turbo {
+            is_turbo = !is_turbo;
+            let multiplier = if is_turbo { turbo } else { normal_speed };
+            let _ = command_sender.send(Command::SetSpeedMultiplier(multiplier));
+        }
+
+        input.update_key_state(&window, &keyboard_keys);
+        input.press_arrow_keys(&window, &keymap);
+        #[cfg(feature = "gamepad")]
+        {
+            if let Some(gamepad) = gamepad.as_mut() {
+                gamepad.update(&mut input);
+            }
+        }
+        let keypad = if pause_menu.is_some() {
+            // Don't leave keys held down under the menu
+            MiniFBInput::new()
+        } else if rotate_keypad {
+            input.remapped(|key| rotation.remap_key(key))
+        } else {
+            input
+        };
+        if command_sender.send(Command::Input(keypad)).is_err() {
+            break;
+        }
+
+        let flash = matches!(&visual_bell, Some(bell) if bell.is_active());
+        let menu_lines = |frame: &Frame| match &pause_menu {
+            Some(menu) => menu.lines(&palette, frame.cycles_per_frame, &frame.quirks),
+            None => Vec::new(),
+        };
+        match frame_receiver.recv_timeout(UI_FRAME_TIMEOUT) {
+            Ok(mut frame) => {
+                let new_title = window_title(&frame);
+                if new_title != title {
+                    window.set_title(&new_title);
+                    title = new_title;
+                }
+                if let Some(memory) = frame.memory.take() {
+                    if let Some(window) = memory_window.as_mut() {
+                        window.show(memory.clone(), frame.pc, frame.i);
+                    }
+                    if let Some(code) = code_window.as_mut() {
+                        code.show(memory.clone(), frame.pc);
+                    }
+                    if let Some(sprites) = sprite_window.as_mut() {
+                        sprites.show(memory);
+                    }
+                }
+                present(
+                    &mut window,
+                    &frame,
+                    rotation,
+                    scale_mode,
+                    palette.background(),
+                    &filters,
+                    flash,
+                    &menu_lines(&frame),
+                )?;
+                is_flashing = flash;
+                needs_redraw = false;
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.show(&frame);
+                }
+                if let Some(previous) = last_frame.replace(frame) {
+                    let _ = recycle_sender.send(previous.pixels);
+                }
+                last_window_size = window.get_size();
+            }
+            Err(RecvTimeoutError::Timeout) => match last_frame.as_ref() {
+                // Redraw at the new size, with the bell, or with the filters toggled right away
+                // rather than on the next change
+                Some(frame)
+                    if window.get_size() != last_window_size
+                        || flash != is_flashing
+                        || needs_redraw =>
+                {
+                    present(
+                        &mut window,
+                        frame,
+                        rotation,
+                        scale_mode,
+                        palette.background(),
+                        &filters,
+                        flash,
+                        &menu_lines(frame),
+                    )?;
+                    is_flashing = flash;
+                    needs_redraw = false;
+                    last_window_size = window.get_size();
+                }
+                _ => window.update(),
+            },
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if let Some(recorder) = recorder.take() {
+        finish_recording(recorder);
+    }
+
+    drop(command_sender);
+    drop(frame_receiver);
+    let debugger = emulation_thread
+        .join()
+        .map_err(|_| "The emulation thread panicked")?;
+    if let Some(path) = matches.value_of("dump-memory-on-exit") {
+        dump_memory(debugger.emulator(), Path::new(path))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! A second window that shows memory as hex bytes, for watching what a ROM keeps where.
+use chip_8::overlay::{self, Line};
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+/// Bytes per row, as many as fit next to the address.
+const ROW_SIZE: usize = 8;
+/// Where browsing starts, the font before it is rarely interesting.
+const ROM_START: usize = 0x200;
+
+/// Shows the memory last passed to [`MemoryWindow::show`], highlighting the instruction at the
+/// PC and the byte at I.
+///
+/// Up and Down scroll by a row, Page Up and Page Down by a window full. Tab jumps to I, Home to
+/// the start of the ROM.
+pub struct MemoryWindow {
+    window: Window,
+    memory: Vec<u8>,
+    pc: u16,
+    i: u16,
+    address: usize,
+    title: String,
+}
+
+impl MemoryWindow {
+    pub fn new() -> minifb::Result<Self> {
+        let opts = WindowOptions {
+            scale: Scale::X2,
+            ..WindowOptions::default()
+        };
+
+        Ok(Self {
+            window: Window::new("Memory", WIDTH, HEIGHT, opts)?,
+            memory: Vec::new(),
+            pc: 0,
+            i: 0,
+            address: ROM_START,
+            title: String::new(),
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn is_active(&mut self) -> bool {
+        self.window.is_active()
+    }
+
+    pub fn show(&mut self, memory: Vec<u8>, pc: u16, i: u16) {
+        self.memory = memory;
+        self.pc = pc;
+        self.i = i;
+    }
+
+    /// Handle the keys pressed in the window and redraw it.
+    pub fn update(&mut self) -> minifb::Result<()> {
+        self.handle_keys();
+
+        let title = format!("Memory at {:#05x}, I: {:#05x}", self.address, self.i);
+        if title != self.title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
+
+        let mut pixels = vec![0; WIDTH * HEIGHT];
+        overlay::draw_lines(&mut pixels, WIDTH, HEIGHT, &self.lines());
+
+        self.window.update_with_buffer(&pixels, WIDTH, HEIGHT)
+    }
+
+    /// A row per line from the current address, e.g. `0200: 00 E0 A2 2A 60 0C 61 08`.
+    fn lines(&self) -> Vec<Line> {
+        let pc = self.pc as usize;
+        let end = (self.address + self.page_size()).min(self.memory.len());
+        let bytes = self.memory.get(self.address..end).unwrap_or(&[]);
+
+        bytes
+            .chunks(ROW_SIZE)
+            .enumerate()
+            .map(|(row, bytes)| {
+                let start = self.address + row * ROW_SIZE;
+                let mut line = Line::from(format!("{:04X}:", start));
+                for (offset, byte) in bytes.iter().enumerate() {
+                    let address = start + offset;
+                    let column = line.text.len() + 1;
+                    line.text.push_str(&format!(" {:02X}", byte));
+                    if address == self.i as usize || address == pc || address == pc + 1 {
+                        line.highlighted.push(column..column + 2);
+                    }
+                }
+
+                line
+            })
+            .collect()
+    }
+
+    fn handle_keys(&mut self) {
+        if self.is_pressed(Key::Down) {
+            self.scroll_to(self.address + ROW_SIZE);
+        }
+        if self.is_pressed(Key::Up) {
+            self.scroll_to(self.address.saturating_sub(ROW_SIZE));
+        }
+        if self.is_pressed(Key::PageDown) {
+            self.scroll_to(self.address + self.page_size());
+        }
+        if self.is_pressed(Key::PageUp) {
+            self.scroll_to(self.address.saturating_sub(self.page_size()));
+        }
+        if self.window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            let i = self.i as usize;
+            self.scroll_to(i - i % ROW_SIZE);
+        }
+        if self.window.is_key_pressed(Key::Home, KeyRepeat::No) {
+            self.scroll_to(ROM_START);
+        }
+    }
+
+    fn is_pressed(&self, key: Key) -> bool {
+        self.window.is_key_pressed(key, KeyRepeat::Yes)
+    }
+
+    /// The number of bytes that fit in the window.
+    fn page_size(&self) -> usize {
+        let (_, rows) = overlay::text_capacity(WIDTH, HEIGHT);
+
+        rows * ROW_SIZE
+    }
+
+    fn scroll_to(&mut self, address: usize) {
+        if address < self.memory.len() {
+            self.address = address;
+        }
+    }
+}
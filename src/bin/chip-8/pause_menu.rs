@@ -0,0 +1,142 @@
+//! The menu shown over the game with Esc, so that the hotkeys aren't the only way to get at
+//! what they do.
+use chip_8::overlay::Line;
+use chip_8::{Palette, Quirks};
+use minifb::Key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Resume,
+    Reset,
+    LoadRom,
+    Palette,
+    Speed,
+    Quirks,
+    Quit,
+}
+
+const ITEMS: [Item; 7] = [
+    Item::Resume,
+    Item::Reset,
+    Item::LoadRom,
+    Item::Palette,
+    Item::Speed,
+    Item::Quirks,
+    Item::Quit,
+];
+
+/// What the frontend should do after a key press in the menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Close the menu and continue.
+    Resume,
+    Reset,
+    /// Pick another ROM to run.
+    LoadRom,
+    SetPalette(Palette),
+    Faster,
+    Slower,
+    SetQuirks(Quirks),
+    Quit,
+}
+
+/// Up and Down move the selection and Enter picks an item. Left and Right change the palette,
+/// speed, and quirk profile in place. Esc closes the menu again.
+#[derive(Debug, Default)]
+pub struct PauseMenu {
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `key` while the game runs with `palette` and `quirks`.
+    pub fn handle(&mut self, key: Key, palette: Palette, quirks: Quirks) -> Option<Action> {
+        let item = ITEMS[self.selected];
+        match key {
+            Key::Up => self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len(),
+            Key::Down => self.selected = (self.selected + 1) % ITEMS.len(),
+            Key::Escape => return Some(Action::Resume),
+            Key::Enter | Key::NumPadEnter => {
+                return match item {
+                    Item::Resume => Some(Action::Resume),
+                    Item::Reset => Some(Action::Reset),
+                    Item::LoadRom => Some(Action::LoadRom),
+                    Item::Quit => Some(Action::Quit),
+                    // Enter cycles forward like Right
+                    _ => self.change(item, true, palette, quirks),
+                };
+            }
+            Key::Left => return self.change(item, false, palette, quirks),
+            Key::Right => return self.change(item, true, palette, quirks),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn change(
+        &self,
+        item: Item,
+        is_forward: bool,
+        palette: Palette,
+        quirks: Quirks,
+    ) -> Option<Action> {
+        match item {
+            Item::Palette => {
+                let presets = Palette::PRESETS.iter().map(|(_, preset)| *preset);
+                Some(Action::SetPalette(cycle(presets, palette, is_forward)))
+            }
+            Item::Speed if is_forward => Some(Action::Faster),
+            Item::Speed => Some(Action::Slower),
+            Item::Quirks => {
+                let presets = Quirks::presets();
+                let presets = presets.iter().map(|(_, preset)| *preset);
+                Some(Action::SetQuirks(cycle(presets, quirks, is_forward)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The menu with the current values, the selected item highlighted.
+    pub fn lines(&self, palette: &Palette, cycles_per_frame: u32, quirks: &Quirks) -> Vec<Line> {
+        let mut lines = vec![Line::from("Paused".to_owned()), Line::default()];
+        for (index, item) in ITEMS.iter().enumerate() {
+            let text = match item {
+                Item::Resume => "Resume".to_owned(),
+                Item::Reset => "Reset".to_owned(),
+                Item::LoadRom => "Load ROM".to_owned(),
+                Item::Palette => format!("Palette       < {} >", palette),
+                Item::Speed => format!("Speed         < {} per frame >", cycles_per_frame),
+                Item::Quirks => format!("Quirk profile < {} >", quirks.name().unwrap_or("custom")),
+                Item::Quit => "Quit".to_owned(),
+            };
+            lines.push(if index == self.selected {
+                Line::highlighted(text)
+            } else {
+                Line::from(text)
+            });
+        }
+
+        lines
+    }
+}
+
+/// The preset after or before `current`, the first or last one when `current` isn't a preset.
+fn cycle<T: PartialEq>(presets: impl Iterator<Item = T>, current: T, is_forward: bool) -> T {
+    let presets = presets.collect::<Vec<_>>();
+    let count = presets.len();
+    let index = match (
+        presets.iter().position(|preset| *preset == current),
+        is_forward,
+    ) {
+        (Some(index), true) => (index + 1) % count,
+        (Some(index), false) => (index + count - 1) % count,
+        (None, true) => 0,
+        (None, false) => count - 1,
+    };
+
+    presets.into_iter().nth(index).unwrap()
+}
@@ -0,0 +1,202 @@
+//! A window to pick the ROM to run from a directory, when the frontend is started without one.
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chip_8::overlay::{self, Line};
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+/// Files without an extension are listed too, many older ROMs don't have one.
+const ROM_EXTENSIONS: &[&str] = &["ch8", "c8", "sc8", "xo8"];
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Parent(PathBuf),
+    Directory(PathBuf),
+    Rom(PathBuf),
+}
+
+impl Entry {
+    fn name(&self) -> String {
+        match self {
+            Entry::Parent(_) => "../".to_owned(),
+            Entry::Directory(path) => format!("{}/", file_name(path)),
+            Entry::Rom(path) => file_name(path),
+        }
+    }
+}
+
+/// Where the menu is at after a key press.
+enum State {
+    Browsing,
+    Picked(PathBuf),
+    Cancelled,
+}
+
+/// The directories and ROMs in a directory, directories first.
+///
+/// Up and Down move the selection, Page Up and Page Down by a window full, Home and End to the
+/// first and last entry. Enter runs the ROM or opens the directory, Backspace opens the parent
+/// directory, and Escape quits.
+struct Menu {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// The first entry shown, the list scrolls to keep the selection in view.
+    top: usize,
+}
+
+impl Menu {
+    fn open(dir: &Path) -> io::Result<Self> {
+        let dir = fs::canonicalize(dir)?;
+        let mut entries = Vec::new();
+        if let Some(parent) = dir.parent() {
+            entries.push(Entry::Parent(parent.to_path_buf()));
+        }
+
+        let mut listed = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if file_name(&path).starts_with('.') {
+                continue;
+            }
+
+            if path.is_dir() {
+                listed.push(Entry::Directory(path));
+            } else if is_rom(&path) {
+                listed.push(Entry::Rom(path));
+            }
+        }
+        listed.sort_by(|a, b| match (a, b) {
+            (Entry::Directory(_), Entry::Rom(_)) => Ordering::Less,
+            (Entry::Rom(_), Entry::Directory(_)) => Ordering::Greater,
+            _ => a.name().to_lowercase().cmp(&b.name().to_lowercase()),
+        });
+        entries.extend(listed);
+
+        Ok(Self {
+            dir,
+            entries,
+            selected: 0,
+            top: 0,
+        })
+    }
+
+    fn handle(&mut self, key: Key, rows: usize) -> State {
+        let last = self.entries.len().saturating_sub(1);
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(last),
+            Key::PageUp => self.selected = self.selected.saturating_sub(rows),
+            Key::PageDown => self.selected = (self.selected + rows).min(last),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = last,
+            Key::Backspace => {
+                if let Some(parent) = self.dir.parent().map(Path::to_path_buf) {
+                    self.change_dir(&parent);
+                }
+            }
+            Key::Enter | Key::NumPadEnter => match self.entries.get(self.selected).cloned() {
+                Some(Entry::Parent(dir)) | Some(Entry::Directory(dir)) => self.change_dir(&dir),
+                Some(Entry::Rom(path)) => return State::Picked(path),
+                None => {}
+            },
+            Key::Escape => return State::Cancelled,
+            _ => {}
+        }
+
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+
+        State::Browsing
+    }
+
+    /// Show `dir` instead, a directory that can't be read is left alone.
+    fn change_dir(&mut self, dir: &Path) {
+        if let Ok(menu) = Menu::open(dir) {
+            *self = menu;
+        }
+    }
+
+    /// The directory followed by `rows` entries from the top, at most `columns` long.
+    fn lines(&self, columns: usize, rows: usize) -> Vec<Line> {
+        let dir = self.dir.to_string_lossy();
+        let mut lines = vec![Line::from(tail(&dir, columns))];
+        if self.entries.is_empty() {
+            lines.push(Line::from("No ROMs here".to_owned()));
+        }
+
+        for (index, entry) in self.entries.iter().enumerate().skip(self.top).take(rows) {
+            let name = tail(&entry.name(), columns);
+            lines.push(if index == self.selected {
+                Line::highlighted(name)
+            } else {
+                Line::from(name)
+            });
+        }
+
+        lines
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn is_rom(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let extension = extension.to_string_lossy().to_lowercase();
+            ROM_EXTENSIONS.contains(&extension.as_str())
+        }
+        None => true,
+    }
+}
+
+/// The end of `text` if it's longer than `columns` characters, the end of a path tells more.
+fn tail(text: &str, columns: usize) -> String {
+    let length = text.chars().count();
+
+    text.chars().skip(length.saturating_sub(columns)).collect()
+}
+
+/// Show the ROMs in `dir` in a window until one is picked, `None` if the menu is closed first.
+pub fn pick(dir: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let mut menu =
+        Menu::open(dir).map_err(|error| format!("Can't list `{}`: {}", dir.display(), error))?;
+    let opts = WindowOptions {
+        scale: Scale::X2,
+        ..WindowOptions::default()
+    };
+    let mut window = Window::new("CHIP-8 - Pick a ROM", WIDTH, HEIGHT, opts)?;
+    let (columns, lines) = overlay::text_capacity(WIDTH, HEIGHT);
+    // The first line shows the directory
+    let rows = lines.saturating_sub(1).max(1);
+
+    while window.is_open() {
+        for key in window.get_keys_pressed(KeyRepeat::Yes).unwrap_or_default() {
+            match menu.handle(key, rows) {
+                State::Browsing => {}
+                State::Picked(path) => return Ok(Some(path)),
+                State::Cancelled => return Ok(None),
+            }
+        }
+
+        let mut pixels = vec![0; WIDTH * HEIGHT];
+        overlay::draw_lines(&mut pixels, WIDTH, HEIGHT, &menu.lines(columns, rows));
+        window.update_with_buffer(&pixels, WIDTH, HEIGHT)?;
+        thread::sleep(Duration::from_millis(16));
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chip_8::recording::{Recording, RecordingFormat};
+use chip_8::{Rotation, Screenshot};
+
+use crate::emulation::Frame;
+
+/// Records the frames shown in the window, as shown, to an animated GIF or APNG.
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordingFormat,
+    rotation: Rotation,
+    limit: Option<Duration>,
+    started: Instant,
+    recording: Recording,
+    /// The frame on screen and since when, added to the recording once it's replaced.
+    current: Option<(Screenshot, Instant)>,
+}
+
+impl Recorder {
+    /// Record to `path`, stopping after `limit` if given.
+    pub fn new(
+        path: PathBuf,
+        format: RecordingFormat,
+        rotation: Rotation,
+        scale: usize,
+        limit: Option<Duration>,
+    ) -> Self {
+        Self {
+            path,
+            format,
+            rotation,
+            limit,
+            started: Instant::now(),
+            recording: Recording::with_scale(scale),
+            current: None,
+        }
+    }
+
+    /// `frame` is shown from now on.
+    pub fn show(&mut self, frame: &Frame) {
+        let now = Instant::now();
+        if let Some((previous, since)) = self.current.take() {
+            self.recording.push(previous, now - since);
+        }
+
+        let (width, height) = self.rotation.dimensions(frame.width, frame.height);
+        let pixels = self
+            .rotation
+            .apply(&frame.pixels, frame.width, frame.height);
+        self.current = Some((Screenshot::new(pixels, width, height), now));
+    }
+
+    /// Whether the recording has gone on for as long as it should.
+    pub fn is_done(&self) -> bool {
+        matches!(self.limit, Some(limit) if self.started.elapsed() >= limit)
+    }
+
+    /// Stop recording and write the file, returning its path.
+    pub fn finish(mut self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let end = match self.limit {
+            Some(limit) => Instant::now().min(self.started + limit),
+            None => Instant::now(),
+        };
+        if let Some((frame, since)) = self.current.take() {
+            self.recording
+                .push(frame, end.saturating_duration_since(since));
+        }
+
+        std::fs::write(&self.path, self.recording.encode(self.format)?)?;
+
+        Ok(self.path)
+    }
+}
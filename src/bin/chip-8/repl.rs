@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use chip_8::batch::NullInput;
+use chip_8::{Display, Emulator, FramebufferDisplay, Opcode, Quirks};
+
+const HELP: &str = "Type one or more opcodes in hex, e.g. `6A05 A050 DA05`, or instructions
+separated by `;`, e.g. `LD VA, 5; LD I, 0x50; DRW VA, VA, 5`, to execute them.
+Commands:
+  screen  Show the display
+  reset   Start over with a blank machine
+  help    Show this message
+  quit    Exit";
+
+/// An interactive prompt that executes opcodes and instructions on a blank machine as they
+/// are typed.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let mut emulator = Emulator::new(FramebufferDisplay::default(), Vec::new(), Quirks::default())?;
+
+    println!("{}\n\n{}", HELP, emulator);
+    prompt()?;
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            "help" => println!("{}", HELP),
+            "screen" => print_screen(&emulator),
+            "reset" => {
+                emulator = emulator.reset();
+                println!("{}", emulator);
+            }
+            input => {
+                let opcodes = match parse_line(input) {
+                    Ok(opcodes) => opcodes,
+                    Err(error) => {
+                        println!("{}", error);
+                        prompt()?;
+                        continue;
+                    }
+                };
+
+                for opcode in opcodes {
+                    if let Err(error) = emulator.execute(opcode, &NullInput) {
+                        println!("{:04X}: {}", opcode, error);
+                        break;
+                    }
+
+                    if emulator.display().is_dirty() {
+                        print_screen(&emulator);
+                    }
+                }
+
+                println!("{}", emulator);
+            }
+        }
+
+        prompt()?;
+    }
+
+    Ok(())
+}
+
+fn prompt() -> io::Result<()> {
+    print!("> ");
+    io::stdout().flush()
+}
+
+/// The opcodes in `line`, either hex opcodes separated by whitespace or instructions and hex
+/// opcodes separated by `;`.
+fn parse_line(line: &str) -> Result<Vec<u16>, String> {
+    let words = line.split_whitespace().map(parse_opcode).collect();
+    if let Some(opcodes) = words {
+        return Ok(opcodes);
+    }
+
+    line.split(';')
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .map(parse_statement)
+        .collect()
+}
+
+/// A hex opcode, or an instruction as the disassembler prints it, e.g. `LD I, 0x50`.
+fn parse_statement(statement: &str) -> Result<u16, String> {
+    if let Some(opcode) = parse_opcode(statement) {
+        return Ok(opcode);
+    }
+
+    let instruction = statement.parse::<Opcode>().map_err(|error| {
+        format!(
+            "`{}` is not an opcode or instruction: {}",
+            statement, error.message
+        )
+    })?;
+    match instruction.encode().as_slice() {
+        [high, low] => Ok(u16::from(*high) << 8 | u16::from(*low)),
+        _ => Err(format!(
+            "`{}` is not 2 bytes, only 2 byte instructions can be executed",
+            statement
+        )),
+    }
+}
+
+fn parse_opcode(word: &str) -> Option<u16> {
+    let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+    if digits.len() != 4 {
+        return None;
+    }
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn print_screen(emulator: &Emulator<FramebufferDisplay>) {
+    let display = emulator.display();
+    let framebuffer = display.rgba_framebuffer();
+
+    for row in framebuffer.chunks(display.width()) {
+        let line = row
+            .iter()
+            .map(|&pixel| if pixel == 0 { '.' } else { '#' })
+            .collect::<String>();
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,123 @@
+//! A second window that draws memory as sprites, for browsing the graphics in a ROM.
+use chip_8::sprites::{find_sprites, SpriteSheet};
+use chip_8::Palette;
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+/// Sprites side by side, the window is as tall as it's wide before scaling.
+const COLUMNS: usize = 16;
+const SIZE: usize = COLUMNS * 9 - 1;
+const MAX_SPRITE_HEIGHT: usize = 16;
+/// Where browsing starts, the font before it is rarely interesting.
+const ROM_START: usize = 0x200;
+
+/// Shows the memory last passed to [`SpriteWindow::show`] as a [`SpriteSheet`].
+///
+/// Up and Down scroll by a row of sprites, Page Up and Page Down by a window full. Left and Right
+/// change how many rows each sprite has, Tab jumps to the next sprite the ROM draws.
+pub struct SpriteWindow {
+    window: Window,
+    memory: Vec<u8>,
+    address: usize,
+    sprite_height: usize,
+    title: String,
+}
+
+impl SpriteWindow {
+    pub fn new() -> minifb::Result<Self> {
+        let opts = WindowOptions {
+            scale: Scale::X4,
+            ..WindowOptions::default()
+        };
+
+        Ok(Self {
+            window: Window::new("Sprites", SIZE, SIZE, opts)?,
+            memory: Vec::new(),
+            address: ROM_START,
+            sprite_height: 8,
+            title: String::new(),
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    pub fn is_active(&mut self) -> bool {
+        self.window.is_active()
+    }
+
+    pub fn show(&mut self, memory: Vec<u8>) {
+        self.memory = memory;
+    }
+
+    /// Handle the keys pressed in the window and redraw it.
+    pub fn update(&mut self, palette: &Palette) -> minifb::Result<()> {
+        self.handle_keys();
+
+        let title = format!("Sprites at {:#05x}, 8x{}", self.address, self.sprite_height);
+        if title != self.title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
+
+        let end = (self.address + self.page_size()).min(self.memory.len());
+        let bytes = self.memory.get(self.address..end).unwrap_or(&[]);
+        let sheet = SpriteSheet::new(bytes, self.sprite_height, COLUMNS);
+        let sprites = sheet.rgba(palette.foreground(), palette.background(), palette.color(2));
+
+        let mut pixels = vec![palette.color(2); SIZE * SIZE];
+        for (y, row) in sprites.chunks(sheet.width().max(1)).enumerate() {
+            pixels[y * SIZE..y * SIZE + row.len()].copy_from_slice(row);
+        }
+
+        self.window.update_with_buffer(&pixels, SIZE, SIZE)
+    }
+
+    fn handle_keys(&mut self) {
+        let row_size = COLUMNS * self.sprite_height;
+        if self.is_pressed(Key::Down) {
+            self.scroll_to(self.address + row_size);
+        }
+        if self.is_pressed(Key::Up) {
+            self.scroll_to(self.address.saturating_sub(row_size));
+        }
+        if self.is_pressed(Key::PageDown) {
+            self.scroll_to(self.address + self.page_size());
+        }
+        if self.is_pressed(Key::PageUp) {
+            self.scroll_to(self.address.saturating_sub(self.page_size()));
+        }
+        if self.is_pressed(Key::Right) && self.sprite_height < MAX_SPRITE_HEIGHT {
+            self.sprite_height += 1;
+        }
+        if self.is_pressed(Key::Left) && self.sprite_height > 1 {
+            self.sprite_height -= 1;
+        }
+        if self.window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            let next = find_sprites(&self.memory)
+                .into_iter()
+                .find(|sprite| sprite.address as usize > self.address);
+            if let Some(sprite) = next {
+                self.address = sprite.address as usize;
+                self.sprite_height = sprite.height.min(MAX_SPRITE_HEIGHT);
+            }
+        }
+    }
+
+    fn is_pressed(&self, key: Key) -> bool {
+        self.window.is_key_pressed(key, KeyRepeat::Yes)
+    }
+
+    /// The number of bytes that fit in the window.
+    fn page_size(&self) -> usize {
+        let rows = (SIZE + 1) / (self.sprite_height + 1);
+
+        rows * COLUMNS * self.sprite_height
+    }
+
+    fn scroll_to(&mut self, address: usize) {
+        if address < self.memory.len() {
+            self.address = address;
+        }
+    }
+}
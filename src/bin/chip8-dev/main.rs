@@ -0,0 +1,526 @@
+//! A developer frontend built on egui, with the display next to panels for the registers,
+//! memory, disassembly, breakpoints, and quirks. Built with `--features dev-frontend`.
+//!
+//! Everything goes through the library's [`Debugger`], the same as the debugger in the `chip-8`
+//! frontend, so the two stop at breakpoints the same way.
+use std::error::Error;
+use std::fs;
+
+use chip_8::clock::SystemClock;
+use chip_8::debugger::Debugger;
+use chip_8::disassembler::Instruction;
+use chip_8::runner::{FRAMES_PER_SECOND, MAX_CATCH_UP_FRAMES};
+use chip_8::{Clock, Display, Emulator, FramePacer, FramebufferDisplay, Input, Quirks, Variant};
+use clap::{crate_authors, crate_version, App, Arg};
+use eframe::egui::{self, Color32, ColorImage, RichText, TextureHandle, TextureOptions};
+
+/// The keypad on the QWERTY keys, 1234, QWER, ASDF, and ZXCV, like the `chip-8` frontend.
+const KEYPAD: [(egui::Key, u8); 16] = [
+    (egui::Key::Num1, 0x1),
+    (egui::Key::Num2, 0x2),
+    (egui::Key::Num3, 0x3),
+    (egui::Key::Num4, 0xC),
+    (egui::Key::Q, 0x4),
+    (egui::Key::W, 0x5),
+    (egui::Key::E, 0x6),
+    (egui::Key::R, 0xD),
+    (egui::Key::A, 0x7),
+    (egui::Key::S, 0x8),
+    (egui::Key::D, 0x9),
+    (egui::Key::F, 0xE),
+    (egui::Key::Z, 0xA),
+    (egui::Key::X, 0x0),
+    (egui::Key::C, 0xB),
+    (egui::Key::V, 0xF),
+];
+/// Bytes per row of the memory panel.
+const ROW_SIZE: usize = 8;
+/// Instructions the disassembly shows before and after the PC.
+const CODE_BEFORE: usize = 8;
+const CODE_AFTER: usize = 24;
+/// How much larger than the CHIP-8 pixels the display is drawn.
+const DISPLAY_SCALE: f32 = 8.0;
+
+/// The keys held in the window.
+#[derive(Default)]
+struct Keypad {
+    keys: [bool; 16],
+}
+
+impl Input for Keypad {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.keys[key as usize]
+    }
+}
+
+/// Which panels are showing, toggled from the menu bar.
+struct Panels {
+    registers: bool,
+    memory: bool,
+    disassembly: bool,
+    breakpoints: bool,
+    quirks: bool,
+}
+
+impl Default for Panels {
+    fn default() -> Self {
+        Self {
+            registers: true,
+            memory: true,
+            disassembly: true,
+            breakpoints: true,
+            quirks: false,
+        }
+    }
+}
+
+struct DevApp {
+    debugger: Debugger<FramebufferDisplay>,
+    /// The ROM as loaded, to reset to.
+    rom: Vec<u8>,
+    keypad: Keypad,
+    clock: SystemClock,
+    pacer: FramePacer,
+    last_update: std::time::Duration,
+    display: Option<TextureHandle>,
+    panels: Panels,
+    /// Where the memory panel scrolls to on the next frame, e.g. after clicking I.
+    memory_target: Option<usize>,
+    /// The address typed into the breakpoints panel.
+    new_breakpoint: String,
+    /// What happened last, a breakpoint log or an error, shown in the status bar.
+    status: String,
+}
+
+impl DevApp {
+    fn new(debugger: Debugger<FramebufferDisplay>, rom: Vec<u8>) -> Self {
+        let clock = SystemClock::new();
+
+        Self {
+            debugger,
+            rom,
+            keypad: Keypad::default(),
+            last_update: clock.now(),
+            clock,
+            pacer: FramePacer::new(FRAMES_PER_SECOND),
+            display: None,
+            panels: Panels::default(),
+            memory_target: None,
+            new_breakpoint: String::new(),
+            status: String::new(),
+        }
+    }
+
+    /// Run the frames that are due, stopping at breakpoints and errors.
+    fn run(&mut self) {
+        let now = self.clock.now();
+        let due_frames = self.pacer.advance(now - self.last_update);
+        self.last_update = now;
+
+        for _ in 0..due_frames.min(MAX_CATCH_UP_FRAMES) {
+            if self.debugger.emulator().is_paused() {
+                break;
+            }
+
+            let mut log = Vec::new();
+            let result = self.debugger.run_frame(&self.keypad, |hit| {
+                log.push(format!("Breakpoint at {:#05x}", hit.address));
+                log.extend(hit.log);
+            });
+            if !log.is_empty() {
+                self.status = log.join(", ");
+            }
+            if let Err(error) = result {
+                self.debugger.emulator_mut().pause();
+                self.status = error.to_string();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        match self.debugger.load_rom(self.rom.clone()) {
+            Ok(()) => self.status = "Reset".to_owned(),
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    /// Switch to a ROM dropped on the window, keeping the variant, quirks, and breakpoints.
+    fn load_dropped(&mut self, file: &egui::DroppedFile, frame: &mut eframe::Frame) {
+        let rom = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => bytes.to_vec(),
+            (None, Some(path)) => match fs::read(path) {
+                Ok(rom) => rom,
+                Err(error) => {
+                    self.status = format!("Can't read `{}`: {}", path.display(), error);
+                    return;
+                }
+            },
+            (None, None) => return,
+        };
+        let name = match &file.path {
+            Some(path) => path.display().to_string(),
+            None => file.name.clone(),
+        };
+
+        match self.debugger.load_rom(rom.clone()) {
+            Ok(()) => {
+                self.rom = rom;
+                self.status = format!("Loaded {}", name);
+                frame.set_window_title(&format!("chip8-dev - {}", name));
+                self.pacer.reset();
+                self.last_update = self.clock.now();
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn toggle_breakpoint(&mut self, address: u16) {
+        if !self.debugger.remove_breakpoint(address) {
+            self.debugger.add_breakpoint(address);
+        }
+    }
+
+    /// The display as an image, one texel per CHIP-8 pixel.
+    fn display_image(&self) -> ColorImage {
+        let display = self.debugger.emulator().display();
+        let pixels = display
+            .rgba_framebuffer()
+            .into_iter()
+            .map(|pixel| Color32::from_rgb((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8))
+            .collect();
+
+        ColorImage {
+            size: [display.width(), display.height()],
+            pixels,
+        }
+    }
+
+    fn menu_bar(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            let is_paused = self.debugger.emulator().is_paused();
+            if ui.button(if is_paused { "Run" } else { "Pause" }).clicked() {
+                if is_paused {
+                    self.debugger.emulator_mut().resume();
+                    // Don't run the time spent paused
+                    self.pacer.reset();
+                    self.last_update = self.clock.now();
+                } else {
+                    self.debugger.emulator_mut().pause();
+                }
+            }
+            if ui.button("Step").clicked() {
+                if let Err(error) = self.debugger.step(&self.keypad) {
+                    self.status = error.to_string();
+                }
+            }
+            if ui.button("Step frame").clicked() {
+                if let Err(error) = self.debugger.step_frame(&self.keypad, |_| {}) {
+                    self.status = error.to_string();
+                }
+            }
+            if ui.button("Reset").clicked() {
+                self.reset();
+            }
+
+            ui.separator();
+            ui.toggle_value(&mut self.panels.registers, "Registers");
+            ui.toggle_value(&mut self.panels.memory, "Memory");
+            ui.toggle_value(&mut self.panels.disassembly, "Disassembly");
+            ui.toggle_value(&mut self.panels.breakpoints, "Breakpoints");
+            ui.toggle_value(&mut self.panels.quirks, "Quirks");
+        });
+    }
+
+    fn registers(&mut self, ui: &mut egui::Ui) {
+        let state = self.debugger.emulator().state();
+        egui::Grid::new("registers").striped(true).show(ui, |ui| {
+            for (row, values) in state.v.chunks(4).enumerate() {
+                for (column, value) in values.iter().enumerate() {
+                    ui.monospace(format!("V{:X}: {:02X}", row * 4 + column, value));
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        ui.monospace(format!("PC: {:#05x}  SP: {}", state.pc, state.sp));
+        if ui.link(format!("I:  {:#05x}", state.i)).clicked() {
+            self.memory_target = Some(state.i as usize);
+        }
+        ui.monospace(format!(
+            "DT: {:02X}  ST: {:02X}",
+            state.delay_timer, state.sound_timer
+        ));
+        ui.monospace(format!(
+            "Cycles: {}",
+            self.debugger.emulator().cycle_count()
+        ));
+        if !state.stack.is_empty() {
+            let stack = state
+                .stack
+                .iter()
+                .map(|address| format!("{:#05x}", address))
+                .collect::<Vec<_>>();
+            ui.monospace(format!("Stack: {}", stack.join(" ")));
+        }
+    }
+
+    fn memory(&mut self, ui: &mut egui::Ui) {
+        let emulator = self.debugger.emulator();
+        let memory = emulator.dump_memory(..);
+        let (pc, i) = (emulator.pc() as usize, emulator.i() as usize);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = memory.len().div_ceil(ROW_SIZE);
+
+        let mut scroll = egui::ScrollArea::vertical().auto_shrink([false, false]);
+        if let Some(target) = self.memory_target.take() {
+            let spacing = ui.spacing().item_spacing.y;
+            scroll =
+                scroll.vertical_scroll_offset((target / ROW_SIZE) as f32 * (row_height + spacing));
+        }
+        scroll.show_rows(ui, row_height, rows, |ui, visible| {
+            for row in visible {
+                let start = row * ROW_SIZE;
+                let bytes = &memory[start..(start + ROW_SIZE).min(memory.len())];
+                let text = bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let text = RichText::new(format!("{:04X}: {}", start, text)).monospace();
+                let range = start..start + bytes.len();
+                let text = if range.contains(&pc) {
+                    text.color(Color32::YELLOW)
+                } else if range.contains(&i) {
+                    text.color(Color32::LIGHT_BLUE)
+                } else {
+                    text
+                };
+                ui.label(text);
+            }
+        });
+    }
+
+    fn disassembly(&mut self, ui: &mut egui::Ui) {
+        let pc = self.debugger.pc() as usize;
+        let lines = {
+            let memory = self.debugger.emulator().dump_memory(..);
+            let word = |address: usize| {
+                memory
+                    .get(address..address + 2)
+                    .map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+            };
+
+            let mut lines = Vec::new();
+            let mut address = pc.saturating_sub(CODE_BEFORE * 2);
+            while lines.len() < CODE_BEFORE + CODE_AFTER {
+                let opcode = match word(address) {
+                    Some(opcode) => opcode,
+                    None => break,
+                };
+                let instruction = Instruction::decode(opcode, word(address + 2).unwrap_or(0));
+                lines.push((address as u16, instruction));
+                let next = address + instruction.size() as usize;
+                // Don't step over the PC when the code before it isn't aligned with it
+                address = if address < pc && next > pc { pc } else { next };
+            }
+
+            lines
+        };
+
+        ui.label("Click an instruction to stop there");
+        let breakpoints = self
+            .debugger
+            .breakpoints()
+            .iter()
+            .map(|breakpoint| breakpoint.address)
+            .collect::<Vec<_>>();
+        for (address, instruction) in lines {
+            let marker = if breakpoints.contains(&address) {
+                "●"
+            } else {
+                " "
+            };
+            let text =
+                RichText::new(format!("{} {:04X}  {}", marker, address, instruction)).monospace();
+            let text = if address as usize == pc {
+                text.color(Color32::YELLOW)
+            } else {
+                text
+            };
+            if ui.selectable_label(false, text).clicked() {
+                self.toggle_breakpoint(address);
+            }
+        }
+    }
+
+    fn breakpoints(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.new_breakpoint);
+            let is_submitted =
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Add").clicked() || is_submitted {
+                let text = self.new_breakpoint.trim();
+                let hex = text.trim_start_matches("0x").trim_start_matches("0X");
+                match u16::from_str_radix(hex, 16) {
+                    Ok(address) => {
+                        self.debugger.add_breakpoint(address);
+                        self.new_breakpoint.clear();
+                    }
+                    Err(_) => self.status = format!("`{}` isn't a hex address", text),
+                }
+            }
+        });
+
+        let addresses = self
+            .debugger
+            .breakpoints()
+            .iter()
+            .map(|breakpoint| breakpoint.address)
+            .collect::<Vec<_>>();
+        if addresses.is_empty() {
+            ui.label("No breakpoints");
+        }
+        for address in addresses {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:#05x}", address));
+                if ui.small_button("Remove").clicked() {
+                    self.debugger.remove_breakpoint(address);
+                }
+            });
+        }
+    }
+
+    fn quirks(&mut self, ui: &mut egui::Ui) {
+        let mut quirks = self.debugger.emulator().quirks();
+        ui.horizontal(|ui| {
+            for (name, preset) in Quirks::presets().iter() {
+                if ui.selectable_label(quirks == *preset, *name).clicked() {
+                    quirks = *preset;
+                }
+            }
+        });
+        ui.checkbox(&mut quirks.shift_uses_vy, "Shifts use VY");
+        ui.checkbox(
+            &mut quirks.load_store_increments_i,
+            "FX55 and FX65 increment I",
+        );
+        ui.checkbox(&mut quirks.jump_uses_vx, "BNNN jumps to XNN + VX");
+        ui.checkbox(&mut quirks.clip_sprites, "Clip sprites");
+        ui.checkbox(&mut quirks.logic_resets_vf, "Logic resets VF");
+        ui.checkbox(&mut quirks.display_wait, "Wait for the display");
+        ui.checkbox(&mut quirks.wrap_memory, "Wrap memory");
+
+        if quirks != self.debugger.emulator().quirks() {
+            self.debugger.emulator_mut().set_quirks(quirks);
+        }
+    }
+}
+
+impl eframe::App for DevApp {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let dropped = ctx.input(|input| input.raw.dropped_files.first().cloned());
+        if let Some(file) = dropped {
+            self.load_dropped(&file, frame);
+        }
+        // Typing into the breakpoints panel shouldn't press keys
+        if !ctx.wants_keyboard_input() {
+            ctx.input(|input| {
+                for (key, chip8_key) in KEYPAD.iter() {
+                    self.keypad.keys[*chip8_key as usize] = input.key_down(*key);
+                }
+            });
+        }
+        self.run();
+
+        let image = self.display_image();
+        match &mut self.display {
+            Some(texture) => texture.set(image, TextureOptions::NEAREST),
+            None => {
+                self.display = Some(ctx.load_texture("display", image, TextureOptions::NEAREST))
+            }
+        }
+
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| self.menu_bar(ui));
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            let state = if self.debugger.emulator().is_paused() {
+                "Paused"
+            } else {
+                "Running"
+            };
+            ui.label(format!("{}. {}", state, self.status));
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(texture) = &self.display {
+                let size = texture.size_vec2() * DISPLAY_SCALE;
+                ui.image(texture, size);
+            }
+        });
+
+        let mut panels = std::mem::take(&mut self.panels);
+        egui::Window::new("Registers")
+            .open(&mut panels.registers)
+            .show(ctx, |ui| self.registers(ui));
+        egui::Window::new("Memory")
+            .open(&mut panels.memory)
+            .default_height(300.0)
+            .show(ctx, |ui| self.memory(ui));
+        egui::Window::new("Disassembly")
+            .open(&mut panels.disassembly)
+            .show(ctx, |ui| self.disassembly(ui));
+        egui::Window::new("Breakpoints")
+            .open(&mut panels.breakpoints)
+            .show(ctx, |ui| self.breakpoints(ui));
+        egui::Window::new("Quirks")
+            .open(&mut panels.quirks)
+            .show(ctx, |ui| self.quirks(ui));
+        self.panels = panels;
+
+        // Keep running without waiting for input
+        ctx.request_repaint();
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("chip8-dev")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("A CHIP-8 emulator with panels for developing and debugging ROMs")
+        .arg(
+            Arg::with_name("variant")
+                .long("variant")
+                .takes_value(true)
+                .value_name("VARIANT")
+                .possible_values(&["chip-8", "eti-660", "hires", "xo-chip"])
+                .default_value("chip-8")
+                .help("The interpreter to emulate"),
+        )
+        .arg(
+            Arg::with_name("start-paused")
+                .long("start-paused")
+                .help("Wait for Run or Step before executing anything"),
+        )
+        .arg(Arg::with_name("ROM").required(true).help("The ROM to run"))
+        .get_matches();
+
+    let path = matches.value_of("ROM").unwrap();
+    let rom = fs::read(path).map_err(|error| format!("Can't read `{}`: {}", path, error))?;
+    let variant = matches.value_of("variant").unwrap().parse::<Variant>()?;
+    let display = FramebufferDisplay::for_variant(variant);
+    let mut emulator = Emulator::for_rom(display, rom.clone(), variant)?;
+    if matches.is_present("start-paused") {
+        emulator.pause();
+    }
+
+    let app = DevApp::new(Debugger::new(emulator), rom);
+    let options = eframe::NativeOptions {
+        initial_window_size: Some(egui::vec2(1200.0, 720.0)),
+        ..eframe::NativeOptions::default()
+    };
+    eframe::run_native(
+        &format!("chip8-dev - {}", path),
+        options,
+        Box::new(|_| Box::new(app)),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,387 @@
+//! A gym style interface for reinforcement learning experiments on CHIP-8 games: pick the keys
+//! to hold, run a step, and get back the display, a reward, and whether the game is over.
+//!
+//! Games keep their score and lives in memory at addresses that differ per ROM, [`Rewards`]
+//! says where. It parses from text such as `score=digits:0x2F0:3 lives=0x2F3` so that the
+//! addresses for each ROM can be kept in a file next to it.
+//!
+//! ```
+//! use chip_8::agent::{Agent, KeyMask};
+//! use chip_8::prelude::*;
+//!
+//! // 1200: JP 0x200
+//! let rom = vec![0x12, 0x00];
+//! let emulator = Emulator::new(FramebufferDisplay::default(), rom, Quirks::default())?;
+//! let mut agent = Agent::new(emulator, "score=0x300".parse()?);
+//! let observation = agent.step(KeyMask::NONE.with(0x5))?;
+//! assert_eq!(observation.reward, 0);
+//! assert!(!observation.done);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::error::Error as StdError;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::memory::Memory;
+use crate::{Emulator, Error, FramebufferDisplay, Input};
+
+/// The keys held during a step, bit `n` is set when key `n` is down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct KeyMask(pub u16);
+
+impl KeyMask {
+    pub const NONE: KeyMask = KeyMask(0);
+
+    /// This mask with `key` held as well.
+    pub fn with(self, key: u8) -> Self {
+        KeyMask(self.0 | 1 << (key & 0xF))
+    }
+}
+
+impl Input for KeyMask {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.0 & 1 << (key & 0xF) != 0
+    }
+}
+
+/// A number a game keeps in memory, `length` bytes from `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    pub address: u16,
+    pub length: u16,
+    /// Whether every byte is a single decimal digit, as written by FX33, rather than the bytes
+    /// of a big endian number.
+    pub digits: bool,
+}
+
+impl Counter {
+    /// The current value, an error if the counter extends past the end of memory.
+    pub fn read(&self, emulator: &Emulator<FramebufferDisplay>) -> Result<u64, Error> {
+        let base = if self.digits { 10 } else { 256 };
+        let bytes = emulator.peek_slice(self.address, self.length)?;
+
+        Ok(bytes
+            .iter()
+            .fold(0, |value, &byte| value * base + u64::from(byte)))
+    }
+}
+
+impl FromStr for Counter {
+    type Err = RewardsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (digits, rest) = match s.strip_prefix("digits:") {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let mut parts = rest.splitn(2, ':');
+        let address = parts
+            .next()
+            .map(|address| address.trim_start_matches("0x").trim_start_matches("0X"))
+            .and_then(|address| u16::from_str_radix(address, 16).ok());
+        let length = match parts.next() {
+            Some(length) => length.parse().ok().filter(|&length| length > 0),
+            None => Some(1),
+        };
+
+        match (address, length) {
+            (Some(address), Some(length)) => Ok(Self {
+                address,
+                length,
+                digits,
+            }),
+            _ => Err(RewardsError::Counter(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.digits {
+            write!(f, "digits:")?;
+        }
+        write!(f, "{:#05x}", self.address)?;
+        if self.length != 1 {
+            write!(f, ":{}", self.length)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where a game keeps what an [`Agent`] reports. Without a score every reward is 0, and
+/// without lives the game is never over.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rewards {
+    /// The reward for a step is how much this changed.
+    pub score: Option<Counter>,
+    /// The game is over once this drops to 0.
+    pub lives: Option<Counter>,
+    /// Anything else to report in every [`Observation`], in order.
+    pub peeks: Vec<Counter>,
+}
+
+impl FromStr for Rewards {
+    type Err = RewardsError;
+
+    /// Parse space separated `score=COUNTER`, `lives=COUNTER`, and `peek=COUNTER`, where a
+    /// counter is a hex address, optionally followed by `:LENGTH` in bytes and preceded by
+    /// `digits:` for one decimal digit per byte.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rewards = Rewards::default();
+        for part in s.split_whitespace() {
+            let mut pair = part.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some("score"), Some(counter)) => rewards.score = Some(counter.parse()?),
+                (Some("lives"), Some(counter)) => rewards.lives = Some(counter.parse()?),
+                (Some("peek"), Some(counter)) => rewards.peeks.push(counter.parse()?),
+                _ => return Err(RewardsError::Syntax(part.to_string())),
+            }
+        }
+
+        Ok(rewards)
+    }
+}
+
+impl fmt::Display for Rewards {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let named = self
+            .score
+            .iter()
+            .map(|counter| ("score", counter))
+            .chain(self.lives.iter().map(|counter| ("lives", counter)))
+            .chain(self.peeks.iter().map(|counter| ("peek", counter)));
+        for (index, (name, counter)) in named.enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", name, counter)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewardsError {
+    /// Something other than `score=`, `lives=`, or `peek=`.
+    Syntax(String),
+    /// A counter that isn't a hex address with an optional length.
+    Counter(String),
+}
+
+impl fmt::Display for RewardsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RewardsError::Syntax(part) => write!(
+                f,
+                "Invalid reward `{}`, expected score=, lives=, or peek= followed by an address",
+                part
+            ),
+            RewardsError::Counter(counter) => write!(
+                f,
+                "Invalid counter `{}`, expected [digits:]ADDR[:LENGTH] e.g. digits:0x2F0:3",
+                counter
+            ),
+        }
+    }
+}
+
+impl StdError for RewardsError {}
+
+/// What an [`Agent`] sees after a step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    /// The planes every pixel is set in, row major.
+    pub pixels: Vec<u8>,
+    /// How much the score changed during the step, negative when it went down.
+    pub reward: i64,
+    pub score: Option<u64>,
+    pub lives: Option<u64>,
+    /// The values of [`Rewards::peeks`] in order.
+    pub peeks: Vec<u64>,
+    /// Whether the game is over, [`Agent::reset`] starts it again.
+    pub done: bool,
+}
+
+/// Runs a game one step at a time with the keys an agent picks.
+pub struct Agent {
+    emulator: Emulator<FramebufferDisplay>,
+    rewards: Rewards,
+    /// Memory as it was when the agent was created, for resets.
+    initial: Memory,
+    frames_per_step: u32,
+    score: Option<u64>,
+    lives: Option<u64>,
+    is_over: bool,
+}
+
+impl Agent {
+    /// Run `emulator`, resets go back to its memory as it is now, e.g. with any pokes applied.
+    pub fn new(emulator: Emulator<FramebufferDisplay>, rewards: Rewards) -> Self {
+        let initial = emulator.memory().clone();
+
+        Self {
+            emulator,
+            rewards,
+            initial,
+            frames_per_step: 1,
+            score: None,
+            lives: None,
+            is_over: false,
+        }
+    }
+
+    pub fn emulator(&self) -> &Emulator<FramebufferDisplay> {
+        &self.emulator
+    }
+
+    pub fn frames_per_step(&self) -> u32 {
+        self.frames_per_step
+    }
+
+    /// How many frames each step runs with the same keys held, 1 initially.
+    pub fn set_frames_per_step(&mut self, frames: u32) {
+        self.frames_per_step = frames.max(1);
+    }
+
+    /// Start the game again and observe the first frame, which has no reward.
+    pub fn reset(&mut self) -> Result<Observation, Error> {
+        self.emulator.restore(&self.initial);
+        self.score = None;
+        self.lives = None;
+        self.is_over = false;
+
+        self.observe()
+    }
+
+    /// Hold `action` for [`Agent::frames_per_step`] frames and observe the result.
+    pub fn step(&mut self, action: KeyMask) -> Result<Observation, Error> {
+        for _ in 0..self.frames_per_step {
+            self.emulator.run_frame(&action)?;
+        }
+
+        self.observe()
+    }
+
+    fn observe(&mut self) -> Result<Observation, Error> {
+        let score = read(self.rewards.score, &self.emulator)?;
+        let lives = read(self.rewards.lives, &self.emulator)?;
+        let peeks = self
+            .rewards
+            .peeks
+            .iter()
+            .map(|counter| counter.read(&self.emulator))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let reward = match (self.score, score) {
+            (Some(before), Some(after)) => after as i64 - before as i64,
+            _ => 0,
+        };
+        // Games that start over by themselves are still over until reset
+        self.is_over |= match (self.lives, lives) {
+            (Some(before), Some(after)) => before > 0 && after == 0,
+            _ => false,
+        };
+        self.score = score;
+        self.lives = lives;
+
+        let buffer = self.emulator.display().buffer();
+        Ok(Observation {
+            width: buffer.width(),
+            height: buffer.height(),
+            pixels: buffer.as_slice().to_vec(),
+            reward,
+            score,
+            lives,
+            peeks,
+            done: self.is_over,
+        })
+    }
+}
+
+fn read(
+    counter: Option<Counter>,
+    emulator: &Emulator<FramebufferDisplay>,
+) -> Result<Option<u64>, Error> {
+    counter.map(|counter| counter.read(emulator)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Agent, Counter, KeyMask, Rewards};
+    use crate::{Emulator, FramebufferDisplay, Input, Quirks};
+
+    #[test]
+    fn test_parse_rewards() {
+        let rewards: Rewards = "score=digits:0x2F0:3 lives=0x2f3 peek=0x2F4:2"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rewards.score,
+            Some(Counter {
+                address: 0x2F0,
+                length: 3,
+                digits: true
+            })
+        );
+        assert_eq!(rewards.lives.unwrap().address, 0x2F3);
+        assert_eq!(rewards.peeks.len(), 1);
+        assert_eq!(
+            rewards.to_string(),
+            "score=digits:0x2f0:3 lives=0x2f3 peek=0x2f4:2"
+        );
+
+        assert!("score".parse::<Rewards>().is_err());
+        assert!("lives=0x2F3:0".parse::<Rewards>().is_err());
+        assert!("health=0x2F3".parse::<Rewards>().is_err());
+    }
+
+    #[test]
+    fn test_key_mask() {
+        let keys = KeyMask::NONE.with(0x0).with(0xF);
+        assert_eq!(keys, KeyMask(0x8001));
+        assert!(keys.is_key_down(0xF));
+        assert!(!keys.is_key_down(0x5));
+    }
+
+    #[test]
+    fn test_step() {
+        // 6105: LD V1, 0x05, 6202: LD V2, 0x02, A300: LD I, 0x300, F255: LD [I], V2,
+        // F30A: LD V3, K, 7001: ADD V0, 0x01, 72FF: ADD V2, 0xFF, 1204: JP 0x204
+        let rom = vec![
+            0x61, 0x05, 0x62, 0x02, 0xA3, 0x00, 0xF2, 0x55, 0xF3, 0x0A, 0x70, 0x01, 0x72, 0xFF,
+            0x12, 0x04,
+        ];
+        let emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let rewards = "score=0x300 lives=0x302 peek=0x301".parse().unwrap();
+        let mut agent = Agent::new(emulator, rewards);
+
+        let first = agent.step(KeyMask::NONE).unwrap();
+        assert_eq!((first.width, first.height), (64, 32));
+        assert_eq!(first.pixels.len(), 64 * 32);
+        assert_eq!(
+            (first.reward, first.score, first.lives),
+            (0, Some(0), Some(2))
+        );
+        assert_eq!(first.peeks, vec![5]);
+
+        // Every press and release of key 5 scores a point and costs a life
+        agent.step(KeyMask::NONE.with(0x5)).unwrap();
+        let observation = agent.step(KeyMask::NONE).unwrap();
+        assert_eq!((observation.reward, observation.lives), (1, Some(1)));
+        assert!(!observation.done);
+
+        agent.step(KeyMask::NONE.with(0x5)).unwrap();
+        let observation = agent.step(KeyMask::NONE).unwrap();
+        assert_eq!((observation.reward, observation.score), (1, Some(2)));
+        assert!(observation.done);
+
+        let observation = agent.reset().unwrap();
+        assert_eq!((observation.reward, observation.score), (0, Some(0)));
+        assert!(!observation.done);
+    }
+}
@@ -0,0 +1,442 @@
+//! Static analysis of ROMs, to find out what a ROM needs without running it: the extension
+//! instructions it uses, constructs that tend to break in emulators, where its sprites are, and
+//! which of the ambiguous instructions it depends on.
+//!
+//! Only the code that [`symbolize`] finds by following execution from the start of the ROM is
+//! looked at, so everything here is an estimate. Code reached through jump tables or written at
+//! runtime is missed.
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::disassembler::{symbolize, LabelKind, Line};
+use crate::Opcode;
+
+/// Where ROMs are loaded, everything below belongs to the interpreter.
+const ROM_BASE_ADDRESS: u16 = 0x200;
+/// The quirks that only some instructions depend on, by their names in `--quirks`.
+const QUIRKS: [&str; 6] = [
+    "shift-vy",
+    "load-store-i",
+    "jump-vx",
+    "vf-reset",
+    "clip",
+    "display-wait",
+];
+
+/// Instruction sets beyond the original CHIP-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SuperChip,
+    XoChip,
+}
+
+impl Extension {
+    /// The name of the quirk preset for interpreters of the extension.
+    pub fn quirks(self) -> &'static str {
+        match self {
+            Extension::SuperChip => "schip",
+            Extension::XoChip => "xochip",
+        }
+    }
+
+    /// The extension that `instruction` belongs to, if any. SUPER-CHIP instructions that this
+    /// interpreter doesn't run are recognized by their opcodes.
+    fn of(instruction: Opcode) -> Option<Self> {
+        match instruction {
+            Opcode::SaveFlags(_) | Opcode::LoadFlags(_) => Some(Extension::SuperChip),
+            instruction if instruction.is_xo_chip() => Some(Extension::XoChip),
+            // 00CN, 00FB to 00FF, DXY0, and FX30
+            Opcode::Sys(0x0C0..=0x0CF) | Opcode::Sys(0x0FB..=0x0FF) | Opcode::Drw(_, _, 0) => {
+                Some(Extension::SuperChip)
+            }
+            Opcode::Data(word) if word & 0xF0FF == 0xF030 => Some(Extension::SuperChip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Extension::SuperChip => write!(f, "SUPER-CHIP"),
+            Extension::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+/// An instruction from an extension, at `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtensionUse {
+    pub extension: Extension,
+    pub address: u16,
+    pub instruction: Opcode,
+}
+
+/// Something in the code that's likely to be a problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// The instruction at `address` stores through I, which was set to `target` in the code.
+    SelfModifying { address: u16, target: u16 },
+    /// The instruction at `address` jumps to or calls `target` in the interpreter area.
+    JumpBelowRom { address: u16, target: u16 },
+    /// 0NNN at `address` calls machine code at `target` on the original hardware, which can't
+    /// be emulated.
+    MachineCode { address: u16, target: u16 },
+    /// `word` at `address` is reached as code but isn't an instruction.
+    UnknownOpcode { address: u16, word: u16 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::SelfModifying { address, target } => write!(
+                f,
+                "{:#05x}: writes to {:#05x}, which is code, the ROM modifies itself",
+                address, target
+            ),
+            Warning::JumpBelowRom { address, target } => write!(
+                f,
+                "{:#05x}: jumps to {:#05x}, below the ROM in the interpreter area",
+                address, target
+            ),
+            Warning::MachineCode { address, target } => write!(
+                f,
+                "{:#05x}: calls a machine code routine at {:#05x}, which can't be emulated",
+                address, target
+            ),
+            Warning::UnknownOpcode { address, word } => write!(
+                f,
+                "{:#05x}: {:04X} is reached as code but isn't an instruction",
+                address, word
+            ),
+        }
+    }
+}
+
+/// Bytes loaded into I that aren't code, most likely sprites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRegion {
+    pub address: u16,
+    pub length: usize,
+}
+
+/// How often a quirk's instructions appear in the code, the ROM only runs as intended with the
+/// quirk set the way it was written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuirkUse {
+    /// The quirk's name in `--quirks`.
+    pub quirk: &'static str,
+    pub instructions: usize,
+}
+
+/// What [`analyze`] found out about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Every extension instruction in the code, in address order.
+    pub extensions: Vec<ExtensionUse>,
+    pub warnings: Vec<Warning>,
+    pub data: Vec<DataRegion>,
+    pub quirks: Vec<QuirkUse>,
+}
+
+impl Analysis {
+    /// The quirk preset for the newest extension the ROM uses, `None` for plain CHIP-8.
+    pub fn suggested_quirks(&self) -> Option<&'static str> {
+        self.extensions
+            .iter()
+            .map(|extension_use| extension_use.extension)
+            .max()
+            .map(Extension::quirks)
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Extensions:")?;
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|extension_use| extension_use.extension)
+            .collect::<BTreeSet<_>>();
+        if extensions.is_empty() {
+            writeln!(f, "  none, plain CHIP-8")?;
+        }
+        for extension in extensions {
+            let mut uses = self
+                .extensions
+                .iter()
+                .filter(|extension_use| extension_use.extension == extension);
+            let first = uses.next().expect("every extension in the set is used");
+            writeln!(
+                f,
+                "  {}: {} at {:#05x} first, {} in total",
+                extension,
+                first.instruction,
+                first.address,
+                uses.count() + 1
+            )?;
+        }
+
+        writeln!(f, "Warnings:")?;
+        if self.warnings.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "  {}", warning)?;
+        }
+
+        writeln!(f, "Sprite data:")?;
+        if self.data.is_empty() {
+            writeln!(f, "  none found")?;
+        }
+        for region in &self.data {
+            writeln!(
+                f,
+                "  {:#05x}-{:#05x}, {} bytes",
+                region.address,
+                region.address as usize + region.length - 1,
+                region.length
+            )?;
+        }
+
+        writeln!(f, "Quirks that change how it runs:")?;
+        if self.quirks.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for quirk in &self.quirks {
+            let plural = if quirk.instructions == 1 { "" } else { "s" };
+            writeln!(
+                f,
+                "  {}: {} instruction{}",
+                quirk.quirk, quirk.instructions, plural
+            )?;
+        }
+        if let Some(preset) = self.suggested_quirks() {
+            writeln!(f, "Suggested quirks: {}", preset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Look through the code of `rom` without running it.
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let lines = symbolize(rom);
+    let code = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instruction {
+                address,
+                instruction,
+                ..
+            } => Some(*address..address + instruction.size()),
+            _ => None,
+        })
+        .flatten()
+        .collect::<BTreeSet<_>>();
+
+    let mut analysis = Analysis {
+        extensions: Vec::new(),
+        warnings: Vec::new(),
+        data: Vec::new(),
+        quirks: Vec::new(),
+    };
+    let mut quirk_counts = [0; QUIRKS.len()];
+    // What I was last set to, as long as it's known
+    let mut i: Option<u16> = None;
+    let mut data: Option<DataRegion> = None;
+
+    for line in &lines {
+        let (address, instruction) = match line {
+            Line::Label(label) => {
+                // Execution can arrive here from anywhere
+                i = None;
+                if let Some(region) = data.take() {
+                    analysis.data.push(region);
+                }
+                if label.kind == LabelKind::Data {
+                    data = Some(DataRegion {
+                        address: label.address,
+                        length: 0,
+                    });
+                }
+                continue;
+            }
+            Line::Data { bytes, .. } => {
+                if let Some(region) = &mut data {
+                    region.length += bytes.len();
+                }
+                continue;
+            }
+            Line::Instruction {
+                address,
+                instruction,
+                ..
+            } => (*address, *instruction),
+        };
+        if let Some(region) = data.take() {
+            analysis.data.push(region);
+        }
+
+        if let Some(extension) = Extension::of(instruction) {
+            analysis.extensions.push(ExtensionUse {
+                extension,
+                address,
+                instruction,
+            });
+        }
+
+        let stored = match instruction {
+            Opcode::Store(x) => Some(u16::from(x) + 1),
+            Opcode::LdBcd(_) => Some(3),
+            Opcode::SaveRange(x, y) => Some(u16::from(x.max(y) - x.min(y)) + 1),
+            _ => None,
+        };
+        if let (Some(target), Some(length)) = (i, stored) {
+            if let Some(&written) = code.range(target..target.saturating_add(length)).next() {
+                analysis.warnings.push(Warning::SelfModifying {
+                    address,
+                    target: written,
+                });
+            }
+        }
+
+        match instruction {
+            Opcode::LdI(target) | Opcode::LdILong(target) => i = Some(target),
+            Opcode::AddI(_) | Opcode::Store(_) | Opcode::Load(_) => i = None,
+            Opcode::Jp(target) | Opcode::Call(target) | Opcode::JpV0(target)
+                if target < ROM_BASE_ADDRESS =>
+            {
+                analysis
+                    .warnings
+                    .push(Warning::JumpBelowRom { address, target });
+            }
+            Opcode::Sys(target) if Extension::of(instruction).is_none() => {
+                analysis
+                    .warnings
+                    .push(Warning::MachineCode { address, target });
+            }
+            Opcode::Data(word) if Extension::of(instruction).is_none() => {
+                analysis
+                    .warnings
+                    .push(Warning::UnknownOpcode { address, word });
+            }
+            _ => {}
+        }
+
+        for quirk in quirks_of(instruction) {
+            let index = QUIRKS.iter().position(|name| name == quirk).unwrap();
+            quirk_counts[index] += 1;
+        }
+    }
+    if let Some(region) = data.take() {
+        analysis.data.push(region);
+    }
+
+    analysis.data.retain(|region| region.length > 0);
+    analysis.quirks = QUIRKS
+        .iter()
+        .zip(&quirk_counts)
+        .filter(|(_, &count)| count > 0)
+        .map(|(&quirk, &instructions)| QuirkUse {
+            quirk,
+            instructions,
+        })
+        .collect();
+
+    analysis
+}
+
+/// The quirks that change what `instruction` does.
+fn quirks_of(instruction: Opcode) -> &'static [&'static str] {
+    match instruction {
+        // Shifting a register in place works the same either way
+        Opcode::Shr(x, y) | Opcode::Shl(x, y) if x != y => &["shift-vy"],
+        Opcode::Store(_) | Opcode::Load(_) => &["load-store-i"],
+        Opcode::JpV0(_) => &["jump-vx"],
+        Opcode::Or(..) | Opcode::And(..) | Opcode::Xor(..) => &["vf-reset"],
+        Opcode::Drw(..) => &["clip", "display-wait"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, DataRegion, Extension, Warning};
+    use crate::assembler::assemble;
+
+    #[test]
+    fn test_plain_rom() {
+        let rom = assemble(
+            "LD I, 0x208
+             DRW V0, V1, 2
+             SHR V2, V3
+             JP 0x200
+             DB 0x80, 0xC0",
+        )
+        .unwrap();
+        let analysis = analyze(&rom);
+
+        assert!(analysis.extensions.is_empty());
+        assert!(analysis.warnings.is_empty());
+        assert_eq!(
+            analysis.data,
+            vec![DataRegion {
+                address: 0x208,
+                length: 2
+            }]
+        );
+        let quirks = analysis
+            .quirks
+            .iter()
+            .map(|quirk| quirk.quirk)
+            .collect::<Vec<_>>();
+        assert_eq!(quirks, vec!["shift-vy", "clip", "display-wait"]);
+        assert_eq!(analysis.suggested_quirks(), None);
+    }
+
+    #[test]
+    fn test_extensions_and_warnings() {
+        let rom = assemble(
+            "PLANE 2
+             DRW V0, V1, 0
+             LD I, 0x200
+             LD [I], V1
+             CALL 0x100
+             DB 0xFF, 0xFF",
+        )
+        .unwrap();
+        let analysis = analyze(&rom);
+
+        let extensions = analysis
+            .extensions
+            .iter()
+            .map(|extension_use| (extension_use.extension, extension_use.address))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            extensions,
+            vec![(Extension::XoChip, 0x200), (Extension::SuperChip, 0x202)]
+        );
+        assert_eq!(analysis.suggested_quirks(), Some("xochip"));
+        assert_eq!(
+            analysis.warnings,
+            vec![
+                Warning::SelfModifying {
+                    address: 0x206,
+                    target: 0x200
+                },
+                Warning::JumpBelowRom {
+                    address: 0x208,
+                    target: 0x100
+                },
+                Warning::UnknownOpcode {
+                    address: 0x20A,
+                    word: 0xFFFF
+                },
+            ]
+        );
+        assert!(analysis
+            .to_string()
+            .contains("XO-CHIP: PLANE 2 at 0x200 first, 1 in total"));
+    }
+}
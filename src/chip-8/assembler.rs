@@ -0,0 +1,447 @@
+//! Assembling source text into ROMs, the inverse of the disassembler.
+//!
+//! The mnemonics are the ones the disassembler prints, so its output assembles back into the
+//! same ROM. Mnemonics and registers are case insensitive. On top of that the assembler
+//! understands:
+//!
+//! ```text
+//! ; Comments run to the end of the line
+//! start:              ; A label, its value is the address of what follows it
+//!     LD I, sprite    ; Labels can be used wherever an address is expected
+//!     DRW V0, V1, 3
+//!     JP start
+//! sprite:
+//!     DB 0x80, 0xC0, 0xE0   ; Raw bytes, e.g. sprite data
+//! ```
+//!
+//! Numbers are decimal, or hex with a `0x` prefix.
+//!
+//! [`assemble_octo`] assembles the [Octo](https://github.com/JohnEarnest/Octo) syntax instead.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub use crate::octo::assemble_octo;
+use crate::Opcode;
+
+/// Where ROMs are loaded, the address of the first assembled byte.
+pub(crate) const ROM_BASE_ADDRESS: u16 = 0x200;
+
+/// Every instruction mnemonic, to tell unknown instructions apart from invalid operands.
+const MNEMONICS: [&str; 26] = [
+    "CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN",
+    "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "SCU", "SAVE", "LOAD", "PLANE", "AUDIO", "PITCH",
+];
+
+/// An error on a line of the source, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblerError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AssemblerError {
+    pub(crate) fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssemblerError {}
+
+/// A number, or a label to be resolved once every address is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Number(u16),
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Operand {
+    Register(u8),
+    I,
+    /// `[I]`
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    Bcd,
+    /// `R`, the persistent flags.
+    Flags,
+    /// `LONG NNNN`, the 16 bit address of `LD I, LONG NNNN`.
+    Long(Value),
+    Immediate(Value),
+}
+
+/// A parsed instruction or `DB` directive on line `line`.
+pub(crate) struct Statement {
+    pub line: usize,
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+}
+
+impl Statement {
+    /// The size in bytes, known before labels are resolved.
+    pub fn size(&self) -> u16 {
+        match (self.mnemonic.as_str(), self.operands.as_slice()) {
+            ("DB", operands) => operands.len() as u16,
+            ("LD", [Operand::I, Operand::Long(_)]) => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Assemble `source` into a ROM to be loaded at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = ROM_BASE_ADDRESS;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut text = line.split(';').next().unwrap_or("").trim();
+
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_label(label) {
+                return Err(AssemblerError::new(
+                    line_number,
+                    format!("Invalid label `{}`", label),
+                ));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(AssemblerError::new(
+                    line_number,
+                    format!("Duplicate label `{}`", label),
+                ));
+            }
+            text = text[colon + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line_number, text)?;
+        address = address
+            .checked_add(statement.size())
+            .ok_or_else(|| AssemblerError::new(line_number, "The ROM is too large".to_string()))?;
+        statements.push(statement);
+    }
+
+    link(&statements, &labels)
+}
+
+/// A single instruction as [`Opcode`]'s `Display` prints it, e.g. `LD V3, 0x42`. `DB` with one
+/// or two bytes parses as [`Opcode::Byte`] or [`Opcode::Data`], labels aren't allowed.
+impl FromStr for Opcode {
+    type Err = AssemblerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.split(';').next().unwrap_or("").trim();
+        if text.is_empty() {
+            return Err(AssemblerError::new(
+                1,
+                "Expected an instruction".to_string(),
+            ));
+        }
+
+        let statement = parse_statement(1, text)?;
+        if statement.mnemonic != "DB" {
+            return encode(&statement, |value| match value {
+                Value::Number(number) => Ok(*number),
+                Value::Label(label) => {
+                    Err(AssemblerError::new(1, format!("Unknown label `{}`", label)))
+                }
+            });
+        }
+
+        match link(&[statement], &HashMap::new())?.as_slice() {
+            [byte] => Ok(Opcode::Byte(*byte)),
+            [high, low] => Ok(Opcode::Data(u16::from(*high) << 8 | u16::from(*low))),
+            _ => Err(AssemblerError::new(
+                1,
+                "Expected one or two bytes".to_string(),
+            )),
+        }
+    }
+}
+
+/// Encode `statements` now that the address of every label in `labels` is known.
+pub(crate) fn link(
+    statements: &[Statement],
+    labels: &HashMap<String, u16>,
+) -> Result<Vec<u8>, AssemblerError> {
+    let mut rom = Vec::new();
+    for statement in statements {
+        let resolve = |value: &Value| match value {
+            Value::Number(number) => Ok(*number),
+            Value::Label(label) => labels.get(label).cloned().ok_or_else(|| {
+                AssemblerError::new(statement.line, format!("Unknown label `{}`", label))
+            }),
+        };
+
+        if statement.mnemonic == "DB" {
+            for operand in &statement.operands {
+                match operand {
+                    Operand::Immediate(value) => {
+                        rom.push(fit(statement.line, resolve(value)?, 0xFF)?)
+                    }
+                    _ => return Err(invalid_operands(statement)),
+                }
+            }
+        } else {
+            rom.extend(encode(statement, resolve)?.encode());
+        }
+    }
+
+    Ok(rom)
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<Statement, AssemblerError> {
+    let mut parts = text.splitn(2, char::is_whitespace);
+    let mnemonic = parts.next().unwrap_or("").to_uppercase();
+    let operands = match parts.next().map(str::trim) {
+        Some(operands) if !operands.is_empty() => operands
+            .split(',')
+            .map(|operand| parse_operand(operand.trim()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| AssemblerError::new(line, format!("Invalid operands `{}`", operands)))?,
+        _ => Vec::new(),
+    };
+
+    Ok(Statement {
+        line,
+        mnemonic,
+        operands,
+    })
+}
+
+fn parse_operand(operand: &str) -> Option<Operand> {
+    let upper = operand.to_ascii_uppercase();
+    let register = upper
+        .strip_prefix('V')
+        .filter(|digit| digit.len() == 1)
+        .and_then(|digit| u8::from_str_radix(digit, 16).ok());
+
+    match upper.as_str() {
+        _ if register.is_some() => register.map(Operand::Register),
+        "I" => Some(Operand::I),
+        "[I]" => Some(Operand::IndirectI),
+        "DT" => Some(Operand::DelayTimer),
+        "ST" => Some(Operand::SoundTimer),
+        "K" => Some(Operand::Key),
+        "F" => Some(Operand::Font),
+        "B" => Some(Operand::Bcd),
+        "R" => Some(Operand::Flags),
+        _ if upper.starts_with("LONG ") => parse_value(operand[5..].trim()).map(Operand::Long),
+        _ => parse_value(operand).map(Operand::Immediate),
+    }
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        u16::from_str_radix(hex, 16).ok().map(Value::Number)
+    } else if value.starts_with(|c: char| c.is_ascii_digit()) {
+        value.parse().ok().map(Value::Number)
+    } else if is_label(value) {
+        Some(Value::Label(value.to_string()))
+    } else {
+        None
+    }
+}
+
+fn is_label(label: &str) -> bool {
+    label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check that `value` is at most `max`, i.e. fits in its field of the instruction.
+fn fit<T: std::convert::TryFrom<u16>>(
+    line: usize,
+    value: u16,
+    max: u16,
+) -> Result<T, AssemblerError> {
+    if value > max {
+        return Err(AssemblerError::new(
+            line,
+            format!("{:#x} is larger than {:#x}", value, max),
+        ));
+    }
+
+    T::try_from(value).map_err(|_| AssemblerError::new(line, format!("Invalid value {:#x}", value)))
+}
+
+fn invalid_operands(statement: &Statement) -> AssemblerError {
+    AssemblerError::new(
+        statement.line,
+        format!("Invalid operands for {}", statement.mnemonic),
+    )
+}
+
+fn encode(
+    statement: &Statement,
+    resolve: impl Fn(&Value) -> Result<u16, AssemblerError>,
+) -> Result<Opcode, AssemblerError> {
+    use Operand::*;
+
+    let line = statement.line;
+    let address = |value: &Value| fit::<u16>(line, resolve(value)?, 0xFFF);
+    let byte = |value: &Value| fit::<u8>(line, resolve(value)?, 0xFF);
+    let nibble = |value: &Value| fit::<u8>(line, resolve(value)?, 0xF);
+
+    let opcode = match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+        ("CLS", []) => Opcode::Cls,
+        ("RET", []) => Opcode::Ret,
+        ("SYS", [Immediate(a)]) => Opcode::Sys(address(a)?),
+        ("JP", [Immediate(a)]) => Opcode::Jp(address(a)?),
+        ("JP", [Register(0), Immediate(a)]) => Opcode::JpV0(address(a)?),
+        ("CALL", [Immediate(a)]) => Opcode::Call(address(a)?),
+        ("SE", [Register(x), Immediate(b)]) => Opcode::SeByte(*x, byte(b)?),
+        ("SE", [Register(x), Register(y)]) => Opcode::SeReg(*x, *y),
+        ("SNE", [Register(x), Immediate(b)]) => Opcode::SneByte(*x, byte(b)?),
+        ("SNE", [Register(x), Register(y)]) => Opcode::SneReg(*x, *y),
+        ("LD", [Register(x), Immediate(b)]) => Opcode::LdByte(*x, byte(b)?),
+        ("LD", [Register(x), Register(y)]) => Opcode::LdReg(*x, *y),
+        ("LD", [I, Immediate(a)]) => Opcode::LdI(address(a)?),
+        ("LD", [I, Long(a)]) => Opcode::LdILong(resolve(a)?),
+        ("LD", [Register(x), DelayTimer]) => Opcode::LdFromDelayTimer(*x),
+        ("LD", [Register(x), Key]) => Opcode::LdKey(*x),
+        ("LD", [DelayTimer, Register(x)]) => Opcode::LdDelayTimer(*x),
+        ("LD", [SoundTimer, Register(x)]) => Opcode::LdSoundTimer(*x),
+        ("LD", [Font, Register(x)]) => Opcode::LdFont(*x),
+        ("LD", [Bcd, Register(x)]) => Opcode::LdBcd(*x),
+        ("LD", [IndirectI, Register(x)]) => Opcode::Store(*x),
+        ("LD", [Register(x), IndirectI]) => Opcode::Load(*x),
+        ("LD", [Flags, Register(x)]) => Opcode::SaveFlags(*x),
+        ("LD", [Register(x), Flags]) => Opcode::LoadFlags(*x),
+        ("ADD", [Register(x), Immediate(b)]) => Opcode::AddByte(*x, byte(b)?),
+        ("ADD", [Register(x), Register(y)]) => Opcode::AddReg(*x, *y),
+        ("ADD", [I, Register(x)]) => Opcode::AddI(*x),
+        ("OR", [Register(x), Register(y)]) => Opcode::Or(*x, *y),
+        ("AND", [Register(x), Register(y)]) => Opcode::And(*x, *y),
+        ("XOR", [Register(x), Register(y)]) => Opcode::Xor(*x, *y),
+        ("SUB", [Register(x), Register(y)]) => Opcode::Sub(*x, *y),
+        ("SUBN", [Register(x), Register(y)]) => Opcode::Subn(*x, *y),
+        ("SHR", [Register(x)]) => Opcode::Shr(*x, *x),
+        ("SHR", [Register(x), Register(y)]) => Opcode::Shr(*x, *y),
+        ("SHL", [Register(x)]) => Opcode::Shl(*x, *x),
+        ("SHL", [Register(x), Register(y)]) => Opcode::Shl(*x, *y),
+        ("RND", [Register(x), Immediate(b)]) => Opcode::Rnd(*x, byte(b)?),
+        ("DRW", [Register(x), Register(y), Immediate(n)]) => Opcode::Drw(*x, *y, nibble(n)?),
+        ("SKP", [Register(x)]) => Opcode::Skp(*x),
+        ("SKNP", [Register(x)]) => Opcode::Sknp(*x),
+        ("SCU", [Immediate(n)]) => Opcode::ScrollUp(nibble(n)?),
+        ("SAVE", [Register(x), Register(y)]) => Opcode::SaveRange(*x, *y),
+        ("LOAD", [Register(x), Register(y)]) => Opcode::LoadRange(*x, *y),
+        ("PLANE", [Immediate(n)]) => Opcode::Plane(nibble(n)?),
+        ("AUDIO", []) => Opcode::Audio,
+        ("PITCH", [Register(x)]) => Opcode::Pitch(*x),
+        (mnemonic, _) if MNEMONICS.contains(&mnemonic) => return Err(invalid_operands(statement)),
+        (mnemonic, _) => {
+            return Err(AssemblerError::new(
+                line,
+                format!("Unknown instruction `{}`", mnemonic),
+            ))
+        }
+    };
+
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, AssemblerError};
+    use crate::disassembler::disassemble;
+    use crate::Opcode;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; Draw a sprite forever
+            start:
+                CLS
+                LD I, sprite
+                ld v0, 10     ; Mixed case is fine
+                DRW V0, V1, 2
+                JP start
+            sprite: DB 0xFF, 129
+        ";
+
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x00, 0xE0, 0xA2, 0x0A, 0x60, 0x0A, 0xD0, 0x12, 0x12, 0x00, 0xFF, 0x81
+            ])
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rom = [
+            0x00, 0xE0, 0x63, 0x42, 0x8A, 0xB6, 0xA2, 0xF0, 0xB3, 0x00, 0xD1, 0x25, 0xE3, 0xA1,
+            0xF5, 0x0A, 0xF2, 0x65, 0xF3, 0x55, 0x00, 0xD4, 0x51, 0x23, 0xF2, 0x01, 0xF0, 0x00,
+            0x12, 0x34, 0xF0, 0x02, 0xF3, 0x75, 0xF3, 0x85, 0x51, 0x21, 0xFF,
+        ];
+        let source = disassemble(&rom)
+            .into_iter()
+            .map(|(_, instruction)| instruction.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(assemble(&source), Ok(rom.to_vec()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |line, message: &str| {
+            Err(AssemblerError {
+                line,
+                message: message.to_string(),
+            })
+        };
+
+        assert_eq!(
+            assemble("CLS\nJP nowhere"),
+            error(2, "Unknown label `nowhere`")
+        );
+        assert_eq!(assemble("a:\na: CLS"), error(2, "Duplicate label `a`"));
+        assert_eq!(
+            assemble("LD V0, 0x100"),
+            error(1, "0x100 is larger than 0xff")
+        );
+        assert_eq!(assemble("DRW V0, V1"), error(1, "Invalid operands for DRW"));
+        assert_eq!(assemble("NOP"), error(1, "Unknown instruction `NOP`"));
+    }
+
+    #[test]
+    fn test_parse_opcode() {
+        for word in 0..=0xFFFF {
+            let opcode = Opcode::decode(word, 0x1234);
+            assert_eq!(opcode.to_string().parse(), Ok(opcode), "{:04X}", word);
+        }
+        assert_eq!("ld v3, 0x42 ; comment".parse(), Ok(Opcode::LdByte(3, 0x42)));
+        assert_eq!("DB 0xAB".parse(), Ok(Opcode::Byte(0xAB)));
+
+        assert_eq!(
+            "JP start".parse::<Opcode>(),
+            Err(AssemblerError {
+                line: 1,
+                message: "Unknown label `start`".to_string()
+            })
+        );
+        assert!("".parse::<Opcode>().is_err());
+        assert!("DB 1, 2, 3".parse::<Opcode>().is_err());
+        assert!("LD V3, 0x100".parse::<Opcode>().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use crate::Opcode;
+
+/// A subroutine call that hasn't returned yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    /// Where execution continues when the subroutine returns.
+    pub return_address: u16,
+    /// The address of the `CALL`, right before the return address.
+    pub call_site: u16,
+    /// The instruction at the call site, `None` if it's outside of memory. A ROM that
+    /// rewrites itself may have replaced the `CALL` since.
+    pub instruction: Option<Opcode>,
+}
+
+/// The subroutine calls on the stack, the innermost first, for finding out how a ROM ended up
+/// where it crashed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Backtrace {
+    pub calls: Vec<Call>,
+}
+
+impl Backtrace {
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, call) in self.calls.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  called from {:#05x}", call.call_site)?;
+            if let Some(instruction) = call.instruction {
+                write!(f, ": {}", instruction)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backtrace, Call};
+    use crate::Opcode;
+
+    #[test]
+    fn test_display() {
+        let backtrace = Backtrace {
+            calls: vec![
+                Call {
+                    return_address: 0x302,
+                    call_site: 0x300,
+                    instruction: Some(Opcode::Call(0x400)),
+                },
+                Call {
+                    return_address: 0x000,
+                    call_site: 0xFFFE,
+                    instruction: None,
+                },
+            ],
+        };
+
+        assert_eq!(
+            backtrace.to_string(),
+            "  called from 0x300: CALL 0x400\n  called from 0xfffe"
+        );
+        assert_eq!(Backtrace::default().to_string(), "");
+    }
+}
@@ -0,0 +1,250 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use rayon::prelude::*;
+
+use crate::hash;
+use crate::{Display, Emulator, Error, FramebufferDisplay, Input, Quirks};
+
+/// An [`Input`] where no key is ever pressed, used when running without a window.
+#[derive(Debug, Default)]
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn is_key_down(&self, _key: u8) -> bool {
+        false
+    }
+}
+
+/// A single ROM to run headlessly for a fixed number of cycles.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    pub rom: Vec<u8>,
+    pub cycles: usize,
+}
+
+impl Job {
+    pub fn new(name: impl Into<String>, rom: Vec<u8>, cycles: usize) -> Self {
+        Self {
+            name: name.into(),
+            rom,
+            cycles,
+        }
+    }
+}
+
+/// How a [`Job`] ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// All cycles were executed, `framebuffer_hash` identifies the final screen.
+    Completed { framebuffer_hash: u64 },
+    /// The emulator stopped with an error, typically because of an unknown opcode, or panicked.
+    Failed { message: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+impl JobResult {
+    pub fn is_success(&self) -> bool {
+        match self.outcome {
+            Outcome::Completed { .. } => true,
+            Outcome::Failed { .. } => false,
+        }
+    }
+}
+
+/// The results of a batch run, in the same order as the jobs that were passed to [`run`].
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub results: Vec<JobResult>,
+}
+
+impl Report {
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|r| r.is_success()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            match &result.outcome {
+                Outcome::Completed { framebuffer_hash } => {
+                    writeln!(f, "ok    {} {:016x}", result.name, framebuffer_hash)?
+                }
+                Outcome::Failed { message } => writeln!(f, "FAIL  {} {}", result.name, message)?,
+            }
+        }
+
+        write!(
+            f,
+            "{} jobs, {} succeeded, {} failed",
+            self.results.len(),
+            self.succeeded(),
+            self.failed()
+        )
+    }
+}
+
+/// Run all `jobs` concurrently, each on its own [`Emulator`].
+pub fn run(jobs: Vec<Job>) -> Report {
+    let results = jobs.into_par_iter().map(run_job).collect();
+
+    Report { results }
+}
+
+fn run_job(job: Job) -> JobResult {
+    let Job { name, rom, cycles } = job;
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| -> Result<u64, Box<dyn StdError>> {
+        let mut emulator = Emulator::new(FramebufferDisplay::default(), rom, Quirks::default())?;
+        run_cycles(&mut emulator, cycles)?;
+
+        Ok(framebuffer_hash(emulator.display()))
+    }));
+
+    let outcome = match outcome {
+        Ok(Ok(framebuffer_hash)) => Outcome::Completed { framebuffer_hash },
+        Ok(Err(error)) => Outcome::Failed {
+            message: error.to_string(),
+        },
+        Err(payload) => Outcome::Failed {
+            message: panic_message(payload.as_ref()),
+        },
+    };
+
+    JobResult { name, outcome }
+}
+
+/// Run `emulator` for `cycles` cycles without input, ticking the timers as if the CPU ran at
+/// its configured speed.
+pub fn run_cycles<D: Display>(emulator: &mut Emulator<D>, cycles: usize) -> Result<(), Error> {
+    let cycles_per_timer_tick = emulator.cycles_per_timer_tick();
+    // The timers tick after the first cycle, then after every `cycles_per_timer_tick` cycles
+    let mut remaining = cycles;
+    let mut until_tick = 1;
+    while remaining > 0 {
+        let chunk = until_tick.min(remaining);
+        // Stopping early means waiting for the tick, the rest of the chunk would do nothing
+        emulator.run_cycles(chunk as u32, &NullInput)?;
+        remaining -= chunk;
+        if chunk == until_tick {
+            emulator.tick_timers();
+        }
+        until_tick = cycles_per_timer_tick;
+    }
+
+    Ok(())
+}
+
+/// Enough to tell whether two runs ended on the same screen.
+pub fn framebuffer_hash(display: &dyn Display) -> u64 {
+    hash::fnv1a(
+        display
+            .rgba_framebuffer()
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes().to_vec()),
+    )
+}
+
+/// The screen as a plain PBM image, lit pixels are black.
+pub fn framebuffer_pbm(display: &dyn Display) -> String {
+    let mut pbm = format!("P1\n{} {}\n", display.width(), display.height());
+    for row in display.rgba_framebuffer().chunks(display.width()) {
+        let pixels = row
+            .iter()
+            .map(|&pixel| if pixel == 0 { "0" } else { "1" })
+            .collect::<Vec<_>>();
+        pbm.push_str(&pixels.join(" "));
+        pbm.push('\n');
+    }
+
+    pbm
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{framebuffer_pbm, run, Job, Outcome};
+    use crate::memory::Memory;
+    use crate::{Display, FramebufferDisplay};
+
+    #[test]
+    fn test_run_reports_in_job_order() {
+        // 00E0: CLS, 1200: JP 0x200
+        let looping = vec![0x00, 0xE0, 0x12, 0x00];
+        // 0001 is not an opcode this interpreter knows
+        let broken = vec![0x00, 0x01];
+
+        let report = run(vec![
+            Job::new("looping", looping.clone(), 100),
+            Job::new("broken", broken, 100),
+            Job::new("looping again", looping, 50),
+        ]);
+
+        assert_eq!(report.results.len(), 3);
+        assert_eq!(report.succeeded(), 2);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.results[1].name, "broken");
+        assert!(!report.results[1].is_success());
+        assert_eq!(report.results[0].outcome, report.results[2].outcome);
+    }
+
+    #[test]
+    fn test_completed_outcome_tracks_screen() {
+        // A050: LD I, 0x050 which is the font sprite for 0
+        // D005: DRW V0, V0, 5
+        // 1204: JP 0x204
+        let drawing = vec![0xA0, 0x50, 0xD0, 0x05, 0x12, 0x04];
+        let blank = vec![0x12, 0x00];
+
+        let report = run(vec![
+            Job::new("drawing", drawing, 10),
+            Job::new("blank", blank, 10),
+        ]);
+
+        match (&report.results[0].outcome, &report.results[1].outcome) {
+            (
+                Outcome::Completed {
+                    framebuffer_hash: drawn,
+                },
+                Outcome::Completed {
+                    framebuffer_hash: blank,
+                },
+            ) => assert_ne!(drawn, blank),
+            outcomes => panic!("Unexpected outcomes {:?}", outcomes),
+        }
+    }
+
+    #[test]
+    fn test_framebuffer_pbm() {
+        let mut display = FramebufferDisplay::with_resolution(8, 2);
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0xA0]);
+        display.draw_sprite(0, 1, 0x300, 1, &memory);
+
+        assert_eq!(
+            framebuffer_pbm(&display),
+            "P1\n8 2\n0 0 0 0 0 0 0 0\n1 0 1 0 0 0 0 0\n"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! Breakpoints with actions, for instrumenting runs without stopping at a prompt.
+//!
+//! A breakpoint is written as an address optionally followed by `;` separated actions:
+//!
+//! ```text
+//! 0x2F0: log V0 I [0x300]; set V1 0x10; poke 0x300:AB; continue
+//! ```
+//!
+//! Without `continue` execution stops after the actions have run.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Display, Emulator, Poke};
+
+/// A value that a `log` action can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// `VX`
+    Register(u8),
+    /// `I`
+    I,
+    /// `PC`
+    Pc,
+    /// `[ADDR]`, a byte of memory.
+    Memory(u16),
+}
+
+impl Operand {
+    /// The operand and its current value, e.g. `V3=2a`.
+    pub fn read<D: Display>(self, emulator: &Emulator<D>) -> String {
+        match self {
+            Operand::Register(register) => {
+                format!("V{:X}={:02x}", register, emulator.register(register))
+            }
+            Operand::I => format!("I={:#05x}", emulator.i()),
+            Operand::Pc => format!("PC={:#05x}", emulator.pc()),
+            Operand::Memory(address) => {
+                format!("[{:#05x}]={:02x}", address, emulator.peek(address))
+            }
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_uppercase();
+
+        match upper.as_str() {
+            "I" => Ok(Operand::I),
+            "PC" => Ok(Operand::Pc),
+            _ if upper.starts_with('V') && upper.len() == 2 => parse_number(&upper[1..])
+                .filter(|&register| register < 16)
+                .map(|register| Operand::Register(register as u8))
+                .ok_or_else(|| BreakpointError::new(format!("Invalid register `{}`", s))),
+            _ if upper.starts_with('[') && upper.ends_with(']') => {
+                parse_address(&upper[1..upper.len() - 1])
+                    .map(Operand::Memory)
+                    .ok_or_else(|| BreakpointError::new(format!("Invalid address in `{}`", s)))
+            }
+            _ => Err(BreakpointError::new(format!("Invalid operand `{}`", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// `log OPERAND...`: report the values of the operands.
+    Log(Vec<Operand>),
+    /// `set VX NN`
+    SetRegister(u8, u8),
+    /// `set I NNN`
+    SetI(u16),
+    /// `poke ADDR:BYTES`
+    Poke(Poke),
+}
+
+impl FromStr for Action {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+
+        match words.as_slice() {
+            ["log", operands @ ..] if !operands.is_empty() => operands
+                .iter()
+                .map(|operand| operand.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map(Action::Log),
+            ["set", target, value] => {
+                let value = parse_number(value)
+                    .ok_or_else(|| BreakpointError::new(format!("Invalid value `{}`", value)))?;
+
+                match target.parse()? {
+                    Operand::Register(register) if value <= 0xFF => {
+                        Ok(Action::SetRegister(register, value as u8))
+                    }
+                    Operand::I if value <= 0xFFF => Ok(Action::SetI(value)),
+                    _ => Err(BreakpointError::new(format!("Cannot set `{}`", s))),
+                }
+            }
+            ["poke", poke] => poke
+                .parse()
+                .map(Action::Poke)
+                .map_err(|error| BreakpointError::new(error.to_string())),
+            _ => Err(BreakpointError::new(format!("Unknown action `{}`", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub actions: Vec<Action>,
+    /// Keep executing once the actions have run rather than stopping.
+    pub resume: bool,
+}
+
+impl Breakpoint {
+    /// A plain breakpoint that stops at `address`.
+    pub fn new(address: u16) -> Self {
+        Self {
+            address,
+            actions: Vec::new(),
+            resume: false,
+        }
+    }
+
+    /// Run the actions against `emulator`, returning the lines produced by `log` actions.
+    pub fn run_actions<D: Display>(
+        &self,
+        emulator: &mut Emulator<D>,
+    ) -> Result<Vec<String>, BreakpointError> {
+        let mut log = Vec::new();
+
+        for action in &self.actions {
+            match action {
+                Action::Log(operands) => {
+                    let values = operands
+                        .iter()
+                        .map(|operand| operand.read(emulator))
+                        .collect::<Vec<_>>();
+                    log.push(format!("{:#05x}: {}", self.address, values.join(" ")));
+                }
+                Action::SetRegister(register, value) => emulator.set_register(*register, *value),
+                Action::SetI(value) => emulator.set_i(*value),
+                Action::Poke(poke) => emulator
+                    .poke(poke)
+                    .map_err(|error| BreakpointError::new(error.to_string()))?,
+            }
+        }
+
+        Ok(log)
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let address = parts.next().unwrap_or("").trim();
+        let address = parse_address(address)
+            .ok_or_else(|| BreakpointError::new(format!("Invalid address `{}`", address)))?;
+        let mut breakpoint = Breakpoint::new(address);
+
+        for action in parts.next().unwrap_or("").split(';') {
+            match action.trim() {
+                "" => {}
+                "continue" => breakpoint.resume = true,
+                action => breakpoint.actions.push(action.parse()?),
+            }
+        }
+
+        Ok(breakpoint)
+    }
+}
+
+/// What happened when execution reached a breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub address: u16,
+    pub log: Vec<String>,
+    /// Execution should stop, either because the breakpoint doesn't `continue` or because
+    /// one of its actions failed.
+    pub should_stop: bool,
+}
+
+/// A set of breakpoints checked before each instruction.
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    breakpoints: Vec<Breakpoint>,
+    /// The address execution last stopped at, so resuming doesn't immediately stop again.
+    stopped_at: Option<u16>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    /// Add `breakpoint`, replacing any existing breakpoint at the same address.
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        self.remove(breakpoint.address);
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove(&mut self, address: u16) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.address != address);
+
+        count != self.breakpoints.len()
+    }
+
+    /// Check whether the instruction about to execute has a breakpoint and if so run its
+    /// actions. Should be called before each [`Emulator::cycle`].
+    pub fn check<D: Display>(&mut self, emulator: &mut Emulator<D>) -> Option<Hit> {
+        let pc = emulator.pc();
+        if self.stopped_at.take() == Some(pc) {
+            return None;
+        }
+
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .find(|breakpoint| breakpoint.address == pc)?;
+
+        let hit = match breakpoint.run_actions(emulator) {
+            Ok(log) => Hit {
+                address: pc,
+                log,
+                should_stop: !breakpoint.resume,
+            },
+            Err(error) => Hit {
+                address: pc,
+                log: vec![format!("{:#05x}: {}", pc, error)],
+                should_stop: true,
+            },
+        };
+        if hit.should_stop {
+            self.stopped_at = Some(pc);
+        }
+
+        Some(hit)
+    }
+}
+
+/// Hex, with or without a `0x` prefix.
+pub(crate) fn parse_number(s: &str) -> Option<u16> {
+    let s = s.trim();
+
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        u16::from_str_radix(s, 16).ok()
+    }
+}
+
+fn parse_address(s: &str) -> Option<u16> {
+    parse_number(s).filter(|&address| address <= 0xFFF)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointError {
+    pub message: String,
+}
+
+impl BreakpointError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl fmt::Display for BreakpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for BreakpointError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Breakpoint, Breakpoints, Operand};
+    use crate::batch::NullInput;
+    use crate::{Emulator, FramebufferDisplay, Poke, Quirks};
+
+    #[test]
+    fn test_parse() {
+        let breakpoint: Breakpoint = "0x2F0: log V0 i [300]; set V1 0x10; set I 0x250; \
+                                      poke 0x300:AB; continue"
+            .parse()
+            .unwrap();
+
+        assert_eq!(breakpoint.address, 0x2F0);
+        assert!(breakpoint.resume);
+        assert_eq!(
+            breakpoint.actions,
+            vec![
+                Action::Log(vec![
+                    Operand::Register(0),
+                    Operand::I,
+                    Operand::Memory(0x300)
+                ]),
+                Action::SetRegister(1, 0x10),
+                Action::SetI(0x250),
+                Action::Poke(Poke {
+                    address: 0x300,
+                    bytes: vec![0xAB]
+                }),
+            ]
+        );
+
+        assert_eq!("0x200".parse(), Ok(Breakpoint::new(0x200)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for breakpoint in &[
+            "",
+            "0x1000",
+            "0x200: jump",
+            "0x200: log",
+            "0x200: log VG",
+            "0x200: set V0 0x100",
+            "0x200: poke 0x300",
+        ] {
+            assert!(
+                breakpoint.parse::<Breakpoint>().is_err(),
+                "{} should not parse",
+                breakpoint
+            );
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        // 6005: LD V0, 0x05, 1202: JP 0x202
+        let rom = vec![0x60, 0x05, 0x12, 0x02];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add("0x202: log V0; set V1 0x10; continue".parse().unwrap());
+
+        assert_eq!(breakpoints.check(&mut emulator), None);
+        emulator.cycle(&NullInput).unwrap();
+
+        let hit = breakpoints.check(&mut emulator).unwrap();
+        assert_eq!(hit.log, vec!["0x202: V0=05"]);
+        assert!(!hit.should_stop);
+        assert_eq!(emulator.register(1), 0x10);
+    }
+
+    #[test]
+    fn test_check_stops_once() {
+        // 1200: JP 0x200
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap();
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add(Breakpoint::new(0x200));
+
+        assert!(breakpoints.check(&mut emulator).unwrap().should_stop);
+        // Resuming from the breakpoint executes the instruction it stopped at
+        assert_eq!(breakpoints.check(&mut emulator), None);
+        emulator.cycle(&NullInput).unwrap();
+        assert!(breakpoints.check(&mut emulator).unwrap().should_stop);
+    }
+}
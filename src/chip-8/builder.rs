@@ -0,0 +1,209 @@
+use crate::emulator::cycles_per_frame_at;
+use crate::{
+    AccessPolicy, BuildError, Display, Emulator, FramebufferDisplay, Profile, Quirks, Variant,
+};
+
+/// Where the display comes from, the variant isn't known until [`EmulatorBuilder::build`].
+enum DisplaySource<D> {
+    Given(D),
+    ForVariant(fn(Variant) -> D),
+}
+
+/// Sets up an [`Emulator`] one setting at a time, anything that isn't set keeps the default of
+/// [`Emulator::for_rom`]:
+///
+/// ```no_run
+/// use chip_8::prelude::*;
+///
+/// let rom = std::fs::read("pong.ch8").unwrap();
+/// let mut emulator = EmulatorBuilder::new(rom).speed(700).seed(42).build().unwrap();
+/// emulator.run_frame(&NullInput).unwrap();
+/// ```
+pub struct EmulatorBuilder<D> {
+    rom: Vec<u8>,
+    display: DisplaySource<D>,
+    variant: Variant,
+    quirks: Option<Quirks>,
+    cycles_per_frame: Option<u32>,
+    seed: Option<u32>,
+    access_policy: AccessPolicy,
+}
+
+impl EmulatorBuilder<FramebufferDisplay> {
+    /// A CHIP-8 emulator for `rom` that draws to a [`FramebufferDisplay`] with the resolution
+    /// of the variant, unless [`EmulatorBuilder::display`] chooses another display.
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            display: DisplaySource::ForVariant(FramebufferDisplay::for_variant),
+            variant: Variant::Chip8,
+            quirks: None,
+            cycles_per_frame: None,
+            seed: None,
+            access_policy: AccessPolicy::Protected,
+        }
+    }
+}
+
+impl<D: Display> EmulatorBuilder<D> {
+    /// Draw to `display`, which is expected to have the variant's resolution.
+    pub fn display<E: Display>(self, display: E) -> EmulatorBuilder<E> {
+        EmulatorBuilder {
+            rom: self.rom,
+            display: DisplaySource::Given(display),
+            variant: self.variant,
+            quirks: self.quirks,
+            cycles_per_frame: self.cycles_per_frame,
+            seed: self.seed,
+            access_policy: self.access_policy,
+        }
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Use `quirks` even for ROMs that the [ROM database](crate::rom_db) has other quirks for.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Run `cycles` instructions every 60Hz frame, even for ROMs that the
+    /// [ROM database](crate::rom_db) has another speed for. [`EmulatorBuilder::build`] fails if
+    /// `cycles` is 0.
+    pub fn cycles_per_frame(mut self, cycles: u32) -> Self {
+        self.cycles_per_frame = Some(cycles);
+        self
+    }
+
+    /// Run about `cycles_per_second` instructions per second, see
+    /// [`EmulatorBuilder::cycles_per_frame`].
+    pub fn speed(self, cycles_per_second: u32) -> Self {
+        // Rounding 0 up to one instruction per frame would hide the mistake from build
+        let cycles = match cycles_per_second {
+            0 => 0,
+            hz => cycles_per_frame_at(hz),
+        };
+        self.cycles_per_frame(cycles)
+    }
+
+    /// The variant, quirks, and speed of `profile`.
+    pub fn profile(self, profile: Profile) -> Self {
+        self.variant(profile.variant())
+            .quirks(profile.quirks())
+            .cycles_per_frame(profile.instructions_per_frame())
+    }
+
+    /// Make CXNN reproducible, see [`Emulator::with_rng_seed`].
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn access_policy(mut self, policy: AccessPolicy) -> Self {
+        self.access_policy = policy;
+        self
+    }
+
+    /// The emulator, fails if the ROM doesn't fit in memory or the speed is 0.
+    pub fn build(self) -> Result<Emulator<D>, BuildError> {
+        if self.cycles_per_frame == Some(0) {
+            return Err(BuildError::NoCycles);
+        }
+
+        let display = match self.display {
+            DisplaySource::Given(display) => display,
+            DisplaySource::ForVariant(create) => create(self.variant),
+        };
+        let mut emulator = match self.quirks {
+            Some(quirks) => Emulator::with_variant(display, self.rom, self.variant, quirks)?,
+            None => Emulator::for_rom(display, self.rom, self.variant)?,
+        };
+        if let Some(cycles) = self.cycles_per_frame {
+            emulator.set_cycles_per_frame(cycles);
+        }
+        if let Some(seed) = self.seed {
+            emulator = emulator.with_rng_seed(seed);
+        }
+        emulator.set_access_policy(self.access_policy);
+
+        Ok(emulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmulatorBuilder;
+    use crate::batch::NullInput;
+    use crate::{AccessPolicy, BuildError, Display, NopDisplay, Quirks, RomError, Variant};
+
+    #[test]
+    fn test_defaults() {
+        let emulator = EmulatorBuilder::new(vec![0x12, 0x00]).build().unwrap();
+
+        assert_eq!(emulator.variant(), Variant::Chip8);
+        assert_eq!(emulator.quirks(), Quirks::default());
+        assert_eq!(emulator.access_policy(), AccessPolicy::Protected);
+        assert_eq!(emulator.display().width(), 64);
+    }
+
+    #[test]
+    fn test_settings() {
+        let quirks = "vip".parse::<Quirks>().unwrap();
+        let emulator = EmulatorBuilder::new(vec![0x12, 0x00])
+            .variant(Variant::HiRes)
+            .quirks(quirks)
+            .speed(600)
+            .access_policy(AccessPolicy::Unrestricted)
+            .build()
+            .unwrap();
+
+        assert_eq!(emulator.variant(), Variant::HiRes);
+        assert_eq!(emulator.quirks(), quirks);
+        assert_eq!(emulator.cycles_per_frame(), 10);
+        assert_eq!(emulator.access_policy(), AccessPolicy::Unrestricted);
+        assert_eq!(emulator.display().height(), 64);
+    }
+
+    #[test]
+    fn test_seed() {
+        // C0FF: RND V0, 0xFF
+        let run = || {
+            let mut emulator = EmulatorBuilder::new(vec![0xC0, 0xFF])
+                .seed(7)
+                .display(NopDisplay)
+                .build()
+                .unwrap();
+            emulator.cycle(&NullInput).unwrap();
+            emulator.register(0)
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let result = EmulatorBuilder::new(vec![0; 0x1000]).build();
+
+        assert!(matches!(
+            result,
+            Err(BuildError::Rom(RomError::TooLarge { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_no_cycles() {
+        let build = |builder: EmulatorBuilder<_>| builder.build().map(|_| ());
+
+        assert_eq!(
+            build(EmulatorBuilder::new(vec![0x12, 0x00]).cycles_per_frame(0)),
+            Err(BuildError::NoCycles)
+        );
+        assert_eq!(
+            build(EmulatorBuilder::new(vec![0x12, 0x00]).speed(0)),
+            Err(BuildError::NoCycles)
+        );
+    }
+}
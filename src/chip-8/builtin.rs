@@ -0,0 +1,82 @@
+//! A few small ROMs embedded in the library, so the emulator can be tried out without finding
+//! ROM files first. They are the test ROMs in `tests/roms`, written for this repository, and are
+//! assembled when they are loaded.
+use crate::assembler::assemble;
+
+/// A ROM embedded in the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinRom {
+    pub name: &'static str,
+    pub description: &'static str,
+    source: &'static str,
+}
+
+impl BuiltinRom {
+    /// The assembly source of the ROM.
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    /// The assembled ROM, ready to load into an [`Emulator`](crate::Emulator).
+    pub fn rom(&self) -> Vec<u8> {
+        assemble(self.source).unwrap_or_else(|error| {
+            panic!("Built-in ROM {} doesn't assemble: {}", self.name, error)
+        })
+    }
+}
+
+const BUILTIN_ROMS: [BuiltinRom; 5] = [
+    BuiltinRom {
+        name: "logo",
+        description: "Draws CHIP-8 in the middle of the screen, like the IBM logo ROM",
+        source: include_str!("../../tests/roms/logo.asm"),
+    },
+    BuiltinRom {
+        name: "pong",
+        description: "Two player Pong, 1 and 4 move the left paddle, C and D the right one",
+        source: include_str!("../../tests/roms/pong.asm"),
+    },
+    BuiltinRom {
+        name: "opcodes",
+        description: "Tests the basic instructions, drawing 0-F when all pass",
+        source: include_str!("../../tests/roms/opcodes.asm"),
+    },
+    BuiltinRom {
+        name: "flags",
+        description: "Tests how instructions set VF, drawing 0-E when all pass",
+        source: include_str!("../../tests/roms/flags.asm"),
+    },
+    BuiltinRom {
+        name: "quirks",
+        description: "Shows which quirks are on",
+        source: include_str!("../../tests/roms/quirks.asm"),
+    },
+];
+
+/// Every built-in ROM.
+pub fn builtin_roms() -> &'static [BuiltinRom] {
+    &BUILTIN_ROMS
+}
+
+/// The built-in ROM called `name`.
+pub fn find(name: &str) -> Option<&'static BuiltinRom> {
+    builtin_roms().iter().find(|rom| rom.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin_roms, find};
+
+    #[test]
+    fn test_builtin_roms_assemble() {
+        for rom in builtin_roms() {
+            assert!(!rom.rom().is_empty(), "{} is empty", rom.name);
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("pong").map(|rom| rom.name), Some("pong"));
+        assert!(find("tetris").is_none());
+    }
+}
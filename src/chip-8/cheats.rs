@@ -0,0 +1,162 @@
+//! Cheats that keep bytes of memory at fixed values, e.g. the lives counter of a game, by
+//! writing them again after every frame.
+//!
+//! Each game has its own cheat file, by default next to the ROM with the extension `.cheats`.
+//! Every line names a cheat and gives the bytes to write as a [`Poke`], cheats start enabled:
+//!
+//! ```text
+//! # Never run out of lives
+//! Infinite lives = 0x3A0:03
+//! ```
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::controller::ParseError;
+use crate::{Display, Emulator, Poke, PokeError};
+
+/// The extension of cheat files, which are found next to the ROM.
+pub const EXTENSION: &str = "cheats";
+
+/// The cheat file for the ROM at `rom`, it might not exist.
+pub fn path_for(rom: &Path) -> PathBuf {
+    rom.with_extension(EXTENSION)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub poke: Poke,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut cheats = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.rsplitn(2, '=');
+            let (poke, name) = match (parts.next(), parts.next()) {
+                (Some(poke), Some(name)) if !name.trim().is_empty() => (poke, name.trim()),
+                _ => {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Expected `name = ADDR:BYTES`, got `{}`", line),
+                    ))
+                }
+            };
+            let poke = poke
+                .trim()
+                .parse::<Poke>()
+                .map_err(|error| ParseError::new(line_number, error.to_string()))?;
+
+            cheats.push(Cheat {
+                name: name.to_string(),
+                poke,
+                enabled: true,
+            });
+        }
+
+        Ok(Self { cheats })
+    }
+
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    /// Turn the cheat at `index` on or off, returns whether there is one.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Write the bytes of every enabled cheat, stopping at the first that doesn't fit in
+    /// memory.
+    pub fn apply<D: Display>(&self, emulator: &mut Emulator<D>) -> Result<(), PokeError> {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            emulator.poke(&cheat.poke)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Cheats {
+    /// One line per cheat, numbered from 1.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, cheat) in self.cheats.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>2} [{}] {} = {}",
+                index + 1,
+                if cheat.enabled { "on" } else { "off" },
+                cheat.name,
+                cheat.poke
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cheats;
+    use crate::{Emulator, FramebufferDisplay, Quirks};
+
+    #[test]
+    fn test_parse_and_apply() {
+        let mut cheats =
+            Cheats::parse("# Pong\nInfinite lives = 0x3A0:03\n\nMax = score = 0x3A4:0999\n")
+                .unwrap();
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats.iter().nth(1).unwrap().name, "Max = score");
+
+        // 1200: JP 0x200
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap();
+        assert!(cheats.set_enabled(1, false));
+        assert!(!cheats.set_enabled(2, false));
+        cheats.apply(&mut emulator).unwrap();
+        assert_eq!(emulator.peek(0x3A0), 3);
+        assert_eq!(emulator.peek(0x3A4), 0);
+        assert_eq!(
+            cheats.to_string(),
+            " 1 [on] Infinite lives = 0x3a0:03\n 2 [off] Max = score = 0x3a4:0999\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Cheats::parse("Lives = 0x3A0:03\n= 0x3A0:03").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(Cheats::parse("Lives = 0x3A0").is_err());
+        assert!(Cheats::parse("Lives").is_err());
+    }
+}
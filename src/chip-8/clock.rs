@@ -0,0 +1,103 @@
+//! Sources of time for running in real time, see [`Runner`](crate::runner::Runner). The
+//! emulator itself never reads the time, only what drives it at 60Hz does, so swapping the
+//! clock makes everything about timing testable without sleeping.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Monotonic time and a way to wait for it to pass.
+pub trait Clock: Send {
+    /// The time since the clock was created, never less than an earlier call returned.
+    fn now(&self) -> Duration;
+
+    /// Wait until `duration` has passed.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The time of the machine, for frontends.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Time that only passes when told to, for tests. Clones share the time, keep one to
+/// [advance](ManualClock::advance) it after handing another to a runner. Sleeping advances it
+/// instantly.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, ManualClock, SystemClock};
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let shared = clock.clone();
+        assert_eq!(clock.now(), Duration::from_secs(0));
+
+        shared.advance(Duration::from_millis(16));
+        clock.sleep(Duration::from_millis(1));
+
+        assert_eq!(clock.now(), Duration::from_millis(17));
+        assert_eq!(shared.now(), clock.now());
+    }
+
+    #[test]
+    fn test_system_clock_is_monotonic() {
+        let clock = SystemClock::new();
+        let earlier = clock.now();
+
+        assert!(clock.now() >= earlier);
+    }
+}
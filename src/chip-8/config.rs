@@ -0,0 +1,414 @@
+//! Frontend settings read from a `chip8.toml` file, with overrides for specific ROMs.
+//!
+//! The file is a small subset of TOML. Settings at the top apply to every ROM, `[rom.<name>]`
+//! tables override them for the ROM with that file name or SHA-1, see [`rom_db::rom_id`].
+//! Values are written the same way as on the command line of the frontend in this crate:
+//!
+//! `rom-dir`, the directory the frontend lists ROMs from when it's started without one, can only
+//! be set at the top.
+//!
+//! ```text
+//! rom-dir = "/home/me/roms"
+//! palette = "amber"
+//! keymap = "azerty"
+//! scale = 12
+//!
+//! [rom."pong.ch8"]
+//! quirks = "vip"
+//! speed = 700
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::controller::ParseError;
+use crate::rom_db;
+use crate::{Palette, Quirks};
+
+/// The file name frontends look for in their config directory.
+pub const FILE_NAME: &str = "chip8.toml";
+
+/// Settings that are `None` when the file doesn't set them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Settings {
+    /// A preset or comma separated hex colors, see [`Palette`].
+    pub palette: Option<String>,
+    /// A keyboard layout or the path of a [keymap file](crate::keymap).
+    pub keymap: Option<String>,
+    /// How many times the size of the display the window starts.
+    pub scale: Option<usize>,
+    /// Presets and quirk names, see [`Quirks`].
+    pub quirks: Option<String>,
+    /// Instructions per second.
+    pub speed: Option<u32>,
+}
+
+impl Settings {
+    /// The names of the settings, as used in the file.
+    pub const NAMES: [&'static str; 5] = ["palette", "keymap", "scale", "quirks", "speed"];
+
+    /// The setting called `name` as it would be written on the command line.
+    pub fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "palette" => self.palette.clone(),
+            "keymap" => self.keymap.clone(),
+            "scale" => self.scale.map(|scale| scale.to_string()),
+            "quirks" => self.quirks.clone(),
+            "speed" => self.speed.map(|speed| speed.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Take every setting that `overrides` sets.
+    pub fn apply(&mut self, overrides: &Settings) {
+        fn or<T: Clone>(setting: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                setting.clone_from(other);
+            }
+        }
+
+        or(&mut self.palette, &overrides.palette);
+        or(&mut self.keymap, &overrides.keymap);
+        or(&mut self.scale, &overrides.scale);
+        or(&mut self.quirks, &overrides.quirks);
+        or(&mut self.speed, &overrides.speed);
+    }
+
+    /// Whether no setting is set.
+    pub fn is_empty(&self) -> bool {
+        Settings::NAMES
+            .iter()
+            .all(|name| self.value(name).is_none())
+    }
+
+    fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match (name, value) {
+            ("palette", Value::String(palette)) => {
+                palette.parse::<Palette>()?;
+                self.palette = Some(palette);
+            }
+            ("keymap", Value::String(keymap)) => self.keymap = Some(keymap),
+            ("scale", Value::Integer(scale)) if scale > 0 => self.scale = Some(scale as usize),
+            ("quirks", Value::String(quirks)) => {
+                quirks.parse::<Quirks>()?;
+                self.quirks = Some(quirks);
+            }
+            ("speed", Value::Integer(speed)) if speed > 0 && speed <= u64::from(u32::MAX) => {
+                self.speed = Some(speed as u32)
+            }
+            (name, _) if Settings::NAMES.contains(&name) => {
+                return Err(format!("Invalid value for `{}`", name))
+            }
+            (name, _) => return Err(format!("Unknown setting `{}`", name)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Settings for every ROM and overrides for some, keyed by file name or SHA-1.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Config {
+    #[cfg_attr(feature = "serde", serde(rename = "rom-dir"))]
+    pub rom_dir: Option<String>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub defaults: Settings,
+    #[cfg_attr(feature = "serde", serde(rename = "rom"))]
+    pub roms: BTreeMap<String, Settings>,
+}
+
+impl Config {
+    /// Parse a config file, see the [module documentation](self) for the format.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut config = Self::default();
+        let mut current: Option<String> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = |message: String| ParseError::new(line_number, message);
+            if line.starts_with('[') && line.ends_with(']') {
+                let table = line[1..line.len() - 1].trim();
+                let name = table
+                    .strip_prefix("rom.")
+                    .and_then(|name| parse_key(name.trim()))
+                    .ok_or_else(|| invalid(format!("Unknown table `{}`", table)))?;
+                config.roms.entry(name.clone()).or_default();
+                current = Some(name);
+
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => {
+                    return Err(invalid(format!(
+                        "Expected `setting = value`, got `{}`",
+                        line
+                    )))
+                }
+            };
+            let value = parse_value(value)
+                .ok_or_else(|| invalid(format!("Expected a string or number, got `{}`", value)))?;
+            if name == "rom-dir" {
+                config.rom_dir = match (&current, value) {
+                    (None, Value::String(dir)) => Some(dir),
+                    (None, _) => return Err(invalid("Invalid value for `rom-dir`".to_owned())),
+                    (Some(_), _) => {
+                        return Err(invalid("`rom-dir` can only be set at the top".to_owned()))
+                    }
+                };
+
+                continue;
+            }
+            let settings = match &current {
+                None => &mut config.defaults,
+                Some(name) => config
+                    .roms
+                    .get_mut(name)
+                    .expect("Table should have been created"),
+            };
+            settings.set(name, value).map_err(invalid)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Read and parse the file at `path`, errors are reported with the path.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let source = fs::read_to_string(path)?;
+
+        Self::parse(&source).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), error),
+            )
+        })
+    }
+
+    /// The settings for `rom`, loaded from a file called `file_name`. Overrides by SHA-1 take
+    /// precedence over the ones by file name, which take precedence over the defaults.
+    pub fn settings_for(&self, file_name: &str, rom: &[u8]) -> Settings {
+        let mut settings = self.defaults.clone();
+        for key in &[file_name.to_owned(), rom_db::rom_id(rom)] {
+            if let Some(overrides) = self.roms.get(key) {
+                settings.apply(overrides);
+            }
+        }
+
+        settings
+    }
+}
+
+/// Written in the format [`Config::parse`] reads.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(dir) = &self.rom_dir {
+            writeln!(f, "rom-dir = {}", quote(dir))?;
+        }
+        write_settings(f, &self.defaults)?;
+        for (index, (name, settings)) in self.roms.iter().enumerate() {
+            if index > 0 || self.rom_dir.is_some() || !self.defaults.is_empty() {
+                writeln!(f)?;
+            }
+            writeln!(f, "[rom.{}]", quote(name))?;
+            write_settings(f, settings)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_settings(f: &mut fmt::Formatter, settings: &Settings) -> fmt::Result {
+    for name in &Settings::NAMES {
+        match (*name, settings.value(name)) {
+            (_, None) => {}
+            ("scale", Some(value)) | ("speed", Some(value)) => writeln!(f, "{} = {}", name, value)?,
+            (_, Some(value)) => writeln!(f, "{} = {}", name, quote(&value))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// `value` as a basic string, the inverse of [`parse_string`].
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+enum Value {
+    String(String),
+    Integer(u64),
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    match parse_string(value) {
+        Some(string) => Some(Value::String(string)),
+        None => value.replace('_', "").parse().ok().map(Value::Integer),
+    }
+}
+
+/// A bare key or a quoted string, e.g. `pong` or `"pong.ch8"`. SHA-1s are made lower case to
+/// match [`rom_db::rom_id`].
+fn parse_key(key: &str) -> Option<String> {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    let key = if is_bare {
+        key.to_owned()
+    } else {
+        parse_string(key)?
+    };
+
+    if key.len() == 40 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(key.to_lowercase())
+    } else {
+        Some(key)
+    }
+}
+
+/// A basic or literal string, only `\"` and `\\` are unescaped in basic strings.
+fn parse_string(value: &str) -> Option<String> {
+    if value.len() < 2 {
+        return None;
+    }
+
+    let inner = &value[1..value.len() - 1];
+    if value.starts_with('\'') && value.ends_with('\'') && !inner.contains('\'') {
+        return Some(inner.to_owned());
+    }
+    if !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ '"') | Some(escaped @ '\\') => string.push(escaped),
+                _ => return None,
+            },
+            '"' => return None,
+            _ => string.push(c),
+        }
+    }
+
+    Some(string)
+}
+
+/// `line` up to a `#` that isn't in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut is_escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !is_escaped => {
+                is_escaped = true;
+                continue;
+            }
+            (Some(open), _) if c == open && !is_escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        is_escaped = false;
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Settings};
+    use crate::rom_db;
+
+    #[test]
+    fn test_parse() {
+        let rom = [0x12, 0x00];
+        let source = format!(
+            "rom-dir = '/home/me/roms'\n\
+             # Everything\n\
+             palette = \"amber\" # the default\n\
+             scale = 12\n\
+             \n\
+             [rom.\"pong.ch8\"]\n\
+             quirks = 'vip'\n\
+             speed = 1_000\n\
+             \n\
+             [rom.{}]\n\
+             speed = 700\n\
+             keymap = \"keys #2.txt\"\n",
+            rom_db::rom_id(&rom).to_uppercase()
+        );
+
+        let config = Config::parse(&source).unwrap();
+
+        assert_eq!(config.rom_dir.as_deref(), Some("/home/me/roms"));
+        assert_eq!(config.defaults.palette.as_deref(), Some("amber"));
+        assert_eq!(config.defaults.scale, Some(12));
+        assert_eq!(
+            config.settings_for("pong.ch8", &[0x00]),
+            Settings {
+                palette: Some("amber".to_owned()),
+                keymap: None,
+                scale: Some(12),
+                quirks: Some("vip".to_owned()),
+                speed: Some(1000),
+            }
+        );
+        let settings = config.settings_for("pong.ch8", &rom);
+        assert_eq!(settings.speed, Some(700));
+        assert_eq!(settings.keymap.as_deref(), Some("keys #2.txt"));
+        assert_eq!(settings.value("scale").as_deref(), Some("12"));
+        assert_eq!(config.settings_for("other.ch8", &[0x00]), config.defaults);
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut config = Config::default();
+        config.defaults.scale = Some(8);
+        config.roms.insert(
+            "my \"game\".ch8".to_owned(),
+            Settings {
+                keymap: Some("C:\\keys.txt".to_owned()),
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            config.to_string(),
+            "scale = 8\n\n[rom.\"my \\\"game\\\".ch8\"]\nkeymap = \"C:\\\\keys.txt\"\n"
+        );
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let line = |source: &str| Config::parse(source).unwrap_err().line;
+
+        assert_eq!(line("scale = 2\nsize = 3"), 2);
+        assert_eq!(line("palette = \"purple\""), 1);
+        assert_eq!(line("quirks = \"vip\"\nscale = \"big\""), 2);
+        assert_eq!(line("speed = 0"), 1);
+        assert_eq!(line("palette = amber"), 1);
+        assert_eq!(line("\n[roms.pong]"), 2);
+        assert_eq!(line("scale"), 1);
+        assert_eq!(line("rom-dir = 1"), 1);
+        assert_eq!(line("[rom.pong]\nrom-dir = \"roms\""), 2);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+}
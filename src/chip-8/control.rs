@@ -0,0 +1,252 @@
+//! The messages of the control channel, for test scripts and editor plugins that drive a
+//! running emulator without linking this crate. Built with `--features control`.
+//!
+//! Every request is a JSON object on a line of its own, with the command in `cmd`, and gets a
+//! [`Response`] on a line of its own:
+//!
+//! ```text
+//! {"cmd": "press_key", "key": "A", "frames": 6}
+//! {"ok":true}
+//! {"cmd": "read_memory", "address": 512, "length": 4}
+//! {"ok":true,"address":512,"bytes":[0,224,162,42]}
+//! ```
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::json::Value;
+
+/// How long [`Request::PressKey`] holds a key without `frames`, a tenth of a second. Games that
+/// check the keys every few frames would miss a shorter press.
+pub const DEFAULT_PRESS_FRAMES: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Switch to the ROM at `path` and start it.
+    LoadRom {
+        path: PathBuf,
+    },
+    Pause,
+    Resume,
+    /// Pause, then run `frames` frames, 1 by default.
+    Step {
+        frames: u32,
+    },
+    /// Save a PNG of the display to `path`.
+    Screenshot {
+        path: PathBuf,
+    },
+    ReadMemory {
+        address: u16,
+        length: u16,
+    },
+    /// Hold `key`, a number or a hex digit, on top of the keyboard for `frames` frames,
+    /// [`DEFAULT_PRESS_FRAMES`] by default.
+    PressKey {
+        key: u8,
+        frames: u32,
+    },
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = Value::parse(s)?;
+        let command = value
+            .get("cmd")
+            .and_then(Value::as_str)
+            .ok_or("Expected an object with the command in `cmd`")?;
+
+        match command {
+            "load_rom" => Ok(Request::LoadRom {
+                path: path(&value)?,
+            }),
+            "pause" => Ok(Request::Pause),
+            "resume" => Ok(Request::Resume),
+            "step" => Ok(Request::Step {
+                frames: number(&value, "frames", Some(1))?,
+            }),
+            "screenshot" => Ok(Request::Screenshot {
+                path: path(&value)?,
+            }),
+            "read_memory" => Ok(Request::ReadMemory {
+                address: number(&value, "address", None)?,
+                length: number(&value, "length", None)?,
+            }),
+            "press_key" => Ok(Request::PressKey {
+                key: key(&value)?,
+                frames: number(&value, "frames", Some(DEFAULT_PRESS_FRAMES))?,
+            }),
+            _ => Err(format!(
+                "Unknown command `{}`, expected one of load_rom, pause, resume, step, \
+                 screenshot, read_memory, or press_key",
+                command
+            )),
+        }
+    }
+}
+
+fn path(value: &Value) -> Result<PathBuf, String> {
+    value
+        .get("path")
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| "Expected the file in `path`".to_owned())
+}
+
+/// The whole number in `name` that fits in a `T`, `default` when it's missing.
+fn number<T: TryFrom<u64>>(value: &Value, name: &str, default: Option<T>) -> Result<T, String> {
+    let number = match (value.get(name), default) {
+        (None, Some(default)) => return Ok(default),
+        (None, None) => return Err(format!("Expected a number in `{}`", name)),
+        (Some(number), _) => number,
+    };
+
+    number
+        .as_u64()
+        .and_then(|number| T::try_from(number).ok())
+        .ok_or_else(|| format!("`{}` is out of range or not a whole number", name))
+}
+
+fn key(value: &Value) -> Result<u8, String> {
+    let key = match value.get("key") {
+        Some(Value::String(digit)) => u8::from_str_radix(digit, 16).ok(),
+        Some(number) => number.as_u64().map(|key| key.min(0x10) as u8),
+        None => None,
+    };
+
+    key.filter(|&key| key <= 0xF)
+        .ok_or_else(|| "Expected a key from 0 to F in `key`".to_owned())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The request was carried out.
+    Done,
+    /// Where execution stopped after [`Request::Step`].
+    Stepped {
+        pc: u16,
+        cycles: u64,
+    },
+    Memory {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    /// The request was invalid or failed.
+    Error(String),
+}
+
+/// The response as a single line of JSON, without the newline.
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ok = ("ok".to_owned(), Value::Bool(true));
+        let fields = match self {
+            Response::Done => vec![ok],
+            Response::Stepped { pc, cycles } => vec![
+                ok,
+                ("pc".to_owned(), u64::from(*pc).into()),
+                ("cycles".to_owned(), (*cycles).into()),
+            ],
+            Response::Memory { address, bytes } => vec![
+                ok,
+                ("address".to_owned(), u64::from(*address).into()),
+                (
+                    "bytes".to_owned(),
+                    Value::Array(bytes.iter().map(|&byte| u64::from(byte).into()).collect()),
+                ),
+            ],
+            Response::Error(message) => vec![
+                ("ok".to_owned(), Value::Bool(false)),
+                ("error".to_owned(), message.as_str().into()),
+            ],
+        };
+
+        write!(f, "{}", Value::Object(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Request, Response, DEFAULT_PRESS_FRAMES};
+
+    #[test]
+    fn test_parse() {
+        let parse = |line: &str| line.parse::<Request>();
+
+        assert_eq!(
+            parse(r#"{"cmd": "load_rom", "path": "roms/pong.ch8"}"#),
+            Ok(Request::LoadRom {
+                path: PathBuf::from("roms/pong.ch8")
+            })
+        );
+        assert_eq!(parse(r#"{"cmd": "pause"}"#), Ok(Request::Pause));
+        assert_eq!(parse(r#"{"cmd": "step"}"#), Ok(Request::Step { frames: 1 }));
+        assert_eq!(
+            parse(r#"{"cmd": "read_memory", "address": 4095, "length": 1}"#),
+            Ok(Request::ReadMemory {
+                address: 0xFFF,
+                length: 1
+            })
+        );
+        assert_eq!(
+            parse(r#"{"cmd": "press_key", "key": "a"}"#),
+            Ok(Request::PressKey {
+                key: 0xA,
+                frames: DEFAULT_PRESS_FRAMES
+            })
+        );
+        assert_eq!(
+            parse(r#"{"cmd": "press_key", "key": 15, "frames": 2}"#),
+            Ok(Request::PressKey {
+                key: 0xF,
+                frames: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for line in &[
+            "pause",
+            r#"{"command": "pause"}"#,
+            r#"{"cmd": "jump"}"#,
+            r#"{"cmd": "screenshot"}"#,
+            r#"{"cmd": "read_memory", "address": 65536, "length": 1}"#,
+            r#"{"cmd": "read_memory", "address": -1, "length": 1}"#,
+            r#"{"cmd": "step", "frames": 1.5}"#,
+            r#"{"cmd": "press_key", "key": 16}"#,
+            r#"{"cmd": "press_key", "key": "G"}"#,
+        ] {
+            assert!(line.parse::<Request>().is_err(), "`{}` parsed", line);
+        }
+    }
+
+    #[test]
+    fn test_responses() {
+        assert_eq!(Response::Done.to_string(), r#"{"ok":true}"#);
+        assert_eq!(
+            Response::Stepped {
+                pc: 0x202,
+                cycles: 10
+            }
+            .to_string(),
+            r#"{"ok":true,"pc":514,"cycles":10}"#
+        );
+        assert_eq!(
+            Response::Memory {
+                address: 0x200,
+                bytes: vec![0x00, 0xE0]
+            }
+            .to_string(),
+            r#"{"ok":true,"address":512,"bytes":[0,224]}"#
+        );
+        assert_eq!(
+            Response::Error("No \"ROM\"".to_owned()).to_string(),
+            r#"{"ok":false,"error":"No \"ROM\""}"#
+        );
+    }
+}
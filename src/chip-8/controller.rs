@@ -0,0 +1,279 @@
+//! Mapping of game controller buttons to CHIP-8 keys, with per-ROM profiles.
+//!
+//! Profiles are read from a small config format where each section maps buttons to keys.
+//! `[default]` applies to every ROM and `[rom <hash>]` sections, keyed by [`rom_hash`],
+//! override individual buttons for a specific game:
+//!
+//! ```text
+//! # Most games use 5 for jump
+//! [default]
+//! south = 0x5
+//!
+//! [rom 1f2e3d4c5b6a7980]
+//! south = 0x6
+//! ```
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::hash;
+
+/// Buttons on a typical game controller, named by position rather than label since
+/// layouts differ between vendors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 12] = [
+        Button::South,
+        Button::East,
+        Button::North,
+        Button::West,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+        Button::LeftTrigger,
+        Button::RightTrigger,
+        Button::Select,
+        Button::Start,
+    ];
+
+    /// The name used for this button in profile configs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::South => "south",
+            Button::East => "east",
+            Button::North => "north",
+            Button::West => "west",
+            Button::DPadUp => "dpad_up",
+            Button::DPadDown => "dpad_down",
+            Button::DPadLeft => "dpad_left",
+            Button::DPadRight => "dpad_right",
+            Button::LeftTrigger => "left_trigger",
+            Button::RightTrigger => "right_trigger",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Button::ALL
+            .iter()
+            .cloned()
+            .find(|button| button.name() == name)
+    }
+}
+
+/// Which CHIP-8 key, if any, each controller button presses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonMap {
+    keys: HashMap<Button, u8>,
+}
+
+impl ButtonMap {
+    /// A map where no button presses any key.
+    pub fn empty() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn key_for(&self, button: Button) -> Option<u8> {
+        self.keys.get(&button).cloned()
+    }
+
+    pub fn set(&mut self, button: Button, key: u8) {
+        assert!(key < 16, "Invalid CHIP-8 key {:#02x}", key);
+
+        self.keys.insert(button, key);
+    }
+}
+
+impl Default for ButtonMap {
+    /// The directional pad is mapped to 2, 8, 4, and 6 which is what most games use for
+    /// movement, with the face buttons on the keys around them.
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.set(Button::DPadUp, 0x2);
+        map.set(Button::DPadDown, 0x8);
+        map.set(Button::DPadLeft, 0x4);
+        map.set(Button::DPadRight, 0x6);
+        map.set(Button::South, 0x5);
+        map.set(Button::East, 0x6);
+        map.set(Button::West, 0x4);
+        map.set(Button::North, 0x1);
+        map.set(Button::Select, 0x0);
+        map.set(Button::Start, 0xF);
+
+        map
+    }
+}
+
+/// The identifier used to select per-ROM profiles.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    hash::fnv1a(rom.iter().cloned())
+}
+
+/// A default [`ButtonMap`] and per-ROM overrides keyed by [`rom_hash`].
+#[derive(Debug, Clone, Default)]
+pub struct ControllerProfiles {
+    default: ButtonMap,
+    roms: HashMap<u64, ButtonMap>,
+}
+
+impl ControllerProfiles {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut profiles = Self::default();
+        let mut current: Option<u64> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let section = line[1..line.len() - 1].trim();
+                current = if section == "default" {
+                    None
+                } else if let Some(hash) = section.strip_prefix("rom ") {
+                    let hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| {
+                        ParseError::new(line_number, format!("Invalid ROM hash `{}`", hash))
+                    })?;
+                    profiles.roms.entry(hash).or_insert_with(ButtonMap::empty);
+
+                    Some(hash)
+                } else {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Unknown section `{}`", section),
+                    ));
+                };
+
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Expected `button = key`, got `{}`", line),
+                    ))
+                }
+            };
+            let button = Button::from_name(name).ok_or_else(|| {
+                ParseError::new(line_number, format!("Unknown button `{}`", name))
+            })?;
+            let key = parse_key(value).ok_or_else(|| {
+                ParseError::new(line_number, format!("Invalid CHIP-8 key `{}`", value))
+            })?;
+
+            match current {
+                None => profiles.default.set(button, key),
+                Some(hash) => profiles
+                    .roms
+                    .get_mut(&hash)
+                    .expect("Section should have been created")
+                    .set(button, key),
+            }
+        }
+
+        Ok(profiles)
+    }
+
+    /// The mapping to use for `rom`, the default mapping with any overrides for `rom` applied.
+    pub fn for_rom(&self, rom: &[u8]) -> ButtonMap {
+        let mut map = self.default.clone();
+        if let Some(overrides) = self.roms.get(&rom_hash(rom)) {
+            map.keys
+                .extend(overrides.keys.iter().map(|(&button, &key)| (button, key)));
+        }
+
+        map
+    }
+}
+
+pub(crate) fn parse_key(value: &str) -> Option<u8> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u8::from_str_radix(digits, 16).ok().filter(|&key| key < 16)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{rom_hash, Button, ButtonMap, ControllerProfiles};
+
+    #[test]
+    fn test_default_profile() {
+        let profiles = ControllerProfiles::default();
+
+        assert_eq!(profiles.for_rom(&[0x12, 0x00]), ButtonMap::default());
+    }
+
+    #[test]
+    fn test_per_rom_profile() {
+        let jumper = [0x00, 0xE0, 0x12, 0x00];
+        let other = [0x12, 0x00];
+        let source = format!(
+            "# comment\n[rom {:016x}]\nsouth = 0x6\n\n[default]\nsouth = 0x5\nstart = c\n",
+            rom_hash(&jumper)
+        );
+
+        let profiles = ControllerProfiles::parse(&source).unwrap();
+
+        assert_eq!(profiles.for_rom(&other).key_for(Button::South), Some(0x5));
+        assert_eq!(profiles.for_rom(&jumper).key_for(Button::South), Some(0x6));
+        // Buttons not mentioned in the ROM section are inherited from the default
+        assert_eq!(profiles.for_rom(&jumper).key_for(Button::Start), Some(0xC));
+        assert_eq!(profiles.for_rom(&jumper).key_for(Button::DPadUp), Some(0x2));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = ControllerProfiles::parse("[default]\nsouth = 0x10").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = ControllerProfiles::parse("[default]\njump = 5").unwrap_err();
+        assert_eq!(error.message, "Unknown button `jump`");
+
+        assert!(ControllerProfiles::parse("[rom xyz]").is_err());
+        assert!(ControllerProfiles::parse("[players]").is_err());
+        assert!(ControllerProfiles::parse("south").is_err());
+    }
+}
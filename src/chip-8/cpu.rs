@@ -1,14 +1,70 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::ops::{Index, IndexMut};
 
+use super::backtrace::{Backtrace, Call};
+use super::error::Error;
 use super::memory::Memory;
+use super::opcode::Opcode;
+use super::quirks::Quirks;
+use super::rng::{self, Rng};
+use super::sound::{AudioBuffer, AUDIO_PATTERN_SIZE, DEFAULT_PITCH};
 use super::timer::Timer;
+use super::variant::Variant;
+use super::watchpoint::{Accesses, Range};
 use super::{Display, Input};
 
+/// A read-only snapshot of the CPU, for debuggers, test harnesses, and overlays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    /// The number of return addresses on the stack.
+    pub sp: u16,
+    /// The return addresses on the stack, oldest first.
+    pub stack: Vec<u16>,
+    /// The same calls with where they were made from, innermost first.
+    pub backtrace: Backtrace,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// How to undo one instruction, see [`CPU::cycle_with_undo`].
+#[derive(Debug, Clone)]
+pub struct Undo {
+    context: Context,
+    /// The address and previous contents of the memory the instruction wrote.
+    memory: Option<(u16, Vec<u8>)>,
+    /// The index and previous value of every pixel the instruction changed.
+    pixels: Vec<(usize, u8)>,
+}
+
+/// Everything an instruction can change in the CPU besides memory and the display, small
+/// enough to copy before every instruction.
+#[derive(Debug, Clone)]
+struct Context {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    opcode: u16,
+    stack: [u16; STACK_SIZE],
+    sp: u16,
+    delay_timer: Timer,
+    sound_timer: Timer,
+    planes: u8,
+    audio_buffer: Option<AudioBuffer>,
+    pitch: u8,
+    pressed_key: Option<u8>,
+    vblank: VBlank,
+    flags: [u8; FLAG_COUNT],
+}
+
 #[derive(Debug)]
 struct Registers([u8; 16]);
 
 impl Registers {
-    fn as_slice_through(&self, idx: u16) -> &[u8] {
+    fn as_slice_through(&self, idx: u8) -> &[u8] {
         assert!(
             idx < 16,
             "Cannot slice register through idx: {}. 15 is the max",
@@ -28,18 +84,18 @@ impl Registers {
     }
 }
 
-impl Index<u16> for Registers {
+impl Index<u8> for Registers {
     type Output = u8;
 
-    fn index(&self, address: u16) -> &Self::Output {
+    fn index(&self, address: u8) -> &Self::Output {
         assert!(address < 16, "Invalid register {:#02x}", address);
 
         &self.0[address as usize]
     }
 }
 
-impl IndexMut<u16> for Registers {
-    fn index_mut(&mut self, address: u16) -> &mut Self::Output {
+impl IndexMut<u8> for Registers {
+    fn index_mut(&mut self, address: u8) -> &mut Self::Output {
         assert!(address < 16, "Invalid register {:#02x}", address);
 
         &mut self.0[address as usize]
@@ -53,7 +109,13 @@ impl Default for Registers {
 }
 
 const STACK_SIZE: usize = 128;
-pub struct CPU {
+/// The number of persistent flags for FX75 and FX85. The HP 48 had 8 RPL user flags, XO-CHIP
+/// extends them to 16.
+pub const FLAG_COUNT: usize = 16;
+
+/// The processor, generic over the display so that drawing is statically dispatched in the
+/// hot loop.
+pub struct CPU<D> {
     // Registers
     v: Registers,
     i: u16,
@@ -62,26 +124,69 @@ pub struct CPU {
     pc: u16,
     // Current opcode
     opcode: u16,
+    /// The instructions decoded so far by address, along with the raw opcode they were decoded
+    /// from so that instructions the ROM overwrote get decoded again.
+    decoded: Vec<Option<(u16, Opcode)>>,
 
     // Stack
     stack: [u16; STACK_SIZE],
     sp: u16,
 
     memory: Memory,
-    pub display: Box<dyn Display>,
+    pub display: D,
 
     delay_timer: Timer,
     sound_timer: Timer,
+
+    pub rng: Box<dyn Rng>,
+
+    variant: Variant,
+    quirks: Quirks,
+    // XO-CHIP drawing planes and audio
+    planes: u8,
+    audio_buffer: Option<AudioBuffer>,
+    pitch: u8,
+    /// The key pressed while FX0A waits for it to be released.
+    pressed_key: Option<u8>,
+    /// Where DXYN is in waiting for the next tick with the display wait quirk.
+    vblank: VBlank,
+    /// The flags saved by FX75, kept across resets.
+    flags: [u8; FLAG_COUNT],
+}
+
+/// What [`CPU::cycle`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleStatus {
+    /// The instruction ran.
+    Executed,
+    /// FX0A is waiting for a key to be pressed and released, it runs again on the next cycle.
+    WaitingForKey,
+    /// DXYN is waiting for the next 60Hz tick with the display wait quirk, running more cycles
+    /// before then does nothing.
+    WaitingForVBlank,
 }
 
-impl CPU {
-    pub fn new(memory: Memory, display: Box<dyn Display>) -> Self {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VBlank {
+    /// Not waiting.
+    Idle,
+    /// DXYN is waiting for the timers to tick.
+    Waiting,
+    /// The timers ticked, DXYN draws when it runs next.
+    Ready,
+}
+
+impl<D: Display> CPU<D> {
+    pub fn new(memory: Memory, mut display: D, variant: Variant, quirks: Quirks) -> Self {
+        display.set_clipping(quirks.clip_sprites);
+        let pc = variant.start_address(memory.as_slice(0x200, 2));
+
         Self {
             v: Registers::default(),
             i: 0,
-            // Program Counter starts at 0x200
-            pc: 0x200,
+            pc,
             opcode: 0,
+            decoded: Vec::new(),
 
             sp: 0,
             stack: [0; STACK_SIZE],
@@ -91,374 +196,1222 @@ impl CPU {
 
             delay_timer: Timer::default(),
             sound_timer: Timer::default(),
+
+            rng: rng::default_rng(),
+
+            variant,
+            quirks,
+            planes: 1,
+            audio_buffer: None,
+            pitch: DEFAULT_PITCH,
+            pressed_key: None,
+            vblank: VBlank::Idle,
+            flags: [0; FLAG_COUNT],
+        }
+    }
+
+    /// The flags saved by FX75 and loaded by FX85.
+    pub fn flags(&self) -> [u8; FLAG_COUNT] {
+        self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: [u8; FLAG_COUNT]) {
+        self.flags = flags;
+    }
+
+    /// The raw opcode of the last instruction fetched.
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        self.v[register]
+    }
+
+    pub fn set_register(&mut self, register: u8, value: u8) {
+        self.v[register] = value;
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            v: self.v.0,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack[..self.sp as usize].to_vec(),
+            backtrace: self.backtrace(),
+            delay_timer: self.delay_timer.current_value(),
+            sound_timer: self.sound_timer.current_value(),
+        }
+    }
+
+    /// The subroutine calls on the stack, innermost first.
+    pub fn backtrace(&self) -> Backtrace {
+        let calls = self.stack[..self.sp as usize]
+            .iter()
+            .rev()
+            .map(|&return_address| {
+                let call_site = return_address.wrapping_sub(2);
+
+                Call {
+                    return_address,
+                    call_site,
+                    instruction: self
+                        .read_word(call_site)
+                        .ok()
+                        .map(|opcode| Opcode::decode(opcode, 0)),
+                }
+            })
+            .collect();
+
+        Backtrace { calls }
+    }
+
+    /// Execute the next instruction like [`CPU::cycle`] and return how to undo it with
+    /// [`CPU::undo`]. Changes to the display can only be undone if it supports
+    /// [`Display::pixels`].
+    pub fn cycle_with_undo(&mut self, input: &dyn Input) -> Result<(CycleStatus, Undo), Error> {
+        let context = self.context();
+        let instruction = self
+            .read_word(self.pc)
+            .and_then(|opcode| self.decode(opcode, self.pc));
+        let memory = instruction
+            .as_ref()
+            .ok()
+            .and_then(|&instruction| self.memory_written(instruction))
+            .and_then(|(address, length)| {
+                let bytes = read_memory(&self.memory, address, length, self.quirks).ok()?;
+
+                Some((address, bytes.into_owned()))
+            });
+        let changes_display = matches!(
+            instruction,
+            Ok(Opcode::Cls) | Ok(Opcode::Drw(..)) | Ok(Opcode::ScrollUp(_))
+        );
+        let pixels_before = if changes_display {
+            self.display.pixels()
+        } else {
+            None
+        };
+
+        let status = self.cycle(input)?;
+
+        let pixels = match (pixels_before, self.display.pixels()) {
+            (Some(before), Some(after)) => before
+                .into_iter()
+                .zip(after)
+                .enumerate()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(index, (before, _))| (index, before))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok((
+            status,
+            Undo {
+                context,
+                memory,
+                pixels,
+            },
+        ))
+    }
+
+    /// Go back to before the instruction that returned `undo`. Undos must be applied in the
+    /// reverse order of the instructions.
+    pub fn undo(&mut self, undo: Undo) {
+        let Context {
+            v,
+            i,
+            pc,
+            opcode,
+            stack,
+            sp,
+            delay_timer,
+            sound_timer,
+            planes,
+            audio_buffer,
+            pitch,
+            pressed_key,
+            vblank,
+            flags,
+        } = undo.context;
+        self.v = Registers(v);
+        self.i = i;
+        self.pc = pc;
+        self.opcode = opcode;
+        self.stack = stack;
+        self.sp = sp;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.planes = planes;
+        self.audio_buffer = audio_buffer;
+        self.pitch = pitch;
+        self.pressed_key = pressed_key;
+        self.vblank = vblank;
+        self.flags = flags;
+
+        if let Some((address, bytes)) = undo.memory {
+            // Split where the write wrapped around with the wrap_memory quirk
+            let start = address as usize % self.memory.size();
+            let (to_end, wrapped) = bytes.split_at(bytes.len().min(self.memory.size() - start));
+            self.memory.copy_from_slice(start as u16, to_end);
+            self.memory.copy_from_slice(0, wrapped);
+        }
+        if !undo.pixels.is_empty() {
+            if let Some(mut pixels) = self.display.pixels() {
+                for (index, value) in undo.pixels {
+                    pixels[index] = value;
+                }
+                self.display.set_pixels(&pixels);
+            }
+        }
+        self.display.select_planes(self.planes);
+    }
+
+    fn context(&self) -> Context {
+        Context {
+            v: self.v.0,
+            i: self.i,
+            pc: self.pc,
+            opcode: self.opcode,
+            stack: self.stack,
+            sp: self.sp,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            planes: self.planes,
+            audio_buffer: self.audio_buffer,
+            pitch: self.pitch,
+            pressed_key: self.pressed_key,
+            vblank: self.vblank,
+            flags: self.flags,
+        }
+    }
+
+    /// Whether the sound timer is running, the tone plays for as long as it is.
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer.is_active()
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer.current_value()
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Go back to the power on state with `memory` loaded, without reallocating anything.
+    /// The display is cleared, the random number provider, access policy, and flags are kept.
+    pub fn restore(&mut self, memory: &Memory) {
+        let policy = self.memory.access_policy();
+        self.v = Registers::default();
+        self.i = 0;
+        self.pc = self.variant.start_address(memory.as_slice(0x200, 2));
+        self.opcode = 0;
+        self.stack = [0; STACK_SIZE];
+        self.sp = 0;
+        self.memory.clone_from(memory);
+        self.memory.set_access_policy(policy);
+        self.delay_timer = Timer::default();
+        self.sound_timer = Timer::default();
+        self.audio_buffer = None;
+        self.pitch = DEFAULT_PITCH;
+        self.pressed_key = None;
+        self.vblank = VBlank::Idle;
+        self.reset_display();
+    }
+
+    /// Clear every plane of the display and go back to drawing in plane 1.
+    pub fn reset_display(&mut self) {
+        self.display.select_planes(0b11);
+        self.display.cls();
+        self.planes = 1;
+        self.display.select_planes(self.planes);
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.display.set_clipping(quirks.clip_sprites);
+        self.quirks = quirks;
+    }
+
+    /// The XO-CHIP audio pattern and pitch, `None` until a pattern is loaded.
+    pub fn audio_buffer(&self) -> Option<AudioBuffer> {
+        self.audio_buffer
+    }
+
+    pub fn cycle(&mut self, input: &dyn Input) -> Result<CycleStatus, Error> {
+        let current_pc = self.pc;
+        self.opcode = self.read_word(current_pc)?;
+        let opcode = match self.decoded.get(current_pc as usize) {
+            Some(&Some((raw, decoded))) if raw == self.opcode => decoded,
+            _ => self.decode_and_cache(current_pc)?,
+        };
+        self.pc = self.execute(opcode, current_pc, input)?;
+
+        Ok(match opcode {
+            Opcode::LdKey(_) if self.pc == current_pc => CycleStatus::WaitingForKey,
+            Opcode::Drw(..) if self.vblank == VBlank::Waiting => CycleStatus::WaitingForVBlank,
+            _ => CycleStatus::Executed,
+        })
+    }
+
+    /// Count the delay and sound timers down by one, at 60Hz independently of the instructions.
+    /// This is also the vertical blank that DXYN waits for with the display wait quirk.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+        if self.vblank == VBlank::Waiting {
+            self.vblank = VBlank::Ready;
         }
     }
 
-    pub fn cycle(&mut self, tick_timers: bool, input: &dyn Input) {
-        self.opcode =
-            (self.memory[self.pc] as u16) << 8 | self.memory[self.pc.wrapping_add(1)] as u16;
-        self.pc = self.execute_opcode(self.opcode, self.pc, tick_timers, input);
+    fn read_word(&self, address: u16) -> Result<u16, Error> {
+        Ok((self.memory.read(address)? as u16) << 8
+            | self.memory.read(address.wrapping_add(1))? as u16)
     }
 
-    fn execute_opcode(
+    /// The size of the instruction at `address`, for skipping over it. Only XO-CHIP has
+    /// instructions that are longer than two bytes.
+    fn size_at(&self, address: u16) -> u16 {
+        if !self.variant.is_xo_chip() {
+            return 2;
+        }
+
+        self.read_word(address).map(Opcode::size_of).unwrap_or(2)
+    }
+
+    fn decode_and_cache(&mut self, current_pc: u16) -> Result<Opcode, Error> {
+        let opcode = self.decode(self.opcode, current_pc)?;
+        // F000 NNNN also depends on the word after it, it isn't worth checking both
+        if opcode.size() == 2 {
+            if self.decoded.len() != self.memory.size() {
+                self.decoded = vec![None; self.memory.size()];
+            }
+            self.decoded[current_pc as usize] = Some((self.opcode, opcode));
+        }
+
+        Ok(opcode)
+    }
+
+    /// Decode the instruction at `current_pc`, rejecting the ones this variant doesn't have.
+    fn decode(&self, opcode: u16, current_pc: u16) -> Result<Opcode, Error> {
+        let unknown = || Error::UnknownOpcode {
+            opcode,
+            address: current_pc,
+            backtrace: self.backtrace(),
+        };
+        // Only F000 NNNN needs the next word, don't read past the end of memory otherwise
+        let next = if opcode == 0xF000 && self.variant.is_xo_chip() {
+            self.read_word(current_pc.wrapping_add(2))?
+        } else {
+            0
+        };
+
+        match Opcode::decode(opcode, next) {
+            Opcode::Sys(_) | Opcode::Data(_) | Opcode::Byte(_) => Err(unknown()),
+            decoded if decoded.is_xo_chip() && !self.variant.is_xo_chip() => Err(unknown()),
+            decoded => Ok(decoded),
+        }
+    }
+
+    fn execute(
         &mut self,
-        opcode: u16,
+        opcode: Opcode,
         current_pc: u16,
-        tick_timers: bool,
         input: &dyn Input,
-    ) -> u16 {
+    ) -> Result<u16, Error> {
         self.display.clear_dirty();
-        // println!("{:04x}: {:04x}", current_pc, opcode);
-        let next_pc = match opcode & 0xF000 {
-            0x0000 => {
-                match opcode & 0x000F {
-                    // 00E0: Clear screen
-                    0x0000 => {
-                        self.display.cls();
-
-                        current_pc + 2
-                    }
-                    // 00EE: Return from subroutine
-                    0x000E => self.stack_pop(),
-                    _ => panic!("Unknown opcode {:#02x}", opcode),
-                }
+        let next = current_pc.wrapping_add(opcode.size());
+        let skip_if = |condition: bool| {
+            if condition {
+                next.wrapping_add(self.size_at(next))
+            } else {
+                next
             }
-            // 1NNN: Jump to address NNN
-            0x1000 => opcode & 0x0FFF,
-            // 2NNN: Call NNN
-            0x2000 => {
-                let mut address = opcode & 0x0FFF;
+        };
+
+        let next_pc = match opcode {
+            Opcode::Cls => {
+                self.display.cls();
+
+                next
+            }
+            Opcode::Ret => self.stack_pop(current_pc)?,
+            Opcode::Jp(address) => address,
+            Opcode::Call(mut address) => {
                 if address < 0x200 {
                     address += 0x200;
                 }
-                self.stack_push(current_pc + 2);
+                self.stack_push(next, current_pc)?;
 
-                // Jump to address
                 address
             }
+            Opcode::SeByte(x, value) => skip_if(self.v[x] == value),
+            Opcode::SneByte(x, value) => skip_if(self.v[x] != value),
+            Opcode::SeReg(x, y) => skip_if(self.v[x] == self.v[y]),
+            Opcode::LdByte(x, value) => {
+                self.v[x] = value;
 
-            // 3XKK: Skip next instruction if VX is equal to KK.
-            0x3000 => {
-                let register = (opcode & 0x0F00) >> 8;
-                let value = (opcode & 0x00FF) as u8;
+                next
+            }
+            // Carry flag is not changed
+            Opcode::AddByte(x, value) => {
+                self.v[x] = self.v[x].wrapping_add(value);
 
-                if self.v[register] == value {
-                    current_pc + 4
-                } else {
-                    current_pc + 2
-                }
+                next
             }
+            Opcode::LdReg(x, y) => {
+                self.v[x] = self.v[y];
 
-            // 4XKK: Skip next instruction if VX is not equal to KK.
-            0x4000 => {
-                let register = (opcode & 0x0F00) >> 8;
-                let value = (opcode & 0x00FF) as u8;
+                next
+            }
+            Opcode::Or(x, y) => {
+                self.v[x] |= self.v[y];
+                self.reset_vf_after_logic();
 
-                if self.v[register] != value {
-                    current_pc + 4
-                } else {
-                    current_pc + 2
-                }
+                next
+            }
+            Opcode::And(x, y) => {
+                self.v[x] &= self.v[y];
+                self.reset_vf_after_logic();
+
+                next
+            }
+            Opcode::Xor(x, y) => {
+                self.v[x] ^= self.v[y];
+                self.reset_vf_after_logic();
+
+                next
+            }
+            // VF is set to 1 if there is a carry, 0 if not.
+            Opcode::AddReg(x, y) => {
+                // VF is written last, it holds the flag even when it's also VX
+                let will_overflow = self.v[x].checked_add(self.v[y]).is_none();
+                self.v[x] = self.v[x].wrapping_add(self.v[y]);
+                self.v[0xF] = if will_overflow { 1 } else { 0 };
+
+                next
             }
+            // VX - VY, VF is set to 0 if there is a borrow, 1 if not.
+            Opcode::Sub(x, y) => {
+                let no_borrow = self.v[x] >= self.v[y];
+                self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                self.v[0xF] = if no_borrow { 1 } else { 0 };
 
-            // 5XY0: Skip next instruction if VX is equal to VY.
-            0x5000 => {
-                let lhs_register = (opcode & 0x0F00) >> 8;
-                let rhs_register = (opcode & 0x00F0) >> 4;
+                next
+            }
+            // Store the least significant bit of VX in VF and then shift VX to the right by 1.
+            // With the shift quirk VY is shifted into VX instead.
+            Opcode::Shr(x, y) => {
+                let value = self.shift_source(x, y);
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 0x1;
+
+                next
+            }
+            // VY - VX, VF is set 0 when there is a borrow, 1 if not.
+            Opcode::Subn(x, y) => {
+                let no_borrow = self.v[y] >= self.v[x];
+                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                self.v[0xF] = if no_borrow { 1 } else { 0 };
+
+                next
+            }
+            // Store the most significant bit of VX in VF and then shift VX to the left by 1.
+            // With the shift quirk VY is shifted into VX instead.
+            Opcode::Shl(x, y) => {
+                let value = self.shift_source(x, y);
+                self.v[x] = value << 1;
+                self.v[0xF] = (value & 0x80) >> 7;
+
+                next
+            }
+            Opcode::SneReg(x, y) => skip_if(self.v[x] != self.v[y]),
+            Opcode::LdI(address) => {
+                self.i = address;
 
-                if self.v[lhs_register] == self.v[rhs_register] {
-                    current_pc + 4
+                next
+            }
+            // With the jump quirk this is BXNN, jumping to XNN + VX
+            Opcode::JpV0(address) => {
+                let register = if self.quirks.jump_uses_vx {
+                    (address >> 8) as u8
                 } else {
-                    current_pc + 2
-                }
+                    0
+                };
+
+                address + self.v[register] as u16
+            }
+            Opcode::Rnd(x, mask) => {
+                self.v[x] = self.rng.next_u8() & mask;
+
+                next
             }
+            Opcode::Drw(..) if self.quirks.display_wait && self.vblank != VBlank::Ready => {
+                self.vblank = VBlank::Waiting;
 
-            // 6XNN: Set VX to NN.
-            0x6000 => {
-                let register = (opcode & 0x0F00) >> 8;
-                let value = (opcode & 0x00FF) as u8;
-
-                self.v[register] = value;
-
-                current_pc + 2
-            }
-
-            // 7XNN: Add NN to VX, carry flag is not changed.
-            0x7000 => {
-                let register = (opcode & 0x0F00) >> 8;
-                let value = (opcode & 0x00FF) as u8;
-
-                self.v[register] = self.v[register].wrapping_add(value);
-
-                current_pc + 2
-            }
-
-            0x8000 => {
-                let lhs_register = (opcode & 0x0F00) >> 8;
-                let rhs_register = (opcode & 0x00F0) >> 4;
-
-                match opcode & 0x000F {
-                    // 8XY0: Set VX to the value of VY.
-                    0x0000 => {
-                        self.v[lhs_register] = self.v[rhs_register];
-                    }
-
-                    // 8XY1: Set VX to the result of VX | VY
-                    0x0001 => {
-                        self.v[lhs_register] |= self.v[rhs_register];
-                    }
-
-                    // 8XY2: Set VX to the result of VX & VY
-                    0x0002 => {
-                        self.v[lhs_register] &= self.v[rhs_register];
-                    }
-
-                    // 8XY3: Set VX to the result of VX ^ VY
-                    0x0003 => {
-                        self.v[lhs_register] ^= self.v[rhs_register];
-                    }
-
-                    // 8XY4: Add VY to VX. VF is set to 1 if there is a carry, 0 if not.
-                    0x0004 => {
-                        let will_overflow = self.v[lhs_register]
-                            .checked_add(self.v[rhs_register])
-                            .is_none();
-                        self.v[0xF] = if will_overflow { 1 } else { 0 };
-
-                        self.v[lhs_register] =
-                            self.v[lhs_register].wrapping_add(self.v[rhs_register]);
-                    }
-
-                    // 8XY5: Subtract VY from VX. VF is set to 0 if there is a borrow, 1 if not.
-                    0x0005 => {
-                        self.v[0xF] = if self.v[lhs_register] > self.v[rhs_register] {
-                            1
-                        } else {
-                            0
-                        };
-
-                        self.v[lhs_register] =
-                            self.v[lhs_register].wrapping_sub(self.v[rhs_register]);
-                    }
-
-                    // 8XY6: Store the least significant bit of VX in VF and then shift VX to the
-                    // right by 1.
-                    0x0006 => {
-                        self.v[0xF] = self.v[lhs_register] & 0x1;
-                        self.v[lhs_register] >>= 1;
-                    }
-
-                    // 8XY7: Set VX to the result of VY - VX. VF is set 0 when there is a borrow, 1
-                    // if not.
-                    0x0007 => {
-                        self.v[0xF] = if self.v[rhs_register] > self.v[lhs_register] {
-                            1
-                        } else {
-                            0
-                        };
-                        self.v[lhs_register] =
-                            self.v[rhs_register].wrapping_sub(self.v[lhs_register]);
-                    }
-
-                    // 8XYE: Store the most significant bit of VX in VF and then shift VX to the
-                    // left by 1.
-                    0x000E => {
-                        self.v[0xF] = (self.v[lhs_register] & 0x80) >> 7;
-                        self.v[lhs_register] <<= 1;
-                    }
-                    _ => panic!("Unknown opcode {:#02x}", opcode),
+                current_pc
+            }
+            Opcode::Drw(x, y, n) => {
+                self.vblank = VBlank::Idle;
+                // The display reads the sprite, one per plane, from memory. Make sure all of
+                // it is there, it wraps around on its own.
+                if !self.quirks.wrap_memory {
+                    self.memory
+                        .read_slice(self.i, n as u16 * self.planes.count_ones() as u16)?;
                 }
 
-                current_pc + 2
+                let collided =
+                    self.display
+                        .draw_sprite(self.v[x], self.v[y], self.i, n, &self.memory);
+                self.v[0xF] = if collided { 1 } else { 0 };
+
+                next
             }
+            // Only the low nibble of VX picks the key, like on the COSMAC VIP
+            Opcode::Skp(x) => skip_if(input.is_key_down(self.v[x] & 0xF)),
+            Opcode::Sknp(x) => skip_if(!input.is_key_down(self.v[x] & 0xF)),
+            Opcode::LdFromDelayTimer(x) => {
+                self.v[x] = self.delay_timer.current_value();
 
-            // 9XY0: Skip the next instruction if VX is not equal VY
-            0x9000 => {
-                let lhs_register = (opcode & 0x0F00) >> 8;
-                let rhs_register = (opcode & 0x00F0) >> 4;
+                next
+            }
+            // Block execution until a key is pressed and released, like the COSMAC VIP.
+            Opcode::LdKey(x) => match self.pressed_key {
+                Some(key) if !input.is_key_down(key) => {
+                    self.pressed_key = None;
+                    self.v[x] = key;
 
-                if self.v[lhs_register] != self.v[rhs_register] {
-                    current_pc + 4
-                } else {
-                    current_pc + 2
+                    next
+                }
+                Some(_) => current_pc,
+                None => {
+                    self.pressed_key = (0..16).find(|&key| input.is_key_down(key));
+
+                    current_pc
                 }
+            },
+            Opcode::LdDelayTimer(x) => {
+                self.delay_timer.set_value(self.v[x]);
+
+                next
             }
+            Opcode::LdSoundTimer(x) => {
+                self.sound_timer.set_value(self.v[x]);
 
-            // ANNN: Set `I` to address NNN
-            0xA000 => {
-                self.i = opcode & 0x0FFF;
+                next
+            }
+            Opcode::AddI(x) => {
+                self.i = self.i.wrapping_add(self.v[x] as u16);
 
-                current_pc + 2
+                next
+            }
+            Opcode::LdFont(x) => {
+                self.i = self.memory.font_address_for_character(self.v[x]);
+
+                next
+            }
+            // Store the BCD representation of VX in memory locations I, I+1, and I+2.
+            Opcode::LdBcd(x) => {
+                let value = self.v[x];
+                let digits = [value / 100, (value / 10) % 10, value % 10];
+                write_memory(&mut self.memory, self.i, &digits, self.quirks)?;
+
+                next
+            }
+            Opcode::Store(x) => {
+                write_memory(
+                    &mut self.memory,
+                    self.i,
+                    self.v.as_slice_through(x),
+                    self.quirks,
+                )?;
+                self.increment_i_after_load_store(x);
+
+                next
             }
+            Opcode::Load(x) => {
+                self.v.clone_from_slice(&read_memory(
+                    &self.memory,
+                    self.i,
+                    x as u16 + 1,
+                    self.quirks,
+                )?);
+                self.increment_i_after_load_store(x);
+
+                next
+            }
+            Opcode::ScrollUp(rows) => {
+                self.display.scroll_up(rows);
 
-            // BNNN: Jump to the address NNN + V0
-            0xB000 => {
-                let address = opcode & 0x0FFF;
+                next
+            }
+            // I is unchanged
+            Opcode::SaveRange(x, y) => {
+                let values = register_range(x, y)
+                    .map(|register| self.v[register])
+                    .collect::<Vec<_>>();
+                write_memory(&mut self.memory, self.i, &values, self.quirks)?;
+
+                next
+            }
+            // I is unchanged
+            Opcode::LoadRange(x, y) => {
+                let count = register_range(x, y).count();
+                let values = read_memory(&self.memory, self.i, count as u16, self.quirks)?.to_vec();
+                for (register, value) in register_range(x, y).zip(values) {
+                    self.v[register] = value;
+                }
 
-                address + self.v[0] as u16
+                next
             }
+            Opcode::LdILong(address) => {
+                self.i = address;
 
-            // CXNN: Set the VX to the result of rand() & NN.
-            0xC000 => {
-                let random: u8 = rand::random();
-                let mask = (opcode & 0x00FF) as u8;
-                let target_register = (opcode & 0x0F00) >> 8;
-                let value = mask & random;
+                next
+            }
+            Opcode::Plane(planes) => {
+                self.planes = planes & 0b11;
+                self.display.select_planes(self.planes);
 
-                self.v[target_register] = value;
+                next
+            }
+            Opcode::Audio => {
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                pattern.copy_from_slice(&read_memory(
+                    &self.memory,
+                    self.i,
+                    AUDIO_PATTERN_SIZE as u16,
+                    self.quirks,
+                )?);
+                self.audio_buffer = Some(AudioBuffer::new(pattern, self.pitch));
+
+                next
+            }
+            Opcode::Pitch(x) => {
+                self.pitch = self.v[x];
+                if let Some(buffer) = self.audio_buffer.as_mut() {
+                    *buffer = AudioBuffer::new(*buffer.pattern(), self.pitch);
+                }
 
-                current_pc + 2
+                next
             }
+            Opcode::SaveFlags(x) => {
+                let registers = self.v.as_slice_through(x);
+                self.flags[..registers.len()].copy_from_slice(registers);
 
-            // DXYN: Draw a sprite at VX, VY of widht 8 and height N.
-            0xD000 => {
-                // println!("{:04x}", opcode);
-                let x = self.v[(opcode & 0x0F00) >> 8];
-                let y = self.v[(opcode & 0x00F0) >> 4];
-                let n = (opcode & 0x000F) as u8;
+                next
+            }
+            Opcode::LoadFlags(x) => {
+                self.v.clone_from_slice(&self.flags[..=x as usize]);
 
-                self.v[0xF] = if self.display.draw_sprite(x, y, self.i, n, &self.memory) {
-                    1
+                next
+            }
+            // Rejected when decoding
+            Opcode::Sys(_) | Opcode::Data(_) | Opcode::Byte(_) => unreachable!(),
+        };
+
+        Ok(next_pc)
+    }
+
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v[0xF] = 0;
+        }
+    }
+
+    /// The value 8XY6 and 8XYE shift.
+    fn shift_source(&self, lhs_register: u8, rhs_register: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.v[rhs_register]
+        } else {
+            self.v[lhs_register]
+        }
+    }
+
+    /// The registers and memory `instruction` would access if executed now.
+    pub fn accesses(&self, instruction: Opcode) -> Accesses {
+        let mut accesses = Accesses::default();
+        let flag = 0xF;
+
+        match instruction {
+            Opcode::SeByte(x, _)
+            | Opcode::SneByte(x, _)
+            | Opcode::Skp(x)
+            | Opcode::Sknp(x)
+            | Opcode::LdDelayTimer(x)
+            | Opcode::LdSoundTimer(x)
+            | Opcode::AddI(x)
+            | Opcode::LdFont(x)
+            | Opcode::Pitch(x) => accesses.read_registers(vec![x]),
+            Opcode::SeReg(x, y) | Opcode::SneReg(x, y) => accesses.read_registers(vec![x, y]),
+            Opcode::LdByte(x, _)
+            | Opcode::Rnd(x, _)
+            | Opcode::LdFromDelayTimer(x)
+            | Opcode::LdKey(x) => accesses.write_registers(vec![x]),
+            Opcode::AddByte(x, _) => {
+                accesses.read_registers(vec![x]);
+                accesses.write_registers(vec![x]);
+            }
+            Opcode::LdReg(x, y) => {
+                accesses.read_registers(vec![y]);
+                accesses.write_registers(vec![x]);
+            }
+            Opcode::Or(x, y) | Opcode::And(x, y) | Opcode::Xor(x, y) => {
+                accesses.read_registers(vec![x, y]);
+                accesses.write_registers(vec![x]);
+                if self.quirks.logic_resets_vf {
+                    accesses.write_registers(vec![flag]);
+                }
+            }
+            Opcode::AddReg(x, y) | Opcode::Sub(x, y) | Opcode::Subn(x, y) => {
+                accesses.read_registers(vec![x, y]);
+                accesses.write_registers(vec![x, flag]);
+            }
+            Opcode::Shr(x, y) | Opcode::Shl(x, y) => {
+                let source = if self.quirks.shift_uses_vy { y } else { x };
+                accesses.read_registers(vec![source]);
+                accesses.write_registers(vec![x, flag]);
+            }
+            Opcode::JpV0(address) => {
+                let register = if self.quirks.jump_uses_vx {
+                    (address >> 8) as u8
                 } else {
                     0
                 };
+                accesses.read_registers(vec![register]);
+            }
+            Opcode::Drw(x, y, n) => {
+                accesses.read_registers(vec![x, y]);
+                accesses.write_registers(vec![flag]);
+                accesses.memory_read =
+                    Range::with_length(self.i, n as u16 * self.planes.count_ones() as u16);
+            }
+            Opcode::LdBcd(x) => accesses.read_registers(vec![x]),
+            Opcode::Store(x) => accesses.read_registers(0..=x),
+            Opcode::Load(x) => {
+                accesses.memory_read = Range::with_length(self.i, x as u16 + 1);
+                accesses.write_registers(0..=x);
+            }
+            Opcode::SaveFlags(x) => accesses.read_registers(0..=x),
+            Opcode::LoadFlags(x) => accesses.write_registers(0..=x),
+            Opcode::SaveRange(x, y) => accesses.read_registers(register_range(x, y)),
+            Opcode::LoadRange(x, y) => {
+                let count = register_range(x, y).count() as u16;
+                accesses.memory_read = Range::with_length(self.i, count);
+                accesses.write_registers(register_range(x, y));
+            }
+            Opcode::Audio => {
+                accesses.memory_read = Range::with_length(self.i, AUDIO_PATTERN_SIZE as u16);
+            }
+            Opcode::Cls
+            | Opcode::Ret
+            | Opcode::Sys(_)
+            | Opcode::Jp(_)
+            | Opcode::Call(_)
+            | Opcode::LdI(_)
+            | Opcode::ScrollUp(_)
+            | Opcode::LdILong(_)
+            | Opcode::Plane(_)
+            | Opcode::Data(_)
+            | Opcode::Byte(_) => {}
+        }
+        accesses.memory_written = self
+            .memory_written(instruction)
+            .and_then(|(address, length)| Range::with_length(address, length));
 
-                current_pc + 2
-            }
-
-            0xE000 => {
-                let register_value = self.v[(opcode & 0x0F00) >> 8];
-
-                match opcode & 0x00FF {
-                    // EX9E: Skip the next instruction if the key stored in VX is pressed
-                    0x009E => {
-                        if input.is_key_down(register_value) {
-                            current_pc + 4
-                        } else {
-                            current_pc + 2
-                        }
-                    }
-
-                    // EXA1: Skip the next instruction if the key stored in VX isn't pressed
-                    0x00A1 => {
-                        if input.is_key_down(register_value) {
-                            current_pc + 2
-                        } else {
-                            current_pc + 4
-                        }
-                    }
-                    _ => panic!("Unknown opcode {:#02x}", opcode),
-                }
+        accesses
+    }
+
+    /// The address and number of bytes `instruction` would write to memory, unlike
+    /// [`CPU::accesses`] not cut short at the end of the address space.
+    fn memory_written(&self, instruction: Opcode) -> Option<(u16, u16)> {
+        let length = match instruction {
+            Opcode::LdBcd(_) => 3,
+            Opcode::Store(x) => x as u16 + 1,
+            Opcode::SaveRange(x, y) => register_range(x, y).count() as u16,
+            _ => return None,
+        };
+
+        Some((self.i, length))
+    }
+
+    fn increment_i_after_load_store(&mut self, register: u8) {
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(register as u16 + 1);
+        }
+    }
+
+    fn stack_push(&mut self, value: u16, current_pc: u16) -> Result<(), Error> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Error::StackOverflow {
+                address: current_pc,
+                backtrace: self.backtrace(),
+            });
+        }
+        self.stack[self.sp as usize] = value;
+        self.sp += 1;
+
+        Ok(())
+    }
+
+    fn stack_pop(&mut self, current_pc: u16) -> Result<u16, Error> {
+        if self.sp == 0 {
+            return Err(Error::StackUnderflow {
+                address: current_pc,
+                backtrace: self.backtrace(),
+            });
+        }
+        let value = self.stack[(self.sp - 1) as usize];
+        self.sp -= 1;
+
+        Ok(value)
+    }
+}
+
+/// `length` bytes of memory from `address`, which wrap around past the end of memory with the
+/// [`Quirks::wrap_memory`] quirk and are an error otherwise.
+fn read_memory(
+    memory: &Memory,
+    address: u16,
+    length: u16,
+    quirks: Quirks,
+) -> Result<Cow<'_, [u8]>, Error> {
+    if quirks.wrap_memory {
+        Ok(memory.read_wrapping(address, length))
+    } else {
+        memory.read_slice(address, length).map(Cow::Borrowed)
+    }
+}
+
+/// Write `bytes` to memory from `address`, like [`read_memory`] reads them.
+fn write_memory(
+    memory: &mut Memory,
+    address: u16,
+    bytes: &[u8],
+    quirks: Quirks,
+) -> Result<(), Error> {
+    if quirks.wrap_memory {
+        memory.write_wrapping(address, bytes)
+    } else {
+        memory.write_slice(address, bytes)
+    }
+}
+
+/// The registers from `from` to `to` inclusive, in reverse when `from` is the larger one.
+fn register_range(from: u8, to: u8) -> Box<dyn Iterator<Item = u8>> {
+    if from <= to {
+        Box::new(from..=to)
+    } else {
+        Box::new((to..=from).rev())
+    }
+}
+
+impl<D> fmt::Display for CPU<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "PC: {:#05x}  I: {:#05x}  SP: {}  DT: {}  ST: {}",
+            self.pc,
+            self.i,
+            self.sp,
+            self.delay_timer.current_value(),
+            self.sound_timer.current_value()
+        )?;
+
+        for row in 0..2 {
+            let registers = (0..8)
+                .map(|column| {
+                    let register = row * 8 + column;
+                    format!("V{:X}: {:02x}", register, self.v[register])
+                })
+                .collect::<Vec<_>>();
+            write!(f, "{}", registers.join("  "))?;
+            if row == 0 {
+                writeln!(f)?;
             }
+        }
 
-            0xF000 => {
-                let register = (opcode & 0x0F00) >> 8;
-                let blocked = match opcode & 0x00FF {
-                    // FX07: Set the VX value to the value of the delay timer
-                    0x0007 => {
-                        self.v[register] = self.delay_timer.current_value();
+        Ok(())
+    }
+}
 
-                        false
-                    }
+#[cfg(test)]
+mod tests {
+    use super::{CycleStatus, CPU};
+    use crate::backtrace::Backtrace;
+    use crate::batch::NullInput;
+    use crate::error::Error;
+    use crate::memory::Memory;
+    use crate::opcode::Opcode;
+    use crate::{AccessPolicy, Input, NopDisplay, Quirks, Variant};
 
-                    // FX0A: Block execution until a key is pressed. Pressed key is stored in VX.
-                    0x000A => match input.last_key_down() {
-                        Some(key) => {
-                            self.v[register] = key;
-                            false
-                        }
-                        None => true,
-                    },
+    fn cpu(variant: Variant) -> CPU<NopDisplay> {
+        let memory = Memory::with_size(variant.memory_size());
 
-                    // FX15: Set the delay timer to the value of VX
-                    0x0015 => {
-                        self.delay_timer.set_value(self.v[register]);
+        CPU::new(memory, NopDisplay, variant, Quirks::default())
+    }
 
-                        false
-                    }
+    #[test]
+    fn test_execute() {
+        let mut cpu = cpu(Variant::Chip8);
 
-                    // FX18: Set the sound timer to the value of VX
-                    0x0018 => {
-                        self.sound_timer.set_value(self.v[register]);
+        assert_eq!(
+            cpu.execute(Opcode::LdByte(0x3, 0x42), 0x200, &NullInput),
+            Ok(0x202)
+        );
+        assert_eq!(cpu.register(0x3), 0x42);
+        assert_eq!(
+            cpu.execute(Opcode::SeByte(0x3, 0x42), 0x202, &NullInput),
+            Ok(0x206)
+        );
+        assert_eq!(
+            cpu.execute(Opcode::AddReg(0x3, 0x3), 0x206, &NullInput),
+            Ok(0x208)
+        );
+        assert_eq!(cpu.register(0x3), 0x84);
+        assert_eq!(cpu.register(0xF), 0x00);
+        // Blocks until a key is pressed
+        assert_eq!(
+            cpu.execute(Opcode::LdKey(0x0), 0x208, &NullInput),
+            Ok(0x208)
+        );
+    }
 
-                        false
-                    }
+    /// Run `opcode` with V0 and V1 set, returning V0 and VF.
+    fn alu(opcode: Opcode, v0: u8, v1: u8) -> (u8, u8) {
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.set_register(0x0, v0);
+        cpu.set_register(0x1, v1);
+        assert_eq!(cpu.execute(opcode, 0x200, &NullInput), Ok(0x202));
 
-                    // FX1E: Add VX to I
-                    0x001E => {
-                        self.i = self.i.wrapping_add(self.v[register] as u16);
+        (cpu.register(0x0), cpu.register(0xF))
+    }
 
-                        false
-                    }
+    /// Run `opcode` with VF as VX, returning VF.
+    fn alu_into_vf(opcode: Opcode, vf: u8, v1: u8) -> u8 {
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.set_register(0xF, vf);
+        cpu.set_register(0x1, v1);
+        assert_eq!(cpu.execute(opcode, 0x200, &NullInput), Ok(0x202));
 
-                    // FX29: Set I to the location of the sprite for the character in VX.
-                    0x0029 => {
-                        self.i = self.memory.font_address_for_character(self.v[register]);
+        cpu.register(0xF)
+    }
 
-                        false
-                    }
+    #[test]
+    fn test_add_reg() {
+        assert_eq!(alu(Opcode::AddReg(0x0, 0x1), 0x10, 0x20), (0x30, 0));
+        assert_eq!(alu(Opcode::AddReg(0x0, 0x1), 0xFF, 0x01), (0x00, 1));
+        // The carry, not the sum
+        assert_eq!(alu_into_vf(Opcode::AddReg(0xF, 0x1), 0xFF, 0x01), 1);
+    }
 
-                    // FX33:  Store BCD representation of Vx in memory locations I, I+1, and I+2.
-                    0x0033 => {
-                        let value = self.v[register];
+    #[test]
+    fn test_sub() {
+        assert_eq!(alu(Opcode::Sub(0x0, 0x1), 0x05, 0x03), (0x02, 1));
+        // Equal values don't borrow
+        assert_eq!(alu(Opcode::Sub(0x0, 0x1), 0x05, 0x05), (0x00, 1));
+        assert_eq!(alu(Opcode::Sub(0x0, 0x1), 0x03, 0x05), (0xFE, 0));
+        assert_eq!(alu_into_vf(Opcode::Sub(0xF, 0x1), 0x05, 0x03), 1);
+    }
 
-                        self.memory[self.i] = value / 100;
-                        self.memory[self.i + 1] = (value / 10) % 10;
-                        self.memory[self.i + 2] = (value % 100) % 10;
+    #[test]
+    fn test_subn() {
+        assert_eq!(alu(Opcode::Subn(0x0, 0x1), 0x03, 0x05), (0x02, 1));
+        // Equal values don't borrow
+        assert_eq!(alu(Opcode::Subn(0x0, 0x1), 0x05, 0x05), (0x00, 1));
+        assert_eq!(alu(Opcode::Subn(0x0, 0x1), 0x05, 0x03), (0xFE, 0));
+        assert_eq!(alu_into_vf(Opcode::Subn(0xF, 0x1), 0x03, 0x05), 1);
+    }
 
-                        false
-                    }
+    #[test]
+    fn test_shr() {
+        assert_eq!(alu(Opcode::Shr(0x0, 0x0), 0x03, 0x00), (0x01, 1));
+        assert_eq!(alu(Opcode::Shr(0x0, 0x0), 0x02, 0x00), (0x01, 0));
+        // The bit shifted out, not the result
+        assert_eq!(alu_into_vf(Opcode::Shr(0xF, 0xF), 0x02, 0x00), 0);
+    }
 
-                    // FX55: Store registers V0 through VX in memory starting at I.
-                    0x0055 => {
-                        self.memory
-                            .copy_from_slice(self.i, self.v.as_slice_through(register));
+    #[test]
+    fn test_shl() {
+        assert_eq!(alu(Opcode::Shl(0x0, 0x0), 0x81, 0x00), (0x02, 1));
+        assert_eq!(alu(Opcode::Shl(0x0, 0x0), 0x40, 0x00), (0x80, 0));
+        assert_eq!(alu_into_vf(Opcode::Shl(0xF, 0xF), 0x81, 0x00), 1);
+    }
 
-                        false
-                    }
+    #[test]
+    fn test_wait_for_key_release() {
+        struct HeldKey(Option<u8>);
 
-                    // FX65: Read into register v0 through VX starting at I.
-                    0x0065 => {
-                        self.v
-                            .clone_from_slice(self.memory.as_slice(self.i, register + 1));
+        impl Input for HeldKey {
+            fn is_key_down(&self, key: u8) -> bool {
+                self.0 == Some(key)
+            }
+        }
 
-                        false
-                    }
+        let mut cpu = cpu(Variant::Chip8);
+        let mut wait = |held| cpu.execute(Opcode::LdKey(0x3), 0x200, &HeldKey(held));
 
-                    _ => panic!("Unknown opcode {:#02x}", opcode),
-                };
+        assert_eq!(wait(None), Ok(0x200));
+        assert_eq!(wait(Some(0x7)), Ok(0x200));
+        // Still held, and other keys don't count until it's released
+        assert_eq!(wait(Some(0x7)), Ok(0x200));
+        assert_eq!(wait(Some(0x2)), Ok(0x202));
+        assert_eq!(cpu.register(0x3), 0x7);
+    }
 
-                if !blocked {
-                    current_pc + 2
-                } else {
-                    current_pc
-                }
+    #[test]
+    fn test_skip_key_uses_low_nibble() {
+        struct Keypad([bool; 16]);
+
+        impl Input for Keypad {
+            fn is_key_down(&self, key: u8) -> bool {
+                self.0[key as usize]
             }
-            _ => panic!("Unknown opcode {:#02x}", opcode),
+        }
+
+        let mut keys = [false; 16];
+        keys[0x5] = true;
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.set_register(0x0, 0xF5);
+
+        assert_eq!(
+            cpu.execute(Opcode::Skp(0x0), 0x200, &Keypad(keys)),
+            Ok(0x204)
+        );
+        assert_eq!(
+            cpu.execute(Opcode::Sknp(0x0), 0x200, &Keypad(keys)),
+            Ok(0x202)
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut cpu = cpu(Variant::XoChip);
+        cpu.v.clone_from_slice(&[1, 2, 3]);
+
+        assert_eq!(
+            cpu.execute(Opcode::SaveFlags(0x1), 0x200, &NullInput),
+            Ok(0x202)
+        );
+        assert_eq!(cpu.flags()[..3], [1, 2, 0]);
+        cpu.set_flags([7; 16]);
+        assert_eq!(
+            cpu.execute(Opcode::LoadFlags(0x2), 0x202, &NullInput),
+            Ok(0x204)
+        );
+        assert_eq!(cpu.v.0[..4], [7, 7, 7, 0]);
+    }
+
+    #[test]
+    fn test_skip_long_instruction() {
+        // 3000: SE V0, 0x00, F000 1234: LD I, LONG 0x1234
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34];
+        let skip = |variant| {
+            let mut cpu = cpu(variant);
+            cpu.memory_mut().copy_from_slice(0x200, &rom);
+            cpu.execute(Opcode::SeByte(0x0, 0x00), 0x200, &NullInput)
         };
 
-        if tick_timers {
-            self.delay_timer.tick();
-            self.sound_timer.tick();
-        }
+        assert_eq!(skip(Variant::XoChip), Ok(0x206));
+        // F000 is a single unknown word for the others
+        assert_eq!(skip(Variant::Chip8), Ok(0x204));
 
-        next_pc
+        // Skipping past the end of memory wraps around
+        let mut cpu = cpu(Variant::XoChip);
+        cpu.memory_mut().copy_from_slice(0xFFFC, &rom[2..6]);
+        assert_eq!(
+            cpu.execute(Opcode::SeByte(0x0, 0x00), 0xFFFA, &NullInput),
+            Ok(0x0000)
+        );
+        assert_eq!(
+            cpu.execute(Opcode::SeByte(0x0, 0x00), 0xFFFC, &NullInput),
+            Ok(0x0000)
+        );
+        assert_eq!(
+            cpu.execute(Opcode::SeByte(0x0, 0x00), 0xFFFE, &NullInput),
+            Ok(0x0002)
+        );
     }
 
-    fn stack_push(&mut self, value: u16) {
-        assert!(
-            (self.sp as usize) < STACK_SIZE,
-            "Attempting to push when stack is full"
+    #[test]
+    fn test_display_wait() {
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.set_quirks(Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        });
+        // D005: DRW V0, V0, 5
+        cpu.memory_mut().copy_from_slice(0x200, &[0xD0, 0x05]);
+
+        assert_eq!(cpu.cycle(&NullInput), Ok(CycleStatus::WaitingForVBlank));
+        assert_eq!(cpu.cycle(&NullInput), Ok(CycleStatus::WaitingForVBlank));
+        assert_eq!(cpu.pc(), 0x200);
+
+        cpu.tick_timers();
+        assert_eq!(cpu.cycle(&NullInput), Ok(CycleStatus::Executed));
+        assert_eq!(cpu.pc(), 0x202);
+    }
+
+    #[test]
+    fn test_memory_past_the_end() {
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.memory_mut()
+            .set_access_policy(AccessPolicy::Unrestricted);
+        cpu.set_register(0x0, 0xAA);
+        cpu.set_register(0x1, 0xBB);
+        cpu.set_i(0xFFF);
+
+        let out_of_bounds = Err(Error::MemoryOutOfBounds { address: 0x1000 });
+        assert_eq!(
+            cpu.execute(Opcode::Store(0x1), 0x200, &NullInput),
+            out_of_bounds
+        );
+        assert_eq!(
+            cpu.execute(Opcode::Load(0x1), 0x200, &NullInput),
+            out_of_bounds
+        );
+        assert_eq!(
+            cpu.execute(Opcode::Drw(0x0, 0x0, 2), 0x200, &NullInput),
+            out_of_bounds
+        );
+        assert_eq!(cpu.memory()[0xFFF], 0);
+
+        cpu.set_quirks(Quirks {
+            wrap_memory: true,
+            ..Quirks::default()
+        });
+        assert_eq!(
+            cpu.execute(Opcode::Store(0x1), 0x200, &NullInput),
+            Ok(0x202)
+        );
+        assert_eq!(cpu.memory()[0xFFF], 0xAA);
+        assert_eq!(cpu.memory()[0x000], 0xBB);
+        cpu.set_register(0x0, 0);
+        cpu.set_register(0x1, 0);
+        assert_eq!(cpu.execute(Opcode::Load(0x1), 0x200, &NullInput), Ok(0x202));
+        assert_eq!((cpu.register(0x0), cpu.register(0x1)), (0xAA, 0xBB));
+        assert_eq!(
+            cpu.execute(Opcode::Drw(0x0, 0x0, 2), 0x200, &NullInput),
+            Ok(0x202)
+        );
+
+        // Wrapping into the interpreter area is still checked
+        cpu.memory_mut().set_access_policy(AccessPolicy::Protected);
+        assert_eq!(
+            cpu.execute(Opcode::Store(0x1), 0x200, &NullInput),
+            Err(Error::ProtectedMemoryWrite { address: 0x000 })
         );
-        self.stack[self.sp as usize] = value;
-        self.sp += 1;
     }
 
-    fn stack_pop(&mut self) -> u16 {
-        assert!(self.sp != 0, "Attempting to pop empty stack");
-        let value = self.stack[(self.sp - 1) as usize];
-        self.sp -= 1;
+    #[test]
+    fn test_pc_wraps_at_the_end_of_memory() {
+        let mut cpu = cpu(Variant::XoChip);
+        // 6005: LD V0, 0x05
+        cpu.memory_mut().copy_from_slice(0xFFFE, &[0x60, 0x05]);
+        cpu.set_pc(0xFFFE);
+
+        assert_eq!(cpu.cycle(&NullInput), Ok(CycleStatus::Executed));
+        assert_eq!(cpu.register(0x0), 0x05);
+        assert_eq!(cpu.pc(), 0x0000);
+        // The return address wraps too
+        assert_eq!(
+            cpu.execute(Opcode::Call(0x300), 0xFFFE, &NullInput),
+            Ok(0x300)
+        );
+        assert_eq!(cpu.execute(Opcode::Ret, 0x300, &NullInput), Ok(0x0000));
+    }
+
+    #[test]
+    fn test_undo_wrapping_write() {
+        let mut cpu = cpu(Variant::Chip8);
+        cpu.memory_mut()
+            .set_access_policy(AccessPolicy::Unrestricted);
+        cpu.set_quirks(Quirks {
+            wrap_memory: true,
+            ..Quirks::default()
+        });
+        cpu.v.clone_from_slice(&[0xAA, 0xBB, 0xCC]);
+        cpu.set_i(0xFFE);
+        // F255: LD [I], V2
+        cpu.memory_mut().copy_from_slice(0x200, &[0xF2, 0x55]);
+        let written = |cpu: &CPU<NopDisplay>| {
+            [0xFFE, 0xFFF, 0x000]
+                .iter()
+                .map(|&address| cpu.memory()[address])
+                .collect::<Vec<_>>()
+        };
+        let before = written(&cpu);
 
-        value
+        let (_, undo) = cpu.cycle_with_undo(&NullInput).unwrap();
+        assert_eq!(written(&cpu), [0xAA, 0xBB, 0xCC]);
+
+        cpu.undo(undo);
+        assert_eq!(written(&cpu), before);
+        assert_eq!(cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        let mut cpu = cpu(Variant::Chip8);
+        // 6001: LD V0, 0x01, 1200: JP 0x200
+        cpu.memory_mut()
+            .copy_from_slice(0x200, &[0x60, 0x01, 0x12, 0x00]);
+        cpu.cycle(&NullInput).unwrap();
+        cpu.cycle(&NullInput).unwrap();
+
+        // 6002: LD V0, 0x02 is decoded again rather than taken from the earlier decoding
+        cpu.memory_mut().copy_from_slice(0x200, &[0x60, 0x02]);
+        cpu.cycle(&NullInput).unwrap();
+
+        assert_eq!(cpu.register(0), 0x02);
+    }
+
+    #[test]
+    fn test_decode() {
+        let xo_chip = cpu(Variant::XoChip);
+        let chip_8 = cpu(Variant::Chip8);
+
+        assert_eq!(chip_8.decode(0x00E0, 0x200), Ok(Opcode::Cls));
+        assert_eq!(xo_chip.decode(0xF201, 0x200), Ok(Opcode::Plane(2)));
+        for &opcode in &[0x0123, 0x5121, 0xF201] {
+            assert_eq!(
+                chip_8.decode(opcode, 0x200),
+                Err(Error::UnknownOpcode {
+                    opcode,
+                    address: 0x200,
+                    backtrace: Backtrace::default(),
+                })
+            );
+        }
     }
 }
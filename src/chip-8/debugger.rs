@@ -0,0 +1,963 @@
+//! Stepping through a program and inspecting the machine, for finding out why a ROM misbehaves.
+//!
+//! The [`Debugger`] wraps an [`Emulator`] and checks its [`Breakpoints`] and [`Watchpoints`]
+//! before every instruction. [`Command`] parses the commands of the interactive `--debug` mode:
+//!
+//! ```text
+//! step [N]                  Execute N instructions, 1 by default
+//! back [N]                  Undo the last N instructions, 1 by default
+//! break ADDR[: ACTIONS]     Add a breakpoint, see the breakpoint module for actions
+//! delete ADDR               Remove the breakpoint at ADDR
+//! print OPERAND             Print V0-VF, I, PC, or [ADDR]
+//! set OPERAND VALUE         Change V0-VF, I, PC, or [ADDR], `set mem ADDR VALUE` for memory
+//! dump mem ADDR LENGTH      Print LENGTH bytes of memory starting at ADDR
+//! hexdump [ADDR [LENGTH]]   Print memory with its ASCII, 16 bytes at I by default
+//! regs                      Print all registers
+//! backtrace                 Print the subroutine calls that led to the program counter
+//! sprites                   List the sprites the ROM draws
+//! sprites ADDR [ROWS [N]]   Draw N sprites ROWS tall from ADDR, 1 sprite 8 rows tall by default
+//! protect on|off            Make writes below 0x200 an error, or allow them
+//! watch TARGET [r|w|rw]     Stop when VX or memory at ADDR[-END] is accessed
+//! unwatch TARGET            Remove the watchpoint on TARGET
+//! profile on|off            Count how often every instruction runs, or stop counting
+//! profile                   Print the most executed instructions
+//! ```
+//!
+//! Addresses are hex like everywhere else, counts and lengths are decimal.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::breakpoint::{parse_number, Breakpoint, Breakpoints, Hit, Operand};
+use crate::sprites::{self, SpriteSheet};
+use crate::watchpoint::{Target, Watchpoint, Watchpoints};
+use crate::{
+    AccessPolicy, CycleStatus, Display, Emulator, Error, FrameResult, Input, Opcode, Poke, RomError,
+};
+
+/// Bytes per line when dumping memory, also what `hexdump` prints by default.
+const DUMP_WIDTH: usize = 16;
+/// How many of the most executed addresses `profile` prints.
+const PROFILE_HOT_SPOTS: usize = 16;
+/// How many sprites `sprites` draws side by side.
+const SPRITE_COLUMNS: usize = 8;
+/// How many instructions frontends should let `back` undo, see [`Emulator::set_undo_limit`].
+pub const UNDO_LIMIT: usize = 10_000;
+
+/// Why [`Debugger::continue_until_break`] stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// A breakpoint or watchpoint stopped execution, nothing has been executed at its address
+    /// yet.
+    Breakpoint(Hit),
+    /// The instruction at the program counter failed.
+    Error(Error),
+    /// The cycle limit was reached without stopping.
+    CycleLimit,
+}
+
+pub struct Debugger<D> {
+    emulator: Emulator<D>,
+    breakpoints: Breakpoints,
+    watchpoints: Watchpoints,
+    /// Where in the timer period execution is, the timers tick whenever this is 0.
+    timer_phase: usize,
+}
+
+impl<D: Display> Debugger<D> {
+    pub fn new(emulator: Emulator<D>) -> Self {
+        Self {
+            emulator,
+            breakpoints: Breakpoints::new(),
+            watchpoints: Watchpoints::new(),
+            timer_phase: 0,
+        }
+    }
+
+    pub fn with_breakpoints(emulator: Emulator<D>, breakpoints: Breakpoints) -> Self {
+        Self {
+            breakpoints,
+            ..Self::new(emulator)
+        }
+    }
+
+    pub fn emulator(&self) -> &Emulator<D> {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator<D> {
+        &mut self.emulator
+    }
+
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+
+    /// Reset the emulator, breakpoints and watchpoints are kept.
+    pub fn reset(self) -> Self {
+        Self {
+            emulator: self.emulator.reset(),
+            breakpoints: self.breakpoints,
+            watchpoints: self.watchpoints,
+            timer_phase: 0,
+        }
+    }
+
+    /// Switch to `rom`, see [`Emulator::load_rom`]. Breakpoints and watchpoints are kept.
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), RomError> {
+        self.emulator.load_rom(rom)?;
+        self.timer_phase = 0;
+
+        Ok(())
+    }
+
+    /// Stop at `address`.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.add(Breakpoint::new(address));
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(address)
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    /// Stop before instructions that access `watchpoint`'s target.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.add(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, target: Target) -> bool {
+        self.watchpoints.remove(target)
+    }
+
+    /// Check for a breakpoint at the program counter and watchpoints on what the next
+    /// instruction accesses and, unless one stops execution, run the instruction. A stopping
+    /// breakpoint or watchpoint pauses the emulator. This is the building
+    /// block for running under a debugger at a real time pace, the timers are left to
+    /// [`Emulator::tick_timers`].
+    pub fn cycle(&mut self, input: &dyn Input) -> Result<Option<Hit>, Error> {
+        if self.emulator.is_paused() {
+            return Ok(None);
+        }
+
+        let hit = self.breakpoints.check(&mut self.emulator);
+        if let Some(hit) = &hit {
+            self.emulator.notify_breakpoint(hit);
+            if hit.should_stop {
+                self.emulator.pause();
+
+                return Ok(Some(hit.clone()));
+            }
+        }
+        if let Some(mut watch_hit) = self.watchpoints.check(&self.emulator) {
+            self.emulator.pause();
+            self.emulator.notify_breakpoint(&watch_hit);
+            if let Some(hit) = hit {
+                watch_hit.log.splice(0..0, hit.log);
+            }
+
+            return Ok(Some(watch_hit));
+        }
+
+        self.emulator.cycle(input)?;
+
+        Ok(hit)
+    }
+
+    /// Like [`Emulator::run_frame`], but checking breakpoints before every instruction.
+    /// `on_hit` is called for each breakpoint reached, a stopping one ends the frame early.
+    pub fn run_frame(
+        &mut self,
+        input: &dyn Input,
+        mut on_hit: impl FnMut(Hit),
+    ) -> Result<FrameResult, Error> {
+        // Nothing to check, run the frame in one go
+        if self.breakpoints.is_empty() && self.watchpoints.is_empty() {
+            return self.emulator.run_frame(input);
+        }
+
+        let mut frame = FrameResult::new();
+        if self.emulator.is_paused() {
+            return Ok(frame);
+        }
+
+        for _ in 0..self.emulator.take_due_frames() {
+            if self.emulator.is_paused() {
+                break;
+            }
+
+            let is_stopped = self.run_checked_frame(input, &mut on_hit, &mut frame)?;
+            if is_stopped {
+                break;
+            }
+        }
+        frame.is_sound_active = self.emulator.is_sound_active();
+
+        Ok(frame)
+    }
+
+    /// One frame of [`Debugger::run_frame`] added to `frame`, returns whether a breakpoint
+    /// stopped it.
+    fn run_checked_frame(
+        &mut self,
+        input: &dyn Input,
+        on_hit: &mut impl FnMut(Hit),
+        frame: &mut FrameResult,
+    ) -> Result<bool, Error> {
+        self.emulator.start_frame();
+        for _ in 0..self.emulator.next_frame_cycles() {
+            if self.emulator.is_paused() {
+                break;
+            }
+
+            let hit = self.cycle(input)?;
+            if let Some(hit) = hit {
+                let should_stop = hit.should_stop;
+                on_hit(hit);
+                if should_stop {
+                    return Ok(true);
+                }
+            }
+            frame.add_cycle(self.emulator.display());
+            if self.emulator.cycle_status() == CycleStatus::WaitingForVBlank {
+                break;
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Like [`Debugger::run_frame`], also when paused, and stay paused afterwards.
+    pub fn step_frame(
+        &mut self,
+        input: &dyn Input,
+        on_hit: impl FnMut(Hit),
+    ) -> Result<FrameResult, Error> {
+        let was_paused = self.emulator.is_paused();
+        self.emulator.resume();
+        let result = self.run_frame(input, on_hit);
+        if was_paused {
+            self.emulator.pause();
+        }
+
+        result
+    }
+
+    /// Execute the next instruction regardless of breakpoints, also when paused. The timers
+    /// don't tick.
+    pub fn step(&mut self, input: &dyn Input) -> Result<(), Error> {
+        let was_paused = self.emulator.is_paused();
+        self.emulator.resume();
+        let result = self.emulator.cycle(input);
+        if was_paused {
+            self.emulator.pause();
+        }
+
+        result
+    }
+
+    /// Undo up to `count` of the last executed instructions, returns how many were undone.
+    /// Needs an undo limit on the emulator, see [`Emulator::set_undo_limit`].
+    pub fn step_back(&mut self, count: usize) -> usize {
+        (0..count).take_while(|_| self.emulator.step_back()).count()
+    }
+
+    /// Resume and run as fast as possible until a breakpoint stops execution, an instruction
+    /// fails, or `max_cycles` instructions have run. Timers tick at the usual rate relative
+    /// to the instructions executed. Returns why execution stopped along with the output of
+    /// breakpoints that didn't stop it.
+    pub fn continue_until_break(
+        &mut self,
+        input: &dyn Input,
+        max_cycles: usize,
+    ) -> (Stop, Vec<String>) {
+        let mut log = Vec::new();
+        self.emulator.resume();
+
+        for _ in 0..max_cycles {
+            match self.cycle(input) {
+                Ok(Some(hit)) if hit.should_stop => return (Stop::Breakpoint(hit), log),
+                Ok(Some(hit)) => log.extend(hit.log),
+                Ok(None) => {}
+                Err(error) => {
+                    self.emulator.pause();
+                    return (Stop::Error(error), log);
+                }
+            }
+
+            if self.timer_phase == 0 {
+                self.emulator.tick_timers();
+            }
+            self.timer_phase = (self.timer_phase + 1) % self.emulator.cycles_per_timer_tick();
+        }
+
+        (Stop::CycleLimit, log)
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.emulator.pc()
+    }
+
+    pub fn i(&self) -> u16 {
+        self.emulator.i()
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        self.emulator.register(register)
+    }
+
+    /// `length` bytes of memory starting at `address`.
+    pub fn dump(&self, address: u16, length: u16) -> Result<&[u8], Error> {
+        self.emulator.peek_slice(address, length)
+    }
+
+    /// The instruction at the program counter.
+    pub fn current_instruction(&self) -> Result<Opcode, Error> {
+        self.emulator.current_instruction()
+    }
+
+    /// Run `command` and return what it printed.
+    pub fn execute(&mut self, command: &Command, input: &dyn Input) -> String {
+        match command {
+            Command::Step(count) => {
+                for _ in 0..*count {
+                    if let Err(error) = self.step(input) {
+                        return error.to_string();
+                    }
+                }
+
+                self.location()
+            }
+            Command::Back(count) => {
+                if self.step_back(*count) == 0 {
+                    return "Nothing to undo".to_string();
+                }
+
+                self.location()
+            }
+            Command::Break(breakpoint) => {
+                let address = breakpoint.address;
+                self.breakpoints.add(breakpoint.clone());
+
+                format!("Breakpoint at {:#05x}", address)
+            }
+            Command::Delete(address) => {
+                if self.remove_breakpoint(*address) {
+                    format!("Removed the breakpoint at {:#05x}", address)
+                } else {
+                    format!("No breakpoint at {:#05x}", address)
+                }
+            }
+            Command::Print(operand) => operand.read(&self.emulator),
+            Command::Set { target, value } => match self.set(*target, *value) {
+                Ok(()) => target.read(&self.emulator),
+                Err(error) => error,
+            },
+            Command::Dump { address, length } => match self.dump(*address, *length) {
+                Ok(bytes) => bytes
+                    .chunks(DUMP_WIDTH)
+                    .enumerate()
+                    .map(|(line, bytes)| {
+                        let hex = bytes
+                            .iter()
+                            .map(|byte| format!("{:02x}", byte))
+                            .collect::<Vec<_>>();
+
+                        format!(
+                            "{:#05x}: {}",
+                            *address as usize + line * DUMP_WIDTH,
+                            hex.join(" ")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(error) => error.to_string(),
+            },
+            Command::Hexdump { address, length } => {
+                let address = address.unwrap_or_else(|| self.i()) as usize;
+                if address >= self.emulator.memory_size() {
+                    return Error::MemoryOutOfBounds { address }.to_string();
+                }
+
+                let end = (address + *length as usize).min(self.emulator.memory_size());
+                hexdump(address, self.emulator.dump_memory(address..end))
+            }
+            Command::Registers => self.emulator.to_string(),
+            Command::Backtrace => {
+                let backtrace = self.emulator.state().backtrace;
+                if backtrace.is_empty() {
+                    return self.location();
+                }
+
+                format!("{}\n{}", self.location(), backtrace)
+            }
+            Command::Sprites => {
+                let memory = self.emulator.dump_memory(..);
+                let sprites = sprites::find_sprites(memory);
+                if sprites.is_empty() {
+                    return "No sprites found".to_owned();
+                }
+
+                sprites
+                    .iter()
+                    .map(|sprite| {
+                        format!(
+                            "{:#05x}: {}x{}",
+                            sprite.address, sprite.width, sprite.height
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Command::DrawSprites {
+                address,
+                height,
+                count,
+            } => {
+                let address = *address as usize;
+                if address >= self.emulator.memory_size() {
+                    return Error::MemoryOutOfBounds { address }.to_string();
+                }
+
+                let end = (address + height * count).min(self.emulator.memory_size());
+                let bytes = self.emulator.dump_memory(address..end);
+                SpriteSheet::new(bytes, *height, SPRITE_COLUMNS.min(*count)).to_string()
+            }
+            Command::Watch(watchpoint) => {
+                self.watchpoints.add(*watchpoint);
+
+                format!("Watching {}", watchpoint)
+            }
+            Command::Unwatch(target) => {
+                if self.remove_watchpoint(*target) {
+                    format!("Removed the watchpoint on {}", target)
+                } else {
+                    format!("No watchpoint on {}", target)
+                }
+            }
+            Command::Profile(enabled) => {
+                self.emulator.set_profiling(*enabled);
+
+                if *enabled {
+                    "Profiling".to_owned()
+                } else {
+                    "Stopped profiling".to_owned()
+                }
+            }
+            Command::ProfileReport => match self.emulator.profile_report() {
+                Some(mut report) => {
+                    report.hot_spots.truncate(PROFILE_HOT_SPOTS);
+
+                    report.to_string().trim_end().to_owned()
+                }
+                None => "Not profiling, start with `profile on`".to_owned(),
+            },
+            Command::Protect(policy) => {
+                self.emulator.set_access_policy(*policy);
+
+                match policy {
+                    AccessPolicy::Protected => "Writes below 0x200 are an error".to_owned(),
+                    AccessPolicy::Unrestricted => "Writes below 0x200 are allowed".to_owned(),
+                }
+            }
+        }
+    }
+
+    /// Write `value` to `target`, an error if it doesn't fit or is outside of memory.
+    fn set(&mut self, target: Operand, value: u16) -> Result<(), String> {
+        let memory_size = self.emulator.memory_size();
+        match target {
+            Operand::Register(register) if value <= 0xFF => {
+                self.emulator.set_register(register, value as u8);
+            }
+            Operand::I if (value as usize) < memory_size => self.emulator.set_i(value),
+            Operand::Pc => self
+                .emulator
+                .set_pc(value)
+                .map_err(|error| error.to_string())?,
+            Operand::Memory(address) if value <= 0xFF => {
+                let poke = Poke {
+                    address,
+                    bytes: vec![value as u8],
+                };
+                self.emulator
+                    .poke(&poke)
+                    .map_err(|error| error.to_string())?;
+            }
+            Operand::I => {
+                return Err(Error::MemoryOutOfBounds {
+                    address: value as usize,
+                }
+                .to_string())
+            }
+            Operand::Register(_) | Operand::Memory(_) => {
+                return Err(format!("{:#x} doesn't fit in a byte", value))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The program counter and the instruction there.
+    fn location(&self) -> String {
+        match self.current_instruction() {
+            Ok(instruction) => format!("{:#05x}: {}", self.pc(), instruction),
+            Err(error) => format!("{:#05x}: {}", self.pc(), error),
+        }
+    }
+}
+
+/// A debugger command, see the [module documentation](self) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Back(usize),
+    Break(Breakpoint),
+    Delete(u16),
+    Print(Operand),
+    /// Change a register or a byte of memory, checking that `value` fits.
+    Set {
+        target: Operand,
+        value: u16,
+    },
+    Dump {
+        address: u16,
+        length: u16,
+    },
+    /// Like [`Command::Dump`] with the bytes as ASCII too, at I when there's no address.
+    Hexdump {
+        address: Option<u16>,
+        length: u16,
+    },
+    Registers,
+    Backtrace,
+    /// List the sprites found with [`sprites::find_sprites`].
+    Sprites,
+    /// Draw `count` sprites `height` rows tall from `address` on as text.
+    DrawSprites {
+        address: u16,
+        height: usize,
+        count: usize,
+    },
+    Protect(AccessPolicy),
+    Watch(Watchpoint),
+    Unwatch(Target),
+    /// Turn the [`profiler`](crate::profiler) on or off.
+    Profile(bool),
+    ProfileReport,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut parts = s.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let arguments = parts.next().unwrap_or("").trim();
+        let words = arguments.split_whitespace().collect::<Vec<_>>();
+
+        match (name, words.as_slice()) {
+            ("step", []) => Ok(Command::Step(1)),
+            ("step", [count]) => count
+                .parse()
+                .map(Command::Step)
+                .map_err(|_| format!("Invalid step count `{}`", count)),
+            ("back", []) => Ok(Command::Back(1)),
+            ("back", [count]) => count
+                .parse()
+                .map(Command::Back)
+                .map_err(|_| format!("Invalid step count `{}`", count)),
+            ("break", [_, ..]) => arguments
+                .parse()
+                .map(Command::Break)
+                .map_err(|error: crate::breakpoint::BreakpointError| error.to_string()),
+            ("delete", [address]) => parse_number(address)
+                .map(Command::Delete)
+                .ok_or_else(|| format!("Invalid address `{}`", address)),
+            ("print", [operand]) => operand
+                .parse()
+                .map(Command::Print)
+                .map_err(|error: crate::breakpoint::BreakpointError| error.to_string()),
+            ("set", ["mem", address, value]) => {
+                match (parse_number(address), parse_number(value)) {
+                    (Some(address), Some(value)) => Ok(Command::Set {
+                        target: Operand::Memory(address),
+                        value,
+                    }),
+                    _ => Err(format!("Invalid set `{}`", s)),
+                }
+            }
+            ("set", [target, value]) => {
+                let target = target
+                    .parse()
+                    .map_err(|error: crate::breakpoint::BreakpointError| error.to_string())?;
+                parse_number(value)
+                    .map(|value| Command::Set { target, value })
+                    .ok_or_else(|| format!("Invalid value `{}`", value))
+            }
+            ("dump", ["mem", address, length]) | ("dump", [address, length]) => {
+                match (parse_number(address), length.parse().ok()) {
+                    (Some(address), Some(length)) => Ok(Command::Dump { address, length }),
+                    _ => Err(format!("Invalid dump `{}`", s)),
+                }
+            }
+            ("hexdump", []) => Ok(Command::Hexdump {
+                address: None,
+                length: DUMP_WIDTH as u16,
+            }),
+            ("hexdump", [address]) => parse_number(address)
+                .map(|address| Command::Hexdump {
+                    address: Some(address),
+                    length: DUMP_WIDTH as u16,
+                })
+                .ok_or_else(|| format!("Invalid address `{}`", address)),
+            ("hexdump", [address, length]) => match (parse_number(address), length.parse().ok()) {
+                (Some(address), Some(length)) => Ok(Command::Hexdump {
+                    address: Some(address),
+                    length,
+                }),
+                _ => Err(format!("Invalid hexdump `{}`", s)),
+            },
+            ("regs", []) => Ok(Command::Registers),
+            ("backtrace", []) => Ok(Command::Backtrace),
+            ("sprites", []) => Ok(Command::Sprites),
+            ("sprites", [address, rest @ ..]) if rest.len() <= 2 => {
+                let address = parse_number(address)
+                    .ok_or_else(|| format!("Invalid address `{}`", address))?;
+                let height = match rest.first() {
+                    Some(height) => height.parse().ok().filter(|&height| height > 0),
+                    None => Some(8),
+                };
+                let count = match rest.get(1) {
+                    Some(count) => count.parse().ok().filter(|&count| count > 0),
+                    None => Some(1),
+                };
+
+                match (height, count) {
+                    (Some(height), Some(count)) => Ok(Command::DrawSprites {
+                        address,
+                        height,
+                        count,
+                    }),
+                    _ => Err(format!("Invalid sprites `{}`", s)),
+                }
+            }
+            ("watch", [_, ..]) => arguments
+                .parse()
+                .map(Command::Watch)
+                .map_err(|error: crate::breakpoint::BreakpointError| error.to_string()),
+            ("unwatch", [target]) => target
+                .parse()
+                .map(Command::Unwatch)
+                .map_err(|error: crate::breakpoint::BreakpointError| error.to_string()),
+            ("profile", []) => Ok(Command::ProfileReport),
+            ("profile", ["on"]) => Ok(Command::Profile(true)),
+            ("profile", ["off"]) => Ok(Command::Profile(false)),
+            ("protect", ["on"]) => Ok(Command::Protect(AccessPolicy::Protected)),
+            ("protect", ["off"]) => Ok(Command::Protect(AccessPolicy::Unrestricted)),
+            _ => Err(format!("Unknown command `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(hit) => write!(f, "Stopped at {:#05x}", hit.address),
+            Stop::Error(error) => write!(f, "Stopped: {}", error),
+            Stop::CycleLimit => write!(f, "Still running"),
+        }
+    }
+}
+
+/// `bytes` from `address` onwards in lines of hex followed by the printable ASCII characters.
+fn hexdump(address: usize, bytes: &[u8]) -> String {
+    bytes
+        .chunks(DUMP_WIDTH)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>();
+            let ascii = bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => byte as char,
+                    _ => '.',
+                })
+                .collect::<String>();
+
+            format!(
+                "{:#05x}: {:<width$}  |{}|",
+                address + line * DUMP_WIDTH,
+                hex.join(" "),
+                ascii,
+                width = DUMP_WIDTH * 3 - 1
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Debugger, Stop, UNDO_LIMIT};
+    use crate::batch::NullInput;
+    use crate::breakpoint::Operand;
+    use crate::{AccessPolicy, Emulator, FramebufferDisplay, Quirks};
+
+    // 6005: LD V0, 0x05, 7001: ADD V0, 0x01, 1202: JP 0x202
+    fn debugger() -> Debugger<FramebufferDisplay> {
+        let rom = vec![0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+        let display = FramebufferDisplay::default();
+
+        Debugger::new(Emulator::new(display, rom, Quirks::default()).unwrap())
+    }
+
+    #[test]
+    fn test_step_and_break() {
+        let mut debugger = debugger();
+        debugger.step(&NullInput).unwrap();
+        assert_eq!(debugger.register(0), 0x05);
+
+        debugger.add_breakpoint(0x204);
+        let (stop, _) = debugger.continue_until_break(&NullInput, 100);
+        match stop {
+            Stop::Breakpoint(hit) => assert_eq!(hit.address, 0x204),
+            stop => panic!("Unexpected stop {:?}", stop),
+        }
+        assert_eq!(debugger.register(0), 0x06);
+        assert!(debugger.emulator().is_paused());
+
+        // Continuing runs the instruction at the breakpoint before stopping there again
+        debugger.continue_until_break(&NullInput, 100);
+        assert_eq!(debugger.register(0), 0x07);
+
+        assert!(debugger.remove_breakpoint(0x204));
+        let (stop, _) = debugger.continue_until_break(&NullInput, 10);
+        assert_eq!(stop, Stop::CycleLimit);
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("step".parse(), Ok(Command::Step(1)));
+        assert_eq!("step 10".parse(), Ok(Command::Step(10)));
+        assert_eq!("back".parse(), Ok(Command::Back(1)));
+        assert_eq!("back 3".parse(), Ok(Command::Back(3)));
+        assert_eq!(
+            "hexdump 0x300 32".parse(),
+            Ok(Command::Hexdump {
+                address: Some(0x300),
+                length: 32
+            })
+        );
+        assert_eq!("print v3".parse(), Ok(Command::Print(Operand::Register(3))));
+        assert_eq!(
+            "dump mem 0x200 32".parse(),
+            Ok(Command::Dump {
+                address: 0x200,
+                length: 32
+            })
+        );
+        assert!("break 0x204".parse::<Command>().is_ok());
+        assert_eq!(
+            "protect off".parse(),
+            Ok(Command::Protect(AccessPolicy::Unrestricted))
+        );
+        assert_eq!("profile on".parse(), Ok(Command::Profile(true)));
+        assert_eq!("sprites".parse(), Ok(Command::Sprites));
+        assert_eq!(
+            "set mem 0x300 0xAB".parse(),
+            Ok(Command::Set {
+                target: Operand::Memory(0x300),
+                value: 0xAB
+            })
+        );
+        assert_eq!(
+            "set v3 0x10".parse(),
+            Ok(Command::Set {
+                target: Operand::Register(3),
+                value: 0x10
+            })
+        );
+        assert!("set v3".parse::<Command>().is_err());
+        assert_eq!(
+            "sprites 0x050 5 3".parse(),
+            Ok(Command::DrawSprites {
+                address: 0x050,
+                height: 5,
+                count: 3
+            })
+        );
+        assert!("sprites 0x050 0".parse::<Command>().is_err());
+        assert_eq!("profile".parse(), Ok(Command::ProfileReport));
+        assert!("print".parse::<Command>().is_err());
+        assert!("profile maybe".parse::<Command>().is_err());
+        assert!("jump 0x200".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut debugger = debugger();
+        assert!(debugger
+            .execute(&Command::ProfileReport, &NullInput)
+            .starts_with("Not profiling"));
+
+        debugger.execute(&Command::Profile(true), &NullInput);
+        debugger.execute(&Command::Step(4), &NullInput);
+
+        let report = debugger.execute(&Command::ProfileReport, &NullInput);
+        assert!(report.starts_with("4 instructions\n\nHot spots:\n0x"));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut debugger = debugger();
+        debugger.execute(&"watch V0 w".parse().unwrap(), &NullInput);
+
+        let (stop, _) = debugger.continue_until_break(&NullInput, 100);
+        match stop {
+            Stop::Breakpoint(hit) => {
+                assert_eq!(hit.address, 0x200);
+                assert_eq!(hit.log, vec!["0x200: V0 written by LD V0, 0x05"]);
+            }
+            stop => panic!("Unexpected stop {:?}", stop),
+        }
+        assert_eq!(debugger.register(0), 0x00);
+
+        // Continuing runs the instruction before stopping at the next write
+        let (stop, _) = debugger.continue_until_break(&NullInput, 100);
+        assert!(matches!(stop, Stop::Breakpoint(hit) if hit.address == 0x202));
+        assert_eq!(debugger.register(0), 0x05);
+
+        assert_eq!(
+            debugger.execute(&"unwatch v0".parse().unwrap(), &NullInput),
+            "Removed the watchpoint on V0"
+        );
+        let (stop, _) = debugger.continue_until_break(&NullInput, 10);
+        assert_eq!(stop, Stop::CycleLimit);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut debugger = debugger();
+        assert_eq!(
+            debugger.execute(&Command::Back(1), &NullInput),
+            "Nothing to undo"
+        );
+
+        debugger.emulator_mut().set_undo_limit(UNDO_LIMIT);
+        debugger.execute(&Command::Step(3), &NullInput);
+        assert_eq!(debugger.register(0), 0x06);
+
+        assert_eq!(
+            debugger.execute(&Command::Back(2), &NullInput),
+            "0x202: ADD V0, 0x01"
+        );
+        assert_eq!(debugger.register(0), 0x05);
+        assert_eq!(debugger.step_back(5), 1);
+        assert_eq!(debugger.pc(), 0x200);
+        assert_eq!(debugger.register(0), 0x00);
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            debugger.execute(&Command::Step(1), &NullInput),
+            "0x202: ADD V0, 0x01"
+        );
+        assert_eq!(
+            debugger.execute(&"print V0".parse().unwrap(), &NullInput),
+            "V0=05"
+        );
+        assert_eq!(
+            debugger.execute(&Command::Backtrace, &NullInput),
+            "0x202: ADD V0, 0x01"
+        );
+        assert_eq!(
+            debugger.execute(
+                &Command::Dump {
+                    address: 0x200,
+                    length: 4
+                },
+                &NullInput
+            ),
+            "0x200: 60 05 70 01"
+        );
+        debugger.emulator_mut().set_i(0x050);
+        assert_eq!(
+            debugger.execute(&"hexdump".parse().unwrap(), &NullInput),
+            "0x050: f0 90 90 90 f0 20 60 20 20 70 f0 10 f0 80 f0 f0  |..... `  p......|"
+        );
+        assert_eq!(
+            debugger.execute(&"hexdump 0x202 6".parse().unwrap(), &NullInput),
+            format!("0x202: 70 01 12 02 00 00{:<32}|p.....|", "")
+        );
+        assert_eq!(
+            debugger.execute(&"hexdump 0x1000".parse().unwrap(), &NullInput),
+            "Memory access at 0x1000 is out of bounds"
+        );
+    }
+
+    #[test]
+    fn test_execute_set() {
+        let mut debugger = debugger();
+        let mut set = |command: &str| debugger.execute(&command.parse().unwrap(), &NullInput);
+
+        assert_eq!(set("set mem 0x300 0xAB"), "[0x300]=ab");
+        assert_eq!(set("set [0x301] 0xCD"), "[0x301]=cd");
+        assert_eq!(set("set v3 0x10"), "V3=10");
+        assert_eq!(set("set i 0x250"), "I=0x250");
+        assert_eq!(set("set pc 0x204"), "PC=0x204");
+        assert_eq!(set("set v3 0x100"), "0x100 doesn't fit in a byte");
+        assert_eq!(
+            set("set pc 0x1000"),
+            "Memory access at 0x1000 is out of bounds"
+        );
+        assert_eq!(set("set mem 0xFFF 0x100"), "0x100 doesn't fit in a byte");
+        assert_eq!(
+            set("set mem 0x1000 0x01"),
+            "Cannot write 1 bytes at 0x1000, it's past the end of memory"
+        );
+
+        assert_eq!(debugger.emulator().peek(0x300), 0xAB);
+        assert_eq!(debugger.emulator().register(3), 0x10);
+        assert_eq!(debugger.emulator().i(), 0x250);
+        assert_eq!(debugger.pc(), 0x204);
+    }
+
+    #[test]
+    fn test_execute_sprites() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            debugger.execute(&Command::Sprites, &NullInput),
+            "No sprites found"
+        );
+        assert_eq!(
+            debugger.execute(&"sprites 0x050 5 2".parse().unwrap(), &NullInput),
+            "####.... ..#.....\n\
+             #..#.... .##.....\n\
+             #..#.... ..#.....\n\
+             #..#.... ..#.....\n\
+             ####.... .###...."
+        );
+
+        // A206: LD I, 0x206, D015: DRW V0, V1, 5, 1202: JP 0x202
+        let rom = vec![
+            0xA2, 0x06, 0xD0, 0x15, 0x12, 0x02, 0x20, 0x60, 0x20, 0x20, 0x70,
+        ];
+        let emulator = Emulator::new(FramebufferDisplay::default(), rom, Quirks::default());
+        let mut debugger = Debugger::new(emulator.unwrap());
+        assert_eq!(
+            debugger.execute(&Command::Sprites, &NullInput),
+            "0x206: 8x5"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Decoding ROMs into readable instructions.
+//!
+//! Mnemonics follow Cowgod's technical reference, e.g. `LD V3, 0x42` or `DRW V1, V2, 5`. The
+//! XO-CHIP instructions use `SCU N`, `SAVE VX, VY`, `LOAD VX, VY`, `LD I, LONG NNNN`, `PLANE N`,
+//! `AUDIO`, and `PITCH VX`. Words that aren't instructions, typically sprite data, are shown as `DB`.
+//!
+//! [`disassemble`] decodes every word, [`symbolize`] follows the code from the start of the ROM
+//! to tell code from data and names the addresses it jumps to, calls, and loads into I, e.g.
+//!
+//! ```text
+//! label_0200:
+//!     CALL sub_0206
+//!     JP label_0200
+//! sub_0206:
+//!     LD I, data_020C
+//!     DRW V0, V1, 2
+//!     RET
+//! data_020C:
+//!     DB 0x80, 0xC0
+//! ```
+//!
+//! The output of both assembles back into the same ROM.
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use crate::opcode::Opcode as Instruction;
+
+/// Where ROMs are loaded and execution starts.
+const ROM_BASE_ADDRESS: u16 = 0x200;
+/// Bytes per `DB` line of data.
+const DATA_WIDTH: usize = 8;
+
+/// What a labelled address is used as, which decides the name of the label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LabelKind {
+    /// Loaded into I, e.g. a sprite.
+    Data,
+    /// Jumped to.
+    Jump,
+    /// Called. An address that's used in several ways gets the kind that comes last.
+    Subroutine,
+}
+
+/// A name for an address in the ROM, like `sub_0242`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+    pub address: u16,
+    pub kind: LabelKind,
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.kind {
+            LabelKind::Data => "data",
+            LabelKind::Jump => "label",
+            LabelKind::Subroutine => "sub",
+        };
+
+        write!(f, "{}_{:04X}", prefix, self.address)
+    }
+}
+
+/// A line of the output of [`symbolize`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    /// Names the address of the following line.
+    Label(Label),
+    /// An instruction that's reached from the start of the ROM, `target` is the label of the
+    /// address it jumps to, calls, or loads into I.
+    Instruction {
+        address: u16,
+        instruction: Instruction,
+        target: Option<Label>,
+    },
+    /// Bytes that aren't reached as code, typically sprites.
+    Data { address: u16, bytes: Vec<u8> },
+}
+
+impl Line {
+    pub fn address(&self) -> u16 {
+        match self {
+            Line::Label(label) => label.address,
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Label(label) => write!(f, "{}:", label),
+            Line::Instruction {
+                instruction,
+                target: Some(target),
+                ..
+            } => match instruction {
+                Instruction::Jp(_) => write!(f, "JP {}", target),
+                Instruction::JpV0(_) => write!(f, "JP V0, {}", target),
+                Instruction::Call(_) => write!(f, "CALL {}", target),
+                Instruction::LdI(_) => write!(f, "LD I, {}", target),
+                Instruction::LdILong(_) => write!(f, "LD I, LONG {}", target),
+                instruction => write!(f, "{}", instruction),
+            },
+            Line::Instruction { instruction, .. } => write!(f, "{}", instruction),
+            Line::Data { bytes, .. } => {
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| format!("0x{:02X}", byte))
+                    .collect::<Vec<_>>();
+
+                write!(f, "DB {}", bytes.join(", "))
+            }
+        }
+    }
+}
+
+/// Decode every word of `rom` as an instruction, along with the address it's loaded at.
+///
+/// This is a linear sweep, sprite data mixed in with the code is decoded too and shows up as
+/// `DB` or as whatever instruction it happens to look like.
+pub fn disassemble(rom: &[u8]) -> Vec<(u16, Instruction)> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let instruction = decode_at(rom, offset);
+        instructions.push((ROM_BASE_ADDRESS + offset as u16, instruction));
+        offset += instruction.size() as usize;
+    }
+
+    instructions
+}
+
+/// Disassemble `rom` with labels, telling code apart from data by following every path
+/// execution can take from the start of the ROM.
+///
+/// Code that's only reached through `JP V0, NNN` beyond the first entry of a jump table, or
+/// that's written at runtime, can't be found this way and shows up as data.
+pub fn symbolize(rom: &[u8]) -> Vec<Line> {
+    let end = ROM_BASE_ADDRESS as usize + rom.len();
+    let in_rom = |address: u16| (ROM_BASE_ADDRESS as usize..end).contains(&(address as usize));
+    let decode = |address: u16| decode_at(rom, (address - ROM_BASE_ADDRESS) as usize);
+
+    let mut instructions = BTreeMap::new();
+    let mut labels = BTreeMap::new();
+    let mut add_label = |address: u16, kind: LabelKind| {
+        if in_rom(address) {
+            let label = labels.entry(address).or_insert(kind);
+            *label = kind.max(*label);
+        }
+    };
+    let mut pending = vec![ROM_BASE_ADDRESS];
+
+    while let Some(address) = pending.pop() {
+        if !in_rom(address) || instructions.contains_key(&address) {
+            continue;
+        }
+        let instruction = decode(address);
+        instructions.insert(address, instruction);
+
+        let next = address.wrapping_add(instruction.size());
+        match instruction {
+            Instruction::Jp(target) | Instruction::JpV0(target) => {
+                add_label(target, LabelKind::Jump);
+                pending.push(target);
+            }
+            Instruction::Call(target) => {
+                add_label(target, LabelKind::Subroutine);
+                pending.push(target);
+                pending.push(next);
+            }
+            Instruction::Ret
+            | Instruction::Sys(_)
+            | Instruction::Data(_)
+            | Instruction::Byte(_) => {}
+            Instruction::SeByte(..)
+            | Instruction::SneByte(..)
+            | Instruction::SeReg(..)
+            | Instruction::SneReg(..)
+            | Instruction::Skp(_)
+            | Instruction::Sknp(_) => {
+                pending.push(next);
+                if in_rom(next) {
+                    pending.push(next.wrapping_add(decode(next).size()));
+                }
+            }
+            Instruction::LdI(target) | Instruction::LdILong(target) => {
+                add_label(target, LabelKind::Data);
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    let label = |address: u16| labels.get(&address).map(|&kind| Label { address, kind });
+    // An instruction can't be shown as one if a label or another instruction starts inside
+    // it, e.g. a ROM that modifies the address of LD I, LONG NNNN
+    let is_split = |address: u16, instruction: Instruction| {
+        (1..instruction.size()).any(|offset| {
+            let inside = address.wrapping_add(offset);
+            labels.contains_key(&inside) || instructions.contains_key(&inside)
+        })
+    };
+
+    let mut lines = Vec::new();
+    let mut data: Option<(u16, Vec<u8>)> = None;
+    let flush = |lines: &mut Vec<Line>, data: &mut Option<(u16, Vec<u8>)>| {
+        if let Some((address, bytes)) = data.take() {
+            lines.push(Line::Data { address, bytes });
+        }
+    };
+    let mut address = ROM_BASE_ADDRESS;
+
+    while in_rom(address) {
+        if let Some(label) = label(address) {
+            flush(&mut lines, &mut data);
+            lines.push(Line::Label(label));
+        }
+
+        match instructions.get(&address) {
+            Some(&instruction) if !is_split(address, instruction) => {
+                flush(&mut lines, &mut data);
+                let target = match instruction {
+                    Instruction::Jp(target)
+                    | Instruction::JpV0(target)
+                    | Instruction::Call(target)
+                    | Instruction::LdI(target)
+                    | Instruction::LdILong(target) => label(target),
+                    _ => None,
+                };
+                lines.push(Line::Instruction {
+                    address,
+                    instruction,
+                    target,
+                });
+                address = address.wrapping_add(instruction.size());
+            }
+            _ => {
+                let byte = rom[(address - ROM_BASE_ADDRESS) as usize];
+                let (_, bytes) = data.get_or_insert_with(|| (address, Vec::new()));
+                bytes.push(byte);
+                if bytes.len() == DATA_WIDTH {
+                    flush(&mut lines, &mut data);
+                }
+                address = address.wrapping_add(1);
+            }
+        }
+    }
+    flush(&mut lines, &mut data);
+
+    lines
+}
+
+/// The instruction at `offset` in `rom`, a [`Instruction::Byte`] for the last byte of a ROM
+/// with an odd length.
+fn decode_at(rom: &[u8], offset: usize) -> Instruction {
+    let word = |offset: usize| (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+    if offset + 1 == rom.len() {
+        return Instruction::Byte(rom[offset]);
+    }
+
+    match Instruction::decode(word(offset), 0) {
+        Instruction::LdILong(_) if offset + 4 > rom.len() => Instruction::Data(word(offset)),
+        Instruction::LdILong(_) => Instruction::LdILong(word(offset + 2)),
+        instruction => instruction,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, symbolize, Instruction, Label, LabelKind, Line};
+    use crate::assembler::assemble;
+
+    #[test]
+    fn test_decode() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x1234, "JP 0x234"),
+            (0x6342, "LD V3, 0x42"),
+            (0x8AB6, "SHR VA, VB"),
+            (0xA2F0, "LD I, 0x2F0"),
+            (0xD125, "DRW V1, V2, 5"),
+            (0xE3A1, "SKNP V3"),
+            (0xF50A, "LD V5, K"),
+            (0xF265, "LD V2, [I]"),
+            (0x00D4, "SCU 4"),
+            (0x5123, "LOAD V1, V2"),
+            (0xF201, "PLANE 2"),
+            (0xF43A, "PITCH V4"),
+            (0xF375, "LD R, V3"),
+            (0xF385, "LD V3, R"),
+            (0x5121, "DB 0x51, 0x21"),
+            (0xE000, "DB 0xE0, 0x00"),
+        ];
+
+        for &(opcode, expected) in &cases {
+            assert_eq!(Instruction::decode(opcode, 0).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_symbolize() {
+        let rom = [
+            0x22, 0x06, // CALL 0x206
+            0x12, 0x00, // JP 0x200
+            0xFF, 0xFF, // Never reached
+            0xA2, 0x0C, // LD I, 0x20C
+            0xD0, 0x12, // DRW V0, V1, 2
+            0x00, 0xEE, // RET
+            0x80, 0xC0, // A sprite
+        ];
+        let listing = symbolize(&rom)
+            .iter()
+            .map(Line::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            listing,
+            vec![
+                "label_0200:",
+                "CALL sub_0206",
+                "JP label_0200",
+                "DB 0xFF, 0xFF",
+                "sub_0206:",
+                "LD I, data_020C",
+                "DRW V0, V1, 2",
+                "RET",
+                "data_020C:",
+                "DB 0x80, 0xC0",
+            ]
+        );
+        assert_eq!(assemble(&listing.join("\n")), Ok(rom.to_vec()));
+    }
+
+    #[test]
+    fn test_symbolize_skips_and_splits() {
+        let rom = [
+            0x30, 0x01, // SE V0, 0x01
+            0xF0, 0x00, 0x02, 0x0A, // LD I, LONG 0x020A, the skip jumps over all 4 bytes
+            0xA2, 0x04, // LD I, 0x204, into the middle of the previous instruction
+            0x00, 0xEE, // RET
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x09, // More than one line of data
+        ];
+        let lines = symbolize(&rom);
+
+        assert_eq!(
+            lines[..3],
+            [
+                Line::Instruction {
+                    address: 0x200,
+                    instruction: Instruction::SeByte(0, 1),
+                    target: None,
+                },
+                Line::Data {
+                    address: 0x202,
+                    bytes: vec![0xF0, 0x00],
+                },
+                Line::Label(Label {
+                    address: 0x204,
+                    kind: LabelKind::Data,
+                }),
+            ]
+        );
+        assert_eq!(lines[4].to_string(), "LD I, data_0204");
+        assert_eq!(lines[5].to_string(), "RET");
+        assert_eq!(lines[6].to_string(), "data_020A:");
+        assert_eq!(lines.len(), 9);
+
+        let source = lines
+            .iter()
+            .map(Line::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(assemble(&source), Ok(rom.to_vec()));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let rom = [0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xFF];
+
+        assert_eq!(
+            disassemble(&rom),
+            vec![
+                (0x200, Instruction::Cls),
+                (0x202, Instruction::LdILong(0x1234)),
+                (0x206, Instruction::Jp(0x200)),
+                (0x208, Instruction::Byte(0xFF)),
+            ]
+        );
+        assert_eq!(
+            disassemble(&[0xF0, 0x00]),
+            vec![(0x200, Instruction::Data(0xF000))]
+        );
+    }
+}
@@ -1,52 +1,205 @@
 use super::memory::Memory;
+use super::palette::Palette;
+use super::pixel_buffer::PixelBuffer;
+use super::variant::Variant;
 use super::Display;
 
 const FRAME_BUFFER_PIXEL_WIDTH: usize = 64;
 const FRAME_BUFFER_PIXEL_HEIGHT: usize = 32;
+const ALL_PLANES: u8 = 0b11;
+
+/// A rectangle of pixels, e.g. the part of the display that changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The smallest rectangle that covers both `self` and `other`.
+    pub fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+/// A pixel that changed, see [`Display::take_dirty_pixels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelChange {
+    pub x: usize,
+    pub y: usize,
+    /// The planes the pixel is set in now, 0 when it's off.
+    pub planes: u8,
+}
+
+/// Shows a [`PixelBuffer`] in the colors of a [`Palette`], keeping track of the part of it that
+/// changed since it was last drawn.
 pub struct FramebufferDisplay {
-    framebuffer: [u8; FRAME_BUFFER_PIXEL_WIDTH * FRAME_BUFFER_PIXEL_HEIGHT],
-    dirty: bool,
+    buffer: PixelBuffer,
+    planes: u8,
+    clip: bool,
+    palette: Palette,
+    /// The bounding box of the pixels changed since the dirty flag was last cleared.
+    dirty_region: Option<Rect>,
+    /// The pixels as of the last [`Display::take_dirty_pixels`], `None` before the first.
+    taken: Option<PixelBuffer>,
+    /// Where the pixels may differ from `taken`. Kept apart from the dirty flag, which the CPU
+    /// clears before every instruction.
+    untaken_region: Option<Rect>,
 }
 
-impl Default for FramebufferDisplay {
-    fn default() -> Self {
+impl FramebufferDisplay {
+    pub fn with_resolution(width: usize, height: usize) -> Self {
         Self {
-            framebuffer: [0; FRAME_BUFFER_PIXEL_WIDTH * FRAME_BUFFER_PIXEL_HEIGHT],
-            dirty: true,
+            buffer: PixelBuffer::new(width, height),
+            planes: 1,
+            clip: false,
+            palette: Palette::default(),
+            dirty_region: Some(Rect::new(0, 0, width, height)),
+            taken: None,
+            untaken_region: None,
         }
     }
+
+    /// The colors pixels are drawn in.
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    /// The pixels without colors.
+    pub fn buffer(&self) -> &PixelBuffer {
+        &self.buffer
+    }
+
+    /// A display with the resolution of `variant`.
+    pub fn for_variant(variant: Variant) -> Self {
+        let (width, height) = variant.resolution();
+
+        Self::with_resolution(width, height)
+    }
+
+    fn mark_dirty(&mut self, region: Rect) {
+        self.dirty_region = Some(match self.dirty_region {
+            Some(dirty_region) => dirty_region.union(region),
+            None => region,
+        });
+        self.untaken_region = Some(match self.untaken_region {
+            Some(untaken_region) => untaken_region.union(region),
+            None => region,
+        });
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.mark_dirty(Rect::new(0, 0, self.buffer.width(), self.buffer.height()));
+    }
+}
+
+impl Default for FramebufferDisplay {
+    fn default() -> Self {
+        Self::with_resolution(FRAME_BUFFER_PIXEL_WIDTH, FRAME_BUFFER_PIXEL_HEIGHT)
+    }
 }
 
 impl Display for FramebufferDisplay {
+    fn width(&self) -> usize {
+        self.buffer.width()
+    }
+
+    fn height(&self) -> usize {
+        self.buffer.height()
+    }
+
     fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty_region.is_some()
     }
 
     fn clear_dirty(&mut self) {
-        self.dirty = false;
+        self.dirty_region = None;
     }
 
-    fn rgba_framebuffer(&self) -> Vec<u32> {
-        self.framebuffer
-            .iter()
-            .map(|&byte| {
-                assert!(
-                    byte == 1 || byte == 0,
-                    "Invalid byte {} in framebuffer",
-                    byte
-                );
-                if byte == 1 {
-                    0x00_FF_FF_FF
-                } else {
-                    0x00_00_00_00
+    fn dirty_region(&self) -> Option<Rect> {
+        self.dirty_region
+    }
+
+    fn write_rgba(&self, pixels: &mut Vec<u32>) {
+        pixels.clear();
+        pixels.extend(self.buffer.as_slice().iter().map(|&byte| {
+            assert!(byte <= ALL_PLANES, "Invalid byte {} in framebuffer", byte);
+            self.palette.color(byte)
+        }));
+    }
+
+    fn take_dirty_pixels(&mut self) -> Vec<PixelChange> {
+        let taken = match &mut self.taken {
+            Some(taken) => taken,
+            None => {
+                self.taken = Some(self.buffer.clone());
+                self.untaken_region = None;
+                return all_pixels(self.buffer.as_slice(), self.buffer.width());
+            }
+        };
+
+        let mut changes = Vec::new();
+        if let Some(region) = self.untaken_region.take() {
+            for y in region.y..region.y + region.height {
+                for x in region.x..region.x + region.width {
+                    let planes = self.buffer.get(x, y);
+                    if planes != taken.get(x, y) {
+                        changes.push(PixelChange { x, y, planes });
+                    }
                 }
-            })
-            .collect()
+            }
+            taken.copy_from_slice(self.buffer.as_slice());
+        }
+
+        changes
     }
 
     fn cls(&mut self) {
-        self.framebuffer = [0; FRAME_BUFFER_PIXEL_WIDTH * FRAME_BUFFER_PIXEL_HEIGHT];
-        self.dirty = true;
+        self.buffer.clear(self.planes);
+        self.mark_all_dirty();
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ALL_PLANES;
+    }
+
+    fn set_clipping(&mut self, clip: bool) {
+        self.clip = clip;
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.mark_all_dirty();
+    }
+
+    fn scroll_up(&mut self, rows: u8) {
+        self.buffer.scroll_up(rows as usize, self.planes);
+        self.mark_all_dirty();
+    }
+
+    fn pixels(&self) -> Option<Vec<u8>> {
+        Some(self.buffer.as_slice().to_vec())
+    }
+
+    fn set_pixels(&mut self, pixels: &[u8]) {
+        self.buffer.copy_from_slice(pixels);
+        self.mark_all_dirty();
     }
 
     fn draw_sprite(
@@ -57,33 +210,331 @@ impl Display for FramebufferDisplay {
         bytes_to_read: u8,
         memory: &Memory,
     ) -> bool {
-        self.dirty = true;
-        let height = bytes_to_read;
-        let sprites = memory.as_slice(base_address, height as u16);
+        let height = bytes_to_read as u16;
+        let planes = self.planes;
 
-        sprites
+        [1, 2]
             .iter()
+            .filter(|&&plane| planes & plane != 0)
             .enumerate()
-            .fold(false, |did_collide, (y_offset, sprite)| {
-                let y_norm = (y + y_offset as u8) % FRAME_BUFFER_PIXEL_HEIGHT as u8;
-                let inner_collide = (0..8_u8).fold(false, |did_collide_inner, x_bit| {
-                    let x_norm = (x + x_bit as u8) % FRAME_BUFFER_PIXEL_WIDTH as u8;
-                    let sprite_pixel = ((sprite << x_bit) & 0x80) >> 7;
-
-                    let buffer_index =
-                        (y_norm as usize * FRAME_BUFFER_PIXEL_WIDTH + x_norm as usize) as usize;
-                    let previous_display_value = self.framebuffer[buffer_index];
-
-                    assert!(sprite_pixel == 0x1 || sprite_pixel == 0);
-                    self.framebuffer[buffer_index] = previous_display_value ^ sprite_pixel;
-                    if sprite_pixel > 0 {
-                        did_collide_inner || previous_display_value == 1
-                    } else {
-                        did_collide_inner
-                    }
-                });
+            .fold(false, |did_collide, (index, &plane)| {
+                let address = base_address.wrapping_add(index as u16 * height);
+                let sprites = memory.read_wrapping(address, height);
+                let (plane_collide, changed) =
+                    self.buffer.draw_sprite(x, y, &sprites, plane, self.clip);
+                if let Some(changed) = changed {
+                    self.mark_dirty(changed);
+                }
+
+                did_collide || plane_collide
+            })
+    }
+}
+
+/// A display that draws nothing, for running the CPU where the output doesn't matter such as
+/// under a fuzzer. Sprites are never reported as colliding.
+#[derive(Debug, Default)]
+pub struct NopDisplay;
+
+impl Display for NopDisplay {
+    fn width(&self) -> usize {
+        FRAME_BUFFER_PIXEL_WIDTH
+    }
+
+    fn height(&self) -> usize {
+        FRAME_BUFFER_PIXEL_HEIGHT
+    }
+
+    fn is_dirty(&self) -> bool {
+        false
+    }
+
+    fn clear_dirty(&mut self) {}
+
+    fn write_rgba(&self, pixels: &mut Vec<u32>) {
+        pixels.clear();
+        pixels.resize(FRAME_BUFFER_PIXEL_WIDTH * FRAME_BUFFER_PIXEL_HEIGHT, 0);
+    }
+
+    fn draw_sprite(
+        &mut self,
+        _x: u8,
+        _y: u8,
+        _base_address: u16,
+        _bytes_to_read: u8,
+        _memory: &Memory,
+    ) -> bool {
+        false
+    }
+
+    fn cls(&mut self) {}
 
-                did_collide || inner_collide
+    fn select_planes(&mut self, _planes: u8) {}
+
+    fn set_clipping(&mut self, _clip: bool) {}
+
+    fn scroll_up(&mut self, _rows: u8) {}
+}
+
+/// Boxed displays, e.g. `Box<dyn Display>` for a [`DynEmulator`](crate::DynEmulator).
+impl<D: Display + ?Sized> Display for Box<D> {
+    fn width(&self) -> usize {
+        (**self).width()
+    }
+
+    fn height(&self) -> usize {
+        (**self).height()
+    }
+
+    fn is_dirty(&self) -> bool {
+        (**self).is_dirty()
+    }
+
+    fn clear_dirty(&mut self) {
+        (**self).clear_dirty()
+    }
+
+    fn dirty_region(&self) -> Option<Rect> {
+        (**self).dirty_region()
+    }
+
+    fn take_dirty_pixels(&mut self) -> Vec<PixelChange> {
+        (**self).take_dirty_pixels()
+    }
+
+    fn write_rgba(&self, pixels: &mut Vec<u32>) {
+        (**self).write_rgba(pixels)
+    }
+
+    fn draw_sprite(
+        &mut self,
+        x: u8,
+        y: u8,
+        base_address: u16,
+        bytes_to_read: u8,
+        memory: &Memory,
+    ) -> bool {
+        (**self).draw_sprite(x, y, base_address, bytes_to_read, memory)
+    }
+
+    fn cls(&mut self) {
+        (**self).cls()
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        (**self).select_planes(planes)
+    }
+
+    fn scroll_up(&mut self, rows: u8) {
+        (**self).scroll_up(rows)
+    }
+
+    fn set_clipping(&mut self, clip: bool) {
+        (**self).set_clipping(clip)
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        (**self).set_palette(palette)
+    }
+
+    fn pixels(&self) -> Option<Vec<u8>> {
+        (**self).pixels()
+    }
+
+    fn set_pixels(&mut self, pixels: &[u8]) {
+        (**self).set_pixels(pixels)
+    }
+
+    fn frame_hash(&self) -> u64 {
+        (**self).frame_hash()
+    }
+}
+
+/// Every pixel of a row major, `width` pixels wide picture as a change.
+pub(crate) fn all_pixels(pixels: &[u8], width: usize) -> Vec<PixelChange> {
+    pixels
+        .iter()
+        .enumerate()
+        .map(|(index, &planes)| PixelChange {
+            x: index % width,
+            y: index / width,
+            planes,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FramebufferDisplay, PixelChange, Rect};
+    use crate::memory::Memory;
+    use crate::{Display, Palette};
+
+    #[test]
+    fn test_dirty_region() {
+        let mut display = FramebufferDisplay::default();
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0x81, 0x00, 0x80]);
+        assert_eq!(display.dirty_region(), Some(Rect::new(0, 0, 64, 32)));
+
+        display.clear_dirty();
+        display.draw_sprite(10, 4, 0x300, 3, &memory);
+        assert_eq!(display.dirty_region(), Some(Rect::new(10, 4, 8, 3)));
+
+        // Wrapping around the edge covers everything in between
+        display.clear_dirty();
+        display.draw_sprite(60, 0, 0x300, 1, &memory);
+        assert_eq!(display.dirty_region(), Some(Rect::new(3, 0, 58, 1)));
+
+        display.clear_dirty();
+        display.draw_sprite(0, 0, 0x301, 1, &memory);
+        assert!(!display.is_dirty());
+
+        display.cls();
+        assert_eq!(display.dirty_region(), Some(Rect::new(0, 0, 64, 32)));
+    }
+
+    #[test]
+    fn test_take_dirty_pixels() {
+        let mut display = FramebufferDisplay::with_resolution(8, 2);
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0xC0, 0x40]);
+        display.draw_sprite(0, 0, 0x300, 1, &memory);
+
+        // Everything at first, then only what changed even though the dirty flag was cleared
+        assert_eq!(display.take_dirty_pixels().len(), 16);
+        assert_eq!(display.take_dirty_pixels(), vec![]);
+        display.draw_sprite(0, 0, 0x301, 1, &memory);
+        display.clear_dirty();
+        display.draw_sprite(0, 1, 0x301, 1, &memory);
+        assert_eq!(
+            display.take_dirty_pixels(),
+            vec![
+                PixelChange {
+                    x: 1,
+                    y: 0,
+                    planes: 0
+                },
+                PixelChange {
+                    x: 1,
+                    y: 1,
+                    planes: 1
+                },
+            ]
+        );
+
+        // Drawing the same sprite twice changes nothing in the end
+        display.draw_sprite(4, 0, 0x300, 1, &memory);
+        display.draw_sprite(4, 0, 0x300, 1, &memory);
+        display.set_palette(Palette::AMBER);
+        assert_eq!(display.take_dirty_pixels(), vec![]);
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut display = FramebufferDisplay::with_resolution(8, 1);
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0x80]);
+        display.draw_sprite(0, 0, 0x300, 1, &memory);
+        assert_eq!(display.rgba_framebuffer()[0..2], [0xFF_FF_FF, 0x00_00_00]);
+
+        display.clear_dirty();
+        display.set_palette(Palette::AMBER);
+        assert!(display.is_dirty());
+        assert_eq!(
+            display.rgba_framebuffer()[0..2],
+            [Palette::AMBER.foreground(), Palette::AMBER.background()]
+        );
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut display = FramebufferDisplay::with_resolution(8, 1);
+        let mut memory = Memory::default();
+        // A sprite per plane, one after the other
+        memory.copy_from_slice(0x300, &[0xC0, 0xA0]);
+
+        display.select_planes(0b11);
+        assert!(!display.draw_sprite(0, 0, 0x300, 1, &memory));
+        assert_eq!(
+            display.rgba_framebuffer()[0..4],
+            [
+                Palette::MONO.color(0b11),
+                Palette::MONO.color(0b01),
+                Palette::MONO.color(0b10),
+                Palette::MONO.color(0b00),
+            ]
+        );
+
+        // Only the selected plane is drawn to and collides
+        display.select_planes(0b10);
+        assert!(!display.draw_sprite(1, 0, 0x301, 1, &memory));
+        assert_eq!(display.buffer().as_slice()[0..4], [0b11, 0b11, 0b10, 0b10]);
+        assert!(display.draw_sprite(0, 0, 0x300, 1, &memory));
+        assert_eq!(display.buffer().as_slice()[0..4], [0b01, 0b01, 0b10, 0b10]);
+
+        display.select_planes(0b11);
+        display.cls();
+        assert!(display.buffer().as_slice().iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn test_frame_hash() {
+        let mut display = FramebufferDisplay::default();
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0x80]);
+        let blank = display.frame_hash();
+        assert_eq!(blank, FramebufferDisplay::default().frame_hash());
+
+        display.draw_sprite(0, 0, 0x300, 1, &memory);
+        let drawn = display.frame_hash();
+        assert_ne!(drawn, blank);
+
+        display.set_palette(Palette::AMBER);
+        assert_eq!(display.frame_hash(), drawn);
+        assert_ne!(
+            FramebufferDisplay::with_resolution(128, 64).frame_hash(),
+            blank
+        );
+    }
+
+    /// The display as rows of `#` for lit pixels and `.` for the rest.
+    fn rows(display: &FramebufferDisplay) -> Vec<String> {
+        display
+            .rgba_framebuffer()
+            .chunks(display.width())
+            .map(|row| {
+                row.iter()
+                    .map(|&pixel| if pixel == 0 { '.' } else { '#' })
+                    .collect()
             })
+            .collect()
+    }
+
+    fn draw_at_edge(clip: bool, x: u8, y: u8) -> Vec<String> {
+        let mut display = FramebufferDisplay::with_resolution(8, 4);
+        display.set_clipping(clip);
+        let mut memory = Memory::default();
+        memory.copy_from_slice(0x300, &[0xF0, 0xF0]);
+        display.draw_sprite(x, y, 0x300, 2, &memory);
+
+        rows(&display)
+    }
+
+    #[test]
+    fn test_wrapping() {
+        assert_eq!(
+            draw_at_edge(false, 6, 3),
+            vec!["##....##", "........", "........", "##....##"]
+        );
+    }
+
+    #[test]
+    fn test_clipping() {
+        assert_eq!(
+            draw_at_edge(true, 6, 3),
+            vec!["........", "........", "........", "......##"]
+        );
+        // The position itself still wraps around, only the parts past the edge are clipped
+        assert_eq!(draw_at_edge(true, 14, 7), draw_at_edge(true, 6, 3));
     }
 }
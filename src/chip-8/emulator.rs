@@ -1,52 +1,2135 @@
-use crate::cpu::CPU;
-use crate::memory::Memory;
-use crate::{Display, Input};
+use std::fmt;
+use std::io;
+use std::ops::RangeBounds;
+use std::time::Duration;
 
-pub struct Emulator {
-    cpu: CPU,
+use crate::breakpoint::Hit;
+use crate::cpu::{CpuState, CycleStatus, CPU, FLAG_COUNT};
+use crate::error::{Error, RomError};
+use crate::hash;
+use crate::memory::{AccessPolicy, Memory};
+use crate::poke::{Poke, PokeError};
+use crate::profiler::{ProfileReport, Profiler};
+use crate::rng::{Rng, Seeded};
+use crate::rom_db::{self, RomDatabase, RomInfo};
+use crate::storage::Storage;
+use crate::trace::{Trace, TraceEvent};
+use crate::undo::{History, Step};
+use crate::watchpoint::Accesses;
+use crate::{
+    AudioBuffer, Display, EmulatorObserver, Input, NopSound, Opcode, Palette, PixelChange, Profile,
+    Quirks, Rect, Screenshot, Sound, Variant,
+};
+
+/// The instructions run per 60Hz frame for ROMs nothing is known about, about as fast as the
+/// COSMAC VIP interpreter.
+pub const DEFAULT_CYCLES_PER_FRAME: u32 = 11;
+/// The rate of the delay and sound timers, and of [`Emulator::run_frame`].
+const TIMER_TICKS_PER_SECOND: u32 = 60;
+/// How many frames the display has to stay the same for [`Emulator::run_until_stable`].
+const STABLE_FRAMES: u32 = 60;
+
+/// What happened during [`Emulator::run_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameResult {
+    /// The number of instructions executed, fewer than planned if the emulator was paused.
+    pub cycles: u32,
+    /// Whether any instruction changed the display.
+    pub is_dirty: bool,
+    /// The part of the display that changed during the frame.
+    pub dirty_region: Option<Rect>,
+    /// Whether the tone should be playing at the end of the frame.
+    pub is_sound_active: bool,
+}
+
+impl FrameResult {
+    pub(crate) fn new() -> Self {
+        Self {
+            cycles: 0,
+            is_dirty: false,
+            dirty_region: None,
+            is_sound_active: false,
+        }
+    }
+
+    /// Count an executed instruction and what it changed on `display`.
+    pub(crate) fn add_cycle<D: Display + ?Sized>(&mut self, display: &D) {
+        self.cycles += 1;
+        self.is_dirty |= display.is_dirty();
+        self.dirty_region = match (self.dirty_region, display.dirty_region()) {
+            (Some(frame), Some(cycle)) => Some(frame.union(cycle)),
+            (frame, cycle) => frame.or(cycle),
+        };
+    }
+
+    /// Add `later`, a frame run right after this one.
+    pub(crate) fn merge(&mut self, later: FrameResult) {
+        self.cycles += later.cycles;
+        self.is_dirty |= later.is_dirty;
+        self.dirty_region = match (self.dirty_region, later.dirty_region) {
+            (Some(earlier), Some(later)) => Some(earlier.union(later)),
+            (earlier, later) => earlier.or(later),
+        };
+        self.is_sound_active = later.is_sound_active;
+    }
+}
+
+/// Counters of what the emulator has done since power on or the last reset, see
+/// [`Emulator::stats`]. Rates come from comparing two snapshots taken some time apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Instructions executed, the same as [`Emulator::cycle_count`].
+    pub cycles: u64,
+    /// Frames run with [`Emulator::run_frame`] or the debugger.
+    pub frames: u64,
+    /// Times the delay and sound timers were counted down.
+    pub timer_ticks: u64,
+}
+
+impl Stats {
+    /// Instructions per second between `earlier` and these stats, `elapsed` apart.
+    pub fn ips_since(&self, earlier: &Stats, elapsed: Duration) -> u32 {
+        per_second(self.cycles.saturating_sub(earlier.cycles), elapsed)
+    }
+
+    /// Frames per second between `earlier` and these stats, `elapsed` apart.
+    pub fn fps_since(&self, earlier: &Stats, elapsed: Duration) -> u32 {
+        per_second(self.frames.saturating_sub(earlier.frames), elapsed)
+    }
+}
+
+fn per_second(count: u64, elapsed: Duration) -> u32 {
+    if elapsed.as_nanos() == 0 {
+        return 0;
+    }
+
+    (count as f64 / elapsed.as_secs_f64()).round() as u32
+}
+
+/// The emulator, generic over its display so that drawing is statically dispatched. See
+/// [`DynEmulator`] for choosing the display at runtime instead.
+pub struct Emulator<D> {
+    cpu: CPU<D>,
     current_rom: Vec<u8>,
+    /// What the ROM database knows about `current_rom`.
+    rom_info: Option<RomInfo>,
     is_initial_state: bool,
+    is_paused: bool,
+    /// Instructions executed since power on.
+    cycle_count: u64,
+    frame_count: u64,
+    timer_ticks: u64,
+    /// What the last cycle did.
+    cycle_status: CycleStatus,
+    trace: Option<Trace>,
+    /// Counts the executed instructions while profiling.
+    profiler: Option<Profiler>,
+    /// The instructions [`Emulator::step_back`] can undo, when turned on.
+    history: Option<History>,
+    observer: Option<Box<dyn EmulatorObserver>>,
+    /// Where the flags saved by FX75 persist, if anywhere.
+    flag_storage: Option<Box<dyn Storage>>,
+    sound: Box<dyn Sound>,
+    is_beeping: bool,
+    /// The audio buffer `sound` was last given.
+    audio_buffer: Option<AudioBuffer>,
+    /// Instructions run in a burst every 60Hz frame.
+    cycles_per_frame: u32,
+    /// How many emulated frames each call to [`Emulator::run_frame`] runs.
+    speed_multiplier: f32,
+    /// Multipliers that aren't whole numbers leave part of a frame for the next call.
+    frame_remainder: f32,
 }
 
-impl Emulator {
-    pub fn new(display: Box<dyn Display>, rom: Vec<u8>) -> Self {
-        let mut memory = Memory::default();
+/// An [`Emulator`] with a display chosen at runtime.
+pub type DynEmulator = Emulator<Box<dyn Display>>;
+
+impl<D: Display> Emulator<D> {
+    /// A CHIP-8 emulator, fails if `rom` doesn't fit in memory. An empty `rom` is allowed,
+    /// see [`Variant::validate_rom`] to reject it too.
+    pub fn new(display: D, rom: Vec<u8>, quirks: Quirks) -> Result<Self, RomError> {
+        Self::with_variant(display, rom, Variant::Chip8, quirks)
+    }
+
+    /// An emulator for `variant` with `quirks`, `display` is expected to have the variant's
+    /// resolution.
+    ///
+    /// ROMs in the [ROM database](crate::rom_db) get the speed recorded for them, see
+    /// [`Emulator::for_rom`] to also use their quirks and [`Emulator::set_cycles_per_frame`] to
+    /// override the speed. Fails if `rom` doesn't fit in memory.
+    pub fn with_variant(
+        display: D,
+        rom: Vec<u8>,
+        variant: Variant,
+        quirks: Quirks,
+    ) -> Result<Self, RomError> {
+        let rom_info = RomDatabase::embedded().lookup(&rom).cloned();
+        Self::with_rom_info(display, rom, variant, Some(quirks), rom_info)
+    }
+
+    /// An emulator for `variant` with the quirks and speed the [ROM database](crate::rom_db)
+    /// records for `rom`, and the defaults for anything it doesn't. `display` is expected to
+    /// have the variant's resolution. Fails if `rom` doesn't fit in memory.
+    pub fn for_rom(display: D, rom: Vec<u8>, variant: Variant) -> Result<Self, RomError> {
+        let rom_info = RomDatabase::embedded().lookup(&rom).cloned();
+        Self::with_rom_info(display, rom, variant, None, rom_info)
+    }
+
+    /// `quirks` wins over the quirks in `rom_info`.
+    fn with_rom_info(
+        display: D,
+        rom: Vec<u8>,
+        variant: Variant,
+        quirks: Option<Quirks>,
+        rom_info: Option<RomInfo>,
+    ) -> Result<Self, RomError> {
+        check_rom_size(&rom, variant)?;
+        let quirks = quirks
+            .or_else(|| rom_info.as_ref().and_then(|info| info.quirks))
+            .unwrap_or_default();
+        let cycles_per_frame = rom_info
+            .as_ref()
+            .and_then(|info| info.cycles_per_second)
+            .map(cycles_per_frame_at)
+            .unwrap_or_else(|| default_cycles_per_frame(variant));
+
+        let mut memory = Memory::with_size(variant.memory_size());
         memory.copy_from_slice(0x200, &rom);
-        let cpu = CPU::new(memory, display);
+        let cpu = CPU::new(memory, display, variant, quirks);
 
-        Self {
+        Ok(Self {
             cpu,
             current_rom: rom,
+            rom_info,
             is_initial_state: true,
+            is_paused: false,
+            cycle_count: 0,
+            frame_count: 0,
+            timer_ticks: 0,
+            cycle_status: CycleStatus::Executed,
+            trace: None,
+            profiler: None,
+            history: None,
+            observer: None,
+            flag_storage: None,
+            sound: Box::new(NopSound),
+            is_beeping: false,
+            audio_buffer: None,
+            cycles_per_frame,
+            speed_multiplier: 1.0,
+            frame_remainder: 0.0,
+        })
+    }
+
+    /// An emulator with the variant, quirks, and speed of `profile`, `display` is expected to
+    /// have the profile's resolution. ROMs in the [ROM database](crate::rom_db) still get the
+    /// quirks and speed recorded for them. Fails if `rom` doesn't fit in memory.
+    pub fn with_profile(display: D, rom: Vec<u8>, profile: Profile) -> Result<Self, RomError> {
+        let mut emulator = Self::for_rom(display, rom, profile.variant())?;
+        let info = emulator.rom_info.as_ref();
+        let (has_quirks, has_speed) = (
+            info.and_then(|info| info.quirks).is_some(),
+            info.and_then(|info| info.cycles_per_second).is_some(),
+        );
+        if !has_quirks {
+            emulator.set_quirks(profile.quirks());
+        }
+        if !has_speed {
+            emulator.set_cycles_per_frame(profile.instructions_per_frame());
         }
+
+        Ok(emulator)
     }
 
     pub fn is_initial_state(&self) -> bool {
         self.is_initial_state
     }
 
-    pub fn reset(self) -> Self {
-        let mut memory = Memory::default();
+    /// The number of instructions executed since power on or the last reset, including one
+    /// that failed.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// What the emulator has done since power on or the last reset.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            cycles: self.cycle_count,
+            frames: self.frame_count,
+            timer_ticks: self.timer_ticks,
+        }
+    }
+
+    /// Whether the last instruction ran or is waiting, see [`CycleStatus`].
+    pub fn cycle_status(&self) -> CycleStatus {
+        self.cycle_status
+    }
+
+    pub fn reset(mut self) -> Self {
+        self.set_beeping(false);
+        self.set_audio_buffer(None);
+
+        let variant = self.cpu.variant();
+        let mut memory = Memory::with_size(variant.memory_size());
         memory.copy_from_slice(0x200, &self.current_rom);
-        let mut cpu = CPU::new(memory, self.cpu.display);
-        cpu.display.cls();
+        memory.set_access_policy(self.access_policy());
+        let quirks = self.cpu.quirks();
+        let flags = self.cpu.flags();
+        let mut cpu = CPU::new(memory, self.cpu.display, variant, quirks);
+        cpu.reset_display();
+        cpu.rng = self.cpu.rng;
+        cpu.set_flags(flags);
 
         Self {
             cpu,
             current_rom: self.current_rom,
+            rom_info: self.rom_info,
             is_initial_state: true,
+            is_paused: false,
+            cycle_count: 0,
+            frame_count: 0,
+            timer_ticks: 0,
+            cycle_status: CycleStatus::Executed,
+            trace: self.trace,
+            profiler: self.profiler,
+            history: self.history.map(|history| History::new(history.limit())),
+            observer: self.observer,
+            flag_storage: self.flag_storage,
+            sound: self.sound,
+            is_beeping: false,
+            audio_buffer: None,
+            cycles_per_frame: self.cycles_per_frame,
+            speed_multiplier: self.speed_multiplier,
+            frame_remainder: 0.0,
         }
     }
 
-    pub fn cycle(&mut self, should_tick_timer: bool, input: &dyn Input) {
+    /// Replace the ROM with `rom` and start it from the power on state, e.g. to switch games
+    /// without a new emulator. The display, sound, observer, and flag storage are kept. ROMs
+    /// in the [ROM database](crate::rom_db) get the quirks and speed recorded for them, others
+    /// keep the current ones, and the random number provider is kept. Fails, changing
+    /// nothing, if `rom` doesn't fit in memory.
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), RomError> {
+        check_rom_size(&rom, self.cpu.variant())?;
+        let mut memory = Memory::with_size(self.memory_size());
+        memory.copy_from_slice(0x200, &rom);
+
+        self.rom_info = RomDatabase::embedded().lookup(&rom).cloned();
+        if let Some(info) = self.rom_info.clone() {
+            if let Some(quirks) = info.quirks {
+                self.set_quirks(quirks);
+            }
+            if let Some(hz) = info.cycles_per_second {
+                self.set_cycles_per_second(hz);
+            }
+        }
+        self.current_rom = rom;
+        self.restore(&memory);
+
+        // The flags belong to the ROM
+        let saved = match self.flag_storage.as_ref() {
+            Some(storage) => storage.load(&self.flags_key()),
+            None => Ok(None),
+        };
+        match saved {
+            Ok(saved) => self.set_saved_flags(saved),
+            Err(error) => {
+                self.set_saved_flags(None);
+                self.notify(|observer| observer.on_storage_error(&error));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reset in place to the power on state with `memory` loaded rather than the ROM, e.g. a
+    /// template shared between many emulators.
+    pub(crate) fn restore(&mut self, memory: &Memory) {
+        self.cpu.restore(memory);
+        self.set_beeping(false);
+        self.set_audio_buffer(None);
+        self.is_initial_state = true;
+        self.is_paused = false;
+        self.cycle_count = 0;
+        self.frame_count = 0;
+        self.timer_ticks = 0;
+        self.cycle_status = CycleStatus::Executed;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+    }
+
+    /// The current contents of memory.
+    pub(crate) fn memory(&self) -> &Memory {
+        self.cpu.memory()
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.cpu.variant()
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.cpu.quirks()
+    }
+
+    /// Change the quirks, they are kept across resets.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    /// What the [ROM database](crate::rom_db) knows about the ROM, if it's in there.
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        self.rom_info.as_ref()
+    }
+
+    /// The XO-CHIP audio pattern loaded by F002 and the pitch set by FX3A, played back while
+    /// the sound timer is active. `None` until a pattern is loaded.
+    pub fn audio_buffer(&self) -> Option<AudioBuffer> {
+        self.cpu.audio_buffer()
+    }
+
+    /// Whether the sound timer is running and the tone should be playing.
+    pub fn is_sound_active(&self) -> bool {
+        self.cpu.is_sound_active()
+    }
+
+    /// The 60ths of a second the tone has left to play.
+    pub fn sound_timer(&self) -> u8 {
+        self.cpu.sound_timer()
+    }
+
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// Set how many instructions [`Emulator::run_frame`] runs in a burst before the next timer
+    /// tick, different ROMs were written for very different speeds. This is how the original
+    /// interpreters, and emulators like Octo, schedule instructions. The speed is kept across
+    /// resets. Panics if `cycles` is 0.
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        assert!(cycles > 0, "At least one instruction has to run per frame");
+        self.cycles_per_frame = cycles;
+    }
+
+    /// The speed in instructions per second at 60 frames per second, `u32::MAX` for speeds
+    /// beyond it.
+    pub fn cycles_per_second(&self) -> u32 {
+        self.cycles_per_frame.saturating_mul(TIMER_TICKS_PER_SECOND)
+    }
+
+    /// Set the speed in instructions per second, rounded to the nearest whole number of
+    /// instructions per frame. See [`Emulator::set_cycles_per_frame`]. Panics if `hz` is 0.
+    pub fn set_cycles_per_second(&mut self, hz: u32) {
+        assert!(hz > 0, "The speed must be at least 1Hz");
+        self.set_cycles_per_frame(cycles_per_frame_at(hz));
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    /// Run `multiplier` frames, with their instructions and timer ticks, every time
+    /// [`Emulator::run_frame`] is called, e.g. 4.0 to fast forward through a slow title screen
+    /// at 4x while the frontend still draws 60 times per second. The sound is muted while
+    /// faster than 1x. The multiplier is kept across resets. Panics unless `multiplier` is
+    /// positive.
+    pub fn set_speed_multiplier(&mut self, multiplier: f32) {
+        assert!(
+            multiplier.is_finite() && multiplier > 0.0,
+            "The speed multiplier must be positive"
+        );
+        self.speed_multiplier = multiplier;
+        self.frame_remainder = 0.0;
+        self.set_beeping(self.cpu.is_sound_active());
+    }
+
+    fn is_fast_forwarding(&self) -> bool {
+        self.speed_multiplier > 1.0
+    }
+
+    /// How many frames the next call to [`Emulator::run_frame`] runs at the speed multiplier.
+    pub(crate) fn take_due_frames(&mut self) -> u32 {
+        self.frame_remainder += self.speed_multiplier;
+        let frames = self.frame_remainder.floor();
+        self.frame_remainder -= frames;
+
+        frames as u32
+    }
+
+    /// How many cycles the next 60Hz frame should run.
+    pub(crate) fn next_frame_cycles(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// How many cycles to run between timer ticks when there's no wall clock to go by, e.g.
+    /// when running headlessly as fast as possible.
+    pub(crate) fn cycles_per_timer_tick(&self) -> usize {
+        self.cycles_per_frame as usize
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// Pause execution. While paused [`Emulator::cycle`] and [`Emulator::tick_timers`] do
+    /// nothing, neither the CPU nor the timers advance.
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+        self.set_beeping(false);
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc()
+    }
+
+    /// Continue execution at `address`, an error if it's outside of memory.
+    pub fn set_pc(&mut self, address: u16) -> Result<(), Error> {
+        if address as usize >= self.memory_size() {
+            return Err(Error::MemoryOutOfBounds {
+                address: address as usize,
+            });
+        }
+
+        self.cpu.set_pc(address);
+
+        Ok(())
+    }
+
+    /// The value of register `VX`, panics if `register` is above 0xF.
+    pub fn register(&self, register: u8) -> u8 {
+        self.cpu.register(register)
+    }
+
+    pub fn set_register(&mut self, register: u8, value: u8) {
+        self.cpu.set_register(register, value);
+    }
+
+    pub fn i(&self) -> u16 {
+        self.cpu.i()
+    }
+
+    pub fn set_i(&mut self, value: u16) {
+        self.cpu.set_i(value);
+    }
+
+    /// Read a byte of memory, panics if `address` is outside of memory.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.cpu.memory()[address]
+    }
+
+    /// `length` bytes of memory starting at `address`, an error if they extend past the end of
+    /// memory.
+    pub fn peek_slice(&self, address: u16, length: u16) -> Result<&[u8], Error> {
+        self.cpu.memory().read_slice(address, length)
+    }
+
+    /// The bytes of memory in `range`, e.g. `..` for all of it. Panics if `range` extends past
+    /// the end of memory.
+    pub fn dump_memory(&self, range: impl RangeBounds<usize>) -> &[u8] {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+
+        &self.cpu.memory().bytes()[range]
+    }
+
+    /// The number of addressable bytes, 4KiB or 64KiB for XO-CHIP.
+    pub fn memory_size(&self) -> usize {
+        self.cpu.memory().size()
+    }
+
+    pub fn access_policy(&self) -> AccessPolicy {
+        self.cpu.memory().access_policy()
+    }
+
+    /// Change which addresses instructions may write to, writes below 0x200 are an error by
+    /// default. Relax it to run ROMs that deliberately change the font.
+    pub fn set_access_policy(&mut self, policy: AccessPolicy) {
+        self.cpu.memory_mut().set_access_policy(policy);
+    }
+
+    /// A snapshot of the registers, stack, and timers.
+    pub fn state(&self) -> CpuState {
+        self.cpu.state()
+    }
+
+    /// A hash of everything that decides what happens next: the registers, stack, timers,
+    /// memory, flags, display, and the number of cycles and frames run. Two runs of the same ROM
+    /// with the same seed and input end with the same hash, see [`Emulator::run_frames`].
+    pub fn state_hash(&self) -> u64 {
+        let state = self.state();
+        let mut bytes = Vec::with_capacity(self.memory_size() + 64);
+        bytes.extend_from_slice(&state.v);
+        bytes.extend_from_slice(&state.i.to_le_bytes());
+        bytes.extend_from_slice(&state.pc.to_le_bytes());
+        for address in &state.stack {
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+        bytes.push(state.delay_timer);
+        bytes.push(state.sound_timer);
+        bytes.extend_from_slice(&self.flags());
+        bytes.extend_from_slice(&self.cycle_count.to_le_bytes());
+        bytes.extend_from_slice(&self.frame_count.to_le_bytes());
+        bytes.extend_from_slice(&self.display().frame_hash().to_le_bytes());
+        bytes.extend_from_slice(self.dump_memory(..));
+
+        hash::fnv1a(bytes)
+    }
+
+    /// Write bytes straight into memory, e.g. to test a single instruction or hot-patch the ROM.
+    pub fn poke(&mut self, poke: &Poke) -> Result<(), PokeError> {
+        let memory = self.cpu.memory_mut();
+        if poke.address as usize + poke.bytes.len() > memory.size() {
+            return Err(PokeError::OutOfBounds {
+                address: poke.address,
+                length: poke.bytes.len(),
+            });
+        }
+
+        memory.copy_from_slice(poke.address, &poke.bytes);
+
+        Ok(())
+    }
+
+    /// Use a sequence of random numbers for CXNN that only depends on `seed`, making runs
+    /// reproducible, see [`Seeded`].
+    pub fn with_rng_seed(mut self, seed: u32) -> Self {
+        self.set_rng(Box::new(Seeded::new(seed)));
+
+        self
+    }
+
+    /// Replace the source of random numbers used by CXNN, e.g. with a seeded sequence to make
+    /// runs reproducible. The source is kept across resets.
+    pub fn set_rng(&mut self, rng: Box<dyn Rng>) {
+        self.cpu.rng = rng;
+    }
+
+    /// Play the tone on `sound` while the sound timer is active. The output is kept across
+    /// resets.
+    pub fn set_sound(&mut self, sound: Box<dyn Sound>) {
+        self.set_beeping(false);
+        self.sound = sound;
+        self.sound.set_audio_buffer(self.audio_buffer);
+    }
+
+    /// Call `trace` after every executed instruction with what it changed, e.g. to log a run
+    /// and compare it with other emulators. The hook is kept across resets.
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    pub fn clear_trace(&mut self) {
+        self.trace = None;
+    }
+
+    /// Count how often every instruction runs from now on, see
+    /// [`Emulator::profile_report`]. Turning profiling off discards the counts.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        match (enabled, self.profiler.is_some()) {
+            (true, false) => self.profiler = Some(Profiler::new()),
+            (false, true) => self.profiler = None,
+            _ => {}
+        }
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.is_some()
+    }
+
+    /// The instructions executed since profiling was turned on, the most executed first, or
+    /// `None` when not profiling. The counts carry over resets.
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.profiler.as_ref().map(Profiler::report)
+    }
+
+    /// Keep what's needed to undo the last `limit` instructions with
+    /// [`Emulator::step_back`], or stop keeping it with a `limit` of 0. The history is
+    /// emptied by changing the limit and by resets.
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.history = if limit == 0 {
+            None
+        } else {
+            Some(History::new(limit))
+        };
+    }
+
+    /// How many instructions [`Emulator::step_back`] can undo.
+    pub fn undoable_cycles(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+
+    /// Undo the last executed instruction, see [`Emulator::set_undo_limit`]. Returns false
+    /// when there's nothing to undo. Registers, timers, memory, and the display go back to
+    /// how they were before the instruction, flags saved to storage by FX75 stay saved.
+    pub fn step_back(&mut self) -> bool {
+        let step = match self.history.as_mut().and_then(History::pop) {
+            Some(step) => step,
+            None => return false,
+        };
+
+        self.cpu.undo(step.undo);
+        self.cycle_count = self.cycle_count.saturating_sub(1);
+        self.cycle_status = step.cycle_status;
+        self.set_audio_buffer(self.cpu.audio_buffer());
+        self.set_beeping(self.cpu.is_sound_active());
+
+        true
+    }
+
+    /// The flags saved by FX75 and loaded by FX85.
+    pub fn flags(&self) -> [u8; FLAG_COUNT] {
+        self.cpu.flags()
+    }
+
+    /// Persist the flags saved by FX75 in `storage` so that games keep e.g. high scores across
+    /// runs, and load the ones saved in an earlier run. The flags are stored per ROM under its
+    /// [ROM id](crate::rom_db::rom_id). Saving failures are reported to the observer, see
+    /// [`EmulatorObserver::on_storage_error`].
+    pub fn set_flag_storage(&mut self, storage: Box<dyn Storage>) -> io::Result<()> {
+        if let Some(saved) = storage.load(&self.flags_key())? {
+            self.set_saved_flags(Some(saved));
+        }
+        self.flag_storage = Some(storage);
+
+        Ok(())
+    }
+
+    /// Set the flags to `saved`, or clear them when nothing was saved.
+    fn set_saved_flags(&mut self, saved: Option<Vec<u8>>) {
+        let mut flags = [0; FLAG_COUNT];
+        if let Some(saved) = saved {
+            let count = saved.len().min(FLAG_COUNT);
+            flags[..count].copy_from_slice(&saved[..count]);
+        }
+        self.cpu.set_flags(flags);
+    }
+
+    fn flags_key(&self) -> String {
+        format!("{}.flags", rom_db::rom_id(&self.current_rom))
+    }
+
+    fn save_flags(&mut self) {
+        let key = self.flags_key();
+        let flags = self.cpu.flags();
+        let result = match self.flag_storage.as_mut() {
+            Some(storage) => storage.store(&key, &flags),
+            None => return,
+        };
+
+        if let Err(error) = result {
+            self.notify(|observer| observer.on_storage_error(&error));
+        }
+    }
+
+    /// Notify `observer` of sound, screen clears, unknown opcodes, and breakpoints from now on,
+    /// replacing any previous observer. The observer is kept across resets.
+    pub fn set_observer(&mut self, observer: Box<dyn EmulatorObserver>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// Tell the observer about a breakpoint hit, for the [`Debugger`](crate::debugger::Debugger).
+    pub(crate) fn notify_breakpoint(&mut self, hit: &Hit) {
+        self.notify(|observer| observer.on_breakpoint(hit));
+    }
+
+    fn notify(&mut self, event: impl FnOnce(&mut dyn EmulatorObserver)) {
+        if let Some(observer) = self.observer.as_mut() {
+            event(observer.as_mut());
+        }
+    }
+
+    /// The instruction at the program counter.
+    pub fn current_instruction(&self) -> Result<Opcode, Error> {
+        let memory = self.cpu.memory();
+        let pc = self.cpu.pc();
+        let opcode = (memory.read(pc)? as u16) << 8 | memory.read(pc.wrapping_add(1))? as u16;
+        let next = memory.read_slice(pc.wrapping_add(2), 2).unwrap_or(&[0, 0]);
+
+        Ok(Opcode::decode(
+            opcode,
+            (next[0] as u16) << 8 | next[1] as u16,
+        ))
+    }
+
+    /// The registers and memory `instruction` would access if it was executed next, see
+    /// [`watchpoint`](crate::watchpoint).
+    pub fn accesses(&self, instruction: Opcode) -> Accesses {
+        self.cpu.accesses(instruction)
+    }
+
+    /// Execute `opcode` as the next instruction by writing it at the program counter and
+    /// cycling once, without ticking the timers.
+    pub fn execute(&mut self, opcode: u16, input: &dyn Input) -> Result<(), Error> {
+        let pc = self.cpu.pc();
+        self.cpu
+            .memory_mut()
+            .write_slice(pc, &opcode.to_be_bytes())?;
+
+        self.cycle(input)
+    }
+
+    /// Count the delay and sound timers down by one. Call this at 60Hz by the wall clock,
+    /// independently of how many instructions run in between.
+    pub fn tick_timers(&mut self) {
+        if self.is_paused {
+            return;
+        }
+
+        self.timer_ticks += 1;
+        self.cpu.tick_timers();
+        self.set_beeping(self.cpu.is_sound_active());
+    }
+
+    /// Run one 60Hz frame: tick the timers once, then execute as many instructions as the
+    /// configured speed calls for. Frontends call this 60 times per second. Stops early when
+    /// an instruction fails or the emulator is paused. With a
+    /// [speed multiplier](Emulator::set_speed_multiplier) this runs that many frames instead,
+    /// and the result covers all of them.
+    pub fn run_frame(&mut self, input: &dyn Input) -> Result<FrameResult, Error> {
+        // Paused frames don't count towards the speed
+        if self.is_paused {
+            return Ok(FrameResult::new());
+        }
+
+        let mut result = FrameResult::new();
+        result.is_sound_active = self.is_sound_active();
+        for _ in 0..self.take_due_frames() {
+            if self.is_paused {
+                break;
+            }
+
+            self.start_frame();
+            let frame = self.run_cycles(self.next_frame_cycles(), input)?;
+            result.merge(frame);
+        }
+
+        Ok(result)
+    }
+
+    /// Run exactly `frames` 60Hz frames like [`Emulator::run_frame`], regardless of the
+    /// [speed multiplier](Emulator::set_speed_multiplier), for golden tests and tool-assisted
+    /// runs. `input` is [advanced](Input::advance) to the cycle count before every frame.
+    ///
+    /// Nothing in the emulator reads the clock, so with a [seed](Emulator::with_rng_seed) and
+    /// an input that only depends on the cycle count, e.g. an
+    /// [`InputReplayer`](crate::replay::InputReplayer), every run ends in the same
+    /// [state](Emulator::state_hash). Stops early when an instruction fails or the emulator is
+    /// paused.
+    pub fn run_frames(
+        &mut self,
+        frames: u32,
+        input: &mut impl Input,
+    ) -> Result<FrameResult, Error> {
+        let mut result = FrameResult::new();
+        result.is_sound_active = self.is_sound_active();
+        for _ in 0..frames {
+            if self.is_paused {
+                break;
+            }
+
+            input.advance(self.cycle_count);
+            self.start_frame();
+            let frame = self.run_cycles(self.next_frame_cycles(), input)?;
+            result.merge(frame);
+        }
+
+        Ok(result)
+    }
+
+    /// Count a frame and tick the timers for it.
+    pub(crate) fn start_frame(&mut self) {
+        self.frame_count += 1;
+        self.tick_timers();
+    }
+
+    /// Execute up to `cycles` instructions without ticking the timers, e.g. many frames worth
+    /// at once for fast forwarding. Stops early like [`Emulator::run_frame`], and when waiting
+    /// for the next tick with the display wait quirk.
+    ///
+    /// Unless tracing, profiling, or keeping an undo history, this skips the per instruction
+    /// bookkeeping of [`Emulator::cycle`]: the display is only asked what changed after
+    /// instructions that draw, and the sound only changes once all instructions have run.
+    pub fn run_cycles(&mut self, cycles: u32, input: &dyn Input) -> Result<FrameResult, Error> {
+        let mut frame = FrameResult::new();
+        if self.trace.is_some() || self.profiler.is_some() || self.history.is_some() {
+            for _ in 0..cycles {
+                if self.is_paused {
+                    break;
+                }
+
+                self.cycle(input)?;
+                frame.add_cycle(self.display());
+                // Nothing happens until the next frame
+                if self.cycle_status == CycleStatus::WaitingForVBlank {
+                    break;
+                }
+            }
+        } else {
+            let result = self.run_untraced_cycles(cycles, input, &mut frame);
+            self.set_audio_buffer(self.cpu.audio_buffer());
+            self.set_beeping(self.cpu.is_sound_active());
+            result?;
+        }
+        frame.is_sound_active = self.is_sound_active();
+
+        Ok(frame)
+    }
+
+    fn run_untraced_cycles(
+        &mut self,
+        cycles: u32,
+        input: &dyn Input,
+        frame: &mut FrameResult,
+    ) -> Result<(), Error> {
+        for _ in 0..cycles {
+            if self.is_paused {
+                break;
+            }
+
+            self.is_initial_state = false;
+            self.cycle_count += 1;
+            let result = self.cpu.cycle(input);
+            self.finish_cycle(result)?;
+
+            // Only these change the display, there's no need to ask it after anything else
+            let opcode = self.cpu.opcode();
+            if opcode == 0x00E0 || opcode & 0xF000 == 0xD000 || opcode & 0xFFF0 == 0x00D0 {
+                frame.add_cycle(self.display());
+            } else {
+                frame.cycles += 1;
+            }
+            if self.cycle_status == CycleStatus::WaitingForVBlank {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run frames with no keys pressed until the display has stayed the same for a second,
+    /// returning its [`Display::frame_hash`]. `None` if it's still changing after `max_cycles`
+    /// instructions, e.g. for a ROM that animates. Makes checking that a ROM draws the
+    /// expected screen quick, as long as it doesn't pause for a second while drawing.
+    pub fn run_until_stable(&mut self, max_cycles: u64) -> Result<Option<u64>, Error> {
+        let start = self.cycle_count;
+        let mut hash = self.display().frame_hash();
+        let mut stable_frames = 0;
+
+        while stable_frames < STABLE_FRAMES {
+            if self.cycle_count - start >= max_cycles {
+                return Ok(None);
+            }
+
+            self.run_frame(&crate::batch::NullInput)?;
+            let new_hash = self.display().frame_hash();
+            if new_hash == hash {
+                stable_frames += 1;
+            } else {
+                hash = new_hash;
+                stable_frames = 0;
+            }
+        }
+
+        Ok(Some(hash))
+    }
+
+    /// Run one frame like [`Emulator::run_frame`], also when paused, and stay paused
+    /// afterwards. For advancing frame by frame while paused.
+    pub fn step_frame(&mut self, input: &dyn Input) -> Result<FrameResult, Error> {
+        let was_paused = self.is_paused;
+        self.resume();
+        let result = self.run_frame(input);
+        if was_paused {
+            self.pause();
+        }
+
+        result
+    }
+
+    /// Execute the next instruction, the timers only change through
+    /// [`Emulator::tick_timers`]. On error the program counter stays at the failing
+    /// instruction. [`Emulator::cycle_status`] tells whether it's waiting instead.
+    pub fn cycle(&mut self, input: &dyn Input) -> Result<(), Error> {
+        if self.is_paused {
+            return Ok(());
+        }
+
         if self.is_initial_state {
             self.is_initial_state = false;
         }
+        self.cycle_count += 1;
+
+        if self.profiler.is_some() {
+            // Failing instructions are counted as well, the error comes from the cycle below
+            if let Ok(instruction) = self.current_instruction() {
+                let pc = self.cpu.pc();
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.record(pc, instruction);
+                }
+            }
+        }
+
+        let result = if self.trace.is_none() {
+            self.cpu_cycle(input)
+        } else {
+            self.traced_cycle(input)
+        };
+        self.set_audio_buffer(self.cpu.audio_buffer());
+        self.set_beeping(self.cpu.is_sound_active());
+
+        self.finish_cycle(result)
+    }
+
+    /// Record the status of a cycle and tell the observer about it.
+    fn finish_cycle(&mut self, result: Result<CycleStatus, Error>) -> Result<(), Error> {
+        match result {
+            Ok(status) => {
+                self.cycle_status = status;
+                match self.cpu.opcode() {
+                    0x00E0 => self.notify(|observer| observer.on_screen_clear()),
+                    opcode if opcode & 0xF0FF == 0xF075 => self.save_flags(),
+                    // Right away rather than after the frame, so the tone starts on time even
+                    // when running a frame's instructions in one go
+                    opcode if opcode & 0xF0FF == 0xF018 => {
+                        self.set_beeping(self.cpu.is_sound_active());
+                        let ticks = self.cpu.sound_timer();
+                        self.notify(|observer| observer.on_sound_timer(ticks));
+                    }
+                    _ => {}
+                }
+
+                Ok(())
+            }
+            Err(error) => {
+                if let Error::UnknownOpcode {
+                    opcode, address, ..
+                } = error
+                {
+                    self.notify(|observer| observer.on_unknown_opcode(opcode, address));
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Execute the next instruction, keeping what's needed to undo it when there's a history.
+    fn cpu_cycle(&mut self, input: &dyn Input) -> Result<CycleStatus, Error> {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return self.cpu.cycle(input),
+        };
+
+        let (status, undo) = self.cpu.cycle_with_undo(input)?;
+        history.push(Step {
+            undo,
+            cycle_status: self.cycle_status,
+        });
+
+        Ok(status)
+    }
+
+    fn traced_cycle(&mut self, input: &dyn Input) -> Result<CycleStatus, Error> {
+        let before = self.cpu.state();
+        let instruction = self.current_instruction()?;
+        let status = self.cpu_cycle(input)?;
+        if let Some(trace) = self.trace.as_mut() {
+            trace(TraceEvent::new(instruction, &before, &self.cpu.state()));
+        }
+
+        Ok(status)
+    }
+
+    fn set_audio_buffer(&mut self, audio_buffer: Option<AudioBuffer>) {
+        if self.audio_buffer != audio_buffer {
+            self.audio_buffer = audio_buffer;
+            self.sound.set_audio_buffer(audio_buffer);
+        }
+    }
+
+    fn set_beeping(&mut self, is_beeping: bool) {
+        let is_beeping = is_beeping && !self.is_fast_forwarding();
+        if self.is_beeping != is_beeping {
+            self.is_beeping = is_beeping;
+            self.sound.beep_active(is_beeping);
+            self.notify(|observer| {
+                if is_beeping {
+                    observer.on_sound_start();
+                } else {
+                    observer.on_sound_stop();
+                }
+            });
+        }
+    }
+
+    pub fn display(&self) -> &D {
+        &self.cpu.display
+    }
+
+    /// The pixels that changed since the last call, see [`Display::take_dirty_pixels`].
+    pub fn take_dirty_pixels(&mut self) -> Vec<PixelChange> {
+        self.cpu.display.take_dirty_pixels()
+    }
+
+    /// A picture of the display in the current palette.
+    pub fn screenshot(&self) -> Screenshot {
+        let display = self.display();
+
+        Screenshot::new(
+            display.rgba_framebuffer(),
+            display.width(),
+            display.height(),
+        )
+    }
+
+    /// Draw in the colors of `palette` from now on.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.cpu.display.set_palette(palette);
+    }
+}
+
+fn check_rom_size(rom: &[u8], variant: Variant) -> Result<(), RomError> {
+    if rom.len() > variant.max_rom_size() {
+        return Err(RomError::TooLarge {
+            size: rom.len(),
+            max: variant.max_rom_size(),
+        });
+    }
+
+    Ok(())
+}
+
+/// The instructions per frame closest to `hz` instructions per second, at least one.
+pub(crate) fn cycles_per_frame_at(hz: u32) -> u32 {
+    // Rounding with the remainder rather than adding half a frame first, which overflows
+    let round_up = hz % TIMER_TICKS_PER_SECOND >= TIMER_TICKS_PER_SECOND / 2;
+    (hz / TIMER_TICKS_PER_SECOND + u32::from(round_up)).max(1)
+}
+
+/// The speed for ROMs nothing is known about, XO-CHIP ROMs expect a much faster interpreter.
+fn default_cycles_per_frame(variant: Variant) -> u32 {
+    match variant {
+        Variant::XoChip => Profile::XoChip.instructions_per_frame(),
+        _ => DEFAULT_CYCLES_PER_FRAME,
+    }
+}
+
+impl<D> fmt::Display for Emulator<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.cpu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Emulator, Stats, DEFAULT_CYCLES_PER_FRAME};
+    use crate::batch::{self, NullInput};
+    use crate::cpu::CycleStatus;
+    use crate::poke::{Poke, PokeError};
+    use crate::replay::InputReplayer;
+    use crate::rom_db::{rom_id, RomDatabase};
+    use crate::storage::{FileStorage, MemoryStorage};
+    use crate::{
+        Backtrace, Call, Display, EmulatorObserver, Error, FramebufferDisplay, Opcode, Palette,
+        Profile, Quirks, Rect, RegisterDelta, RomError, Sound, Variant,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_frames_is_deterministic() {
+        // C03F: RND V0, 0x3F, C11F: RND V1, 0x1F, C20F: RND V2, 0x0F, F229: LD F, V2,
+        // D015: DRW V0, V1, 5, E59E: SKP V5, 1200: JP 0x200, 7301: ADD V3, 0x01, 1200: JP 0x200
+        let rom = vec![
+            0xC0, 0x3F, 0xC1, 0x1F, 0xC2, 0x0F, 0xF2, 0x29, 0xD0, 0x15, 0xE5, 0x9E, 0x12, 0x00,
+            0x73, 0x01, 0x12, 0x00,
+        ];
+        // V5 stays 0, so SKP V5 counts while 0 is held
+        let script = "100 0 down\n300 0 up\n";
+        let run = |seed: u32, script: &str| {
+            let mut emulator = Emulator::new(
+                FramebufferDisplay::default(),
+                rom.clone(),
+                Quirks::default(),
+            )
+            .unwrap()
+            .with_rng_seed(seed);
+            // Runs exactly the frames asked for regardless of the speed
+            emulator.set_speed_multiplier(2.5);
+            let mut input = InputReplayer::parse(script).unwrap();
+            emulator.run_frames(60, &mut input).unwrap();
+            assert_eq!(
+                emulator.cycle_count(),
+                60 * u64::from(DEFAULT_CYCLES_PER_FRAME)
+            );
+
+            (emulator.state_hash(), emulator.state().v[3])
+        };
+
+        let (hash, presses) = run(1, script);
+        assert!(presses > 0);
+        assert_eq!(run(1, script), (hash, presses));
+        assert_ne!(run(2, script).0, hash);
+        assert_ne!(run(1, "").0, hash);
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        // 00E0: CLS
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x00, 0xE0],
+            Quirks::default(),
+        )
+        .unwrap();
+
+        emulator.pause();
+        emulator.cycle(&NullInput).unwrap();
+        assert!(emulator.is_paused());
+        assert!(emulator.is_initial_state());
+
+        emulator.resume();
+        emulator.cycle(&NullInput).unwrap();
+        assert!(!emulator.is_paused());
+        assert!(!emulator.is_initial_state());
+    }
+
+    #[test]
+    fn test_poke() {
+        // 1200: JP 0x200
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap();
+
+        // A050: LD I, 0x050, D005: DRW V0, V0, 5
+        emulator.poke(&"0x200:A050D005".parse().unwrap()).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+
+        assert!(emulator
+            .display()
+            .rgba_framebuffer()
+            .iter()
+            .any(|&pixel| pixel != 0));
+    }
+
+    #[test]
+    fn test_dump_memory() {
+        // 6303: LD V3, 0x03, A300: LD I, 0x300, F333: LD B, V3, F355: LD [I], V3
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x63, 0x03, 0xA3, 0x00, 0xF3, 0x33, 0xF3, 0x55],
+            Quirks::default(),
+        )
+        .unwrap();
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(emulator.dump_memory(0x300..0x303), &[0, 0, 3]);
+
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(emulator.dump_memory(0x300..=0x304), &[0, 0, 0, 3, 0]);
+        assert_eq!(emulator.dump_memory(..).len(), 4096);
+        assert_eq!(emulator.dump_memory(0x200..0x202), &[0x63, 0x03]);
+    }
+
+    #[test]
+    fn test_poke_out_of_bounds() {
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap();
+        let poke = Poke {
+            address: 0xFFF,
+            bytes: vec![0x00, 0xE0],
+        };
+
+        assert_eq!(
+            emulator.poke(&poke),
+            Err(PokeError::OutOfBounds {
+                address: 0xFFF,
+                length: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), vec![], Quirks::default()).unwrap();
+
+        // 6A05: LD VA, 0x05
+        emulator.execute(0x6A05, &NullInput).unwrap();
+        // 1300: JP 0x300
+        emulator.execute(0x1300, &NullInput).unwrap();
+
+        let state = emulator.to_string();
+        assert!(state.starts_with("PC: 0x300"), "{}", state);
+        assert!(state.contains("VA: 05"), "{}", state);
+    }
+
+    #[test]
+    fn test_rng_seed() {
+        // C0FF: RND V0, 0xFF, C1FF: RND V1, 0xFF, 1204: JP 0x204
+        let rom = vec![0xC0, 0xFF, 0xC1, 0xFF, 0x12, 0x04];
+        let run = |seed| {
+            let display = FramebufferDisplay::default();
+            let mut emulator = Emulator::new(display, rom.clone(), Quirks::default())
+                .unwrap()
+                .with_rng_seed(seed);
+            emulator.cycle(&NullInput).unwrap();
+            emulator.cycle(&NullInput).unwrap();
+
+            (emulator.register(0x0), emulator.register(0x1))
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn test_with_profile() {
+        let display = FramebufferDisplay::for_variant(Variant::XoChip);
+        let emulator = Emulator::with_profile(display, vec![0x12, 0x00], Profile::XoChip).unwrap();
+
+        assert_eq!(emulator.variant(), Variant::XoChip);
+        assert_eq!(emulator.memory_size(), 0x10000);
+        assert_eq!(emulator.quirks(), Quirks::xo_chip());
+        assert_eq!(emulator.cycles_per_second(), 6000);
+    }
+
+    #[test]
+    fn test_explicit_quirks_win_over_the_rom_database() {
+        let rom = vec![0x00, 0xE0, 0x12, 0x00];
+        let source = format!(
+            "[{}]\nname = Blank\nquirks = vip\nspeed = 600\n",
+            rom_id(&rom)
+        );
+        let database = RomDatabase::parse(&source).unwrap();
+        let with_info = |quirks| {
+            let rom_info = database.lookup(&rom).cloned();
+            let display = FramebufferDisplay::default();
+            Emulator::with_rom_info(display, rom.clone(), Variant::Chip8, quirks, rom_info).unwrap()
+        };
+
+        let emulator = with_info(Some(Quirks::default()));
+        assert_eq!(emulator.quirks(), Quirks::default());
+        assert_eq!(emulator.cycles_per_frame(), 10);
+
+        let emulator = with_info(None);
+        assert_eq!(emulator.quirks(), Quirks::cosmac_vip());
+        assert_eq!(emulator.cycles_per_frame(), 10);
+    }
+
+    #[test]
+    fn test_trace() {
+        // 6342: LD V3, 0x42, A2F0: LD I, 0x2F0, 1204: JP 0x204
+        let rom = vec![0x63, 0x42, 0xA2, 0xF0, 0x12, 0x04];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        emulator.set_trace(Box::new(move |event| sink.lock().unwrap().push(event)));
+
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].instruction, Opcode::LdByte(3, 0x42));
+        assert_eq!(
+            events[0].registers,
+            vec![RegisterDelta {
+                register: 3,
+                old: 0,
+                new: 0x42
+            }]
+        );
+        assert_eq!(events[1].i, Some((0, 0x2F0)));
+        assert_eq!(events[2].to_string(), "0x204  JP 0x204");
+        assert_eq!(
+            events[0].to_string(),
+            "0x200  LD V3, 0x42           V3: 00 -> 42"
+        );
+    }
+
+    #[test]
+    fn test_profile_report() {
+        // 6000: LD V0, 0x00, 7001: ADD V0, 0x01, 1202: JP 0x202
+        let rom = vec![0x60, 0x00, 0x70, 0x01, 0x12, 0x02];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        assert_eq!(emulator.profile_report(), None);
+
+        emulator.set_profiling(true);
+        for _ in 0..21 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+
+        let report = emulator.profile_report().unwrap();
+        assert_eq!(report.cycles, 21);
+        assert_eq!(report.hot_spots.len(), 3);
+        assert_eq!(report.hot_spots[0].address, 0x202);
+        assert_eq!(report.hot_spots[0].count, 10);
+        assert_eq!(report.hot_spots[0].instruction, Opcode::AddByte(0, 1));
+        assert_eq!(report.patterns[2], ("6XNN", 1));
+
+        emulator.set_profiling(false);
+        assert_eq!(emulator.profile_report(), None);
+    }
+
+    struct RecordingSound(Arc<Mutex<Vec<bool>>>);
+
+    impl Sound for RecordingSound {
+        fn beep_active(&mut self, active: bool) {
+            self.0.lock().unwrap().push(active);
+        }
+    }
+
+    #[test]
+    fn test_sound() {
+        // 6002: LD V0, 0x02, F018: LD ST, V0, 1204: JP 0x204
+        let rom = vec![0x60, 0x02, 0xF0, 0x18, 0x12, 0x04];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let beeps = Arc::new(Mutex::new(Vec::new()));
+        emulator.set_sound(Box::new(RecordingSound(Arc::clone(&beeps))));
+
+        emulator.cycle(&NullInput).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(*beeps.lock().unwrap(), vec![true]);
+
+        emulator.pause();
+        emulator.resume();
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(*beeps.lock().unwrap(), vec![true, false, true]);
+
+        emulator.tick_timers();
+        emulator.tick_timers();
+        assert_eq!(*beeps.lock().unwrap(), vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_speed_multiplier() {
+        // 6010: LD V0, 0x10, F018: LD ST, V0, 1204: JP 0x204
+        let rom = vec![0x60, 0x10, 0xF0, 0x18, 0x12, 0x04];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let beeps = Arc::new(Mutex::new(Vec::new()));
+        emulator.set_sound(Box::new(RecordingSound(Arc::clone(&beeps))));
+        emulator.set_cycles_per_frame(10);
+
+        emulator.set_speed_multiplier(4.0);
+        assert_eq!(emulator.run_frame(&NullInput).unwrap().cycles, 40);
+        assert_eq!(emulator.stats().frames, 4);
+        assert_eq!(emulator.state().sound_timer, 0x10 - 3);
+        // Muted while fast forwarding
+        assert_eq!(*beeps.lock().unwrap(), vec![]);
+
+        emulator.set_speed_multiplier(1.0);
+        assert_eq!(*beeps.lock().unwrap(), vec![true]);
+
+        emulator.set_speed_multiplier(0.5);
+        let cycles = (0..4)
+            .map(|_| emulator.run_frame(&NullInput).unwrap().cycles)
+            .collect::<Vec<_>>();
+        assert_eq!(cycles, vec![0, 10, 0, 10]);
+
+        let emulator = emulator.reset();
+        assert_eq!(emulator.speed_multiplier(), 0.5);
+    }
+
+    #[test]
+    fn test_cycles_per_frame() {
+        // 600A: LD V0, 0x0A, F015: LD DT, V0, 1204: JP 0x204
+        let rom = vec![0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        assert_eq!(emulator.cycles_per_frame(), DEFAULT_CYCLES_PER_FRAME);
+
+        // Two cycles per timer tick, the timer ticks on cycles 0, 2, 4, ...
+        emulator.set_cycles_per_frame(2);
+        assert_eq!(emulator.cycles_per_second(), 120);
+        batch::run_cycles(&mut emulator, 10).unwrap();
+        assert_eq!(emulator.state().delay_timer, 0x0A - 4);
+
+        let mut emulator = emulator.reset();
+        assert_eq!(emulator.cycles_per_frame(), 2);
+
+        emulator.set_cycles_per_second(1000);
+        assert_eq!(emulator.cycles_per_frame(), 17);
+        emulator.set_cycles_per_second(1);
+        assert_eq!(emulator.cycles_per_frame(), 1);
+        emulator.set_cycles_per_second(u32::MAX);
+        assert_eq!(emulator.cycles_per_frame(), u32::MAX / 60);
+
+        emulator.set_cycles_per_frame(u32::MAX);
+        assert_eq!(emulator.cycles_per_second(), u32::MAX);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // 6002: LD V0, 0x02, F018: LD ST, V0, A050: LD I, 0x050, D005: DRW V0, V0, 5,
+        // 1208: JP 0x208
+        let rom = vec![0x60, 0x02, 0xF0, 0x18, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x08];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let frames = (0..3)
+            .map(|_| emulator.run_frame(&NullInput).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            frames.iter().map(|frame| frame.cycles).collect::<Vec<_>>(),
+            vec![DEFAULT_CYCLES_PER_FRAME; 3]
+        );
+        assert!(frames[0].is_dirty);
+        // The font sprite for 0 is 4x5 pixels, drawn at V0, V0
+        assert_eq!(frames[0].dirty_region, Some(Rect::new(2, 2, 4, 5)));
+        assert!(!frames[1].is_dirty);
+        assert_eq!(frames[1].dirty_region, None);
+        assert!(frames[0].is_sound_active);
+        assert!(frames[1].is_sound_active);
+        assert!(!frames[2].is_sound_active);
+
+        emulator.pause();
+        assert_eq!(emulator.run_frame(&NullInput).unwrap().cycles, 0);
+        assert_eq!(
+            emulator.step_frame(&NullInput).unwrap().cycles,
+            DEFAULT_CYCLES_PER_FRAME
+        );
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        // 6002: LD V0, 0x02, A050: LD I, 0x050, D005: DRW V0, V0, 5, 1206: JP 0x206
+        let rom = vec![0x60, 0x02, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x06];
+        let run = |profile: bool| {
+            let mut emulator = Emulator::new(
+                FramebufferDisplay::default(),
+                rom.clone(),
+                Quirks::default(),
+            )
+            .unwrap();
+            emulator.set_profiling(profile);
+            let frame = emulator.run_cycles(100_000, &NullInput).unwrap();
+
+            (frame, emulator.cycle_count(), emulator.screenshot())
+        };
+
+        let (frame, cycle_count, screenshot) = run(false);
+        assert_eq!(frame.cycles, 100_000);
+        assert_eq!(cycle_count, 100_000);
+        assert_eq!(frame.dirty_region, Some(Rect::new(2, 2, 4, 5)));
+        // The same as going through every cycle
+        assert_eq!(run(true), (frame, cycle_count, screenshot));
+    }
+
+    #[test]
+    fn test_stats() {
+        // 1200: JP 0x200
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap();
+        emulator.set_cycles_per_second(600);
+        for _ in 0..3 {
+            emulator.run_frame(&NullInput).unwrap();
+        }
+        emulator.tick_timers();
+
+        let stats = emulator.stats();
+        assert_eq!(
+            stats,
+            Stats {
+                cycles: 30,
+                frames: 3,
+                timer_ticks: 4,
+            }
+        );
+        assert_eq!(
+            stats.ips_since(&Stats::default(), Duration::from_millis(50)),
+            600
+        );
+        assert_eq!(
+            stats.fps_since(&Stats::default(), Duration::from_millis(50)),
+            60
+        );
+        assert_eq!(
+            stats.fps_since(&Stats::default(), Duration::from_secs(0)),
+            0
+        );
+
+        emulator.pause();
+        emulator.run_frame(&NullInput).unwrap();
+        assert_eq!(emulator.stats(), stats);
+
+        let emulator = emulator.reset();
+        assert_eq!(emulator.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_step_back() {
+        // 60FF: LD V0, 0xFF, A300: LD I, 0x300, F033: LD B, V0, 6100: LD V1, 0x00,
+        // D115: DRW V1, V1, 5, 00E0: CLS
+        let rom = vec![
+            0x60, 0xFF, 0xA3, 0x00, 0xF0, 0x33, 0x61, 0x00, 0xD1, 0x15, 0x00, 0xE0,
+        ];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        assert!(!emulator.step_back());
+
+        emulator.set_undo_limit(4);
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            states.push((
+                emulator.state(),
+                emulator.peek_slice(0x300, 3).unwrap().to_vec(),
+                emulator.screenshot(),
+            ));
+            emulator.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(emulator.cycle_count(), 6);
+        assert_eq!(emulator.undoable_cycles(), 4);
+
+        for (state, memory, screenshot) in states.into_iter().rev().take(4) {
+            assert!(emulator.step_back());
+            assert_eq!(emulator.state(), state);
+            assert_eq!(emulator.peek_slice(0x300, 3).unwrap(), memory.as_slice());
+            assert_eq!(emulator.screenshot(), screenshot);
+        }
+        assert_eq!(emulator.cycle_count(), 2);
+        assert_eq!(emulator.peek_slice(0x300, 3).unwrap(), &[0, 0, 0]);
+        assert!(!emulator.step_back());
+
+        // Executing again after going back gives the same result
+        for _ in 0..4 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(emulator.peek_slice(0x300, 3).unwrap(), &[2, 5, 5]);
+        assert_eq!(emulator.pc(), 0x20C);
+    }
+
+    #[test]
+    fn test_run_cycles_display_wait() {
+        // D005: DRW V0, V0, 5, 1202: JP 0x202
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0xD0, 0x05, 0x12, 0x02],
+            Quirks {
+                display_wait: true,
+                ..Quirks::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(emulator.run_cycles(100, &NullInput).unwrap().cycles, 1);
+        emulator.tick_timers();
+        assert_eq!(emulator.run_cycles(100, &NullInput).unwrap().cycles, 100);
+    }
+
+    #[test]
+    fn test_run_until_stable() {
+        // A050: LD I, 0x050, D005: DRW V0, V0, 5, 1204: JP 0x204
+        let rom = vec![0xA0, 0x50, 0xD0, 0x05, 0x12, 0x04];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let blank = emulator.display().frame_hash();
+
+        let hash = emulator.run_until_stable(100_000).unwrap();
+        assert_eq!(hash, Some(emulator.display().frame_hash()));
+        assert_ne!(hash, Some(blank));
+
+        // A050: LD I, 0x050, D005: DRW V0, V0, 5, 7001: ADD V0, 0x01, 1202: JP 0x202, moves
+        // the sprite forever
+        let rom = vec![0xA0, 0x50, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x02];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        assert_eq!(emulator.run_until_stable(10_000).unwrap(), None);
+        assert!(emulator.cycle_count() >= 10_000);
+    }
+
+    struct RecordingObserver(Arc<Mutex<Vec<String>>>);
+
+    impl EmulatorObserver for RecordingObserver {
+        fn on_sound_start(&mut self) {
+            self.0.lock().unwrap().push("sound start".to_owned());
+        }
+
+        fn on_sound_stop(&mut self) {
+            self.0.lock().unwrap().push("sound stop".to_owned());
+        }
+
+        fn on_sound_timer(&mut self, ticks: u8) {
+            self.0.lock().unwrap().push(format!("sound for {}", ticks));
+        }
+
+        fn on_screen_clear(&mut self) {
+            self.0.lock().unwrap().push("clear".to_owned());
+        }
+
+        fn on_unknown_opcode(&mut self, opcode: u16, address: u16) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("unknown {:04X} at {:#05x}", opcode, address));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        // 6001: LD V0, 0x01, F018: LD ST, V0, 00E0: CLS, FFFF: unknown
+        let rom = vec![0x60, 0x01, 0xF0, 0x18, 0x00, 0xE0, 0xFF, 0xFF];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        emulator.set_observer(Box::new(RecordingObserver(Arc::clone(&events))));
+
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+        emulator.tick_timers();
+        assert!(emulator.cycle(&NullInput).is_err());
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "sound start",
+                "sound for 1",
+                "clear",
+                "sound stop",
+                "unknown FFFF at 0x206"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer_sound_edges_within_a_frame() {
+        // 6003: LD V0, 0x03, F018: LD ST, V0, 6100: LD V1, 0x00, F118: LD ST, V1, F018: LD ST, V0,
+        // 120A: JP 0x20A
+        let rom = vec![
+            0x60, 0x03, 0xF0, 0x18, 0x61, 0x00, 0xF1, 0x18, 0xF0, 0x18, 0x12, 0x0A,
+        ];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        emulator.set_observer(Box::new(RecordingObserver(Arc::clone(&events))));
+
+        // The whole frame runs in one go, every edge is still reported in order
+        for _ in 0..4 {
+            emulator.run_frame(&NullInput).unwrap();
+        }
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "sound start",
+                "sound for 3",
+                "sound stop",
+                "sound for 0",
+                "sound start",
+                "sound for 3",
+                "sound stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flag_storage() {
+        let root = std::env::temp_dir().join(format!("chip-8-flags-{}", std::process::id()));
+        let emulator = |rom: &[u8]| {
+            let display = FramebufferDisplay::for_variant(Variant::XoChip);
+            let mut emulator =
+                Emulator::with_variant(display, rom.to_vec(), Variant::XoChip, Quirks::default())
+                    .unwrap();
+            emulator
+                .set_flag_storage(Box::new(FileStorage::new(&root)))
+                .unwrap();
+
+            emulator
+        };
+        // 6142: LD V1, 0x42, F175: LD R, V1, F085: LD V0, R
+        let rom = [0x61, 0x42, 0xF1, 0x75, 0xF0, 0x85];
+
+        let mut first_run = emulator(&rom);
+        for _ in 0..3 {
+            first_run.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(first_run.flags()[..2], [0x00, 0x42]);
+        assert_eq!(first_run.register(0x0), 0x00);
+
+        // The flags are saved per ROM
+        assert_eq!(emulator(&rom).flags()[..2], [0x00, 0x42]);
+        assert_eq!(emulator(&rom[..4]).flags()[..2], [0x00, 0x00]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_load_rom() {
+        // 6142: LD V1, 0x42, F175: LD R, V1, A050: LD I, 0x050, D005: DRW V0, V0, 5
+        let first = vec![0x61, 0x42, 0xF1, 0x75, 0xA0, 0x50, 0xD0, 0x05];
+        let mut emulator = Emulator::with_variant(
+            FramebufferDisplay::for_variant(Variant::XoChip),
+            first,
+            Variant::XoChip,
+            Quirks::default(),
+        )
+        .unwrap();
+        emulator
+            .set_flag_storage(Box::new(MemoryStorage::new()))
+            .unwrap();
+        for _ in 0..4 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(emulator.flags()[1], 0x42);
+
+        // 6005: LD V0, 0x05
+        emulator.load_rom(vec![0x60, 0x05]).unwrap();
+        assert!(emulator.is_initial_state());
+        assert_eq!(emulator.pc(), 0x200);
+        assert_eq!(emulator.register(0x1), 0x00);
+        assert_eq!(emulator.flags()[1], 0x00);
+        assert!(emulator
+            .display()
+            .rgba_framebuffer()
+            .iter()
+            .all(|&pixel| pixel == 0));
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(emulator.register(0x0), 0x05);
+
+        assert_eq!(
+            emulator.load_rom(vec![0; 0x10000]),
+            Err(RomError::TooLarge {
+                size: 0x10000,
+                max: 0xFE00
+            })
+        );
+        assert_eq!(emulator.register(0x0), 0x05);
+    }
+
+    #[test]
+    fn test_load_rom_size() {
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x00, 0xE0],
+            Quirks::default(),
+        )
+        .unwrap();
+
+        assert_eq!(emulator.load_rom(vec![0; 3584]), Ok(()));
+        assert_eq!(
+            emulator.load_rom(vec![0; 3585]),
+            Err(RomError::TooLarge {
+                size: 3585,
+                max: 3584
+            })
+        );
+    }
+
+    #[test]
+    fn test_hires_start_address() {
+        // 1260: JP 0x260 into the hi-res patches, the program starts at 0x2C0
+        let mut rom = vec![0; 0xC2];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        // 6005: LD V0, 0x05
+        rom[0xC0..].copy_from_slice(&[0x60, 0x05]);
+        let mut emulator = Emulator::with_variant(
+            FramebufferDisplay::for_variant(Variant::HiRes),
+            rom,
+            Variant::HiRes,
+            Quirks::default(),
+        )
+        .unwrap();
+
+        assert_eq!(emulator.pc(), 0x2C0);
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(emulator.register(0x0), 0x05);
+        assert_eq!(emulator.display().height(), 64);
+
+        let emulator = emulator.reset();
+        assert_eq!(emulator.pc(), 0x2C0);
+    }
+
+    #[test]
+    fn test_new_too_large() {
+        let emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0; 3585],
+            Quirks::default(),
+        );
+
+        assert_eq!(
+            emulator.err(),
+            Some(RomError::TooLarge {
+                size: 3585,
+                max: 3584
+            })
+        );
+    }
+
+    #[test]
+    fn test_display_wait() {
+        // 6002: LD V0, 0x02, F018: LD ST, V0, A050: LD I, 0x050, D005: DRW V0, V0, 5,
+        // 1208: JP 0x208
+        let rom = vec![0x60, 0x02, 0xF0, 0x18, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x08];
+        let quirks = Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        };
+        let mut emulator = Emulator::new(FramebufferDisplay::default(), rom, quirks).unwrap();
+
+        // The frame ends at DXYN, which draws at the start of the next one
+        let frame = emulator.run_frame(&NullInput).unwrap();
+        assert_eq!(frame.cycles, 4);
+        assert!(!frame.is_dirty);
+        assert_eq!(emulator.cycle_status(), CycleStatus::WaitingForVBlank);
+
+        let frame = emulator.run_frame(&NullInput).unwrap();
+        assert_eq!(frame.cycles, DEFAULT_CYCLES_PER_FRAME);
+        assert!(frame.is_dirty);
+        assert_eq!(emulator.cycle_status(), CycleStatus::Executed);
+    }
+
+    #[test]
+    fn test_screenshot() {
+        // 6000: LD V0, 0x00, F029: LD F, V0, D005: DRW V0, V0, 5
+        let mut emulator = Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05],
+            Quirks::default(),
+        )
+        .unwrap();
+        emulator.set_palette(Palette::AMBER);
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+
+        let screenshot = emulator.screenshot();
+
+        assert_eq!((screenshot.width, screenshot.height), (64, 32));
+        // The top row of the 0 sprite is 0xF0
+        assert_eq!(screenshot.pixels[..4], [Palette::AMBER.foreground(); 4]);
+        assert_eq!(screenshot.pixels[4], Palette::AMBER.background());
+    }
+
+    #[test]
+    fn test_state() {
+        // 2206: CALL 0x206, 6A05: LD VA, 0x05, F315: LD DT, V3, 6305: LD V3, 0x05, 2202: CALL 0x202
+        let rom = vec![0x22, 0x06, 0x6A, 0x05, 0xF3, 0x15, 0x63, 0x05, 0x22, 0x02];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+
+        for _ in 0..5 {
+            emulator.cycle(&NullInput).unwrap();
+        }
 
-        self.cpu.cycle(should_tick_timer, input);
+        let state = emulator.state();
+        assert_eq!(state.pc, 0x206);
+        assert_eq!(state.sp, 2);
+        assert_eq!(state.stack, vec![0x202, 0x20A]);
+        assert_eq!(state.v[0x3], 0x05);
+        assert_eq!(state.v[0xA], 0x05);
+        assert_eq!(state.delay_timer, 0x05);
+        assert_eq!(emulator.peek_slice(0x200, 2), Ok(&[0x22, 0x06][..]));
+        assert_eq!(emulator.memory_size(), 4096);
     }
 
-    pub fn display(&self) -> &dyn Display {
-        self.cpu.display.as_ref()
+    #[test]
+    fn test_cycle_errors() {
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), vec![], Quirks::default()).unwrap();
+
+        assert_eq!(
+            emulator.execute(0x00EE, &NullInput),
+            Err(Error::StackUnderflow {
+                address: 0x200,
+                backtrace: Backtrace::default()
+            })
+        );
+        assert_eq!(
+            emulator.execute(0xE000, &NullInput),
+            Err(Error::UnknownOpcode {
+                opcode: 0xE000,
+                address: 0x200,
+                backtrace: Backtrace::default()
+            })
+        );
+
+        // AFFF: LD I, 0xFFF, F255: LD [I], V2
+        emulator.execute(0xAFFF, &NullInput).unwrap();
+        assert_eq!(
+            emulator.execute(0xF255, &NullInput),
+            Err(Error::MemoryOutOfBounds { address: 0x1000 })
+        );
+        assert_eq!(emulator.pc(), 0x202);
+    }
+
+    fn xo_chip(rom: Vec<u8>) -> Emulator<FramebufferDisplay> {
+        let display = FramebufferDisplay::for_variant(Variant::XoChip);
+
+        Emulator::with_variant(display, rom, Variant::XoChip, Quirks::default()).unwrap()
+    }
+
+    #[test]
+    fn test_xo_chip_memory() {
+        let rom = vec![
+            0xF0, 0x00, 0xFF, 0x00, // LD I, 0xFF00
+            0x60, 0x01, 0x61, 0x02, 0x62, 0x03, // LD V0, 1; LD V1, 2; LD V2, 3
+            0x50, 0x22, // SAVE V0 - V2
+            0x60, 0x00, 0x61, 0x00, 0x62, 0x00, // LD V0, 0; LD V1, 0; LD V2, 0
+            0x52, 0x03, // LOAD V2 - V0
+        ];
+        let mut emulator = xo_chip(rom);
+        for _ in 0..9 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+
+        assert_eq!(emulator.i(), 0xFF00);
+        assert_eq!(emulator.peek(0xFF02), 3);
+        assert_eq!(
+            (0..3).map(|x| emulator.register(x)).collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), vec![], Quirks::default()).unwrap();
+        assert_eq!(
+            emulator.execute(0x5022, &NullInput),
+            Err(Error::UnknownOpcode {
+                opcode: 0x5022,
+                address: 0x200,
+                backtrace: Backtrace::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_backtrace() {
+        // 2206: CALL 0x206, 0000, 0000, 220A: CALL 0x20A, 0000, FFFF
+        let rom = vec![0x22, 0x06, 0, 0, 0, 0, 0x22, 0x0A, 0, 0, 0xFF, 0xFF];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+
+        let backtrace = Backtrace {
+            calls: vec![
+                Call {
+                    return_address: 0x208,
+                    call_site: 0x206,
+                    instruction: Some(Opcode::Call(0x20A)),
+                },
+                Call {
+                    return_address: 0x202,
+                    call_site: 0x200,
+                    instruction: Some(Opcode::Call(0x206)),
+                },
+            ],
+        };
+        assert_eq!(emulator.state().backtrace, backtrace);
+
+        let error = emulator.cycle(&NullInput).unwrap_err();
+        assert_eq!(
+            error,
+            Error::UnknownOpcode {
+                opcode: 0xFFFF,
+                address: 0x20A,
+                backtrace
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Unknown opcode FFFF at 0x20a\n\
+             \x20 called from 0x206: CALL 0x20A\n\
+             \x20 called from 0x200: CALL 0x206"
+        );
+    }
+
+    #[test]
+    fn test_xo_chip_planes() {
+        // F201: PLANE 2, A050: LD I, 0x050, D005: DRW V0, V0, 5, 00D1: SCROLL-UP 1
+        let mut emulator = xo_chip(vec![0xF2, 0x01, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xD1]);
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+
+        // The first row of the sprite for 0 is 0xF0 and the second 0x90
+        let framebuffer = emulator.display().rgba_framebuffer();
+        assert_eq!(framebuffer[0..2], [0x00AA_AAAA, 0x00AA_AAAA]);
+
+        emulator.cycle(&NullInput).unwrap();
+        let framebuffer = emulator.display().rgba_framebuffer();
+        assert_eq!(framebuffer[0..2], [0x00AA_AAAA, 0x0]);
+    }
+
+    #[test]
+    fn test_xo_chip_audio() {
+        let rom = vec![
+            0x60, 0x70, 0xF0, 0x3A, // LD V0, 0x70; PITCH V0
+            0xA0, 0x50, 0xF0, 0x02, // LD I, 0x050; AUDIO
+            0x60, 0x40, 0xF0, 0x3A, // LD V0, 0x40; PITCH V0
+        ];
+        let mut emulator = xo_chip(rom);
+        for _ in 0..3 {
+            emulator.cycle(&NullInput).unwrap();
+        }
+        assert_eq!(emulator.audio_buffer(), None);
+
+        emulator.cycle(&NullInput).unwrap();
+        let buffer = emulator.audio_buffer().unwrap();
+        assert_eq!(buffer.pitch(), 0x70);
+        // The font sprites for 0 and 1, and the first 6 bytes of 2
+        assert_eq!(buffer.pattern()[0..6], [0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20]);
+
+        emulator.cycle(&NullInput).unwrap();
+        emulator.cycle(&NullInput).unwrap();
+        assert_eq!(emulator.audio_buffer().unwrap().pitch(), 0x40);
+        assert_eq!(emulator.audio_buffer().unwrap().pattern(), buffer.pattern());
+    }
+
+    #[test]
+    fn test_quirks() {
+        let rom = vec![
+            0x61, 0x05, 0x62, 0x03, // LD V1, 0x05; LD V2, 0x03
+            0x81, 0x26, // SHR V1, V2
+            0xA3, 0x00, 0xF1, 0x55, // LD I, 0x300; LD [I], V1
+            0x6F, 0x01, 0x81, 0x21, // LD VF, 0x01; OR V1, V2
+        ];
+        let run = |quirks| {
+            let display = FramebufferDisplay::default();
+            let mut emulator = Emulator::new(display, rom.clone(), quirks).unwrap();
+            for _ in 0..7 {
+                emulator.cycle(&NullInput).unwrap();
+            }
+
+            emulator
+        };
+
+        let emulator = run(Quirks::default());
+        assert_eq!(emulator.register(1), 0x03);
+        assert_eq!(emulator.i(), 0x300);
+        assert_eq!(emulator.register(0xF), 0x01);
+
+        let emulator = run(Quirks::cosmac_vip());
+        assert_eq!(emulator.register(1), 0x03);
+        assert_eq!(emulator.peek(0x301), 0x01);
+        assert_eq!(emulator.i(), 0x302);
+        assert_eq!(emulator.register(0xF), 0x00);
     }
 }
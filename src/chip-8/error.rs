@@ -0,0 +1,138 @@
+use std::fmt;
+
+use crate::Backtrace;
+
+/// Errors that stop the CPU, typically caused by a buggy or incompatible ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The instruction at `address` isn't one that this interpreter knows.
+    UnknownOpcode {
+        opcode: u16,
+        address: u16,
+        backtrace: Backtrace,
+    },
+    /// A subroutine was called at `address` with the stack already full.
+    StackOverflow { address: u16, backtrace: Backtrace },
+    /// A subroutine returned at `address` with nothing on the stack.
+    StackUnderflow { address: u16, backtrace: Backtrace },
+    /// Memory at `address` was accessed but it's outside of memory.
+    MemoryOutOfBounds { address: usize },
+    /// The program wrote to `address` in the interpreter area, see
+    /// [`AccessPolicy`](crate::AccessPolicy).
+    ProtectedMemoryWrite { address: u16 },
+}
+
+impl Error {
+    /// The subroutine calls that led to the error, `None` for errors that aren't about the
+    /// control flow.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            Error::UnknownOpcode { backtrace, .. }
+            | Error::StackOverflow { backtrace, .. }
+            | Error::StackUnderflow { backtrace, .. } => Some(backtrace),
+            Error::MemoryOutOfBounds { .. } | Error::ProtectedMemoryWrite { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOpcode {
+                opcode, address, ..
+            } => write!(f, "Unknown opcode {:04X} at {:#05x}", opcode, address),
+            Error::StackOverflow { address, .. } => {
+                write!(f, "Stack overflow when calling at {:#05x}", address)
+            }
+            Error::StackUnderflow { address, .. } => write!(
+                f,
+                "Stack underflow when returning at {:#05x}, the stack is empty",
+                address
+            ),
+            Error::MemoryOutOfBounds { address } => {
+                write!(f, "Memory access at {:#05x} is out of bounds", address)
+            }
+            Error::ProtectedMemoryWrite { address } => write!(
+                f,
+                "Write to {:#05x} in the interpreter area below 0x200",
+                address
+            ),
+        }?;
+
+        match self.backtrace() {
+            Some(backtrace) if !backtrace.is_empty() => write!(f, "\n{}", backtrace),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Why a ROM can't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    /// The ROM has no instructions, e.g. an empty file.
+    Empty,
+    /// The ROM is `size` bytes but only `max` fit in memory after 0x200, 3584 for CHIP-8.
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "The ROM is empty"),
+            RomError::TooLarge { size, max } => write!(
+                f,
+                "The ROM is {} bytes, only {} bytes fit in memory",
+                size, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+/// Why [`EmulatorBuilder::build`](crate::EmulatorBuilder::build) failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    Rom(RomError),
+    /// The speed is 0 instructions per frame, at least one has to run.
+    NoCycles,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Rom(error) => write!(f, "{}", error),
+            BuildError::NoCycles => write!(f, "At least one instruction has to run per frame"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<RomError> for BuildError {
+    fn from(error: RomError) -> Self {
+        BuildError::Rom(error)
+    }
+}
+
+/// Something odd about a ROM that can still be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    /// The ROM is `size` bytes, instructions are two bytes so it's likely truncated or not a
+    /// ROM at all.
+    OddLength { size: usize },
+}
+
+impl fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomWarning::OddLength { size } => write!(
+                f,
+                "The ROM is an odd number of bytes ({}), it may be truncated",
+                size
+            ),
+        }
+    }
+}
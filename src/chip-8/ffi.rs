@@ -0,0 +1,231 @@
+//! C bindings for embedding the emulator in C, C++ or anything else with a C FFI, built with
+//! `cargo build --release --features ffi`. The declarations are in `include/chip8.h`,
+//! regenerate it with `cbindgen --config cbindgen.toml --output include/chip8.h` after
+//! changing anything here.
+//!
+//! Like [`wasm`](crate::wasm) nothing here keeps time, call [`chip8_run_frame`] 60 times a
+//! second, or [`chip8_cycle`] and [`chip8_tick_timers`] for finer control, and draw
+//! [`chip8_framebuffer`] after each frame. None of the functions are thread safe, an emulator
+//! must only be used from one thread at a time.
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+
+use crate::{Display, Emulator, FramebufferDisplay, Input, Variant};
+
+/// The keys held in the frontend, updated by [`chip8_key_event`].
+struct Keypad {
+    keys: [bool; 16],
+}
+
+impl Input for Keypad {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.keys[key as usize]
+    }
+}
+
+/// An emulator, opaque to C.
+pub struct Chip8 {
+    emulator: Emulator<FramebufferDisplay>,
+    keypad: Keypad,
+    /// The pixels returned by [`chip8_framebuffer`], kept here so the pointer stays valid.
+    pixels: Vec<u32>,
+}
+
+/// Create an emulator running the `length` bytes of ROM at `rom`. Returns null if the ROM
+/// doesn't fit in memory. The emulator must be freed with [`chip8_free`].
+///
+/// # Safety
+///
+/// `rom` must point to `length` readable bytes, it's only read during the call.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_new(rom: *const u8, length: usize) -> *mut Chip8 {
+    if rom.is_null() {
+        return ptr::null_mut();
+    }
+
+    let rom = slice::from_raw_parts(rom, length).to_vec();
+    match Emulator::for_rom(FramebufferDisplay::default(), rom, Variant::Chip8) {
+        Ok(emulator) => Box::into_raw(Box::new(Chip8 {
+            emulator,
+            keypad: Keypad { keys: [false; 16] },
+            pixels: Vec::new(),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free an emulator from [`chip8_new`], does nothing for null.
+///
+/// # Safety
+///
+/// `chip8` must be null or from [`chip8_new`] and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Execute a single instruction. Returns 0, or -1 if the ROM did something invalid, after
+/// which the emulator shouldn't be run any further.
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cycle(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    match chip8.emulator.cycle(&chip8.keypad) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Tick the delay and sound timers, 60 times a second.
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) {
+    (*chip8).emulator.tick_timers();
+}
+
+/// Run 1/60th of a second worth of instructions and tick the timers. Returns 1 if the display
+/// changed, 0 if it didn't, or -1 like [`chip8_cycle`].
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Chip8) -> c_int {
+    let chip8 = &mut *chip8;
+    match chip8.emulator.run_frame(&chip8.keypad) {
+        Ok(frame) => frame.is_dirty as c_int,
+        Err(_) => -1,
+    }
+}
+
+/// The display as `width * height` XRGB pixels, row by row, with the size written to `width`
+/// and `height` unless they're null. The pixels stay valid until the next call to a function
+/// taking the emulator.
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed, `width` and `height` must be null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    chip8: *mut Chip8,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u32 {
+    let chip8 = &mut *chip8;
+    let display = chip8.emulator.display();
+    display.write_rgba(&mut chip8.pixels);
+    if !width.is_null() {
+        *width = display.width();
+    }
+    if !height.is_null() {
+        *height = display.height();
+    }
+
+    chip8.pixels.as_ptr()
+}
+
+/// Press `key`, 0 to F, if `pressed` is non-zero, otherwise release it. Other keys are
+/// ignored.
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_key_event(chip8: *mut Chip8, key: u8, pressed: c_int) {
+    if let Some(held) = (*chip8).keypad.keys.get_mut(key as usize) {
+        *held = pressed != 0;
+    }
+}
+
+/// 1 if the tone should be playing, otherwise 0.
+///
+/// # Safety
+///
+/// `chip8` must be from [`chip8_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_sound_active(chip8: *const Chip8) -> c_int {
+    (*chip8).emulator.is_sound_active() as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        // 6005: LD V0, 0x05, F029: LD F, V0, D115: DRW V1, V1, 5, E09E: SKP V0, 1200: JP 0x200
+        let rom = [
+            0x60, 0x05, 0xF0, 0x29, 0xD1, 0x15, 0xE0, 0x9E, 0x12, 0x00, 0x12, 0x0A,
+        ];
+
+        unsafe {
+            assert!(chip8_new(ptr::null(), 0).is_null());
+            assert!(chip8_new([0; 0x2000].as_ptr(), 0x2000).is_null());
+
+            let chip8 = chip8_new(rom.as_ptr(), rom.len());
+            assert!(!chip8.is_null());
+            for _ in 0..4 {
+                assert_eq!(chip8_cycle(chip8), 0);
+            }
+            assert_eq!((*chip8).emulator.pc(), 0x208);
+
+            let (mut width, mut height) = (0, 0);
+            let pixels = chip8_framebuffer(chip8, &mut width, &mut height);
+            assert_eq!((width, height), (64, 32));
+            let pixels = std::slice::from_raw_parts(pixels, width * height);
+            // The top row of the 5 is 0xF0
+            assert_ne!(pixels[0], pixels[4]);
+            assert_eq!(pixels[3], pixels[0]);
+            assert_eq!(
+                chip8_framebuffer(chip8, ptr::null_mut(), ptr::null_mut()),
+                pixels.as_ptr()
+            );
+
+            // SKP V0 skips the jump back to the start while 5 is held
+            chip8_key_event(chip8, 0x5, 1);
+            chip8_key_event(chip8, 0x10, 1);
+            (*chip8).emulator.set_pc(0x206).unwrap();
+            assert_eq!(chip8_cycle(chip8), 0);
+            assert_eq!((*chip8).emulator.pc(), 0x20A);
+            chip8_key_event(chip8, 0x5, 0);
+            (*chip8).emulator.set_pc(0x206).unwrap();
+            assert_eq!(chip8_cycle(chip8), 0);
+            assert_eq!((*chip8).emulator.pc(), 0x208);
+
+            assert_eq!(chip8_is_sound_active(chip8), 0);
+            chip8_tick_timers(chip8);
+            assert!(chip8_run_frame(chip8) >= 0);
+
+            chip8_free(chip8);
+            chip8_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_skip_with_a_large_key() {
+        // 60FF: LD V0, 0xFF, E09E: SKP V0 looks at key F rather than past the keypad
+        let rom = [0x60, 0xFF, 0xE0, 0x9E];
+
+        unsafe {
+            let chip8 = chip8_new(rom.as_ptr(), rom.len());
+            chip8_key_event(chip8, 0xF, 1);
+            assert_eq!(chip8_cycle(chip8), 0);
+            assert_eq!(chip8_cycle(chip8), 0);
+            assert_eq!((*chip8).emulator.pc(), 0x206);
+
+            chip8_free(chip8);
+        }
+    }
+}
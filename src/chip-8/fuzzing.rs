@@ -0,0 +1,85 @@
+//! Entry points for fuzz targets, see `fuzz/`.
+//!
+//! With the `fuzzing` feature enabled CXNN draws from a fixed sequence instead of `rand`, so a
+//! crashing input reproduces on every run. Nothing here touches the file system, threads or
+//! stdio which also makes it suitable for running under Miri.
+use crate::batch::NullInput;
+use crate::{Emulator, NopDisplay, Quirks, Variant};
+
+/// The most cycles to run a single input for, enough to run through all of XO-CHIP memory
+/// without timing out.
+pub const MAX_CYCLES: usize = 40_000;
+
+/// Run `data` as a ROM until it errors or [`MAX_CYCLES`] have executed. Errors are expected
+/// for most inputs, anything else going wrong e.g. a panic or an overflow is a bug.
+///
+/// The first byte picks the [`Variant`] and the bits of the second turn on the [`Quirks`], in
+/// the order they're declared, so that every combination gets fuzzed. The rest is the ROM.
+pub fn run(data: &[u8]) {
+    let (variant, quirks, rom) = match data {
+        [variant, quirks, rom @ ..] => (*variant, *quirks, rom),
+        _ => return,
+    };
+    let variant = Variant::ALL[variant as usize % Variant::ALL.len()];
+    let rom = rom[..rom.len().min(variant.max_rom_size())].to_vec();
+    let mut emulator = match Emulator::with_variant(NopDisplay, rom, variant, quirks_from(quirks)) {
+        Ok(emulator) => emulator,
+        // Only an empty ROM, everything else is truncated to fit
+        Err(_) => return,
+    };
+
+    for cycle in 0..MAX_CYCLES {
+        if emulator.cycle(&NullInput).is_err() {
+            break;
+        }
+        if cycle % 16 == 0 {
+            emulator.tick_timers();
+        }
+    }
+}
+
+fn quirks_from(bits: u8) -> Quirks {
+    let bit = |index: u8| bits & 1 << index != 0;
+
+    Quirks {
+        shift_uses_vy: bit(0),
+        load_store_increments_i: bit(1),
+        jump_uses_vx: bit(2),
+        clip_sprites: bit(3),
+        logic_resets_vf: bit(4),
+        display_wait: bit(5),
+        wrap_memory: bit(6),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+
+    const CHIP_8: u8 = 0;
+    const XO_CHIP: u8 = 3;
+    const NO_QUIRKS: u8 = 0;
+    const ALL_QUIRKS: u8 = 0xFF;
+
+    #[test]
+    fn test_run_survives_bad_input() {
+        run(&[]);
+        run(&[CHIP_8, NO_QUIRKS]);
+        // 00EE: RET with an empty stack
+        run(&[CHIP_8, NO_QUIRKS, 0x00, 0xEE]);
+        // F065: LD V0, [I] with I at the end of memory, after AFFF: LD I, 0xFFF
+        run(&[CHIP_8, NO_QUIRKS, 0xAF, 0xFF, 0xFF, 0x65]);
+        run(&[CHIP_8, ALL_QUIRKS, 0xAF, 0xFF, 0xFF, 0x65]);
+        // 2200: CALL 0x200 until the stack overflows
+        run(&[CHIP_8, NO_QUIRKS, 0x22, 0x00]);
+        run(&[0xFF; 0x2000]);
+    }
+
+    #[test]
+    fn test_run_to_the_end_of_xo_chip_memory() {
+        // 6000: LD V0, 0x00 all the way to 0xFFFE, after which the PC wraps around
+        let mut data = vec![XO_CHIP, ALL_QUIRKS];
+        data.extend([0x60, 0x00].iter().cycle().take(0x10000 - 0x200));
+        run(&data);
+    }
+}
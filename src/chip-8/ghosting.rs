@@ -0,0 +1,171 @@
+//! Phosphor ghosting, pixels that turn off fade out over a few frames like on a CRT instead of
+//! disappearing at once. This hides most of the flicker from games that erase and redraw their
+//! sprites every frame with XOR.
+
+/// Fades pixels that turn off from their last color to the background.
+#[derive(Debug, Clone)]
+pub struct Ghosting {
+    decay: f32,
+    /// The last pixels returned by [`Ghosting::apply`].
+    previous: Vec<u32>,
+    is_fading: bool,
+}
+
+impl Ghosting {
+    /// `decay` is how much of a pixel's color is left after each frame, in `0.0..1.0`. 0.0
+    /// turns pixels off at once, 0.5 makes them fade out over a few frames.
+    pub fn new(decay: f32) -> Self {
+        assert!(
+            (0.0..1.0).contains(&decay),
+            "Ghosting decay {} isn't in 0.0..1.0",
+            decay
+        );
+
+        Self {
+            decay,
+            previous: Vec::new(),
+            is_fading: false,
+        }
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    /// Whether the last frame still had pixels fading out. Frontends that only redraw when the
+    /// display changes need to keep applying the ghosting while this is true.
+    pub fn is_fading(&self) -> bool {
+        self.is_fading
+    }
+
+    /// The next frame to show for the XRGB `pixels` of the display, drawn on `background`.
+    /// Call once per frame, each call fades the pixels that are off a bit more.
+    pub fn apply(&mut self, pixels: &[u32], background: u32) -> Vec<u32> {
+        let mut output = pixels.to_vec();
+        self.apply_in_place(&mut output, background);
+
+        output
+    }
+
+    /// Like [`Ghosting::apply`], replacing `pixels` with the next frame rather than allocating
+    /// a new one.
+    pub fn apply_in_place(&mut self, pixels: &mut [u32], background: u32) {
+        if self.previous.len() != pixels.len() {
+            // A new resolution, there is nothing to fade from
+            self.previous.clear();
+            self.previous.extend_from_slice(pixels);
+        }
+
+        let decay = self.decay;
+        let mut is_fading = false;
+        for (pixel, &previous) in pixels.iter_mut().zip(&self.previous) {
+            if *pixel != background || previous == background {
+                continue;
+            }
+
+            *pixel = blend(background, previous, decay);
+            is_fading |= *pixel != background;
+        }
+
+        self.previous.copy_from_slice(pixels);
+        self.is_fading = is_fading;
+    }
+
+    /// Forget the previous frame, e.g. after a reset so that nothing fades from the old game.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+        self.is_fading = false;
+    }
+}
+
+/// `from` moved towards `to` by `amount`, channel by channel. Rounds towards `from` so that
+/// fading always reaches it.
+fn blend(from: u32, to: u32, amount: f32) -> u32 {
+    (0..3).fold(0, |color, channel| {
+        let shift = channel * 8;
+        let from = ((from >> shift) & 0xFF) as f32;
+        let to = ((to >> shift) & 0xFF) as f32;
+        let value = from + (to - from) * amount;
+        let value = if to > from {
+            value.floor()
+        } else {
+            value.ceil()
+        } as u32;
+
+        color | (value << shift)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ghosting;
+
+    const BACKGROUND: u32 = 0x00_00_00;
+    const FOREGROUND: u32 = 0xFF_FF_FF;
+
+    #[test]
+    fn test_fades_out() {
+        let mut ghosting = Ghosting::new(0.5);
+
+        assert_eq!(ghosting.apply(&[FOREGROUND], BACKGROUND), vec![FOREGROUND]);
+        assert!(!ghosting.is_fading());
+
+        assert_eq!(ghosting.apply(&[BACKGROUND], BACKGROUND), vec![0x7F_7F_7F]);
+        assert!(ghosting.is_fading());
+        assert_eq!(ghosting.apply(&[BACKGROUND], BACKGROUND), vec![0x3F_3F_3F]);
+
+        let frames = (0..10)
+            .take_while(|_| {
+                ghosting.apply(&[BACKGROUND], BACKGROUND);
+                ghosting.is_fading()
+            })
+            .count();
+        assert!(frames < 10);
+        assert_eq!(ghosting.apply(&[BACKGROUND], BACKGROUND), vec![BACKGROUND]);
+    }
+
+    #[test]
+    fn test_turns_on_at_once() {
+        let mut ghosting = Ghosting::new(0.5);
+
+        ghosting.apply(&[FOREGROUND, BACKGROUND], BACKGROUND);
+        ghosting.apply(&[BACKGROUND, BACKGROUND], BACKGROUND);
+
+        assert_eq!(
+            ghosting.apply(&[FOREGROUND, FOREGROUND], BACKGROUND),
+            vec![FOREGROUND, FOREGROUND]
+        );
+    }
+
+    #[test]
+    fn test_light_background() {
+        let mut ghosting = Ghosting::new(0.5);
+
+        ghosting.apply(&[0x00_00_00], 0xFF_FF_FF);
+
+        assert_eq!(ghosting.apply(&[0xFF_FF_FF], 0xFF_FF_FF), vec![0x80_80_80]);
+    }
+
+    #[test]
+    fn test_no_decay() {
+        let mut ghosting = Ghosting::new(0.0);
+
+        ghosting.apply(&[FOREGROUND], BACKGROUND);
+
+        assert_eq!(ghosting.apply(&[BACKGROUND], BACKGROUND), vec![BACKGROUND]);
+        assert!(!ghosting.is_fading());
+    }
+
+    #[test]
+    fn test_apply_in_place() {
+        let mut ghosting = Ghosting::new(0.5);
+        let mut pixels = vec![FOREGROUND, FOREGROUND];
+
+        ghosting.apply_in_place(&mut pixels, BACKGROUND);
+        pixels.copy_from_slice(&[BACKGROUND, FOREGROUND]);
+        ghosting.apply_in_place(&mut pixels, BACKGROUND);
+
+        assert_eq!(pixels, vec![0x7F_7F_7F, FOREGROUND]);
+        assert!(ghosting.is_fading());
+    }
+}
@@ -0,0 +1,339 @@
+//! A small JSON reader and writer for the messages of [`control`](crate::control).
+//!
+//! It reads any valid JSON, numbers become `f64` and objects keep their keys in order. Writing
+//! prints integral numbers without a fraction and escapes only what JSON requires.
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: text.chars().peekable(),
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            Some(c) => Err(format!("Unexpected `{}` after the value", c)),
+            None => Ok(value),
+        }
+    }
+
+    /// The value of `key` if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The number if it's a whole one that isn't negative.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Number(number)
+                if number >= 0.0 && number.fract() == 0.0 && number <= u64::MAX as f64 =>
+            {
+                Some(number as u64)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::String(string.to_owned())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(number: u64) -> Self {
+        Value::Number(number as f64)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{}", *number as i64)
+            }
+            Value::Number(number) if number.is_finite() => write!(f, "{}", number),
+            // JSON has no infinity or NaN
+            Value::Number(_) => write!(f, "null"),
+            Value::String(string) => write_string(f, string),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Value::String),
+            Some('-') | Some('0'..='9') => self.number(),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Null),
+            Some(c) => Err(format!("Unexpected `{}`", c)),
+            None => Err("Unexpected end of input".to_owned()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(entries)),
+                _ => return Err("Expected `,` or `}` in an object".to_owned()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err("Expected `,` or `]` in an array".to_owned()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let c = match self.chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err("Invalid escape in a string".to_owned()),
+                    };
+                    string.push(c);
+                }
+                Some(c) => string.push(c),
+                None => return Err("Unterminated string".to_owned()),
+            }
+        }
+    }
+
+    /// The character of a `\u` escape after the `u`, joining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                return Err("Unpaired surrogate in a string".to_owned());
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err("Unpaired surrogate in a string".to_owned());
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        std::char::from_u32(code).ok_or_else(|| "Invalid `\\u` escape in a string".to_owned())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = (0..4).filter_map(|_| self.chars.next()).collect::<String>();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() == 4)
+            .ok_or_else(|| format!("Invalid `\\u{}` escape in a string", digits))
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9') {
+                break;
+            }
+            text.push(c);
+            self.chars.next();
+        }
+
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| format!("Invalid number `{}`", text))
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("Expected `{}`", word));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected `{}`, got `{}`", expected, c)),
+            None => Err(format!("Expected `{}`, got the end of input", expected)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(
+            self.chars.peek(),
+            Some(' ') | Some('\t') | Some('\n') | Some('\r')
+        ) {
+            self.chars.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn test_parse() {
+        let value = Value::parse(
+            r#" {"cmd": "step", "frames": 3, "list": [1.5, -2e2, true, null], "s": "a\"é😀"} "#,
+        )
+        .unwrap();
+
+        assert_eq!(value.get("cmd").and_then(Value::as_str), Some("step"));
+        assert_eq!(value.get("frames").and_then(Value::as_u64), Some(3));
+        assert_eq!(
+            value.get("list"),
+            Some(&Value::Array(vec![
+                Value::Number(1.5),
+                Value::Number(-200.0),
+                Value::Bool(true),
+                Value::Null
+            ]))
+        );
+        assert_eq!(value.get("s").and_then(Value::as_str), Some("a\"é😀"));
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(Value::Number(1.5).as_u64(), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in &["", "{", "[1,]", r#"{"a" 1}"#, r#""\x""#, "tru", "1 2", "-"] {
+            assert!(Value::parse(text).is_err(), "`{}` parsed", text);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let value = Value::Object(vec![
+            ("ok".to_owned(), Value::Bool(true)),
+            ("bytes".to_owned(), Value::Array(vec![0.into(), 255.into()])),
+            ("error".to_owned(), "a \"b\"\n".into()),
+            ("ratio".to_owned(), Value::Number(0.25)),
+        ]);
+
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r#"{"ok":true,"bytes":[0,255],"error":"a \"b\"\n","ratio":0.25}"#
+        );
+        assert_eq!(Value::parse(&text), Ok(value));
+    }
+}
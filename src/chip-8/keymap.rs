@@ -0,0 +1,284 @@
+//! Mapping of keyboard keys to CHIP-8 keys, with per-ROM layouts.
+//!
+//! Keymaps use the same config format as [controller profiles](crate::controller). Keys are
+//! named by the letter or digit printed on them, `layout` starts a section from one of
+//! [`KeyMap::LAYOUTS`], and `[rom <hash>]` sections, keyed by [`rom_hash`], override
+//! individual keys for a specific game:
+//!
+//! ```text
+//! [default]
+//! layout = azerty
+//!
+//! # Jump with B as well as Z
+//! [rom 1f2e3d4c5b6a7980]
+//! B = 0x5
+//! ```
+use std::collections::HashMap;
+
+use crate::controller::{parse_key, rom_hash, ParseError};
+
+/// The 4x4 CHIP-8 keypad in the order its keys are laid out, row by row.
+const KEYPAD: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+/// Which CHIP-8 key, if any, each keyboard key presses.
+///
+/// Keyboard keys are letters and digits, letters are stored in upper case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: HashMap<char, u8>,
+}
+
+impl KeyMap {
+    /// The names of the built-in layouts, see [`KeyMap::layout`].
+    pub const LAYOUTS: [&'static str; 3] = ["qwerty", "azerty", "qwertz"];
+
+    /// A map where no keyboard key presses any CHIP-8 key.
+    pub fn empty() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+
+    /// The built-in layout called `name`, the left hand side of the keyboard arranged like the
+    /// CHIP-8 keypad.
+    pub fn layout(name: &str) -> Option<Self> {
+        let rows = match name {
+            "qwerty" => "1234QWERASDFZXCV",
+            "azerty" => "1234AZERQSDFWXCV",
+            "qwertz" => "1234QWERASDFYXCV",
+            _ => return None,
+        };
+
+        let mut map = Self::empty();
+        for (keyboard_key, &key) in rows.chars().zip(KEYPAD.iter()) {
+            map.set(keyboard_key, key);
+        }
+
+        Some(map)
+    }
+
+    pub fn key_for(&self, keyboard_key: char) -> Option<u8> {
+        self.keys.get(&keyboard_key.to_ascii_uppercase()).cloned()
+    }
+
+    pub fn set(&mut self, keyboard_key: char, key: u8) {
+        assert!(
+            keyboard_key.is_ascii_alphanumeric(),
+            "Invalid keyboard key {:?}",
+            keyboard_key
+        );
+        assert!(key < 16, "Invalid CHIP-8 key {:#02x}", key);
+
+        self.keys.insert(keyboard_key.to_ascii_uppercase(), key);
+    }
+
+    /// Every mapped keyboard key and the CHIP-8 key it presses.
+    pub fn keys(&self) -> impl Iterator<Item = (char, u8)> + '_ {
+        self.keys
+            .iter()
+            .map(|(&keyboard_key, &key)| (keyboard_key, key))
+    }
+}
+
+impl Default for KeyMap {
+    /// The QWERTY layout, 1234, QWER, ASDF, and ZXCV.
+    fn default() -> Self {
+        Self::layout("qwerty").expect("qwerty is a built-in layout")
+    }
+}
+
+/// A default [`KeyMap`] and per-ROM layouts keyed by [`rom_hash`].
+#[derive(Debug, Clone, Default)]
+pub struct KeyMaps {
+    default: KeyMap,
+    roms: HashMap<u64, KeyMapSection>,
+}
+
+/// The keys set in a `[rom <hash>]` section, on top of `layout` if one was given.
+#[derive(Debug, Clone)]
+struct KeyMapSection {
+    layout: Option<KeyMap>,
+    overrides: KeyMap,
+}
+
+impl KeyMaps {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut keymaps = Self::default();
+        let mut current: Option<u64> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let section = line[1..line.len() - 1].trim();
+                current = if section == "default" {
+                    None
+                } else if let Some(hash) = section.strip_prefix("rom ") {
+                    let hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| {
+                        ParseError::new(line_number, format!("Invalid ROM hash `{}`", hash))
+                    })?;
+                    keymaps.roms.entry(hash).or_insert_with(|| KeyMapSection {
+                        layout: None,
+                        overrides: KeyMap::empty(),
+                    });
+
+                    Some(hash)
+                } else {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Unknown section `{}`", section),
+                    ));
+                };
+
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Expected `key = key`, got `{}`", line),
+                    ))
+                }
+            };
+
+            if name == "layout" {
+                let layout = KeyMap::layout(value).ok_or_else(|| {
+                    ParseError::new(line_number, format!("Unknown layout `{}`", value))
+                })?;
+                match current {
+                    None => keymaps.default = layout,
+                    Some(hash) => keymaps.section(hash).layout = Some(layout),
+                }
+
+                continue;
+            }
+
+            let mut chars = name.chars();
+            let keyboard_key = match (chars.next(), chars.next()) {
+                (Some(keyboard_key), None) if keyboard_key.is_ascii_alphanumeric() => keyboard_key,
+                _ => {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Unknown keyboard key `{}`", name),
+                    ))
+                }
+            };
+            let key = parse_key(value).ok_or_else(|| {
+                ParseError::new(line_number, format!("Invalid CHIP-8 key `{}`", value))
+            })?;
+
+            match current {
+                None => keymaps.default.set(keyboard_key, key),
+                Some(hash) => keymaps.section(hash).overrides.set(keyboard_key, key),
+            }
+        }
+
+        Ok(keymaps)
+    }
+
+    /// The mapping to use for `rom`, its own layout or the default one with any overrides for
+    /// `rom` applied.
+    pub fn for_rom(&self, rom: &[u8]) -> KeyMap {
+        match self.roms.get(&rom_hash(rom)) {
+            Some(section) => {
+                let mut map = section
+                    .layout
+                    .clone()
+                    .unwrap_or_else(|| self.default.clone());
+                map.keys.extend(section.overrides.keys());
+
+                map
+            }
+            None => self.default.clone(),
+        }
+    }
+
+    fn section(&mut self, hash: u64) -> &mut KeyMapSection {
+        self.roms
+            .get_mut(&hash)
+            .expect("Section should have been created")
+    }
+}
+
+impl From<KeyMap> for KeyMaps {
+    /// `map` for every ROM.
+    fn from(map: KeyMap) -> Self {
+        Self {
+            default: map,
+            roms: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyMap, KeyMaps};
+    use crate::controller::rom_hash;
+
+    #[test]
+    fn test_layouts() {
+        let qwerty = KeyMap::default();
+        assert_eq!(qwerty.key_for('1'), Some(0x1));
+        assert_eq!(qwerty.key_for('q'), Some(0x4));
+        assert_eq!(qwerty.key_for('V'), Some(0xF));
+        assert_eq!(qwerty.key_for('Y'), None);
+
+        let azerty = KeyMap::layout("azerty").unwrap();
+        assert_eq!(azerty.key_for('A'), Some(0x4));
+        assert_eq!(azerty.key_for('Z'), Some(0x5));
+        assert_eq!(azerty.key_for('Q'), Some(0x7));
+        assert_eq!(azerty.key_for('W'), Some(0xA));
+
+        assert_eq!(KeyMap::layout("qwertz").unwrap().key_for('Y'), Some(0xA));
+        assert!(KeyMap::layout("dvorak").is_none());
+        for name in &KeyMap::LAYOUTS {
+            assert_eq!(KeyMap::layout(name).unwrap().keys().count(), 16);
+        }
+    }
+
+    #[test]
+    fn test_per_rom_keymap() {
+        let jumper = [0x00, 0xE0, 0x12, 0x00];
+        let racer = [0x12, 0x02, 0x12, 0x00];
+        let other = [0x12, 0x00];
+        let source = format!(
+            "# comment\n[default]\nlayout = azerty\nm = 5\n\n[rom {:016x}]\nb = 0x5\n\n[rom {:016x}]\nlayout = qwerty\n",
+            rom_hash(&jumper),
+            rom_hash(&racer)
+        );
+
+        let keymaps = KeyMaps::parse(&source).unwrap();
+
+        assert_eq!(keymaps.for_rom(&other).key_for('A'), Some(0x4));
+        assert_eq!(keymaps.for_rom(&other).key_for('M'), Some(0x5));
+        assert_eq!(keymaps.for_rom(&other).key_for('B'), None);
+        // Keys not mentioned in the ROM section are inherited from the default
+        assert_eq!(keymaps.for_rom(&jumper).key_for('B'), Some(0x5));
+        assert_eq!(keymaps.for_rom(&jumper).key_for('M'), Some(0x5));
+        // A layout in the ROM section replaces the default one
+        assert_eq!(keymaps.for_rom(&racer), KeyMap::default());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = KeyMaps::parse("[default]\nQ = 0x10").unwrap_err();
+        assert_eq!(error.line, 2);
+
+        let error = KeyMaps::parse("[default]\nlayout = dvorak").unwrap_err();
+        assert_eq!(error.message, "Unknown layout `dvorak`");
+
+        assert!(KeyMaps::parse("[default]\nspace = 5").is_err());
+        assert!(KeyMaps::parse("[default]\n; = 5").is_err());
+        assert!(KeyMaps::parse("[rom xyz]").is_err());
+        assert!(KeyMaps::parse("Q").is_err());
+    }
+}
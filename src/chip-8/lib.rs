@@ -1,43 +1,252 @@
+pub mod agent;
+pub mod analyzer;
+pub mod assembler;
+mod backtrace;
+pub mod batch;
+pub mod breakpoint;
+mod builder;
+#[cfg(feature = "builtin-roms")]
+pub mod builtin;
+pub mod cheats;
+pub mod clock;
+pub mod config;
+#[cfg(feature = "control")]
+pub mod control;
+pub mod controller;
 mod cpu;
+pub mod debugger;
+pub mod disassembler;
 mod display;
 mod emulator;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+mod ghosting;
+mod hash;
+#[cfg(feature = "control")]
+mod json;
+pub mod keymap;
 mod memory;
+mod octo;
+mod opcode;
+pub mod overlay;
+mod pacer;
+mod palette;
+mod pixel_buffer;
+mod poke;
+pub mod pool;
+pub mod prelude;
+mod profile;
+pub mod profiler;
+mod quirks;
+pub mod recording;
+pub mod replay;
+pub mod rng;
+pub mod rom_db;
+mod rotation;
+pub mod runner;
+mod scaling;
+mod screenshot;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod settings;
+mod sound;
+pub mod sprites;
+pub mod storage;
+pub mod test_harness;
 mod timer;
+mod trace;
+mod undo;
+mod variant;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watchpoint;
 
-pub use display::FramebufferDisplay;
-pub use emulator::Emulator;
+pub use backtrace::{Backtrace, Call};
+pub use builder::EmulatorBuilder;
+#[cfg(feature = "builtin-roms")]
+pub use builtin::builtin_roms;
+pub use clock::Clock;
+pub use cpu::{CpuState, CycleStatus};
+pub use display::{FramebufferDisplay, NopDisplay, PixelChange, Rect};
+pub use emulator::{DynEmulator, Emulator, FrameResult, Stats, DEFAULT_CYCLES_PER_FRAME};
+pub use error::{BuildError, Error, RomError, RomWarning};
+pub use ghosting::Ghosting;
+pub use memory::{AccessPolicy, Memory};
+pub use opcode::{DecodeError, Opcode};
+pub use pacer::FramePacer;
+pub use palette::Palette;
+pub use pixel_buffer::PixelBuffer;
+pub use poke::{Poke, PokeError};
+pub use profile::Profile;
+pub use quirks::Quirks;
+pub use rng::Rng;
+pub use rotation::Rotation;
+pub use scaling::ScaleMode;
+pub use screenshot::Screenshot;
+pub use sound::{AudioBuffer, NopSound, SoundSinks};
+pub use trace::{RegisterDelta, Trace, TraceEvent};
+pub use variant::Variant;
 
+/// The state of the 16 key keypad, read every cycle. Presses and releases are seen by comparing
+/// the state between cycles, e.g. for FX0A which waits for a key to be pressed and released.
 pub trait Input {
+    /// Whether `key`, 0 to F, is held.
     fn is_key_down(&self, key: u8) -> bool;
-    fn last_key_down(&self) -> Option<u8>;
+
+    /// Catch up to `cycle` instructions since power on, called before every frame of
+    /// [`Emulator::run_frames`] for inputs that follow a script. Does nothing by default.
+    fn advance(&mut self, _cycle: u64) {}
+}
+
+/// Sound output, a single tone that plays while the sound timer is active.
+pub trait Sound: Send {
+    /// Start or stop the tone. Only called when it changes, not on every cycle.
+    fn beep_active(&mut self, active: bool);
+
+    /// Play `buffer` instead of the tone, or the tone again for `None`. Called when a ROM
+    /// loads an XO-CHIP audio pattern or changes the pitch. Ignored by default.
+    fn set_audio_buffer(&mut self, _buffer: Option<AudioBuffer>) {}
+}
+
+/// Notified of what happens in an [`Emulator`], so embedders don't need to poll its state every
+/// frame. Every method does nothing by default, implement the ones of interest and pass the
+/// observer to [`Emulator::set_observer`].
+pub trait EmulatorObserver: Send {
+    /// The sound timer became active.
+    fn on_sound_start(&mut self) {}
+
+    /// The sound timer ran out.
+    fn on_sound_stop(&mut self) {}
+
+    /// FX18 set the sound timer to `ticks` 60ths of a second, e.g. to schedule the end of the
+    /// tone ahead of time. Called after [`EmulatorObserver::on_sound_start`] when it starts the
+    /// tone, and also when it changes how long a playing tone lasts.
+    fn on_sound_timer(&mut self, _ticks: u8) {}
+
+    /// 00E0 cleared the screen.
+    fn on_screen_clear(&mut self) {}
+
+    /// `opcode` at `address` isn't an instruction that the variant knows, execution stops
+    /// there.
+    fn on_unknown_opcode(&mut self, _opcode: u16, _address: u16) {}
+
+    /// Execution reached a breakpoint or watchpoint of a [`Debugger`](debugger::Debugger).
+    fn on_breakpoint(&mut self, _hit: &breakpoint::Hit) {}
+
+    /// Saving the flags to the [`Emulator::set_flag_storage`] storage failed, the ROM keeps
+    /// running with the flags in memory.
+    fn on_storage_error(&mut self, _error: &std::io::Error) {}
 }
 
 /// The Display for the emulator, typically 64x32 pixels.
-pub trait Display {
+pub trait Display: Send {
+    /// Width of the display in pixels.
+    fn width(&self) -> usize;
+
+    /// Height of the display in pixels.
+    fn height(&self) -> usize;
+
     /// Wether the Display is dirty i.e. needs to be rewdrawn in the next draw cycle.
     fn is_dirty(&self) -> bool;
 
     /// Clear the dirty flag, typically after drawing in a draw cycle.
     fn clear_dirty(&mut self);
 
-    /// The current framebuffer as a packed vector of u32 values. Each
-    /// value u32 values represents a single pixel on the format XRGB. The `X`
+    /// The part of the display that changed since the dirty flag was last cleared, for
+    /// frontends that only redraw what changed. The whole display by default.
+    fn dirty_region(&self) -> Option<Rect> {
+        if self.is_dirty() {
+            Some(Rect::new(0, 0, self.width(), self.height()))
+        } else {
+            None
+        }
+    }
+
+    /// Replace the contents of `pixels` with the current framebuffer as packed u32 values,
+    /// reusing its allocation. Each value represents a single pixel in the format XRGB. The `X`
     /// nibble is ignored when rendering as alpha is not supported.
     ///
-    /// Should be in row major layout.
-    fn rgba_framebuffer(&self) -> Vec<u32>;
+    /// Should be in row major layout, [`Display::width`] pixels per row.
+    fn write_rgba(&self, pixels: &mut Vec<u32>);
+
+    /// The current framebuffer as written by [`Display::write_rgba`], in a new vector.
+    fn rgba_framebuffer(&self) -> Vec<u32> {
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+        self.write_rgba(&mut pixels);
+
+        pixels
+    }
 
     /// Draw a sprite at `x`, `y` in the display starting from `base_address` in the RAM.
-    /// `bytes_to_read` specifies the height of sprite to draw.
+    /// `bytes_to_read` specifies the height of sprite to draw. With more than one plane
+    /// selected there is a sprite per plane, one after the other in memory. Sprites that
+    /// extend past the end of memory wrap around to address 0, see
+    /// [`Memory::read_wrapping`].
     fn draw_sprite(
         &mut self,
         x: u8,
         y: u8,
         base_address: u16,
         bytes_to_read: u8,
-        memory: &memory::Memory,
+        memory: &Memory,
     ) -> bool;
 
-    /// Clear the screen by setting all pixels back to 0.
+    /// Clear the screen by setting all pixels in the selected planes back to 0.
     fn cls(&mut self);
+
+    /// Select the planes that drawing, clearing, and scrolling affect as a bit mask, only
+    /// plane 1 is selected initially. Only XO-CHIP has more than one plane, pixels set in
+    /// plane 2 or both planes are drawn in additional colors.
+    fn select_planes(&mut self, planes: u8);
+
+    /// Scroll the selected planes up by `rows` pixels, filling in blank rows at the bottom.
+    fn scroll_up(&mut self, rows: u8);
+
+    /// Whether sprites that go past the edges of the display are clipped, rather than wrapped
+    /// around to the other side. Sprites are wrapped initially.
+    fn set_clipping(&mut self, clip: bool);
+
+    /// The colors [`Display::rgba_framebuffer`] draws pixels in, ignored by displays that
+    /// don't produce colors.
+    fn set_palette(&mut self, _palette: Palette) {}
+
+    /// The bit mask of the planes every pixel is set in, row major, for undoing instructions
+    /// in a [`Debugger`](debugger::Debugger). `None` by default, displays that return the
+    /// pixels also need to implement [`Display::set_pixels`].
+    fn pixels(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Replace every pixel with `pixels` as returned by [`Display::pixels`].
+    fn set_pixels(&mut self, _pixels: &[u8]) {}
+
+    /// The pixels that changed since the last call, for frontends that send the display
+    /// elsewhere, e.g. over a network, and only want to send what changed. The first call
+    /// returns every pixel. Unlike [`Display::dirty_region`] this doesn't depend on the dirty
+    /// flag, and pixels that changed back aren't included. Every pixel in [`Display::pixels`]
+    /// by default.
+    fn take_dirty_pixels(&mut self) -> Vec<PixelChange> {
+        match self.pixels() {
+            Some(pixels) => display::all_pixels(&pixels, self.width()),
+            None => Vec::new(),
+        }
+    }
+
+    /// A hash of what's on the display, for checking that a ROM draws the expected screen
+    /// without storing an image of it. Doesn't depend on the palette when the display
+    /// implements [`Display::pixels`].
+    fn frame_hash(&self) -> u64 {
+        let size = [self.width() as u8, self.height() as u8];
+        match self.pixels() {
+            Some(pixels) => hash::fnv1a(size.iter().chain(&pixels).cloned()),
+            None => hash::fnv1a(
+                self.rgba_framebuffer()
+                    .iter()
+                    .flat_map(|pixel| pixel.to_le_bytes().to_vec()),
+            ),
+        }
+    }
 }
@@ -1,6 +1,11 @@
+use std::borrow::Cow;
 use std::ops::{Index, IndexMut};
 
+use crate::error::Error;
+
 const MEMORY_SIZE: usize = 4096;
+/// Where programs are loaded, everything before it belongs to the interpreter.
+const PROGRAM_START: u16 = 0x200;
 const FONTSET_BASE_ADDRESS: u16 = 0x50;
 const FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -21,12 +26,26 @@ const FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-/// Main memory holding 4KiB of data.
+/// Which addresses programs may write to, [`AccessPolicy::Protected`] unless changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPolicy {
+    /// Writes below 0x200, the interpreter area with the font, are an error. A program doing
+    /// so is almost certainly buggy, or running with the wrong quirks.
+    Protected,
+    /// Everything in memory can be written, e.g. for deliberately patching the font.
+    Unrestricted,
+}
+
+/// Main memory holding 4KiB of data, or 64KiB for XO-CHIP.
 /// The first 0x200 locations are reserved for private
 /// use, namely the built in font.
 ///
+/// Displays outside of this crate read sprites from it in
+/// [`Display::draw_sprite`](crate::Display::draw_sprite).
+#[derive(Clone)]
 pub struct Memory {
-    memory: [u8; MEMORY_SIZE],
+    memory: Vec<u8>,
+    policy: AccessPolicy,
 }
 
 impl Memory {
@@ -36,11 +55,34 @@ impl Memory {
     /// and a ROM can be loaded at 0x200 to start execution.
     ///
     fn new() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+        Self::with_size(MEMORY_SIZE)
+    }
+
+    /// Memory with `size` bytes, at most 64KiB since addresses are 16 bits.
+    pub fn with_size(size: usize) -> Self {
+        assert!(
+            size > 0x200 && size <= 0x10000,
+            "Invalid memory size {:#x}",
+            size
+        );
+        let mut memory = vec![0; size];
         memory[(FONTSET_BASE_ADDRESS as usize)..(FONTSET_BASE_ADDRESS as usize + FONTSET.len())]
             .copy_from_slice(&FONTSET);
 
-        Self { memory }
+        Self {
+            memory,
+            policy: AccessPolicy::Protected,
+        }
+    }
+
+    pub fn access_policy(&self) -> AccessPolicy {
+        self.policy
+    }
+
+    /// Change which addresses [`Memory::write`] and [`Memory::write_slice`] accept. Loading
+    /// with [`Memory::copy_from_slice`] is never restricted.
+    pub fn set_access_policy(&mut self, policy: AccessPolicy) {
+        self.policy = policy;
     }
 
     pub fn font_address_for_character(&self, character: u8) -> u16 {
@@ -52,9 +94,119 @@ impl Memory {
             .copy_from_slice(slice);
     }
 
+    /// The number of addressable bytes.
+    pub fn size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Every byte of memory, including the interpreter area.
+    pub fn bytes(&self) -> &[u8] {
+        &self.memory
+    }
+
     pub fn as_slice(&self, base_address: u16, length: u16) -> &[u8] {
         &self.memory[base_address as usize..(base_address as usize + length as usize)]
     }
+
+    /// Like indexing, but out of bounds addresses are an error rather than a panic.
+    pub fn read(&self, address: u16) -> Result<u8, Error> {
+        self.memory
+            .get(address as usize)
+            .cloned()
+            .ok_or(Error::MemoryOutOfBounds {
+                address: address as usize,
+            })
+    }
+
+    /// Like indexing, but out of bounds addresses, and protected ones under the
+    /// [`AccessPolicy`], are an error rather than a panic.
+    pub fn write(&mut self, address: u16, value: u8) -> Result<(), Error> {
+        self.check_writable(address)?;
+        let byte = self
+            .memory
+            .get_mut(address as usize)
+            .ok_or(Error::MemoryOutOfBounds {
+                address: address as usize,
+            })?;
+        *byte = value;
+
+        Ok(())
+    }
+
+    /// Like [`Memory::as_slice`], but a range that extends past the end of memory is an error.
+    pub fn read_slice(&self, base_address: u16, length: u16) -> Result<&[u8], Error> {
+        let range = self.checked_range(base_address, length as usize)?;
+
+        Ok(&self.memory[range])
+    }
+
+    /// Like [`Memory::copy_from_slice`], but a range that extends past the end of memory, or
+    /// starts at a protected address, is an error.
+    pub fn write_slice(&mut self, base_address: u16, slice: &[u8]) -> Result<(), Error> {
+        if !slice.is_empty() {
+            self.check_writable(base_address)?;
+        }
+        let range = self.checked_range(base_address, slice.len())?;
+        self.memory[range].copy_from_slice(slice);
+
+        Ok(())
+    }
+
+    /// Like [`Memory::read_slice`], but a range that extends past the end of memory wraps
+    /// around to address 0. Only copies when it does.
+    pub fn read_wrapping(&self, base_address: u16, length: u16) -> Cow<'_, [u8]> {
+        let start = base_address as usize % self.memory.len();
+        let end = start + length as usize;
+        if end <= self.memory.len() {
+            return Cow::Borrowed(&self.memory[start..end]);
+        }
+
+        Cow::Owned(
+            (start..end)
+                .map(|address| self.memory[address % self.memory.len()])
+                .collect(),
+        )
+    }
+
+    /// Like [`Memory::write_slice`], but a range that extends past the end of memory wraps
+    /// around to address 0. Wrapping into the interpreter area is an error under
+    /// [`AccessPolicy::Protected`], and nothing is written then.
+    pub fn write_wrapping(&mut self, base_address: u16, slice: &[u8]) -> Result<(), Error> {
+        let size = self.memory.len();
+        let addresses = (0..slice.len()).map(|offset| (base_address as usize + offset) % size);
+        for address in addresses.clone() {
+            self.check_writable(address as u16)?;
+        }
+        for (address, &value) in addresses.zip(slice) {
+            self.memory[address] = value;
+        }
+
+        Ok(())
+    }
+
+    fn check_writable(&self, address: u16) -> Result<(), Error> {
+        match self.policy {
+            AccessPolicy::Protected if address < PROGRAM_START => {
+                Err(Error::ProtectedMemoryWrite { address })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn checked_range(
+        &self,
+        base_address: u16,
+        length: usize,
+    ) -> Result<std::ops::Range<usize>, Error> {
+        let end = base_address as usize + length;
+        if end > self.memory.len() {
+            return Err(Error::MemoryOutOfBounds {
+                address: self.memory.len().max(base_address as usize),
+            });
+        }
+
+        Ok(base_address as usize..end)
+    }
 }
 
 impl Default for Memory {
@@ -68,7 +220,7 @@ impl Index<u16> for Memory {
 
     fn index(&self, address: u16) -> &Self::Output {
         assert!(
-            address < MEMORY_SIZE as u16,
+            (address as usize) < self.memory.len(),
             "Invalid memory address {:#02x}",
             address
         );
@@ -80,7 +232,7 @@ impl Index<u16> for Memory {
 impl IndexMut<u16> for Memory {
     fn index_mut(&mut self, address: u16) -> &mut Self::Output {
         assert!(
-            address < MEMORY_SIZE as u16,
+            (address as usize) < self.memory.len(),
             "Invalid memory address {:#02x}",
             address
         );
@@ -91,7 +243,8 @@ impl IndexMut<u16> for Memory {
 
 #[cfg(test)]
 mod tests {
-    use super::{Memory, FONTSET_BASE_ADDRESS};
+    use super::{AccessPolicy, Memory, FONTSET_BASE_ADDRESS};
+    use crate::error::Error;
 
     #[test]
     fn test_default() {
@@ -130,4 +283,56 @@ mod tests {
 
         assert_eq!(memory.as_slice(FONTSET_BASE_ADDRESS + 20, 5), &expected);
     }
+
+    #[test]
+    fn test_checked_access() {
+        let mut memory = Memory::default();
+
+        memory.write(0x300, 0xAB).unwrap();
+        assert_eq!(memory.read(0x300), Ok(0xAB));
+        memory.write_slice(0xFFE, &[0x01, 0x02]).unwrap();
+        assert_eq!(memory.read_slice(0xFFE, 2), Ok(&[0x01, 0x02][..]));
+
+        let out_of_bounds = Error::MemoryOutOfBounds { address: 0x1000 };
+        assert_eq!(memory.read(0x1000), Err(out_of_bounds.clone()));
+        assert_eq!(memory.write(0x1000, 0), Err(out_of_bounds.clone()));
+        assert_eq!(memory.read_slice(0xFFE, 3), Err(out_of_bounds.clone()));
+        assert_eq!(memory.write_slice(0xFFF, &[0, 0]), Err(out_of_bounds));
+    }
+
+    #[test]
+    fn test_wrapping_access() {
+        let mut memory = Memory::default();
+        memory.set_access_policy(AccessPolicy::Unrestricted);
+
+        memory.write_wrapping(0xFFE, &[0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(memory[0xFFF], 0x02);
+        assert_eq!(memory[0x000], 0x03);
+        assert_eq!(memory.read_wrapping(0xFFE, 3), &[0x01, 0x02, 0x03][..]);
+        // Addresses past the end of smaller memories wrap too
+        assert_eq!(memory.read_wrapping(0x1050, 1), &[0xF0][..]);
+
+        memory.set_access_policy(AccessPolicy::Protected);
+        assert_eq!(
+            memory.write_wrapping(0xFFF, &[0xAA, 0xBB]),
+            Err(Error::ProtectedMemoryWrite { address: 0x000 })
+        );
+        assert_eq!(memory[0xFFF], 0x02);
+    }
+
+    #[test]
+    fn test_access_policy() {
+        let mut memory = Memory::default();
+
+        let protected = Error::ProtectedMemoryWrite { address: 0x1FF };
+        assert_eq!(memory.write(0x1FF, 0xAB), Err(protected.clone()));
+        assert_eq!(memory.write_slice(0x1FF, &[0xAB, 0xCD]), Err(protected));
+        assert_eq!(memory.write(0x200, 0xAB), Ok(()));
+        // Loading a ROM or the font isn't restricted
+        memory.copy_from_slice(0x100, &[0xAB]);
+
+        memory.set_access_policy(AccessPolicy::Unrestricted);
+        assert_eq!(memory.write_slice(0x1FF, &[0xAB, 0xCD]), Ok(()));
+        assert_eq!(memory.read_slice(0x1FF, 2), Ok(&[0xAB, 0xCD][..]));
+    }
 }
@@ -0,0 +1,820 @@
+//! Assembling [Octo](https://github.com/JohnEarnest/Octo) source, the syntax most CHIP-8
+//! programs are written in nowadays.
+//!
+//! ```text
+//! # Comments start with a hash
+//! :alias x v0
+//! :const SPEED 2
+//!
+//! : main
+//!     i := ball
+//!     loop
+//!         sprite x x 2
+//!         x += SPEED
+//!         if x == 60 then x := 0
+//!     again
+//!
+//! : ball
+//!     0x80 0xC0
+//! ```
+//!
+//! Supported are labels, `:alias`, `:const`, `:call`, and `:byte`, every statement that maps to
+//! an instruction of the variants the emulator runs, `if ... then`,
+//! `if ... begin ... else ... end`, `loop ... again` with `while`, and numbers as raw bytes.
+//! Macros, `:calc`, `:org`, the comparisons other than `==`, `!=`, `key`, and `-key`, and the
+//! SUPER-CHIP scrolling and resolution instructions aren't.
+use std::collections::HashMap;
+
+use crate::assembler::{link, AssemblerError, Operand, Statement, Value, ROM_BASE_ADDRESS};
+
+/// Octo features that are reported as unsupported rather than as unknown.
+const UNSUPPORTED: [&str; 14] = [
+    ":macro",
+    ":calc",
+    ":org",
+    ":next",
+    ":unpack",
+    ":pointer",
+    ":stringmode",
+    ":assert",
+    "hires",
+    "lores",
+    "scroll-down",
+    "scroll-left",
+    "scroll-right",
+    "exit",
+];
+
+/// A condition of `if` and `while`.
+enum Condition {
+    Equal(u8, Operand),
+    NotEqual(u8, Operand),
+    /// The key in the register is held down.
+    Key(u8),
+    NotKey(u8),
+}
+
+impl Condition {
+    /// The instruction that skips the next one when the condition is `is_true`.
+    fn skip_if(self, is_true: bool) -> (&'static str, Vec<Operand>) {
+        match self {
+            Condition::Equal(x, operand) if is_true => ("SE", vec![Operand::Register(x), operand]),
+            Condition::Equal(x, operand) => ("SNE", vec![Operand::Register(x), operand]),
+            Condition::NotEqual(x, operand) => Condition::Equal(x, operand).skip_if(!is_true),
+            Condition::Key(x) if is_true => ("SKP", vec![Operand::Register(x)]),
+            Condition::Key(x) => ("SKNP", vec![Operand::Register(x)]),
+            Condition::NotKey(x) => Condition::Key(x).skip_if(!is_true),
+        }
+    }
+}
+
+/// An `if ... begin` or `loop` waiting for its `end` or `again`, started on `line`.
+enum Block {
+    If {
+        line: usize,
+        /// Where to go when the condition doesn't hold, the `else` or the `end`.
+        otherwise: String,
+        /// The `end`, once there's an `else` to jump over.
+        end: Option<String>,
+    },
+    Loop {
+        line: usize,
+        start: String,
+        /// Where `while` jumps to, right after the `again`.
+        end: String,
+    },
+}
+
+/// Turns the tokens of a program into statements, keeping track of the names it defines.
+struct Parser<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    position: usize,
+    aliases: HashMap<String, u8>,
+    constants: HashMap<String, u16>,
+    labels: HashMap<String, u16>,
+    statements: Vec<Statement>,
+    address: u16,
+    blocks: Vec<Block>,
+    /// For naming the labels of blocks, which can't clash with the program's own labels
+    /// since they contain a space.
+    block_count: usize,
+}
+
+/// Assemble Octo `source` into a ROM to be loaded at 0x200, see the [module
+/// documentation](self) for what's supported.
+pub fn assemble_octo(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |token| (index + 1, token))
+        })
+        .collect();
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        aliases: HashMap::new(),
+        constants: HashMap::new(),
+        labels: HashMap::new(),
+        statements: Vec::new(),
+        address: ROM_BASE_ADDRESS,
+        blocks: Vec::new(),
+        block_count: 0,
+    };
+
+    while let Some((line, token)) = parser.next_token() {
+        parser.parse_statement(line, token)?;
+    }
+    if let Some(block) = parser.blocks.last() {
+        return Err(match block {
+            Block::If { line, .. } => AssemblerError::new(*line, "`if` without `end`".to_string()),
+            Block::Loop { line, .. } => {
+                AssemblerError::new(*line, "`loop` without `again`".to_string())
+            }
+        });
+    }
+
+    link(&parser.statements, &parser.labels)
+}
+
+impl<'a> Parser<'a> {
+    fn next_token(&mut self) -> Option<(usize, &'a str)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+
+        token
+    }
+
+    /// The next token, which `after` on `line` needs.
+    fn expect(&mut self, line: usize, after: &str) -> Result<&'a str, AssemblerError> {
+        self.next_token()
+            .map(|(_, token)| token)
+            .ok_or_else(|| AssemblerError::new(line, format!("Expected more after `{}`", after)))
+    }
+
+    /// Check that the next token is `keyword`.
+    fn keyword(&mut self, line: usize, keyword: &str) -> Result<(), AssemblerError> {
+        match self.next_token() {
+            Some((_, token)) if token == keyword => Ok(()),
+            Some((line, token)) => Err(AssemblerError::new(
+                line,
+                format!("Expected `{}`, found `{}`", keyword, token),
+            )),
+            None => Err(AssemblerError::new(line, format!("Expected `{}`", keyword))),
+        }
+    }
+
+    fn parse_statement(&mut self, line: usize, token: &'a str) -> Result<(), AssemblerError> {
+        use Operand::*;
+
+        match token {
+            ":" => {
+                let name = self.expect(line, ":")?;
+                self.define_label(line, name)?;
+            }
+            ":alias" => {
+                let name = self.name(line, ":alias")?;
+                let register = self.expect(line, name)?;
+                let register = self.register(line, register)?;
+                self.aliases.insert(name.to_string(), register);
+            }
+            ":const" => {
+                let name = self.name(line, ":const")?;
+                let value = self.expect(line, name)?;
+                let value = self.number(value).ok_or_else(|| {
+                    AssemblerError::new(line, format!("Invalid constant `{}`", value))
+                })?;
+                self.constants.insert(name.to_string(), value);
+            }
+            ":call" => {
+                let target = self.expect(line, token)?;
+                let target = self.address(line, target)?;
+                self.push(line, "CALL", vec![Immediate(target)])?;
+            }
+            ":byte" => {
+                let value = self.expect(line, token)?;
+                let value = self.byte(line, value)?;
+                self.push(line, "DB", vec![Immediate(value)])?;
+            }
+            "clear" => self.push(line, "CLS", vec![])?,
+            "return" | ";" => self.push(line, "RET", vec![])?,
+            "jump" => {
+                let target = self.expect(line, token)?;
+                let target = self.address(line, target)?;
+                self.push(line, "JP", vec![Immediate(target)])?;
+            }
+            "jump0" => {
+                let target = self.expect(line, token)?;
+                let target = self.address(line, target)?;
+                self.push(line, "JP", vec![Register(0), Immediate(target)])?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.expect(line, token)?;
+                let x = self.register(line, x)?;
+                let operands = match token {
+                    "bcd" => vec![Bcd, Register(x)],
+                    "saveflags" => vec![Flags, Register(x)],
+                    _ => vec![Register(x), Flags],
+                };
+                self.push(line, "LD", operands)?;
+            }
+            "save" | "load" => {
+                let x = self.expect(line, token)?;
+                let x = self.register(line, x)?;
+                let is_range = matches!(self.tokens.get(self.position), Some((_, "-")));
+                if is_range {
+                    self.position += 1;
+                    let y = self.expect(line, "-")?;
+                    let y = self.register(line, y)?;
+                    let mnemonic = if token == "save" { "SAVE" } else { "LOAD" };
+                    self.push(line, mnemonic, vec![Register(x), Register(y)])?;
+                } else if token == "save" {
+                    self.push(line, "LD", vec![IndirectI, Register(x)])?;
+                } else {
+                    self.push(line, "LD", vec![Register(x), IndirectI])?;
+                }
+            }
+            "sprite" => {
+                let x = self.expect(line, token)?;
+                let x = self.register(line, x)?;
+                let y = self.expect(line, token)?;
+                let y = self.register(line, y)?;
+                let height = self.expect(line, token)?;
+                let height = self.byte(line, height)?;
+                self.push(
+                    line,
+                    "DRW",
+                    vec![Register(x), Register(y), Immediate(height)],
+                )?;
+            }
+            "plane" | "scroll-up" => {
+                let n = self.expect(line, token)?;
+                let n = self.byte(line, n)?;
+                let mnemonic = if token == "plane" { "PLANE" } else { "SCU" };
+                self.push(line, mnemonic, vec![Immediate(n)])?;
+            }
+            "audio" => self.push(line, "AUDIO", vec![])?,
+            "pitch" => {
+                self.keyword(line, ":=")?;
+                let x = self.expect(line, ":=")?;
+                let x = self.register(line, x)?;
+                self.push(line, "PITCH", vec![Register(x)])?;
+            }
+            "delay" | "buzzer" => {
+                self.keyword(line, ":=")?;
+                let x = self.expect(line, ":=")?;
+                let x = self.register(line, x)?;
+                let timer = if token == "delay" {
+                    DelayTimer
+                } else {
+                    SoundTimer
+                };
+                self.push(line, "LD", vec![timer, Register(x)])?;
+            }
+            "i" => self.parse_i(line)?,
+            "if" => {
+                let condition = self.condition(line)?;
+                match self.expect(line, "if")? {
+                    "then" => {
+                        let (mnemonic, operands) = condition.skip_if(false);
+                        self.push(line, mnemonic, operands)?;
+                    }
+                    "begin" => {
+                        let otherwise = self.block_label("else");
+                        let (mnemonic, operands) = condition.skip_if(true);
+                        self.push(line, mnemonic, operands)?;
+                        self.push(line, "JP", vec![Immediate(Value::Label(otherwise.clone()))])?;
+                        self.blocks.push(Block::If {
+                            line,
+                            otherwise,
+                            end: None,
+                        });
+                    }
+                    other => {
+                        return Err(AssemblerError::new(
+                            line,
+                            format!("Expected `then` or `begin`, found `{}`", other),
+                        ))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If {
+                    line: if_line,
+                    otherwise,
+                    end: None,
+                }) => {
+                    let end = self.block_label("end");
+                    self.push(line, "JP", vec![Immediate(Value::Label(end.clone()))])?;
+                    self.define_label(line, &otherwise)?;
+                    self.blocks.push(Block::If {
+                        line: if_line,
+                        otherwise,
+                        end: Some(end),
+                    });
+                }
+                _ => return Err(AssemblerError::new(line, "Unexpected `else`".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { otherwise, end, .. }) => {
+                    self.define_label(line, &end.unwrap_or(otherwise))?;
+                }
+                _ => return Err(AssemblerError::new(line, "Unexpected `end`".to_string())),
+            },
+            "loop" => {
+                let start = self.block_label("loop");
+                let end = self.block_label("again");
+                self.define_label(line, &start)?;
+                self.blocks.push(Block::Loop { line, start, end });
+            }
+            "while" => {
+                let end = self.blocks.iter().rev().find_map(|block| match block {
+                    Block::Loop { end, .. } => Some(end.clone()),
+                    Block::If { .. } => None,
+                });
+                let end = end.ok_or_else(|| {
+                    AssemblerError::new(line, "`while` outside of a loop".to_string())
+                })?;
+                let (mnemonic, operands) = self.condition(line)?.skip_if(true);
+                self.push(line, mnemonic, operands)?;
+                self.push(line, "JP", vec![Immediate(Value::Label(end))])?;
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, end, .. }) => {
+                    self.push(line, "JP", vec![Immediate(Value::Label(start))])?;
+                    self.define_label(line, &end)?;
+                }
+                _ => return Err(AssemblerError::new(line, "Unexpected `again`".to_string())),
+            },
+            _ if UNSUPPORTED.contains(&token) => {
+                return Err(AssemblerError::new(
+                    line,
+                    format!("`{}` isn't supported", token),
+                ))
+            }
+            _ if self.is_register(token) => self.parse_assignment(line, token)?,
+            _ if self.number(token).is_some() => {
+                let value = self.byte(line, token)?;
+                self.push(line, "DB", vec![Immediate(value)])?;
+            }
+            _ if is_name(token) => self.push(
+                line,
+                "CALL",
+                vec![Immediate(Value::Label(token.to_string()))],
+            )?,
+            _ => {
+                return Err(AssemblerError::new(
+                    line,
+                    format!("Unknown statement `{}`", token),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `i := NNN`, `i := long NNNN`, `i := hex VX`, or `i += VX`.
+    fn parse_i(&mut self, line: usize) -> Result<(), AssemblerError> {
+        use Operand::*;
+
+        match self.expect(line, "i")? {
+            ":=" => match self.expect(line, ":=")? {
+                "long" => {
+                    let target = self.expect(line, "long")?;
+                    let target = self.address(line, target)?;
+                    self.push(line, "LD", vec![I, Long(target)])
+                }
+                "hex" => {
+                    let x = self.expect(line, "hex")?;
+                    let x = self.register(line, x)?;
+                    self.push(line, "LD", vec![Font, Register(x)])
+                }
+                "bighex" => Err(AssemblerError::new(
+                    line,
+                    "`bighex` isn't supported".to_string(),
+                )),
+                target => {
+                    let target = self.address(line, target)?;
+                    self.push(line, "LD", vec![I, Immediate(target)])
+                }
+            },
+            "+=" => {
+                let x = self.expect(line, "+=")?;
+                let x = self.register(line, x)?;
+                self.push(line, "ADD", vec![I, Register(x)])
+            }
+            other => Err(AssemblerError::new(
+                line,
+                format!("Unknown operator `{}` for i", other),
+            )),
+        }
+    }
+
+    /// A statement starting with `VX`, e.g. `VX := NN` or `VX ^= VY`.
+    fn parse_assignment(&mut self, line: usize, register: &str) -> Result<(), AssemblerError> {
+        use Operand::*;
+
+        let x = self.register(line, register)?;
+        let operator = self.expect(line, register)?;
+        let source = self.expect(line, operator)?;
+        let is_register = self.is_register(source);
+
+        let (mnemonic, operands) = match operator {
+            ":=" if source == "random" => {
+                let mask = self.expect(line, "random")?;
+                let mask = self.byte(line, mask)?;
+                ("RND", vec![Register(x), Immediate(mask)])
+            }
+            ":=" if source == "delay" => ("LD", vec![Register(x), DelayTimer]),
+            ":=" if source == "key" => ("LD", vec![Register(x), Key]),
+            ":=" if is_register => (
+                "LD",
+                vec![Register(x), Register(self.register(line, source)?)],
+            ),
+            ":=" => ("LD", vec![Register(x), Immediate(self.byte(line, source)?)]),
+            "+=" if is_register => (
+                "ADD",
+                vec![Register(x), Register(self.register(line, source)?)],
+            ),
+            "+=" => (
+                "ADD",
+                vec![Register(x), Immediate(self.byte(line, source)?)],
+            ),
+            "-=" if is_register => (
+                "SUB",
+                vec![Register(x), Register(self.register(line, source)?)],
+            ),
+            "-=" => {
+                // There's no instruction for it, add the two's complement instead
+                let value = match self.number(source) {
+                    Some(value) if value <= 0xFF => (0x100 - value) & 0xFF,
+                    _ => {
+                        return Err(AssemblerError::new(
+                            line,
+                            format!("Invalid number `{}`", source),
+                        ))
+                    }
+                };
+                ("ADD", vec![Register(x), Immediate(Value::Number(value))])
+            }
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = self.register(line, source)?;
+                let mnemonic = match operator {
+                    "=-" => "SUBN",
+                    "|=" => "OR",
+                    "&=" => "AND",
+                    "^=" => "XOR",
+                    ">>=" => "SHR",
+                    _ => "SHL",
+                };
+                (mnemonic, vec![Register(x), Register(y)])
+            }
+            _ => {
+                return Err(AssemblerError::new(
+                    line,
+                    format!("Unknown operator `{}`", operator),
+                ))
+            }
+        };
+
+        self.push(line, mnemonic, operands)
+    }
+
+    /// The condition of an `if` or `while`: `VX == VY`, `VX != NN`, `VX key`, or `VX -key`.
+    fn condition(&mut self, line: usize) -> Result<Condition, AssemblerError> {
+        let x = self.expect(line, "if")?;
+        let x = self.register(line, x)?;
+        let operator = self.expect(line, "if")?;
+        let operand = |parser: &mut Self| -> Result<Operand, AssemblerError> {
+            let operand = parser.expect(line, operator)?;
+            if parser.is_register(operand) {
+                Ok(Operand::Register(parser.register(line, operand)?))
+            } else {
+                Ok(Operand::Immediate(parser.byte(line, operand)?))
+            }
+        };
+
+        match operator {
+            "==" => Ok(Condition::Equal(x, operand(self)?)),
+            "!=" => Ok(Condition::NotEqual(x, operand(self)?)),
+            "key" => Ok(Condition::Key(x)),
+            "-key" => Ok(Condition::NotKey(x)),
+            "<" | ">" | "<=" | ">=" => Err(AssemblerError::new(
+                line,
+                format!(
+                    "`{}` isn't supported, only `==`, `!=`, `key`, and `-key`",
+                    operator
+                ),
+            )),
+            _ => Err(AssemblerError::new(
+                line,
+                format!("Unknown comparison `{}`", operator),
+            )),
+        }
+    }
+
+    fn push(
+        &mut self,
+        line: usize,
+        mnemonic: &str,
+        operands: Vec<Operand>,
+    ) -> Result<(), AssemblerError> {
+        let statement = Statement {
+            line,
+            mnemonic: mnemonic.to_string(),
+            operands,
+        };
+        self.address = self
+            .address
+            .checked_add(statement.size())
+            .ok_or_else(|| AssemblerError::new(line, "The ROM is too large".to_string()))?;
+        self.statements.push(statement);
+
+        Ok(())
+    }
+
+    fn define_label(&mut self, line: usize, name: &str) -> Result<(), AssemblerError> {
+        if !is_name(name) && !name.contains(' ') {
+            return Err(AssemblerError::new(
+                line,
+                format!("Invalid label `{}`", name),
+            ));
+        }
+        if self.labels.insert(name.to_string(), self.address).is_some() {
+            return Err(AssemblerError::new(
+                line,
+                format!("Duplicate label `{}`", name),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// A new label for a block, e.g. `else 3`.
+    fn block_label(&mut self, kind: &str) -> String {
+        self.block_count += 1;
+
+        format!("{} {}", kind, self.block_count)
+    }
+
+    /// The name that follows `directive`.
+    fn name(&mut self, line: usize, directive: &str) -> Result<&'a str, AssemblerError> {
+        let name = self.expect(line, directive)?;
+        if !is_name(name) {
+            return Err(AssemblerError::new(
+                line,
+                format!("Invalid name `{}`", name),
+            ));
+        }
+
+        Ok(name)
+    }
+
+    fn is_register(&self, token: &str) -> bool {
+        parse_register(token).is_some() || self.aliases.contains_key(token)
+    }
+
+    fn register(&self, line: usize, token: &str) -> Result<u8, AssemblerError> {
+        parse_register(token)
+            .or_else(|| self.aliases.get(token).cloned())
+            .ok_or_else(|| {
+                AssemblerError::new(line, format!("Expected a register, found `{}`", token))
+            })
+    }
+
+    /// A number or a constant.
+    fn number(&self, token: &str) -> Option<u16> {
+        parse_number(token).or_else(|| self.constants.get(token).cloned())
+    }
+
+    /// A byte operand, negative numbers count down from 0x100.
+    fn byte(&self, line: usize, token: &str) -> Result<Value, AssemblerError> {
+        self.number(token)
+            .map(Value::Number)
+            .ok_or_else(|| AssemblerError::new(line, format!("Invalid number `{}`", token)))
+    }
+
+    /// An address operand, a number, a constant, or a label that may be defined later.
+    fn address(&self, line: usize, token: &str) -> Result<Value, AssemblerError> {
+        match self.number(token) {
+            Some(number) => Ok(Value::Number(number)),
+            None if is_name(token) => Ok(Value::Label(token.to_string())),
+            None => Err(AssemblerError::new(
+                line,
+                format!("Invalid address `{}`", token),
+            )),
+        }
+    }
+}
+
+/// `v0` to `vF`, in any case.
+fn parse_register(token: &str) -> Option<u8> {
+    let digit = token
+        .strip_prefix('v')
+        .or_else(|| token.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// A decimal, `0x` hex, or `0b` binary number. Negative numbers are bytes counting down from
+/// 0x100, e.g. -1 is 0xFF.
+fn parse_number(token: &str) -> Option<u16> {
+    let (is_negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let number = if let Some(hex) = digits.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    match (is_negative, number) {
+        (false, _) => Some(number),
+        (true, 1..=0x80) => Some(0x100 - number),
+        (true, _) => None,
+    }
+}
+
+/// Octo names may also contain dashes, e.g. `draw-player`.
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assemble_octo;
+    use crate::assembler::{assemble, AssemblerError};
+
+    fn error(line: usize, message: &str) -> Result<Vec<u8>, AssemblerError> {
+        Err(AssemblerError::new(line, message.to_string()))
+    }
+
+    #[test]
+    fn test_statements() {
+        let octo = "
+            : main
+                clear
+                v3 := 0x42  va := vb  v1 := random 0x0F  v2 := delay  v5 := key
+                v3 += 1  v3 += v4  v3 -= v4  v3 =- v4  v3 -= 1
+                v3 |= v4  v3 &= v4  v3 ^= v4  v3 >>= v4  v3 <<= v4
+                i := sprite  i := long sprite  i := hex v1  i += v2
+                delay := v1  buzzer := v2  bcd v3  save v2  load v2  save v1 - v2  load v2 - v1
+                saveflags v3  loadflags v3  plane 2  audio  pitch := v4  scroll-up 4
+                sprite v1 v2 5
+                :call main  main  jump main  jump0 main
+                return ;
+            : sprite
+                0x80 0b11000000 255 -1 :byte 3
+        ";
+        let classic = "
+            main:
+                CLS
+                LD V3, 0x42
+                LD VA, VB
+                RND V1, 0x0F
+                LD V2, DT
+                LD V5, K
+                ADD V3, 1
+                ADD V3, V4
+                SUB V3, V4
+                SUBN V3, V4
+                ADD V3, 0xFF
+                OR V3, V4
+                AND V3, V4
+                XOR V3, V4
+                SHR V3, V4
+                SHL V3, V4
+                LD I, sprite
+                LD I, LONG sprite
+                LD F, V1
+                ADD I, V2
+                LD DT, V1
+                LD ST, V2
+                LD B, V3
+                LD [I], V2
+                LD V2, [I]
+                SAVE V1, V2
+                LOAD V2, V1
+                LD R, V3
+                LD V3, R
+                PLANE 2
+                AUDIO
+                PITCH V4
+                SCU 4
+                DRW V1, V2, 5
+                CALL main
+                CALL main
+                JP main
+                JP V0, main
+                RET
+                RET
+            sprite:
+                DB 0x80, 0xC0, 0xFF, 0xFF, 3
+        ";
+
+        assert_eq!(assemble_octo(octo), assemble(classic));
+    }
+
+    #[test]
+    fn test_names() {
+        let octo = "
+            :alias x v4
+            :const speed 3
+            x := speed
+            x += -2  # Comments are ignored
+            jump draw-it
+            : draw-it
+            sprite x x speed
+        ";
+
+        assert_eq!(
+            assemble_octo(octo),
+            Ok(vec![0x64, 0x03, 0x74, 0xFE, 0x12, 0x06, 0xD4, 0x43])
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let octo = "
+            if v0 == 1 then v1 := 2
+            if v0 != v1 then clear
+            if v2 key then clear
+            if v2 -key then clear
+            if v0 == 5 begin
+                v1 := 1
+            else
+                v1 := 2
+            end
+            loop
+                v0 += 1
+                while v0 != 10
+            again
+        ";
+        let classic = "
+                SNE V0, 1
+                LD V1, 2
+                SE V0, V1
+                CLS
+                SKNP V2
+                CLS
+                SKP V2
+                CLS
+                SE V0, 5
+                JP else
+                LD V1, 1
+                JP end
+            else:
+                LD V1, 2
+            end:
+            loop:
+                ADD V0, 1
+                SNE V0, 10
+                JP again
+                JP loop
+            again:
+        ";
+
+        assert_eq!(assemble_octo(octo), assemble(classic));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble_octo("\nv0 := vx"), error(2, "Invalid number `vx`"));
+        assert_eq!(
+            assemble_octo("jump nowhere"),
+            error(1, "Unknown label `nowhere`")
+        );
+        assert_eq!(assemble_octo(": a\n: a"), error(2, "Duplicate label `a`"));
+        assert_eq!(
+            assemble_octo("if v0 == 1 begin"),
+            error(1, "`if` without `end`")
+        );
+        assert_eq!(
+            assemble_octo("loop\nagain\nagain"),
+            error(3, "Unexpected `again`")
+        );
+        assert_eq!(
+            assemble_octo("while v0 == 1"),
+            error(1, "`while` outside of a loop")
+        );
+        assert_eq!(assemble_octo("hires"), error(1, "`hires` isn't supported"));
+        assert_eq!(
+            assemble_octo("v0 := 256"),
+            error(1, "0x100 is larger than 0xff")
+        );
+        assert_eq!(assemble_octo("i :="), error(1, "Expected more after `:=`"));
+        assert_eq!(assemble_octo("v0 ?= v1"), error(1, "Unknown operator `?=`"));
+    }
+}
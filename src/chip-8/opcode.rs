@@ -0,0 +1,450 @@
+//! Decoding raw 16 bit opcodes into instructions, shared by the CPU and the disassembler.
+//!
+//! Mnemonics follow Cowgod's technical reference, e.g. `LD V3, 0x42` or `DRW V1, V2, 5`.
+//! Opcodes convert from and to their 16 bit words and parse from their mnemonics, for tools
+//! that build on the decoding:
+//!
+//! ```
+//! use std::convert::TryFrom;
+//!
+//! use chip_8::Opcode;
+//!
+//! let opcode = Opcode::try_from(0xD125).unwrap();
+//! assert_eq!(opcode.to_string(), "DRW V1, V2, 5");
+//! assert_eq!("DRW V1, V2, 5".parse(), Ok(opcode));
+//! assert_eq!(u16::from(opcode), 0xD125);
+//! ```
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// 00E0
+    Cls,
+    /// 00EE
+    Ret,
+    /// 0NNN, call a machine code routine on the original hardware.
+    Sys(u16),
+    /// 1NNN
+    Jp(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SeByte(u8, u8),
+    /// 4XNN
+    SneByte(u8, u8),
+    /// 5XY0
+    SeReg(u8, u8),
+    /// 6XNN
+    LdByte(u8, u8),
+    /// 7XNN
+    AddByte(u8, u8),
+    /// 8XY0
+    LdReg(u8, u8),
+    /// 8XY1
+    Or(u8, u8),
+    /// 8XY2
+    And(u8, u8),
+    /// 8XY3
+    Xor(u8, u8),
+    /// 8XY4
+    AddReg(u8, u8),
+    /// 8XY5
+    Sub(u8, u8),
+    /// 8XY6
+    Shr(u8, u8),
+    /// 8XY7
+    Subn(u8, u8),
+    /// 8XYE
+    Shl(u8, u8),
+    /// 9XY0
+    SneReg(u8, u8),
+    /// ANNN
+    LdI(u16),
+    /// BNNN
+    JpV0(u16),
+    /// CXNN
+    Rnd(u8, u8),
+    /// DXYN
+    Drw(u8, u8, u8),
+    /// EX9E
+    Skp(u8),
+    /// EXA1
+    Sknp(u8),
+    /// FX07
+    LdFromDelayTimer(u8),
+    /// FX0A
+    LdKey(u8),
+    /// FX15
+    LdDelayTimer(u8),
+    /// FX18
+    LdSoundTimer(u8),
+    /// FX1E
+    AddI(u8),
+    /// FX29
+    LdFont(u8),
+    /// FX33
+    LdBcd(u8),
+    /// FX55
+    Store(u8),
+    /// FX65
+    Load(u8),
+    /// 00DN (XO-CHIP)
+    ScrollUp(u8),
+    /// 5XY2 (XO-CHIP)
+    SaveRange(u8, u8),
+    /// 5XY3 (XO-CHIP)
+    LoadRange(u8, u8),
+    /// F000 NNNN (XO-CHIP), the only instruction that is 4 bytes.
+    LdILong(u16),
+    /// FN01 (XO-CHIP)
+    Plane(u8),
+    /// F002 (XO-CHIP)
+    Audio,
+    /// FX3A (XO-CHIP)
+    Pitch(u8),
+    /// FX75 (SUPER-CHIP, XO-CHIP), save V0 through VX to the persistent flags.
+    SaveFlags(u8),
+    /// FX85 (SUPER-CHIP, XO-CHIP), load V0 through VX from the persistent flags.
+    LoadFlags(u8),
+    /// A word that isn't an instruction.
+    Data(u16),
+    /// A trailing byte at the end of a ROM with an odd length, only produced when
+    /// disassembling.
+    Byte(u8),
+}
+
+impl Opcode {
+    /// Decode `opcode`. `next` is the word after it, only used by the 4 byte `F000 NNNN`.
+    pub fn decode(opcode: u16, next: u16) -> Self {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        match (opcode & 0xF000, n) {
+            (0x0000, _) => match opcode {
+                0x00E0 => Opcode::Cls,
+                0x00EE => Opcode::Ret,
+                _ if opcode & 0xFFF0 == 0x00D0 => Opcode::ScrollUp(n),
+                _ => Opcode::Sys(nnn),
+            },
+            (0x1000, _) => Opcode::Jp(nnn),
+            (0x2000, _) => Opcode::Call(nnn),
+            (0x3000, _) => Opcode::SeByte(x, nn),
+            (0x4000, _) => Opcode::SneByte(x, nn),
+            (0x5000, 0x0) => Opcode::SeReg(x, y),
+            (0x5000, 0x2) => Opcode::SaveRange(x, y),
+            (0x5000, 0x3) => Opcode::LoadRange(x, y),
+            (0x6000, _) => Opcode::LdByte(x, nn),
+            (0x7000, _) => Opcode::AddByte(x, nn),
+            (0x8000, 0x0) => Opcode::LdReg(x, y),
+            (0x8000, 0x1) => Opcode::Or(x, y),
+            (0x8000, 0x2) => Opcode::And(x, y),
+            (0x8000, 0x3) => Opcode::Xor(x, y),
+            (0x8000, 0x4) => Opcode::AddReg(x, y),
+            (0x8000, 0x5) => Opcode::Sub(x, y),
+            (0x8000, 0x6) => Opcode::Shr(x, y),
+            (0x8000, 0x7) => Opcode::Subn(x, y),
+            (0x8000, 0xE) => Opcode::Shl(x, y),
+            (0x9000, 0x0) => Opcode::SneReg(x, y),
+            (0xA000, _) => Opcode::LdI(nnn),
+            (0xB000, _) => Opcode::JpV0(nnn),
+            (0xC000, _) => Opcode::Rnd(x, nn),
+            (0xD000, _) => Opcode::Drw(x, y, n),
+            (0xE000, _) if nn == 0x9E => Opcode::Skp(x),
+            (0xE000, _) if nn == 0xA1 => Opcode::Sknp(x),
+            (0xF000, _) => match nn {
+                0x00 if x == 0 => Opcode::LdILong(next),
+                0x01 => Opcode::Plane(x),
+                0x02 if x == 0 => Opcode::Audio,
+                0x07 => Opcode::LdFromDelayTimer(x),
+                0x0A => Opcode::LdKey(x),
+                0x15 => Opcode::LdDelayTimer(x),
+                0x18 => Opcode::LdSoundTimer(x),
+                0x1E => Opcode::AddI(x),
+                0x29 => Opcode::LdFont(x),
+                0x3A => Opcode::Pitch(x),
+                0x33 => Opcode::LdBcd(x),
+                0x55 => Opcode::Store(x),
+                0x65 => Opcode::Load(x),
+                0x75 => Opcode::SaveFlags(x),
+                0x85 => Opcode::LoadFlags(x),
+                _ => Opcode::Data(opcode),
+            },
+            _ => Opcode::Data(opcode),
+        }
+    }
+
+    /// The bytes of the instruction, the inverse of [`Opcode::decode`].
+    pub fn encode(self) -> Vec<u8> {
+        let xy = |high: u16, x: u8, y: u8, n: u16| high | (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |high: u16, x: u8, nn: u8| high | (x as u16) << 8 | nn as u16;
+
+        let word = match self {
+            Opcode::Cls => 0x00E0,
+            Opcode::Ret => 0x00EE,
+            Opcode::Sys(address) => address,
+            Opcode::Jp(address) => 0x1000 | address,
+            Opcode::Call(address) => 0x2000 | address,
+            Opcode::SeByte(x, byte) => xnn(0x3000, x, byte),
+            Opcode::SneByte(x, byte) => xnn(0x4000, x, byte),
+            Opcode::SeReg(x, y) => xy(0x5000, x, y, 0x0),
+            Opcode::LdByte(x, byte) => xnn(0x6000, x, byte),
+            Opcode::AddByte(x, byte) => xnn(0x7000, x, byte),
+            Opcode::LdReg(x, y) => xy(0x8000, x, y, 0x0),
+            Opcode::Or(x, y) => xy(0x8000, x, y, 0x1),
+            Opcode::And(x, y) => xy(0x8000, x, y, 0x2),
+            Opcode::Xor(x, y) => xy(0x8000, x, y, 0x3),
+            Opcode::AddReg(x, y) => xy(0x8000, x, y, 0x4),
+            Opcode::Sub(x, y) => xy(0x8000, x, y, 0x5),
+            Opcode::Shr(x, y) => xy(0x8000, x, y, 0x6),
+            Opcode::Subn(x, y) => xy(0x8000, x, y, 0x7),
+            Opcode::Shl(x, y) => xy(0x8000, x, y, 0xE),
+            Opcode::SneReg(x, y) => xy(0x9000, x, y, 0x0),
+            Opcode::LdI(address) => 0xA000 | address,
+            Opcode::JpV0(address) => 0xB000 | address,
+            Opcode::Rnd(x, byte) => xnn(0xC000, x, byte),
+            Opcode::Drw(x, y, n) => xy(0xD000, x, y, n as u16),
+            Opcode::Skp(x) => xnn(0xE000, x, 0x9E),
+            Opcode::Sknp(x) => xnn(0xE000, x, 0xA1),
+            Opcode::LdFromDelayTimer(x) => xnn(0xF000, x, 0x07),
+            Opcode::LdKey(x) => xnn(0xF000, x, 0x0A),
+            Opcode::LdDelayTimer(x) => xnn(0xF000, x, 0x15),
+            Opcode::LdSoundTimer(x) => xnn(0xF000, x, 0x18),
+            Opcode::AddI(x) => xnn(0xF000, x, 0x1E),
+            Opcode::LdFont(x) => xnn(0xF000, x, 0x29),
+            Opcode::LdBcd(x) => xnn(0xF000, x, 0x33),
+            Opcode::Store(x) => xnn(0xF000, x, 0x55),
+            Opcode::Load(x) => xnn(0xF000, x, 0x65),
+            Opcode::ScrollUp(n) => 0x00D0 | n as u16,
+            Opcode::SaveRange(x, y) => xy(0x5000, x, y, 0x2),
+            Opcode::LoadRange(x, y) => xy(0x5000, x, y, 0x3),
+            Opcode::LdILong(address) => {
+                return vec![0xF0, 0x00, (address >> 8) as u8, address as u8]
+            }
+            Opcode::Plane(n) => xnn(0xF000, n, 0x01),
+            Opcode::Audio => 0xF002,
+            Opcode::Pitch(x) => xnn(0xF000, x, 0x3A),
+            Opcode::SaveFlags(x) => xnn(0xF000, x, 0x75),
+            Opcode::LoadFlags(x) => xnn(0xF000, x, 0x85),
+            Opcode::Data(word) => word,
+            Opcode::Byte(byte) => return vec![byte],
+        };
+
+        vec![(word >> 8) as u8, word as u8]
+    }
+
+    /// Whether this is one of the XO-CHIP extensions, including the SUPER-CHIP instructions
+    /// that this interpreter only supports as part of XO-CHIP.
+    pub fn is_xo_chip(self) -> bool {
+        matches!(
+            self,
+            Opcode::ScrollUp(_)
+                | Opcode::SaveRange(_, _)
+                | Opcode::LoadRange(_, _)
+                | Opcode::LdILong(_)
+                | Opcode::Plane(_)
+                | Opcode::Audio
+                | Opcode::Pitch(_)
+                | Opcode::SaveFlags(_)
+                | Opcode::LoadFlags(_)
+        )
+    }
+
+    /// The size of the instruction in bytes.
+    pub fn size(self) -> u16 {
+        match self {
+            Opcode::LdILong(_) => 4,
+            Opcode::Byte(_) => 1,
+            _ => 2,
+        }
+    }
+
+    /// The size of the instruction that starts with `opcode` in bytes, without decoding it,
+    /// e.g. to skip over it. Only `F000 NNNN` is longer than one word.
+    pub fn size_of(opcode: u16) -> u16 {
+        if opcode == 0xF000 {
+            4
+        } else {
+            2
+        }
+    }
+
+    /// The hex pattern of the instruction, e.g. `8XY4` for every `ADD VX, VY`.
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Opcode::Cls => "00E0",
+            Opcode::Ret => "00EE",
+            Opcode::Sys(_) => "0NNN",
+            Opcode::Jp(_) => "1NNN",
+            Opcode::Call(_) => "2NNN",
+            Opcode::SeByte(..) => "3XNN",
+            Opcode::SneByte(..) => "4XNN",
+            Opcode::SeReg(..) => "5XY0",
+            Opcode::LdByte(..) => "6XNN",
+            Opcode::AddByte(..) => "7XNN",
+            Opcode::LdReg(..) => "8XY0",
+            Opcode::Or(..) => "8XY1",
+            Opcode::And(..) => "8XY2",
+            Opcode::Xor(..) => "8XY3",
+            Opcode::AddReg(..) => "8XY4",
+            Opcode::Sub(..) => "8XY5",
+            Opcode::Shr(..) => "8XY6",
+            Opcode::Subn(..) => "8XY7",
+            Opcode::Shl(..) => "8XYE",
+            Opcode::SneReg(..) => "9XY0",
+            Opcode::LdI(_) => "ANNN",
+            Opcode::JpV0(_) => "BNNN",
+            Opcode::Rnd(..) => "CXNN",
+            Opcode::Drw(..) => "DXYN",
+            Opcode::Skp(_) => "EX9E",
+            Opcode::Sknp(_) => "EXA1",
+            Opcode::LdFromDelayTimer(_) => "FX07",
+            Opcode::LdKey(_) => "FX0A",
+            Opcode::LdDelayTimer(_) => "FX15",
+            Opcode::LdSoundTimer(_) => "FX18",
+            Opcode::AddI(_) => "FX1E",
+            Opcode::LdFont(_) => "FX29",
+            Opcode::LdBcd(_) => "FX33",
+            Opcode::Store(_) => "FX55",
+            Opcode::Load(_) => "FX65",
+            Opcode::ScrollUp(_) => "00DN",
+            Opcode::SaveRange(..) => "5XY2",
+            Opcode::LoadRange(..) => "5XY3",
+            Opcode::LdILong(_) => "F000",
+            Opcode::Plane(_) => "FN01",
+            Opcode::Audio => "F002",
+            Opcode::Pitch(_) => "FX3A",
+            Opcode::SaveFlags(_) => "FX75",
+            Opcode::LoadFlags(_) => "FX85",
+            Opcode::Data(_) | Opcode::Byte(_) => "DB",
+        }
+    }
+}
+
+/// Why a word couldn't be converted into an [`Opcode`] with [`Opcode::try_from`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The word isn't an instruction.
+    Unknown(u16),
+    /// `F000 NNNN` takes the word after it too, decode it with [`Opcode::decode`].
+    NeedsNextWord,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Unknown(word) => write!(f, "{:#06x} isn't an instruction", word),
+            DecodeError::NeedsNextWord => write!(f, "LD I, LONG needs the word after it"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decode a single word, [`Opcode::decode`] also decodes words that aren't instructions.
+impl TryFrom<u16> for Opcode {
+    type Error = DecodeError;
+
+    fn try_from(word: u16) -> Result<Self, Self::Error> {
+        match Opcode::decode(word, 0) {
+            Opcode::LdILong(_) => Err(DecodeError::NeedsNextWord),
+            Opcode::Data(word) => Err(DecodeError::Unknown(word)),
+            opcode => Ok(opcode),
+        }
+    }
+}
+
+/// The first word of the instruction, see [`Opcode::encode`] for all of its bytes. That's
+/// `0xF000` for `LD I, LONG NNNN`, and the byte itself for [`Opcode::Byte`].
+impl From<Opcode> for u16 {
+    fn from(opcode: Opcode) -> Self {
+        match opcode.encode().as_slice() {
+            [byte] => u16::from(*byte),
+            [high, low, ..] => u16::from(*high) << 8 | u16::from(*low),
+            [] => unreachable!("Every opcode has at least one byte"),
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Opcode::Cls => write!(f, "CLS"),
+            Opcode::Ret => write!(f, "RET"),
+            Opcode::Sys(address) => write!(f, "SYS 0x{:03X}", address),
+            Opcode::Jp(address) => write!(f, "JP 0x{:03X}", address),
+            Opcode::Call(address) => write!(f, "CALL 0x{:03X}", address),
+            Opcode::SeByte(x, byte) => write!(f, "SE V{:X}, 0x{:02X}", x, byte),
+            Opcode::SneByte(x, byte) => write!(f, "SNE V{:X}, 0x{:02X}", x, byte),
+            Opcode::SeReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Opcode::LdByte(x, byte) => write!(f, "LD V{:X}, 0x{:02X}", x, byte),
+            Opcode::AddByte(x, byte) => write!(f, "ADD V{:X}, 0x{:02X}", x, byte),
+            Opcode::LdReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Opcode::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Opcode::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Opcode::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Opcode::AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Opcode::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Opcode::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Opcode::Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Opcode::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Opcode::SneReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Opcode::LdI(address) => write!(f, "LD I, 0x{:03X}", address),
+            Opcode::JpV0(address) => write!(f, "JP V0, 0x{:03X}", address),
+            Opcode::Rnd(x, byte) => write!(f, "RND V{:X}, 0x{:02X}", x, byte),
+            Opcode::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Opcode::Skp(x) => write!(f, "SKP V{:X}", x),
+            Opcode::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Opcode::LdFromDelayTimer(x) => write!(f, "LD V{:X}, DT", x),
+            Opcode::LdKey(x) => write!(f, "LD V{:X}, K", x),
+            Opcode::LdDelayTimer(x) => write!(f, "LD DT, V{:X}", x),
+            Opcode::LdSoundTimer(x) => write!(f, "LD ST, V{:X}", x),
+            Opcode::AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Opcode::LdFont(x) => write!(f, "LD F, V{:X}", x),
+            Opcode::LdBcd(x) => write!(f, "LD B, V{:X}", x),
+            Opcode::Store(x) => write!(f, "LD [I], V{:X}", x),
+            Opcode::Load(x) => write!(f, "LD V{:X}, [I]", x),
+            Opcode::ScrollUp(n) => write!(f, "SCU {}", n),
+            Opcode::SaveRange(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Opcode::LoadRange(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Opcode::LdILong(address) => write!(f, "LD I, LONG 0x{:04X}", address),
+            Opcode::Plane(n) => write!(f, "PLANE {}", n),
+            Opcode::Audio => write!(f, "AUDIO"),
+            Opcode::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Opcode::SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            Opcode::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Opcode::Data(word) => write!(f, "DB 0x{:02X}, 0x{:02X}", word >> 8, word & 0xFF),
+            Opcode::Byte(byte) => write!(f, "DB 0x{:02X}", byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{DecodeError, Opcode};
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(Opcode::try_from(0x00E0), Ok(Opcode::Cls));
+        assert_eq!(Opcode::try_from(0x8AB4), Ok(Opcode::AddReg(0xA, 0xB)));
+        assert_eq!(Opcode::try_from(0x5AB1), Err(DecodeError::Unknown(0x5AB1)));
+        assert_eq!(Opcode::try_from(0xF000), Err(DecodeError::NeedsNextWord));
+    }
+
+    #[test]
+    fn test_into_word() {
+        for word in 0..=0xFFFF {
+            if let Ok(opcode) = Opcode::try_from(word) {
+                assert_eq!(u16::from(opcode), word, "{}", opcode);
+            }
+        }
+        assert_eq!(u16::from(Opcode::LdILong(0x1234)), 0xF000);
+        assert_eq!(u16::from(Opcode::Byte(0xAB)), 0x00AB);
+    }
+}
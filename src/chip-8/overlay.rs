@@ -0,0 +1,478 @@
+//! A text overlay with the CPU state drawn over the picture, to watch what a game is doing
+//! while it runs.
+//!
+//! The picture is scaled up by [`OVERLAY_SCALE`] so that the text can be drawn at a legible
+//! size, in a small built-in 3x5 font. Values that changed since an earlier state are drawn
+//! in another color, e.g. to follow along one instruction at a time. Frontends can draw their
+//! own text in the same font with [`draw_lines`] and [`draw_panel`].
+//!
+//! [`draw_scope`] adds a small oscilloscope of the sound that's playing, for debugging audio.
+use std::ops::Range;
+
+use crate::sound::AUDIO_PATTERN_SIZE;
+use crate::{AudioBuffer, CpuState, Display, Emulator};
+
+/// How many times larger than the display the picture with the overlay is.
+pub const OVERLAY_SCALE: usize = 8;
+/// The size of every font pixel in picture pixels.
+const TEXT_SCALE: usize = 2;
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// The distance between the start of consecutive characters and lines.
+const ADVANCE: usize = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+const LINE_HEIGHT: usize = (GLYPH_HEIGHT + 2) * TEXT_SCALE;
+const MARGIN: usize = 4;
+const TEXT_COLOR: u32 = 0xFF_FF_FF;
+const HIGHLIGHT_COLOR: u32 = 0xFF_FF_00;
+/// One loop of an XO-CHIP audio pattern, or a few periods of the beep.
+const SCOPE_SAMPLES: usize = AUDIO_PATTERN_SIZE * 8;
+/// The samples of a period of the beep's square wave in the scope, it's only a picture of it.
+const BEEP_PERIOD: usize = 16;
+const SCOPE_HEIGHT: usize = 8 * TEXT_SCALE;
+
+/// A line of text in the overlay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Line {
+    pub text: String,
+    /// The characters drawn in the highlight color.
+    pub highlighted: Vec<Range<usize>>,
+}
+
+impl Line {
+    /// A line drawn in the highlight color as a whole.
+    pub fn highlighted(text: String) -> Self {
+        let mut line = Line::default();
+        line.push_value("", text, true);
+
+        line
+    }
+
+    /// Append `label` and then `value`, highlighting the value when `is_highlighted`.
+    fn push_value(&mut self, label: &str, value: String, is_highlighted: bool) {
+        self.text.push_str(label);
+        let start = self.text.chars().count();
+        self.text.push_str(&value);
+        if is_highlighted {
+            self.highlighted.push(start..start + value.chars().count());
+        }
+    }
+
+    fn is_highlighted(&self, index: usize) -> bool {
+        self.highlighted.iter().any(|range| range.contains(&index))
+    }
+}
+
+impl From<String> for Line {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            highlighted: Vec::new(),
+        }
+    }
+}
+
+/// The lines of text to show for `emulator`, measured to run at `fps` frames and `ips`
+/// instructions per second. The values that differ from `previous` are highlighted.
+pub fn lines<D: Display>(
+    emulator: &Emulator<D>,
+    fps: u32,
+    ips: u32,
+    previous: Option<&CpuState>,
+) -> Vec<Line> {
+    let state = emulator.state();
+    let before = previous.unwrap_or(&state);
+    let instruction = emulator
+        .current_instruction()
+        .map(|instruction| instruction.to_string())
+        .unwrap_or_else(|_| "?".to_owned());
+
+    let mut position = Line::default();
+    // Every instruction moves the PC, only jumps, calls, and skips stand out
+    let is_jump = previous.is_some() && state.pc != before.pc.wrapping_add(2);
+    position.push_value("PC ", format!("{:#05x}", state.pc), is_jump);
+    position.push_value("  I ", format!("{:#05x}", state.i), state.i != before.i);
+
+    let mut lines = vec![position, Line::from(instruction)];
+    for (row, registers) in state.v.chunks(4).enumerate() {
+        let mut line = Line::default();
+        for (column, &value) in registers.iter().enumerate() {
+            let index = row * 4 + column;
+            let label = if column == 0 {
+                format!("V{:X} ", index)
+            } else {
+                format!(" V{:X} ", index)
+            };
+            line.push_value(&label, format!("{:02x}", value), value != before.v[index]);
+        }
+        lines.push(line);
+    }
+
+    let mut timers = Line::default();
+    timers.push_value(
+        "DT ",
+        format!("{:02x}", state.delay_timer),
+        state.delay_timer != before.delay_timer,
+    );
+    timers.push_value(
+        "  ST ",
+        format!("{:02x}", state.sound_timer),
+        state.sound_timer != before.sound_timer,
+    );
+    lines.push(timers);
+    lines.push(Line::from(format!("{} FPS  {} IPS", fps, ips)));
+
+    lines
+}
+
+/// Scale up a row major `width` by `height` XRGB picture by [`OVERLAY_SCALE`] and draw `lines`
+/// over its top left corner on a darkened background. Returns the picture and its size.
+pub fn render(
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    lines: &[Line],
+) -> (Vec<u32>, usize, usize) {
+    let (mut scaled, scaled_width, scaled_height) = scale(pixels, width, height);
+    draw_panel(&mut scaled, scaled_width, scaled_height, lines);
+
+    (scaled, scaled_width, scaled_height)
+}
+
+/// Scale up a row major `width` by `height` XRGB picture by [`OVERLAY_SCALE`], to draw text or
+/// the scope over. Returns the picture and its size.
+pub fn scale(pixels: &[u32], width: usize, height: usize) -> (Vec<u32>, usize, usize) {
+    let (scaled_width, scaled_height) = (width * OVERLAY_SCALE, height * OVERLAY_SCALE);
+    let scaled = (0..scaled_height)
+        .flat_map(|y| {
+            let row = &pixels[y / OVERLAY_SCALE * width..][..width];
+            (0..scaled_width).map(move |x| row[x / OVERLAY_SCALE])
+        })
+        .collect();
+
+    (scaled, scaled_width, scaled_height)
+}
+
+/// Draw an oscilloscope of the sound in the bottom left corner of a row major `width` by
+/// `height` XRGB picture, with the sound timer above it. That's the XO-CHIP pattern in
+/// `audio_buffer` when there is one and the beep's square wave otherwise. The wave is drawn
+/// in the highlight color while the sound timer runs, and is a flat line while it doesn't.
+pub fn draw_scope(
+    pixels: &mut [u32],
+    width: usize,
+    height: usize,
+    audio_buffer: Option<&AudioBuffer>,
+    sound_timer: u8,
+) {
+    let mut label = Line::default();
+    label.push_value("ST ", format!("{:02x}", sound_timer), sound_timer > 0);
+    match audio_buffer {
+        Some(buffer) => label.push_value("  PITCH ", format!("{:02x}", buffer.pitch()), false),
+        None => label.text.push_str("  BEEP"),
+    }
+
+    let panel_width = SCOPE_SAMPLES * TEXT_SCALE + 2 * MARGIN;
+    let panel_height = LINE_HEIGHT + SCOPE_HEIGHT + 2 * MARGIN;
+    let top = height.saturating_sub(panel_height);
+    for y in top..height {
+        for pixel in &mut pixels[y * width..][..panel_width.min(width)] {
+            *pixel = (*pixel >> 2) & 0x3F_3F_3F;
+        }
+    }
+    draw_text(pixels, width, height, MARGIN, top + MARGIN, &label);
+
+    let is_playing = sound_timer > 0;
+    let color = if is_playing {
+        HIGHLIGHT_COLOR
+    } else {
+        TEXT_COLOR
+    };
+    let wave_top = top + MARGIN + LINE_HEIGHT;
+    let level = |index: usize| match (is_playing, audio_buffer) {
+        (false, _) => SCOPE_HEIGHT / 2,
+        (true, Some(buffer)) if buffer.sample(index) => 0,
+        (true, None) if index % BEEP_PERIOD < BEEP_PERIOD / 2 => 0,
+        (true, _) => SCOPE_HEIGHT - 1,
+    };
+    let mut set = |x: usize, y: usize| {
+        if x < width && y < height {
+            pixels[y * width + x] = color;
+        }
+    };
+    for index in 0..SCOPE_SAMPLES {
+        let x = MARGIN + index * TEXT_SCALE;
+        let y = level(index);
+        for dx in 0..TEXT_SCALE {
+            set(x + dx, wave_top + y);
+        }
+        // The edge from the previous sample
+        if index > 0 {
+            let previous = level(index - 1);
+            for edge in previous.min(y)..previous.max(y) {
+                set(x, wave_top + edge);
+            }
+        }
+    }
+}
+
+/// Like [`draw_lines`], on a darkened background as large as the text.
+pub fn draw_panel(pixels: &mut [u32], width: usize, height: usize, lines: &[Line]) {
+    let longest = lines
+        .iter()
+        .map(|line| line.text.chars().count())
+        .max()
+        .unwrap_or(0);
+    let panel_width = (longest * ADVANCE + 2 * MARGIN).min(width);
+    let panel_height = (lines.len() * LINE_HEIGHT + 2 * MARGIN).min(height);
+    for y in 0..panel_height {
+        for pixel in &mut pixels[y * width..][..panel_width] {
+            // A quarter of the brightness, so the picture still shows through
+            *pixel = (*pixel >> 2) & 0x3F_3F_3F;
+        }
+    }
+
+    draw_lines(pixels, width, height, lines);
+}
+
+/// How many characters fit on a line and how many lines fit in a `width` by `height` picture.
+pub fn text_capacity(width: usize, height: usize) -> (usize, usize) {
+    (
+        width.saturating_sub(2 * MARGIN) / ADVANCE,
+        height.saturating_sub(2 * MARGIN) / LINE_HEIGHT,
+    )
+}
+
+/// Draw `lines` in the top left corner of a row major `width` by `height` XRGB picture, text
+/// that doesn't fit is cut off.
+pub fn draw_lines(pixels: &mut [u32], width: usize, height: usize, lines: &[Line]) {
+    for (line_index, line) in lines.iter().enumerate() {
+        draw_text(
+            pixels,
+            width,
+            height,
+            MARGIN,
+            MARGIN + line_index * LINE_HEIGHT,
+            line,
+        );
+    }
+}
+
+/// Draw `line` with its top left corner at `left`, `top`.
+fn draw_text(
+    pixels: &mut [u32],
+    width: usize,
+    height: usize,
+    left: usize,
+    top: usize,
+    line: &Line,
+) {
+    for (char_index, c) in line.text.chars().enumerate() {
+        let left = left + char_index * ADVANCE;
+        let color = if line.is_highlighted(char_index) {
+            HIGHLIGHT_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+
+                let (x, y) = (left + column * TEXT_SCALE, top + row * TEXT_SCALE);
+                for dy in 0..TEXT_SCALE {
+                    for dx in 0..TEXT_SCALE {
+                        if x + dx < width && y + dy < height {
+                            pixels[(y + dy) * width + x + dx] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The rows of `c` in the 3x5 font, the low 3 bits of every row from left to right. Letters
+/// are all drawn in upper case except for the `x` in hex numbers. Anything the font doesn't
+/// have is drawn as `?`.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        _ if c == 'x' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '&' => [0b010, 0b101, 0b010, 0b101, 0b011],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        draw_lines, draw_scope, glyph, lines, render, text_capacity, Line, HIGHLIGHT_COLOR,
+        OVERLAY_SCALE,
+    };
+    use crate::{AudioBuffer, Emulator, FramebufferDisplay, Quirks};
+
+    #[test]
+    fn test_lines() {
+        // 6342: LD V3, 0x42, A2F0: LD I, 0x2F0
+        let rom = vec![0x63, 0x42, 0xA2, 0xF0];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        emulator.cycle(&crate::batch::NullInput).unwrap();
+
+        let lines = lines(&emulator, 60, 1000, None);
+
+        assert_eq!(lines[0].text, "PC 0x202  I 0x000");
+        assert_eq!(lines[1].text, "LD I, 0x2F0");
+        assert_eq!(lines[2].text, "V0 00 V1 00 V2 00 V3 42");
+        assert_eq!(lines[6].text, "DT 00  ST 00");
+        assert_eq!(lines[7].text, "60 FPS  1000 IPS");
+        assert!(lines.iter().all(|line| line.highlighted.is_empty()));
+        // Everything is in the font
+        let unknown = glyph('~');
+        assert!(lines
+            .iter()
+            .flat_map(|line| line.text.chars())
+            .all(|c| glyph(c) != unknown));
+    }
+
+    #[test]
+    fn test_highlighted_lines() {
+        // 6342: LD V3, 0x42, A2F0: LD I, 0x2F0, 1200: JP 0x200
+        let rom = vec![0x63, 0x42, 0xA2, 0xF0, 0x12, 0x00];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let mut step = || {
+            let before = emulator.state();
+            emulator.cycle(&crate::batch::NullInput).unwrap();
+            lines(&emulator, 60, 1000, Some(&before))
+        };
+
+        let lines = step();
+        assert_eq!(lines[0].highlighted, vec![]);
+        assert_eq!(lines[2].highlighted, vec![21..23]);
+
+        let lines = step();
+        assert_eq!(lines[0].highlighted, vec![12..17]);
+        assert_eq!(lines[2].highlighted, vec![]);
+
+        // Jumps stand out
+        let lines = step();
+        assert_eq!(lines[0].highlighted, vec![3..8]);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut picture = vec![0x00_00_00; 4 * 4];
+        picture[0] = 0xFF_FF_FF;
+        picture[12] = 0xFF_FF_FF;
+        let (pixels, width, height) = render(&picture, 4, 4, &[Line::from("1".to_owned())]);
+
+        assert_eq!((width, height), (4 * OVERLAY_SCALE, 4 * OVERLAY_SCALE));
+        assert_eq!(pixels.len(), width * height);
+        // The panel darkens the white pixel, the top of the 1 is drawn in white over it
+        assert_eq!(pixels[0], 0x3F_3F_3F);
+        assert_eq!(pixels[4 * width + 6], 0xFF_FF_FF);
+        // Outside the panel the picture is untouched
+        assert_eq!(pixels[(height - 1) * width], 0xFF_FF_FF);
+        assert_eq!(pixels[width - 1], 0x00_00_00);
+
+        let (pixels, _, _) = render(&picture, 4, 4, &[Line::highlighted("1".to_owned())]);
+        assert_eq!(pixels[4 * width + 6], 0xFF_FF_00);
+    }
+
+    #[test]
+    fn test_draw_scope() {
+        let (width, height) = (300, 40);
+        // The top and bottom rows of the wave, above the bottom margin
+        let (high, low) = (height - 4 - 16, height - 4 - 1);
+        let draw = |audio_buffer: Option<&AudioBuffer>, sound_timer| {
+            let mut pixels = vec![0; width * height];
+            draw_scope(&mut pixels, width, height, audio_buffer, sound_timer);
+            pixels
+        };
+
+        // Silence is a flat line in the middle
+        let pixels = draw(None, 0);
+        assert_eq!(pixels[(high + 8) * width + 4], 0xFF_FF_FF);
+        assert_eq!(pixels[high * width + 4], 0);
+
+        // The beep starts high and drops after half a period
+        let pixels = draw(None, 10);
+        assert_eq!(pixels[high * width + 4], HIGHLIGHT_COLOR);
+        assert_eq!(pixels[low * width + 4 + 8 * 2], HIGHLIGHT_COLOR);
+
+        let mut pattern = [0; 16];
+        pattern[0] = 0b0100_0000;
+        let pixels = draw(Some(&AudioBuffer::new(pattern, 64)), 10);
+        assert_eq!(pixels[low * width + 4], HIGHLIGHT_COLOR);
+        assert_eq!(pixels[high * width + 4 + 2], HIGHLIGHT_COLOR);
+
+        // Fits in a picture that's too small for it
+        let mut pixels = vec![0; 8 * 8];
+        draw_scope(&mut pixels, 8, 8, None, 1);
+    }
+
+    #[test]
+    fn test_draw_lines() {
+        let (width, height) = (20, 16);
+        let mut pixels = vec![0; width * height];
+
+        draw_lines(&mut pixels, width, height, &[Line::from("1111".to_owned())]);
+
+        assert_eq!(text_capacity(width, height), (1, 0));
+        assert_eq!(pixels[4 * width + 6], 0xFF_FF_FF);
+        // Text past the edge is cut off
+        assert_eq!(pixels[4 * width + 14], 0xFF_FF_FF);
+        assert_eq!(pixels[(height - 1) * width + 6], 0);
+    }
+}
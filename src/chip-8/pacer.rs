@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+const MICROS_PER_SECOND: u128 = 1_000_000;
+
+/// Paces a loop at an exact rate in Hz, e.g. 60 frames per second for the timers and the display.
+///
+/// Elapsed time is accumulated in units of `micros * rate` so that rates which don't divide a
+/// second evenly, such as 60Hz, don't drift. Whatever is left over after the due frames have
+/// been taken out is carried forward to the next call to [`FramePacer::advance`].
+#[derive(Debug)]
+pub struct FramePacer {
+    rate: u32,
+    accumulated: u128,
+}
+
+impl FramePacer {
+    pub fn new(rate: u32) -> Self {
+        assert!(rate > 0, "A FramePacer needs a rate above 0Hz");
+
+        Self {
+            rate,
+            accumulated: 0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Account for `elapsed` time and return how many frames are now due.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulated += elapsed.as_micros() * self.rate as u128;
+
+        let frames = self.accumulated / MICROS_PER_SECOND;
+        self.accumulated %= MICROS_PER_SECOND;
+
+        frames as u32
+    }
+
+    /// How long until the next frame is due, useful for sleeping between frames.
+    pub fn time_until_next_frame(&self) -> Duration {
+        let remaining = MICROS_PER_SECOND - self.accumulated;
+        // Round up so that sleeping this long always makes the frame due.
+        let micros = remaining.div_ceil(self.rate as u128);
+
+        Duration::from_micros(micros as u64)
+    }
+
+    /// Drop any partial frame, typically after a reset or a long stall.
+    pub fn reset(&mut self) {
+        self.accumulated = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FramePacer;
+    use std::time::Duration;
+
+    #[test]
+    fn test_advance_carries_remainder() {
+        let mut pacer = FramePacer::new(60);
+
+        // 16ms is slightly less than a 60Hz frame
+        assert_eq!(pacer.advance(Duration::from_millis(16)), 0);
+        // Together with the 16ms carried over from the last call this is just over two frames
+        assert_eq!(pacer.advance(Duration::from_micros(17_400)), 2);
+    }
+
+    #[test]
+    fn test_advance_does_not_drift() {
+        let mut pacer = FramePacer::new(60);
+
+        let frames: u32 = (0..1000)
+            .map(|_| pacer.advance(Duration::from_millis(1)))
+            .sum();
+
+        assert_eq!(frames, 60);
+    }
+
+    #[test]
+    fn test_time_until_next_frame() {
+        let mut pacer = FramePacer::new(60);
+
+        assert_eq!(pacer.time_until_next_frame(), Duration::from_micros(16_667));
+
+        pacer.advance(Duration::from_micros(10_000));
+        assert_eq!(pacer.time_until_next_frame(), Duration::from_micros(6_667));
+
+        let next = pacer.time_until_next_frame();
+        assert_eq!(pacer.advance(next), 1);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut pacer = FramePacer::new(60);
+
+        pacer.advance(Duration::from_millis(10));
+        pacer.reset();
+
+        assert_eq!(pacer.advance(Duration::from_millis(10)), 0);
+    }
+}
@@ -0,0 +1,174 @@
+//! The colors pixels are drawn in.
+use std::fmt;
+use std::str::FromStr;
+
+/// Colors in XRGB for pixels that are set in no plane, plane 1, plane 2, and both planes. Only
+/// XO-CHIP draws in more than the first two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; 4],
+}
+
+impl Palette {
+    /// The named presets, in the order the frontend cycles through them.
+    pub const PRESETS: [(&'static str, Palette); 5] = [
+        ("mono", Palette::MONO),
+        ("classic", Palette::CLASSIC),
+        ("lcd", Palette::LCD),
+        ("amber", Palette::AMBER),
+        ("gameboy", Palette::GAMEBOY),
+    ];
+
+    /// White on black with grays for the XO-CHIP planes, the default.
+    pub const MONO: Palette = Palette::new([0x00_00_00, 0xFF_FF_FF, 0xAA_AA_AA, 0x55_55_55]);
+    /// Light blue on dark blue.
+    pub const CLASSIC: Palette = Palette::new([0x2C_50_66, 0x68_BB_ED, 0xE8_A3_3D, 0xF4_ED_D8]);
+    /// Dark gray on a green-gray LCD.
+    pub const LCD: Palette = Palette::new([0xB7_C2_A6, 0x2A_33_28, 0x6B_7A_5E, 0x0E_12_0D]);
+    /// Amber on black, like a monochrome monitor.
+    pub const AMBER: Palette = Palette::new([0x1A_0F_00, 0xFF_B0_00, 0x99_6A_00, 0xFF_D5_80]);
+    /// The four greens of the original Game Boy.
+    pub const GAMEBOY: Palette = Palette::new([0x9B_BC_0F, 0x0F_38_0F, 0x8B_AC_0F, 0x30_62_30]);
+
+    pub const fn new(colors: [u32; 4]) -> Self {
+        Self { colors }
+    }
+
+    /// A two color palette, the XO-CHIP planes are all drawn in `foreground`.
+    pub const fn two_color(background: u32, foreground: u32) -> Self {
+        Self::new([background, foreground, foreground, foreground])
+    }
+
+    pub fn background(&self) -> u32 {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> u32 {
+        self.colors[1]
+    }
+
+    /// The color of a pixel set in the planes of the bit mask `planes`.
+    pub fn color(&self, planes: u8) -> u32 {
+        self.colors[planes as usize & 0b11]
+    }
+
+    /// The preset after `self` in [`Palette::PRESETS`], wrapping around. Custom palettes are
+    /// followed by the first preset.
+    pub fn next_preset(&self) -> Palette {
+        let index = Self::PRESETS
+            .iter()
+            .position(|(_, preset)| preset == self)
+            .map_or(0, |index| (index + 1) % Self::PRESETS.len());
+
+        Self::PRESETS[index].1
+    }
+
+    /// The name of the preset, if this is one.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, preset)| preset == self)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::MONO
+    }
+}
+
+/// A preset name, or 2 or 4 comma separated hex colors starting with the background, e.g.
+/// `amber` or `#000000,#33FF33`.
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((_, preset)) = Self::PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(*preset);
+        }
+
+        let colors = s
+            .split(',')
+            .map(|color| {
+                let color = color.trim();
+                let hex = color
+                    .strip_prefix('#')
+                    .or_else(|| color.strip_prefix("0x"))
+                    .unwrap_or(color);
+
+                if hex.len() == 6 {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Invalid palette `{}`", s))?;
+
+        match colors.as_slice() {
+            [background, foreground] => Ok(Palette::two_color(*background, *foreground)),
+            [background, plane_1, plane_2, both] => {
+                Ok(Palette::new([*background, *plane_1, *plane_2, *both]))
+            }
+            _ => Err(format!(
+                "Invalid palette `{}`, expected a preset or 2 or 4 colors",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => {
+                let colors = self
+                    .colors
+                    .iter()
+                    .map(|color| format!("#{:06X}", color))
+                    .collect::<Vec<_>>();
+
+                write!(f, "{}", colors.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("amber".parse(), Ok(Palette::AMBER));
+        assert_eq!(
+            "#000000, 0x33FF33".parse(),
+            Ok(Palette::two_color(0x000000, 0x33FF33))
+        );
+        assert_eq!(
+            "000000,111111,222222,333333".parse(),
+            Ok(Palette::new([0x000000, 0x111111, 0x222222, 0x333333]))
+        );
+        assert!("neon".parse::<Palette>().is_err());
+        assert!("#000000".parse::<Palette>().is_err());
+        assert!("#000000,#FFF".parse::<Palette>().is_err());
+
+        let custom = Palette::two_color(0x000000, 0x33FF33);
+        assert_eq!(custom.to_string(), "#000000,#33FF33,#33FF33,#33FF33");
+        assert_eq!(custom.to_string().parse(), Ok(custom));
+        assert_eq!(Palette::LCD.to_string(), "lcd");
+    }
+
+    #[test]
+    fn test_next_preset() {
+        assert_eq!(Palette::MONO.next_preset(), Palette::CLASSIC);
+        assert_eq!(Palette::GAMEBOY.next_preset(), Palette::MONO);
+        assert_eq!(
+            Palette::two_color(0x000000, 0x33FF33).next_preset(),
+            Palette::MONO
+        );
+    }
+}
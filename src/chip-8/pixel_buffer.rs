@@ -0,0 +1,172 @@
+//! The pixels of the display as the CPU sees them, without anything about how they're shown.
+use crate::Rect;
+
+/// Every pixel is a bit mask of the planes it's set in, in the low bit with a single plane and
+/// the low two with XO-CHIP's two planes. Drawing, clearing, and scrolling take the planes they
+/// affect, the buffer doesn't remember which are selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelBuffer {
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl PixelBuffer {
+    /// A blank buffer, panics if either dimension is 0.
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "Invalid display resolution {}x{}",
+            width,
+            height
+        );
+
+        Self {
+            pixels: vec![0; width * height],
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The planes the pixel at `x`, `y` is set in.
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Every pixel, row major.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Replace every pixel with `pixels` as returned by [`PixelBuffer::as_slice`], panics if
+    /// the length differs.
+    pub fn copy_from_slice(&mut self, pixels: &[u8]) {
+        self.pixels.copy_from_slice(pixels);
+    }
+
+    /// Unset every pixel in `planes`.
+    pub fn clear(&mut self, planes: u8) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel &= !planes);
+    }
+
+    /// Move `planes` up by `rows` pixels, filling in blank rows at the bottom.
+    pub fn scroll_up(&mut self, rows: usize, planes: u8) {
+        let shift = rows * self.width;
+
+        for index in 0..self.pixels.len() {
+            let below = self.pixels.get(index + shift).cloned().unwrap_or(0);
+            self.pixels[index] = (self.pixels[index] & !planes) | (below & planes);
+        }
+    }
+
+    /// XOR an 8 pixel wide sprite with a row per byte of `rows` into `plane` at `x`, `y`. The
+    /// position always wraps around, the parts that go past the edges are cut off when `clip`
+    /// is set and wrap around too otherwise.
+    ///
+    /// Returns whether a pixel was turned off and the bounding box of the pixels that changed.
+    pub fn draw_sprite(
+        &mut self,
+        x: u8,
+        y: u8,
+        rows: &[u8],
+        plane: u8,
+        clip: bool,
+    ) -> (bool, Option<Rect>) {
+        let mut did_collide = false;
+        let mut changed: Option<Rect> = None;
+
+        for (y_offset, row) in rows.iter().enumerate() {
+            let y_pixel = y as usize % self.height + y_offset;
+            if clip && y_pixel >= self.height {
+                break;
+            }
+            let y_norm = y_pixel % self.height;
+
+            for x_bit in 0..8 {
+                let x_pixel = x as usize % self.width + x_bit;
+                if clip && x_pixel >= self.width {
+                    break;
+                }
+                if row & (0x80 >> x_bit) == 0 {
+                    continue;
+                }
+
+                let x_norm = x_pixel % self.width;
+                let pixel = &mut self.pixels[y_norm * self.width + x_norm];
+                did_collide |= *pixel & plane != 0;
+                *pixel ^= plane;
+
+                let region = Rect::new(x_norm, y_norm, 1, 1);
+                changed = Some(match changed {
+                    Some(changed) => changed.union(region),
+                    None => region,
+                });
+            }
+        }
+
+        (did_collide, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PixelBuffer;
+    use crate::Rect;
+
+    #[test]
+    fn test_draw_sprite() {
+        let mut buffer = PixelBuffer::new(16, 8);
+
+        let (did_collide, changed) = buffer.draw_sprite(14, 6, &[0xC0, 0x80], 0b01, false);
+        assert!(!did_collide);
+        assert_eq!(changed, Some(Rect::new(14, 6, 2, 2)));
+        assert_eq!(buffer.get(15, 6), 0b01);
+
+        // Another plane doesn't collide, drawing the same plane again does
+        assert!(!buffer.draw_sprite(14, 6, &[0x80], 0b10, false).0);
+        assert_eq!(buffer.get(14, 6), 0b11);
+        assert!(buffer.draw_sprite(14, 6, &[0x80], 0b01, false).0);
+        assert_eq!(buffer.get(14, 6), 0b10);
+
+        // Wraps around to the left edge and the top, unless clipped
+        buffer.draw_sprite(15, 7, &[0xC0, 0xC0], 0b01, false);
+        assert_eq!(buffer.get(0, 0), 0b01);
+        let (_, changed) = buffer.draw_sprite(15, 7, &[0xC0, 0xC0], 0b01, true);
+        assert_eq!(changed, Some(Rect::new(15, 7, 1, 1)));
+        assert_eq!(
+            buffer.draw_sprite(0, 0, &[0x00], 0b01, false),
+            (false, None)
+        );
+    }
+
+    #[test]
+    fn test_clear_and_scroll() {
+        let mut buffer = PixelBuffer::new(8, 4);
+        buffer.draw_sprite(0, 1, &[0x80, 0x80], 0b11, false);
+
+        buffer.scroll_up(1, 0b01);
+        assert_eq!(buffer.get(0, 0), 0b01);
+        assert_eq!(buffer.get(0, 1), 0b11);
+        assert_eq!(buffer.get(0, 2), 0b10);
+
+        buffer.clear(0b10);
+        assert_eq!(buffer.get(0, 1), 0b01);
+        assert_eq!(buffer.get(0, 2), 0);
+        assert_eq!(
+            buffer
+                .as_slice()
+                .iter()
+                .filter(|&&pixel| pixel != 0)
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Bytes to write into memory at an address, written as `ADDR:BYTES` in hex e.g. `0x300:A2F0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poke {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+}
+
+impl FromStr for Poke {
+    type Err = PokeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let (address, bytes) = match (parts.next(), parts.next()) {
+            (Some(address), Some(bytes)) => (address.trim(), bytes),
+            _ => return Err(PokeError::Syntax(s.to_string())),
+        };
+
+        let address = u16::from_str_radix(
+            address.trim_start_matches("0x").trim_start_matches("0X"),
+            16,
+        )
+        .map_err(|_| PokeError::Syntax(s.to_string()))?;
+
+        let digits = bytes
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return Err(PokeError::Syntax(s.to_string()));
+        }
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| {
+                let pair = pair.iter().collect::<String>();
+                u8::from_str_radix(&pair, 16).map_err(|_| PokeError::Syntax(s.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { address, bytes })
+    }
+}
+
+impl fmt::Display for Poke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#05x}:", self.address)?;
+        for byte in &self.bytes {
+            write!(f, "{:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PokeError {
+    /// The text wasn't on the form `ADDR:BYTES`.
+    Syntax(String),
+    /// The bytes don't fit in memory when written at the address.
+    OutOfBounds { address: u16, length: usize },
+}
+
+impl fmt::Display for PokeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PokeError::Syntax(poke) => write!(
+                f,
+                "Invalid poke `{}`, expected ADDR:BYTES in hex e.g. 0x300:A2F0",
+                poke
+            ),
+            PokeError::OutOfBounds { address, length } => write!(
+                f,
+                "Cannot write {} bytes at {:#05x}, it's past the end of memory",
+                length, address
+            ),
+        }
+    }
+}
+
+impl Error for PokeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Poke, PokeError};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "0x300:A2F0".parse(),
+            Ok(Poke {
+                address: 0x300,
+                bytes: vec![0xA2, 0xF0]
+            })
+        );
+        assert_eq!(
+            "2a0: 00 e0".parse(),
+            Ok(Poke {
+                address: 0x2A0,
+                bytes: vec![0x00, 0xE0]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for poke in &["0x300", "0x300:", "0x300:A2F", "xyz:00", "0x300:GG"] {
+            assert_eq!(
+                poke.parse::<Poke>(),
+                Err(PokeError::Syntax(poke.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let poke: Poke = "0x300:A2F0".parse().unwrap();
+
+        assert_eq!(poke.to_string(), "0x300:A2F0");
+        assert_eq!(poke.to_string().parse(), Ok(poke));
+    }
+}
@@ -0,0 +1,299 @@
+//! Many emulators running in parallel, for workloads such as genetic algorithms, Monte-Carlo
+//! bot search, or compatibility sweeps where setting up each emulator would otherwise dominate.
+//!
+//! The ROM and any fixtures are loaded once into a template. Resetting an emulator copies the
+//! template over its memory instead of building a new one. Emulators usually share a template,
+//! but [`Pool::from_templates`] gives each its own ROM and seed.
+use std::fmt;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::memory::Memory;
+use crate::rng::Seeded;
+use crate::{
+    Emulator, Error, FramebufferDisplay, Input, PixelBuffer, Poke, PokeError, Quirks, RomError,
+    Variant,
+};
+
+/// The state every emulator in a [`Pool`] starts from.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub rom: Vec<u8>,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    /// Bytes written into memory after the ROM has been loaded.
+    pub fixtures: Vec<Poke>,
+    /// The seed for CXNN's random numbers, the sequence starts over on every reset. Random
+    /// numbers aren't reproducible without one.
+    pub seed: Option<u32>,
+}
+
+impl Template {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Self {
+            rom,
+            variant: Variant::Chip8,
+            quirks: Quirks::default(),
+            fixtures: Vec::new(),
+            seed: None,
+        }
+    }
+
+    /// The memory of an emulator that has just loaded the ROM and fixtures.
+    fn load(&self) -> Result<Arc<Memory>, TemplateError> {
+        let display = FramebufferDisplay::for_variant(self.variant);
+        let mut emulator =
+            Emulator::with_variant(display, self.rom.clone(), self.variant, self.quirks)?;
+        for fixture in &self.fixtures {
+            emulator.poke(fixture)?;
+        }
+
+        Ok(Arc::new(emulator.memory().clone()))
+    }
+}
+
+/// Why a [`Template`] can't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    Rom(RomError),
+    Fixture(PokeError),
+}
+
+impl From<RomError> for TemplateError {
+    fn from(error: RomError) -> Self {
+        TemplateError::Rom(error)
+    }
+}
+
+impl From<PokeError> for TemplateError {
+    fn from(error: PokeError) -> Self {
+        TemplateError::Fixture(error)
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Rom(error) => write!(f, "{}", error),
+            TemplateError::Fixture(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+struct Instance {
+    emulator: Emulator<FramebufferDisplay>,
+    template: Arc<Memory>,
+    seed: Option<u32>,
+    /// Where in the timer period this emulator is, the timers tick whenever this is 0.
+    timer_phase: usize,
+}
+
+impl Instance {
+    fn new(template: &Template, memory: Arc<Memory>) -> Self {
+        let display = FramebufferDisplay::for_variant(template.variant);
+        let emulator =
+            Emulator::with_variant(display, Vec::new(), template.variant, template.quirks)
+                .expect("an empty ROM always fits");
+        let mut instance = Self {
+            emulator,
+            template: memory,
+            seed: template.seed,
+            timer_phase: 0,
+        };
+        instance.reset();
+
+        instance
+    }
+
+    fn reset(&mut self) {
+        self.emulator.restore(&self.template);
+        if let Some(seed) = self.seed {
+            self.emulator.set_rng(Box::new(Seeded::new(seed)));
+        }
+        self.timer_phase = 0;
+    }
+
+    /// Run up to `cycles` cycles, stopping at the first error.
+    fn step(&mut self, cycles: usize, input: &dyn Input) -> Result<(), Error> {
+        for _ in 0..cycles {
+            let tick_timers = self.timer_phase == 0;
+            self.timer_phase = (self.timer_phase + 1) % self.emulator.cycles_per_timer_tick();
+            self.emulator.cycle(input)?;
+            if tick_timers {
+                self.emulator.tick_timers();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Pool {
+    instances: Vec<Instance>,
+}
+
+impl Pool {
+    /// Create `size` emulators all in the state described by `template`.
+    pub fn new(template: &Template, size: usize) -> Result<Self, TemplateError> {
+        let memory = template.load()?;
+        let instances = (0..size)
+            .map(|_| Instance::new(template, Arc::clone(&memory)))
+            .collect();
+
+        Ok(Self { instances })
+    }
+
+    /// Create an emulator for each of `templates`, e.g. to run different ROMs or seeds side by
+    /// side.
+    pub fn from_templates(templates: &[Template]) -> Result<Self, TemplateError> {
+        let instances = templates
+            .iter()
+            .map(|template| Ok(Instance::new(template, template.load()?)))
+            .collect::<Result<_, TemplateError>>()?;
+
+        Ok(Self { instances })
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Emulator<FramebufferDisplay>> {
+        self.instances.get(index).map(|instance| &instance.emulator)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Emulator<FramebufferDisplay>> {
+        self.instances
+            .get_mut(index)
+            .map(|instance| &mut instance.emulator)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Emulator<FramebufferDisplay>> {
+        self.instances.iter().map(|instance| &instance.emulator)
+    }
+
+    /// The pixels of every emulator in order, e.g. to feed them all to a model after a step.
+    pub fn framebuffers(&self) -> Vec<&PixelBuffer> {
+        self.iter()
+            .map(|emulator| emulator.display().buffer())
+            .collect()
+    }
+
+    /// Put the emulator at `index` back in the template state, panics if `index` is out of
+    /// bounds.
+    pub fn reset(&mut self, index: usize) {
+        self.instances[index].reset();
+    }
+
+    pub fn reset_all(&mut self) {
+        for index in 0..self.instances.len() {
+            self.reset(index);
+        }
+    }
+
+    /// Run every emulator for `cycles` cycles in parallel with the same input. Returns the
+    /// outcome for each emulator in order, an emulator that errors stops at the failing
+    /// instruction.
+    pub fn step(&mut self, cycles: usize, input: &(dyn Input + Sync)) -> Vec<Result<(), Error>> {
+        self.instances
+            .par_iter_mut()
+            .map(|instance| instance.step(cycles, input))
+            .collect()
+    }
+
+    /// Like [`Pool::step`] but with one input per emulator, panics unless there are exactly
+    /// as many inputs as emulators.
+    pub fn step_each<I: Input + Sync>(
+        &mut self,
+        cycles: usize,
+        inputs: &[I],
+    ) -> Vec<Result<(), Error>> {
+        assert_eq!(
+            inputs.len(),
+            self.instances.len(),
+            "Expected one input per emulator"
+        );
+
+        self.instances
+            .par_iter_mut()
+            .zip(inputs.par_iter())
+            .map(|(instance, input)| instance.step(cycles, input))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pool, Template};
+    use crate::batch::NullInput;
+    use crate::Input;
+
+    struct HeldKey(u8);
+
+    impl Input for HeldKey {
+        fn is_key_down(&self, key: u8) -> bool {
+            key == self.0
+        }
+    }
+
+    // 6000: LD V0, 0x00, E19E: SKP V1, 7001: ADD V0, 0x01, 1202: JP 0x202
+    const COUNT_UNLESS_HELD: [u8; 8] = [0x60, 0x00, 0xE1, 0x9E, 0x70, 0x01, 0x12, 0x02];
+
+    #[test]
+    fn test_step_and_reset() {
+        let mut template = Template::new(COUNT_UNLESS_HELD.to_vec());
+        // 6105: LD V1, 0x05 instead of LD V0, 0x00 so that V1 holds the key to check
+        template.fixtures.push("0x200:6105".parse().unwrap());
+        let mut pool = Pool::new(&template, 2).unwrap();
+
+        let results = pool.step_each(7, &[HeldKey(0x5), HeldKey(0x6)]);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(pool.get(0).unwrap().register(0), 0);
+        assert_eq!(pool.get(1).unwrap().register(0), 2);
+
+        pool.reset(1);
+        assert_eq!(pool.get(1).unwrap().register(0), 0);
+        assert_eq!(pool.get(1).unwrap().pc(), 0x200);
+        assert!(pool.get(1).unwrap().is_initial_state());
+    }
+
+    #[test]
+    fn test_step_reports_errors() {
+        // 00EE: RET with an empty stack
+        let mut pool = Pool::new(&Template::new(vec![0x00, 0xEE]), 3).unwrap();
+
+        let results = pool.step(10, &NullInput);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(Result::is_err));
+    }
+
+    #[test]
+    fn test_from_templates() {
+        // F029: LD F, V0, D005: DRW V0, V0, 5, 1204: JP 0x204
+        let draw_zero = Template::new(vec![0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04]);
+        // C0FF: RND V0, 0xFF, 1202: JP 0x202
+        let mut random = Template::new(vec![0xC0, 0xFF, 0x12, 0x02]);
+        random.seed = Some(7);
+        let mut pool = Pool::from_templates(&[draw_zero, random.clone(), random]).unwrap();
+
+        assert!(pool.step(3, &NullInput).iter().all(Result::is_ok));
+        let framebuffers = pool.framebuffers();
+        assert_eq!(framebuffers.len(), 3);
+        assert_eq!(framebuffers[0].get(0, 0), 1);
+        assert_eq!(framebuffers[1].get(0, 0), 0);
+
+        // The same seed gives the same numbers, also after a reset
+        let number = pool.get(1).unwrap().register(0);
+        assert_eq!(pool.get(2).unwrap().register(0), number);
+        pool.reset(1);
+        pool.step(1, &NullInput);
+        assert_eq!(pool.get(1).unwrap().register(0), number);
+    }
+}
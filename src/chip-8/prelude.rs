@@ -0,0 +1,6 @@
+//! The types needed to embed the emulator, `use chip_8::prelude::*;` brings them all in scope.
+pub use crate::batch::NullInput;
+pub use crate::{
+    Display, Emulator, EmulatorBuilder, Error, FramebufferDisplay, Input, Palette, Quirks,
+    RomError, Sound, Variant,
+};
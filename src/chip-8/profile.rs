@@ -0,0 +1,111 @@
+//! Named bundles of the variant, quirks, and speed that a family of ROMs expects, so that
+//! picking one doesn't require knowing every quirk.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Quirks, Variant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// The original COSMAC VIP interpreter.
+    Vip,
+    /// CHIP-48 on the HP 48, which introduced the BXNN jump and shifting in place.
+    Chip48,
+    /// SUPER-CHIP 1.1 on the HP 48, in its 64x32 low resolution mode.
+    SuperChip,
+    /// XO-CHIP as implemented by Octo.
+    XoChip,
+    /// Hi-res CHIP-8 on the COSMAC VIP.
+    HiRes,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 5] = [
+        Profile::Vip,
+        Profile::Chip48,
+        Profile::SuperChip,
+        Profile::XoChip,
+        Profile::HiRes,
+    ];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Vip => "vip",
+            Profile::Chip48 => "chip48",
+            Profile::SuperChip => "schip",
+            Profile::XoChip => "xochip",
+            Profile::HiRes => "hires",
+        }
+    }
+
+    /// The variant, which decides the display resolution and memory size.
+    pub fn variant(self) -> Variant {
+        match self {
+            Profile::XoChip => Variant::XoChip,
+            Profile::HiRes => Variant::HiRes,
+            _ => Variant::Chip8,
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Profile::Vip | Profile::HiRes => Quirks::cosmac_vip(),
+            // SUPER-CHIP kept the CHIP-48 behavior
+            Profile::Chip48 | Profile::SuperChip => Quirks::super_chip(),
+            Profile::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    /// How many instructions run per 60Hz frame, about as many as the original interpreters
+    /// managed. XO-CHIP has no hardware, it gets the speed Octo defaults to.
+    pub fn instructions_per_frame(self) -> u32 {
+        match self {
+            Profile::Vip | Profile::HiRes => 11,
+            Profile::Chip48 | Profile::SuperChip => 30,
+            Profile::XoChip => 100,
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Profile::ALL
+            .iter()
+            .find(|profile| profile.name() == s.trim())
+            .cloned()
+            .ok_or_else(|| {
+                let names = Profile::ALL
+                    .iter()
+                    .map(|profile| profile.name())
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "Unknown profile `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profile;
+
+    #[test]
+    fn test_parse() {
+        for profile in &Profile::ALL {
+            assert_eq!(profile.to_string().parse(), Ok(*profile));
+        }
+        assert!("chip-8".parse::<Profile>().is_err());
+    }
+}
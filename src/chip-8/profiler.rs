@@ -0,0 +1,170 @@
+//! Where a ROM spends its time: how often every instruction runs, by address and by kind of
+//! instruction. Turn it on with [`Emulator::set_profiling`](crate::Emulator::set_profiling)
+//! and read the results with
+//! [`Emulator::profile_report`](crate::Emulator::profile_report).
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Opcode;
+
+/// Counts the executed instructions.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    /// Executions and the last instruction seen per address, self-modifying ROMs can run
+    /// different instructions at the same address.
+    addresses: HashMap<u16, (u64, Opcode)>,
+    /// Executions per [`Opcode::pattern`].
+    patterns: HashMap<&'static str, u64>,
+    cycles: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `instruction` at `address` is about to be executed.
+    pub fn record(&mut self, address: u16, instruction: Opcode) {
+        let entry = self.addresses.entry(address).or_insert((0, instruction));
+        entry.0 += 1;
+        entry.1 = instruction;
+        *self.patterns.entry(instruction.pattern()).or_insert(0) += 1;
+        self.cycles += 1;
+    }
+
+    /// The counts so far, the most executed first.
+    pub fn report(&self) -> ProfileReport {
+        let mut hot_spots: Vec<_> = self
+            .addresses
+            .iter()
+            .map(|(&address, &(count, instruction))| HotSpot {
+                address,
+                count,
+                instruction,
+            })
+            .collect();
+        hot_spots.sort_by(|a, b| b.count.cmp(&a.count).then(a.address.cmp(&b.address)));
+
+        let mut patterns: Vec<_> = self
+            .patterns
+            .iter()
+            .map(|(&pattern, &count)| (pattern, count))
+            .collect();
+        patterns.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        ProfileReport {
+            cycles: self.cycles,
+            hot_spots,
+            patterns,
+        }
+    }
+}
+
+/// How often the instruction at an address ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HotSpot {
+    pub address: u16,
+    pub count: u64,
+    /// The instruction at `address` when it last ran.
+    pub instruction: Opcode,
+}
+
+/// The results of a [`Profiler`], sorted by how often they ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// The number of instructions profiled.
+    pub cycles: u64,
+    pub hot_spots: Vec<HotSpot>,
+    /// Executions per kind of instruction, e.g. `8XY4`.
+    pub patterns: Vec<(&'static str, u64)>,
+}
+
+impl ProfileReport {
+    fn percentage(&self, count: u64) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.cycles as f64
+        }
+    }
+}
+
+/// The hot spots with their disassembly, then the kinds of instructions, e.g.
+/// `0x204  JP 0x204                    1000   50.0%`.
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} instructions", self.cycles)?;
+
+        writeln!(f, "\nHot spots:")?;
+        for hot_spot in &self.hot_spots {
+            writeln!(
+                f,
+                "{:#05x}  {:<20}  {:>10}  {:>5.1}%",
+                hot_spot.address,
+                hot_spot.instruction.to_string(),
+                hot_spot.count,
+                self.percentage(hot_spot.count)
+            )?;
+        }
+
+        writeln!(f, "\nInstructions:")?;
+        for &(pattern, count) in &self.patterns {
+            writeln!(
+                f,
+                "{:<27}  {:>10}  {:>5.1}%",
+                pattern,
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HotSpot, Profiler};
+    use crate::Opcode;
+
+    #[test]
+    fn test_report() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, Opcode::LdByte(0, 1));
+        for _ in 0..3 {
+            profiler.record(0x202, Opcode::AddByte(0, 1));
+            profiler.record(0x204, Opcode::AddByte(1, 1));
+            profiler.record(0x206, Opcode::Jp(0x202));
+        }
+
+        let report = profiler.report();
+
+        assert_eq!(report.cycles, 10);
+        assert_eq!(
+            report.hot_spots[0],
+            HotSpot {
+                address: 0x202,
+                count: 3,
+                instruction: Opcode::AddByte(0, 1)
+            }
+        );
+        assert_eq!(report.hot_spots[3].address, 0x200);
+        assert_eq!(report.patterns, vec![("7XNN", 6), ("1NNN", 3), ("6XNN", 1)]);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, Opcode::LdByte(0, 1));
+        profiler.record(0x202, Opcode::Jp(0x202));
+        profiler.record(0x202, Opcode::Jp(0x202));
+        profiler.record(0x202, Opcode::Jp(0x202));
+
+        let report = profiler.report().to_string();
+        let lines: Vec<_> = report.lines().collect();
+
+        assert_eq!(lines[0], "4 instructions");
+        assert_eq!(lines[3], "0x202  JP 0x202                       3   75.0%");
+        assert_eq!(lines[7], "1NNN                                  3   75.0%");
+    }
+}
@@ -0,0 +1,162 @@
+//! Behavior that differs between CHIP-8 interpreters. The original documentation left some
+//! instructions ambiguous and interpreters, and the ROMs written for them, disagree on them.
+use std::str::FromStr;
+
+/// Which interpretation to use for each of the ambiguous instructions. The default is this
+/// interpreter's original behavior, where every quirk is off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY and store the result in VX, rather than shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing after the last register stored or loaded.
+    pub load_store_increments_i: bool,
+    /// BNNN is BXNN, jumping to XNN + VX rather than NNN + V0.
+    pub jump_uses_vx: bool,
+    /// DXYN clips sprites at the edges of the screen rather than wrapping them around.
+    pub clip_sprites: bool,
+    /// 8XY1, 8XY2, and 8XY3 reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// DXYN waits for the next 60Hz tick before drawing, like the COSMAC VIP waiting for the
+    /// vertical blank interrupt.
+    pub display_wait: bool,
+    /// Instructions that access memory at I, such as FX55, FX65, and DXYN, wrap around to
+    /// address 0 when they reach past the end of memory, rather than stopping with an error.
+    pub wrap_memory: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+            display_wait: true,
+            wrap_memory: false,
+        }
+    }
+
+    /// SUPER-CHIP on the HP 48.
+    pub fn super_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            display_wait: false,
+            wrap_memory: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo.
+    pub fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            display_wait: false,
+            wrap_memory: true,
+        }
+    }
+
+    /// The presets by their names on the command line.
+    pub fn presets() -> [(&'static str, Quirks); 4] {
+        [
+            ("none", Quirks::default()),
+            ("vip", Quirks::cosmac_vip()),
+            ("schip", Quirks::super_chip()),
+            ("xochip", Quirks::xo_chip()),
+        ]
+    }
+
+    /// The name of the preset with exactly these quirks, if there is one.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::presets()
+            .iter()
+            .find(|(_, preset)| preset == self)
+            .map(|(name, _)| *name)
+    }
+}
+
+/// A comma separated list of presets and individual quirks, e.g. `vip` or `clip,vf-reset`.
+/// Later entries are added on top of earlier ones.
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quirks = Quirks::default();
+
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match name {
+                "none" => quirks = Quirks::default(),
+                "vip" => quirks = Quirks::cosmac_vip(),
+                "schip" => quirks = Quirks::super_chip(),
+                "xochip" => quirks = Quirks::xo_chip(),
+                "shift-vy" => quirks.shift_uses_vy = true,
+                "load-store-i" => quirks.load_store_increments_i = true,
+                "jump-vx" => quirks.jump_uses_vx = true,
+                "clip" => quirks.clip_sprites = true,
+                "vf-reset" => quirks.logic_resets_vf = true,
+                "display-wait" => quirks.display_wait = true,
+                "wrap-memory" => quirks.wrap_memory = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown quirk `{}`, expected one of none, vip, schip, xochip, shift-vy, \
+                         load-store-i, jump-vx, clip, vf-reset, display-wait, or wrap-memory",
+                        name
+                    ))
+                }
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quirks;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("".parse(), Ok(Quirks::default()));
+        assert_eq!("vip".parse(), Ok(Quirks::cosmac_vip()));
+        assert_eq!("xochip".parse(), Ok(Quirks::xo_chip()));
+        assert_eq!(
+            "schip, vf-reset".parse(),
+            Ok(Quirks {
+                logic_resets_vf: true,
+                ..Quirks::super_chip()
+            })
+        );
+        assert_eq!(
+            "display-wait".parse(),
+            Ok(Quirks {
+                display_wait: true,
+                ..Quirks::default()
+            })
+        );
+        assert_eq!(
+            "wrap-memory".parse(),
+            Ok(Quirks {
+                wrap_memory: true,
+                ..Quirks::default()
+            })
+        );
+        assert!("wrap".parse::<Quirks>().is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        for (name, preset) in &Quirks::presets() {
+            assert_eq!(name.parse(), Ok(*preset));
+            assert_eq!(preset.name(), Some(*name));
+        }
+        assert_eq!("vip,jump-vx".parse::<Quirks>().unwrap().name(), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! Recording the display as an animated GIF or APNG.
+//!
+//! A [`Recording`] collects [`Screenshot`]s with how long each was shown. Most frames are the
+//! same as the one before, those only make the previous frame last longer. Frames are kept at
+//! the size of the display and only scaled up while encoding, to keep long recordings small.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::Screenshot;
+
+/// GIFs can only use this many colors.
+const MAX_GIF_COLORS: usize = 256;
+/// Most viewers show GIF frames shorter than this many hundredths of a second for much longer.
+const MIN_GIF_DELAY: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    /// The format for `path` by its extension, `.gif`, `.png`, or `.apng`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.to_ascii_lowercase().parse().ok())
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+        }
+    }
+}
+
+impl FromStr for RecordingFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(RecordingFormat::Gif),
+            "png" | "apng" => Ok(RecordingFormat::Apng),
+            _ => Err(format!(
+                "Unknown recording format `{}`, expected gif or apng",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingError {
+    /// Nothing was recorded.
+    Empty,
+    /// The frames use more colors than a GIF can.
+    TooManyColors(usize),
+    /// The GIF or PNG encoder failed.
+    Encoding(String),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordingError::Empty => write!(f, "Nothing was recorded"),
+            RecordingError::TooManyColors(colors) => write!(
+                f,
+                "The recording uses {} colors, a GIF can have at most {}",
+                colors, MAX_GIF_COLORS
+            ),
+            RecordingError::Encoding(error) => write!(f, "Can't encode the recording: {}", error),
+        }
+    }
+}
+
+impl Error for RecordingError {}
+
+/// Frames and how long each was shown, all the same size.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    frames: Vec<(Screenshot, Duration)>,
+    scale: usize,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::with_scale(1)
+    }
+
+    /// A recording encoded with every pixel `scale` by `scale` pixels large.
+    pub fn with_scale(scale: usize) -> Self {
+        assert!(scale > 0, "Can't scale by 0");
+
+        Self {
+            frames: Vec::new(),
+            scale,
+        }
+    }
+
+    /// Add `frame`, shown for `duration`. A frame that's the same as the previous one makes the
+    /// previous one last longer instead.
+    pub fn push(&mut self, frame: Screenshot, duration: Duration) {
+        if let Some((previous, previous_duration)) = self.frames.last_mut() {
+            assert_eq!(
+                (frame.width, frame.height),
+                (previous.width, previous.height),
+                "Every frame of a recording should be the same size"
+            );
+
+            if previous.pixels == frame.pixels {
+                *previous_duration += duration;
+                return;
+            }
+        }
+
+        self.frames.push((frame, duration));
+    }
+
+    /// How many distinct frames were recorded.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|&(_, duration)| duration).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn encode(&self, format: RecordingFormat) -> Result<Vec<u8>, RecordingError> {
+        match format {
+            RecordingFormat::Gif => self.to_gif(),
+            RecordingFormat::Apng => self.to_apng(),
+        }
+    }
+
+    /// The recording as a GIF that loops forever.
+    pub fn to_gif(&self) -> Result<Vec<u8>, RecordingError> {
+        let (first, _) = self.frames.first().ok_or(RecordingError::Empty)?;
+
+        let mut colors = Vec::new();
+        let mut color_indices = HashMap::new();
+        for (frame, _) in &self.frames {
+            for &pixel in &frame.pixels {
+                color_indices.entry(pixel & 0x00ff_ffff).or_insert_with(|| {
+                    colors.push(pixel & 0x00ff_ffff);
+                    colors.len() - 1
+                });
+            }
+        }
+        if colors.len() > MAX_GIF_COLORS {
+            return Err(RecordingError::TooManyColors(colors.len()));
+        }
+        let mut palette = Vec::with_capacity(colors.len() * 3);
+        for color in &colors {
+            palette.extend_from_slice(&color.to_be_bytes()[1..]);
+        }
+
+        let (width, height) = gif_size(first.width * self.scale, first.height * self.scale)?;
+        let mut encoder =
+            gif::Encoder::new(Vec::new(), width, height, &palette).map_err(encoding_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(encoding_error)?;
+
+        for (frame, delay) in self.gif_delays() {
+            let indices = frame
+                .scaled(self.scale)
+                .pixels
+                .iter()
+                .map(|pixel| color_indices[&(pixel & 0x00ff_ffff)] as u8)
+                .collect::<Vec<_>>();
+            let mut frame = gif::Frame::from_indexed_pixels(width, height, indices, None);
+            frame.delay = delay.min(u64::from(u16::MAX)) as u16;
+            // Keep the frame when drawing the next
+            frame.dispose = gif::DisposalMethod::Keep;
+            encoder.write_frame(&frame).map_err(encoding_error)?;
+        }
+
+        encoder.into_inner().map_err(encoding_error)
+    }
+
+    /// The recording as an animated PNG that loops forever.
+    pub fn to_apng(&self) -> Result<Vec<u8>, RecordingError> {
+        let (first, _) = self.frames.first().ok_or(RecordingError::Empty)?;
+
+        let first = first.scaled(self.scale);
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, first.width as u32, first.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len() as u32, 0)
+            .map_err(encoding_error)?;
+        let mut writer = encoder.write_header().map_err(encoding_error)?;
+        for (frame, duration) in &self.frames {
+            let milliseconds = duration.as_millis();
+            let (delay, unit) = if milliseconds <= u128::from(u16::MAX) {
+                (milliseconds as u16, 1000u16)
+            } else {
+                ((milliseconds / 10).min(u128::from(u16::MAX)) as u16, 100)
+            };
+            writer
+                .set_frame_delay(delay, unit)
+                .map_err(encoding_error)?;
+            writer
+                .write_image_data(&frame.scaled(self.scale).rgb())
+                .map_err(encoding_error)?;
+        }
+        writer.finish().map_err(encoding_error)?;
+
+        Ok(png)
+    }
+
+    /// The frames to put in a GIF and their delays in hundredths of a second. Rounding is done
+    /// from the start of the recording so that it doesn't add up, and frames too short to show
+    /// are dropped with their time going to the next frame.
+    fn gif_delays(&self) -> Vec<(&Screenshot, u64)> {
+        let mut delays = Vec::with_capacity(self.frames.len());
+        let mut shown_until = 0;
+        let mut elapsed = Duration::from_secs(0);
+        for (index, (frame, duration)) in self.frames.iter().enumerate() {
+            elapsed += *duration;
+            let end = (elapsed.as_millis() as u64 + 5) / 10;
+            let is_last = index == self.frames.len() - 1;
+            if end - shown_until >= MIN_GIF_DELAY || is_last {
+                delays.push((frame, end - shown_until));
+                shown_until = end;
+            }
+        }
+
+        delays
+    }
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The size of a GIF frame, GIFs are at most 65535 pixels wide and high.
+fn gif_size(width: usize, height: usize) -> Result<(u16, u16), RecordingError> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(RecordingError::Encoding(format!(
+            "{}x{} is too large for a GIF",
+            width, height
+        ))),
+    }
+}
+
+fn encoding_error(error: impl fmt::Display) -> RecordingError {
+    RecordingError::Encoding(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recording, RecordingError, RecordingFormat};
+    use crate::Screenshot;
+    use std::path::Path;
+    use std::time::Duration;
+
+    fn frame(pixels: &[u32]) -> Screenshot {
+        Screenshot::new(pixels.to_vec(), 2, 1)
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            RecordingFormat::from_path(Path::new("out.GIF")),
+            Some(RecordingFormat::Gif)
+        );
+        assert_eq!(
+            RecordingFormat::from_path(Path::new("out.apng")),
+            Some(RecordingFormat::Apng)
+        );
+        assert_eq!(RecordingFormat::from_path(Path::new("out.mp4")), None);
+        assert_eq!(RecordingFormat::from_path(Path::new("out")), None);
+    }
+
+    #[test]
+    fn test_deduplication() {
+        let mut recording = Recording::new();
+        recording.push(frame(&[0, 1]), Duration::from_millis(10));
+        recording.push(frame(&[0, 1]), Duration::from_millis(20));
+        recording.push(frame(&[1, 0]), Duration::from_millis(30));
+
+        assert_eq!(recording.frame_count(), 2);
+        assert_eq!(recording.duration(), Duration::from_millis(60));
+        assert_eq!(recording.frames[0].1, Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_gif_delays() {
+        let mut recording = Recording::new();
+        for pixel in 0..6 {
+            recording.push(frame(&[pixel, 0]), Duration::from_micros(16_667));
+        }
+
+        // 1.67 hundredths per frame, rounding leaves some frames too short
+        let delays = recording
+            .gif_delays()
+            .into_iter()
+            .map(|(frame, delay)| (frame.pixels[0], delay))
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [(0, 2), (2, 3), (3, 2), (5, 3)]);
+    }
+
+    #[test]
+    fn test_to_gif() {
+        let mut recording = Recording::new();
+        recording.push(frame(&[0x00ff_0000, 0]), Duration::from_millis(100));
+        recording.push(frame(&[0, 0x00ff_0000]), Duration::from_millis(100));
+
+        let gif = recording.to_gif().unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (2, 1));
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.buffer.to_vec(), frame.delay));
+        }
+        // Two frames of 10 hundredths each
+        assert_eq!(
+            frames,
+            [
+                (vec![0xff, 0, 0, 0xff, 0, 0, 0, 0xff], 10),
+                (vec![0, 0, 0, 0xff, 0xff, 0, 0, 0xff], 10)
+            ]
+        );
+
+        let mut scaled = Recording::with_scale(3);
+        scaled.push(frame(&[0x00ff_0000, 0]), Duration::from_millis(100));
+        let gif = scaled.to_gif().unwrap();
+        let decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (6, 3));
+    }
+
+    #[test]
+    fn test_gif_too_many_colors() {
+        let mut recording = Recording::new();
+        for color in 0..129 {
+            recording.push(
+                frame(&[color * 2, color * 2 + 1]),
+                Duration::from_millis(10),
+            );
+        }
+
+        assert_eq!(
+            recording.to_gif().unwrap_err(),
+            RecordingError::TooManyColors(258)
+        );
+    }
+
+    #[test]
+    fn test_to_apng() {
+        let mut recording = Recording::new();
+        recording.push(frame(&[0x00ff_0000, 0]), Duration::from_millis(100));
+        recording.push(frame(&[0, 0x00ff_0000]), Duration::from_millis(250));
+
+        let png = recording.to_apng().unwrap();
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let animation = reader.info().animation_control.unwrap();
+        // Looping forever
+        assert_eq!((animation.num_frames, animation.num_plays), (2, 0));
+        let mut frames = Vec::new();
+        for _ in 0..2 {
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut pixels).unwrap();
+            let control = reader.info().frame_control.unwrap();
+            frames.push((pixels, control.delay_num, control.delay_den));
+        }
+        assert_eq!(
+            frames,
+            [
+                (vec![0xff, 0, 0, 0, 0, 0], 100, 1000),
+                (vec![0, 0, 0, 0xff, 0, 0], 250, 1000)
+            ]
+        );
+        assert_eq!(Recording::new().to_apng(), Err(RecordingError::Empty));
+    }
+}
@@ -0,0 +1,277 @@
+//! Recording the keypad during a run and playing it back, for reproducible bug reports and
+//! tool-assisted runs.
+//!
+//! Recordings are keyed by [`Emulator::cycle_count`](crate::Emulator::cycle_count), the number
+//! of instructions executed since power on. A replay behaves exactly like the recorded run as
+//! long as it runs at the same speed, from the same random seed, with the keypad sampled at the
+//! same points, e.g. before every [`Emulator::run_frame`](crate::Emulator::run_frame).
+//!
+//! The format is one event per line, `<cycle> <key> down|up`:
+//!
+//! ```text
+//! # chip-8 input recording
+//! 1200 5 down
+//! 1450 5 up
+//! ```
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::controller::{parse_key, ParseError};
+use crate::Input;
+
+const HEADER: &str = "# chip-8 input recording";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    Down(u8),
+    Up(u8),
+}
+
+/// A [`KeyEvent`] and the cycle it happened at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedEvent {
+    pub cycle: u64,
+    pub event: KeyEvent,
+}
+
+impl fmt::Display for RecordedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.event {
+            KeyEvent::Down(key) => write!(f, "{} {:X} down", self.cycle, key),
+            KeyEvent::Up(key) => write!(f, "{} {:X} up", self.cycle, key),
+        }
+    }
+}
+
+/// Writes the presses and releases of another [`Input`] to `writer` as they happen, and acts
+/// as the last input it sampled.
+pub struct InputRecorder<W: Write> {
+    writer: W,
+    keys: [bool; 16],
+}
+
+impl<W: Write> InputRecorder<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+
+        Ok(Self {
+            writer,
+            keys: [false; 16],
+        })
+    }
+
+    /// Sample `input` at `cycle` and record every key that changed since the last sample.
+    pub fn record(&mut self, cycle: u64, input: &dyn Input) -> io::Result<()> {
+        for key in 0..16 {
+            let is_down = input.is_key_down(key);
+            if is_down == self.keys[key as usize] {
+                continue;
+            }
+
+            self.keys[key as usize] = is_down;
+            let event = if is_down {
+                KeyEvent::Down(key)
+            } else {
+                KeyEvent::Up(key)
+            };
+            writeln!(self.writer, "{}", RecordedEvent { cycle, event })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Input for InputRecorder<W> {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+}
+
+/// Plays back a recording made by [`InputRecorder`].
+#[derive(Debug, Clone)]
+pub struct InputReplayer {
+    events: Vec<RecordedEvent>,
+    next: usize,
+    keys: [bool; 16],
+}
+
+impl InputReplayer {
+    pub fn new(mut events: Vec<RecordedEvent>) -> Self {
+        events.sort_by_key(|event| event.cycle);
+
+        Self {
+            events,
+            next: 0,
+            keys: [false; 16],
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut events = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || ParseError::new(line_number, format!("Invalid event `{}`", line));
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let (cycle, key, state) = match parts.as_slice() {
+                [cycle, key, state] => (*cycle, *key, *state),
+                _ => return Err(invalid()),
+            };
+            let cycle = cycle.parse::<u64>().map_err(|_| invalid())?;
+            let key = parse_key(key).ok_or_else(invalid)?;
+            let event = match state {
+                "down" => KeyEvent::Down(key),
+                "up" => KeyEvent::Up(key),
+                _ => return Err(invalid()),
+            };
+
+            events.push(RecordedEvent { cycle, event });
+        }
+
+        Ok(Self::new(events))
+    }
+
+    /// Apply every event recorded up to and including `cycle`.
+    pub fn advance(&mut self, cycle: u64) {
+        while let Some(recorded) = self.events.get(self.next) {
+            if recorded.cycle > cycle {
+                break;
+            }
+
+            let (key, is_down) = match recorded.event {
+                KeyEvent::Down(key) => (key, true),
+                KeyEvent::Up(key) => (key, false),
+            };
+            // Events built by hand can name keys past the keypad, there's nothing to hold then
+            if let Some(held) = self.keys.get_mut(key as usize) {
+                *held = is_down;
+            }
+            self.next += 1;
+        }
+    }
+
+    /// Start over from the beginning of the recording, e.g. after a reset.
+    pub fn rewind(&mut self) {
+        self.next = 0;
+        self.keys = [false; 16];
+    }
+
+    /// Whether every event has been played.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
+
+impl Input for InputReplayer {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+
+    fn advance(&mut self, cycle: u64) {
+        InputReplayer::advance(self, cycle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputRecorder, InputReplayer, KeyEvent, RecordedEvent};
+    use crate::batch::NullInput;
+    use crate::{Emulator, FramebufferDisplay, Input, Quirks};
+
+    struct HeldKey(u8);
+
+    impl Input for HeldKey {
+        fn is_key_down(&self, key: u8) -> bool {
+            key == self.0
+        }
+    }
+
+    // F30A: LD V3, K, 7401: ADD V4, 0x01, 1200: JP 0x200
+    const COUNT_KEY_PRESSES: [u8; 6] = [0xF3, 0x0A, 0x74, 0x01, 0x12, 0x00];
+
+    fn emulator() -> Emulator<FramebufferDisplay> {
+        let display = FramebufferDisplay::default();
+
+        Emulator::new(display, COUNT_KEY_PRESSES.to_vec(), Quirks::default()).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let presses: &[(u64, &dyn Input)] = &[
+            (0, &NullInput),
+            (10, &HeldKey(0x7)),
+            (20, &NullInput),
+            (30, &HeldKey(0xA)),
+            (40, &NullInput),
+        ];
+        let mut recorder = InputRecorder::new(Vec::new()).unwrap();
+        let mut recorded = emulator();
+        for &(cycle, input) in presses {
+            while recorded.cycle_count() < cycle {
+                recorded.cycle(&recorder).unwrap();
+            }
+            recorder.record(cycle, input).unwrap();
+        }
+        // Finish the FX0A waiting for the last release and count it
+        for _ in 0..2 {
+            recorded.cycle(&recorder).unwrap();
+        }
+        assert_eq!(recorded.register(0x4), 2);
+        assert_eq!(recorded.register(0x3), 0xA);
+
+        let source = String::from_utf8(recorder.writer).unwrap();
+        assert_eq!(
+            source,
+            "# chip-8 input recording\n10 7 down\n20 7 up\n30 A down\n40 A up\n"
+        );
+
+        let mut replayer = InputReplayer::parse(&source).unwrap();
+        let mut replayed = emulator();
+        while replayed.cycle_count() < recorded.cycle_count() {
+            replayer.advance(replayed.cycle_count());
+            replayed.cycle(&replayer).unwrap();
+        }
+        assert!(replayer.is_finished());
+        assert_eq!(replayed.state(), recorded.state());
+    }
+
+    #[test]
+    fn test_keys_past_the_keypad() {
+        let mut replayer = InputReplayer::new(vec![RecordedEvent {
+            cycle: 0,
+            event: KeyEvent::Down(0x1F),
+        }]);
+        replayer.advance(0);
+        assert!(replayer.is_finished());
+        assert!(!replayer.is_key_down(0x1F));
+        assert!(!replayer.is_key_down(0xFF));
+        assert!(!InputRecorder::new(Vec::new()).unwrap().is_key_down(0xFF));
+
+        // 60FF: LD V0, 0xFF, E09E: SKP V0
+        let rom = vec![0x60, 0xFF, 0xE0, 0x9E];
+        let display = FramebufferDisplay::default();
+        let mut emulator = Emulator::new(display, rom, Quirks::default()).unwrap();
+        emulator.cycle(&replayer).unwrap();
+        emulator.cycle(&replayer).unwrap();
+        assert_eq!(emulator.pc(), 0x204);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |source: &str| InputReplayer::parse(source).unwrap_err().line;
+
+        assert_eq!(parse("10 5"), 1);
+        assert_eq!(parse("# comment\n10 5 sideways"), 2);
+        assert_eq!(parse("ten 5 down"), 1);
+        assert_eq!(parse("10 10 down"), 1);
+    }
+}
@@ -0,0 +1,104 @@
+//! Sources of random numbers for CXNN. Unlike a closure the state of these can be inspected and
+//! copied, e.g. to check that a replay starts from the same seed.
+
+/// Produces the random bytes CXNN masks with NN.
+pub trait Rng: Send {
+    fn next_u8(&mut self) -> u8;
+}
+
+/// Random numbers from the thread local RNG of `rand`, different on every run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsRandom;
+
+impl Rng for OsRandom {
+    fn next_u8(&mut self) -> u8 {
+        rand::random()
+    }
+}
+
+/// A xorshift sequence starting from a seed, the same seed always gives the same numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Seeded {
+    state: u32,
+}
+
+impl Seeded {
+    pub fn new(seed: u32) -> Self {
+        // xorshift never leaves 0
+        let state = if seed == 0 { 0x2545_F491 } else { seed };
+
+        Self { state }
+    }
+
+    /// Where the sequence is at, `Seeded::new(state)` continues from here.
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+}
+
+impl Rng for Seeded {
+    fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+
+        self.state as u8
+    }
+}
+
+/// The same number every time, for tests of ROMs that use CXNN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constant(pub u8);
+
+impl Rng for Constant {
+    fn next_u8(&mut self) -> u8 {
+        self.0
+    }
+}
+
+/// Random numbers from `rand`.
+#[cfg(not(any(feature = "fuzzing", feature = "wasm")))]
+pub(crate) fn default_rng() -> Box<dyn Rng> {
+    Box::new(OsRandom)
+}
+
+/// A fixed sequence so that every run with the same input behaves the same.
+#[cfg(feature = "fuzzing")]
+pub(crate) fn default_rng() -> Box<dyn Rng> {
+    Box::new(Seeded::new(0x2545_F491))
+}
+
+/// A sequence seeded from `Math.random`, `rand` has no source of entropy in the browser.
+#[cfg(all(feature = "wasm", not(feature = "fuzzing")))]
+pub(crate) fn default_rng() -> Box<dyn Rng> {
+    Box::new(Seeded::new(
+        (js_sys::Math::random() * f64::from(u32::MAX)) as u32,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Constant, Rng, Seeded};
+
+    fn take(rng: &mut dyn Rng, count: usize) -> Vec<u8> {
+        (0..count).map(|_| rng.next_u8()).collect()
+    }
+
+    #[test]
+    fn test_seeded() {
+        let mut first = Seeded::new(42);
+        let mut second = Seeded::new(42);
+        assert_eq!(take(&mut first, 8), take(&mut second, 8));
+
+        let mut resumed = Seeded::new(first.state());
+        assert_eq!(take(&mut first, 8), take(&mut resumed, 8));
+
+        let mut zero = Seeded::new(0);
+        assert!(take(&mut zero, 8).iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn test_constant() {
+        assert_eq!(take(&mut Constant(7), 3), vec![7, 7, 7]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Per-game settings, looked up by the SHA-1 of the ROM.
+//!
+//! Many games only run well with particular quirks or at a particular speed. The database
+//! embedded in the library records these, along with a palette and which keys the arrow keys
+//! and enter should press, in the same format as [controller profiles](crate::controller):
+//!
+//! ```text
+//! [0123456789abcdef0123456789abcdef01234567]
+//! name = Blinky
+//! quirks = vip
+//! speed = 700
+//! palette = amber
+//! up = 0x3
+//! down = 0x6
+//! ```
+use std::collections::HashMap;
+
+use crate::controller::{parse_key, ParseError};
+use crate::hash;
+use crate::{Palette, Quirks};
+
+const EMBEDDED: &str = include_str!("rom_db.txt");
+
+/// The CHIP-8 keys the arrow keys and enter press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keymap {
+    pub up: u8,
+    pub down: u8,
+    pub left: u8,
+    pub right: u8,
+    pub action: u8,
+}
+
+impl Default for Keymap {
+    /// 2, 8, 4, and 6 for movement with 5 in the middle, which is what most games use.
+    fn default() -> Self {
+        Self {
+            up: 0x2,
+            down: 0x8,
+            left: 0x4,
+            right: 0x6,
+            action: 0x5,
+        }
+    }
+}
+
+/// What the database knows about a game, `None` where the defaults work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub name: String,
+    pub quirks: Option<Quirks>,
+    /// The `speed` in instructions per second, run as the nearest whole instructions per frame.
+    pub cycles_per_second: Option<u32>,
+    pub palette: Option<Palette>,
+    pub keymap: Keymap,
+}
+
+impl RomInfo {
+    fn new() -> Self {
+        Self {
+            name: String::new(),
+            quirks: None,
+            cycles_per_second: None,
+            palette: None,
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+/// The identifier of `rom` in the database, its SHA-1 in hex.
+pub fn rom_id(rom: &[u8]) -> String {
+    hash::to_hex(&hash::sha1(rom))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// The database that ships with the library.
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED).expect("The embedded ROM database should be valid")
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut database = Self::default();
+        let mut current: Option<&mut RomInfo> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let id = line[1..line.len() - 1].trim().to_lowercase();
+                if id.len() != 40 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Invalid SHA-1 `{}`", id),
+                    ));
+                }
+                current = Some(database.roms.entry(id).or_insert_with(RomInfo::new));
+
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("Expected `setting = value`, got `{}`", line),
+                    ))
+                }
+            };
+            let info = current.as_mut().ok_or_else(|| {
+                ParseError::new(line_number, "Expected a `[sha1]` section first".to_owned())
+            })?;
+            let invalid = |message: String| ParseError::new(line_number, message);
+            let key = || {
+                parse_key(value).ok_or_else(|| invalid(format!("Invalid CHIP-8 key `{}`", value)))
+            };
+
+            match name {
+                "name" => info.name = value.to_owned(),
+                "quirks" => info.quirks = Some(value.parse().map_err(invalid)?),
+                "speed" => {
+                    info.cycles_per_second = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|&hz| hz > 0)
+                            .ok_or_else(|| invalid(format!("Invalid speed `{}`", value)))?,
+                    )
+                }
+                "palette" => info.palette = Some(value.parse().map_err(invalid)?),
+                "up" => info.keymap.up = key()?,
+                "down" => info.keymap.down = key()?,
+                "left" => info.keymap.left = key()?,
+                "right" => info.keymap.right = key()?,
+                "action" => info.keymap.action = key()?,
+                _ => return Err(invalid(format!("Unknown setting `{}`", name))),
+            }
+        }
+
+        Ok(database)
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&rom_id(rom))
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rom_id, Keymap, RomDatabase};
+    use crate::{Palette, Quirks};
+
+    #[test]
+    fn test_embedded() {
+        RomDatabase::embedded();
+    }
+
+    #[test]
+    fn test_lookup() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let source = format!(
+            "# comment\n[{}]\nname = Blank\nquirks = vip\nspeed = 500\npalette = amber\n\
+             up = 0x3\naction = a\n",
+            rom_id(&rom).to_uppercase()
+        );
+
+        let database = RomDatabase::parse(&source).unwrap();
+        let info = database.lookup(&rom).unwrap();
+
+        assert_eq!(info.name, "Blank");
+        assert_eq!(info.quirks, Some(Quirks::cosmac_vip()));
+        assert_eq!(info.cycles_per_second, Some(500));
+        assert_eq!(info.palette, Some(Palette::AMBER));
+        assert_eq!(
+            info.keymap,
+            Keymap {
+                up: 0x3,
+                action: 0xA,
+                ..Keymap::default()
+            }
+        );
+        assert!(database.lookup(&[0x12, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let id = rom_id(&[0x12, 0x00]);
+        let parse = |source: &str| RomDatabase::parse(source).unwrap_err().line;
+
+        assert_eq!(parse("name = Orphan"), 1);
+        assert_eq!(parse("[1234]"), 1);
+        assert_eq!(parse(&format!("[{}]\n\nspeed = 0", id)), 3);
+        assert_eq!(parse(&format!("[{}]\nquirks = wrong", id)), 2);
+        assert_eq!(parse(&format!("[{}]\nup = 0x10", id)), 2);
+        assert_eq!(parse(&format!("[{}]\ncolor = red", id)), 2);
+    }
+}
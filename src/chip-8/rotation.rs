@@ -0,0 +1,147 @@
+//! Rotating the picture for screens that are mounted sideways or upside down, e.g. portrait
+//! displays on handhelds.
+use std::fmt;
+use std::str::FromStr;
+
+/// A clockwise rotation of the picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// The width and height of a `width` by `height` picture after rotating it.
+    pub fn dimensions(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::Deg0 | Rotation::Deg180 => (width, height),
+            Rotation::Deg90 | Rotation::Deg270 => (height, width),
+        }
+    }
+
+    /// Rotate a row major `width` by `height` buffer, e.g. a framebuffer. The result is row
+    /// major in the dimensions given by [`Rotation::dimensions`].
+    pub fn apply<T: Copy>(self, buffer: &[T], width: usize, height: usize) -> Vec<T> {
+        assert_eq!(
+            buffer.len(),
+            width * height,
+            "Buffer doesn't match {}x{}",
+            width,
+            height
+        );
+        let (rotated_width, rotated_height) = self.dimensions(width, height);
+
+        (0..rotated_height)
+            .flat_map(|y| (0..rotated_width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (source_x, source_y) = match self {
+                    Rotation::Deg0 => (x, y),
+                    Rotation::Deg90 => (y, height - 1 - x),
+                    Rotation::Deg180 => (width - 1 - x, height - 1 - y),
+                    Rotation::Deg270 => (width - 1 - y, x),
+                };
+
+                buffer[source_y * width + source_x]
+            })
+            .collect()
+    }
+
+    /// Games conventionally use 2, 4, 6, and 8 as directions on the 3x3 block of keys from 1 to
+    /// 9. When the picture is rotated so are the directions, pressing the key on the right of
+    /// the block should move right on the rotated screen. Maps the key that was pressed to the
+    /// key the game expects, keys outside of the block are left as is.
+    pub fn remap_key(self, key: u8) -> u8 {
+        if !(1..=9).contains(&key) {
+            return key;
+        }
+
+        let (row, column) = ((key - 1) / 3, (key - 1) % 3);
+        // Turn the block the opposite way of the picture
+        let (row, column) = match self {
+            Rotation::Deg0 => (row, column),
+            Rotation::Deg90 => (2 - column, row),
+            Rotation::Deg180 => (2 - row, 2 - column),
+            Rotation::Deg270 => (column, 2 - row),
+        };
+
+        row * 3 + column + 1
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" => Ok(Rotation::Deg0),
+            "90" => Ok(Rotation::Deg90),
+            "180" => Ok(Rotation::Deg180),
+            "270" => Ok(Rotation::Deg270),
+            _ => Err(format!(
+                "Invalid rotation `{}`, expected one of 0, 90, 180, or 270",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let degrees = match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 90,
+            Rotation::Deg180 => 180,
+            Rotation::Deg270 => 270,
+        };
+
+        write!(f, "{}", degrees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rotation;
+
+    // 1 2 3
+    // 4 5 6
+    const BUFFER: [u8; 6] = [1, 2, 3, 4, 5, 6];
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(Rotation::Deg0.apply(&BUFFER, 3, 2), BUFFER.to_vec());
+        assert_eq!(Rotation::Deg90.apply(&BUFFER, 3, 2), vec![4, 1, 5, 2, 6, 3]);
+        assert_eq!(
+            Rotation::Deg180.apply(&BUFFER, 3, 2),
+            vec![6, 5, 4, 3, 2, 1]
+        );
+        assert_eq!(
+            Rotation::Deg270.apply(&BUFFER, 3, 2),
+            vec![3, 6, 2, 5, 1, 4]
+        );
+        assert_eq!(Rotation::Deg90.dimensions(64, 32), (32, 64));
+    }
+
+    #[test]
+    fn test_remap_key() {
+        // The picture is turned clockwise so up on the game's screen is now to the right
+        assert_eq!(Rotation::Deg90.remap_key(0x6), 0x2);
+        assert_eq!(Rotation::Deg90.remap_key(0x2), 0x4);
+        assert_eq!(Rotation::Deg180.remap_key(0x8), 0x2);
+        assert_eq!(Rotation::Deg270.remap_key(0x4), 0x2);
+        assert_eq!(Rotation::Deg270.remap_key(0x1), 0x3);
+
+        for rotation in &[Rotation::Deg90, Rotation::Deg180, Rotation::Deg270] {
+            assert_eq!(rotation.remap_key(0x5), 0x5);
+            assert_eq!(rotation.remap_key(0xA), 0xA);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("270".parse(), Ok(Rotation::Deg270));
+        assert_eq!(Rotation::Deg90.to_string().parse(), Ok(Rotation::Deg90));
+        assert!("45".parse::<Rotation>().is_err());
+    }
+}
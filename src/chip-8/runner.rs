@@ -0,0 +1,272 @@
+//! A run loop that isn't tied to a particular frontend. [`Runner`] takes care of running the
+//! right number of frames as time passes and of redrawing when the display changes, a frontend
+//! only has to read input and put pixels on the screen.
+//!
+//! ```no_run
+//! use chip_8::runner::Runner;
+//! use chip_8::{Emulator, FramebufferDisplay, Input, Quirks};
+//!
+//! struct NoKeys;
+//!
+//! impl Input for NoKeys {
+//!     fn is_key_down(&self, _key: u8) -> bool {
+//!         false
+//!     }
+//! }
+//!
+//! let rom = std::fs::read("pong.ch8").unwrap();
+//! let emulator = Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+//! let mut runner = Runner::new(emulator, Box::new(NoKeys), |pixels| {
+//!     // Draw the 64x32 RGBA pixels
+//! });
+//! runner.run(|_| true).unwrap();
+//! ```
+use std::time::Duration;
+
+use crate::clock::SystemClock;
+use crate::{Clock, Display, Emulator, Error, FramePacer, Input};
+
+/// Timers run at exactly this rate, each frame also runs a 60th of a second of instructions.
+pub const FRAMES_PER_SECOND: u32 = 60;
+/// After a stall, e.g. the machine sleeping, run at most this many frames to catch up rather
+/// than fast forwarding through everything that was missed.
+pub const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// Puts the display's pixels, one RGBA value per pixel, on the screen.
+pub type Present = Box<dyn FnMut(&[u32])>;
+
+/// Owns an [`Emulator`] and runs it in real time, calling `present` with the display's pixels
+/// whenever they have changed.
+pub struct Runner<D> {
+    emulator: Emulator<D>,
+    input: Box<dyn Input>,
+    present: Present,
+    /// What [`Runner::run`] goes by.
+    clock: Box<dyn Clock>,
+    frames: FramePacer,
+    redraws: FramePacer,
+    /// Whether the display has changed since it was last presented.
+    frame_pending: bool,
+    /// The display's pixels, kept to not allocate them again on every redraw.
+    pixels: Vec<u32>,
+}
+
+impl<D: Display> Runner<D> {
+    /// A runner that redraws at up to 60Hz by the [`SystemClock`], the first
+    /// [`Runner::update`] that redraws always presents the display.
+    pub fn new(
+        emulator: Emulator<D>,
+        input: Box<dyn Input>,
+        present: impl FnMut(&[u32]) + 'static,
+    ) -> Self {
+        Self {
+            emulator,
+            input,
+            present: Box::new(present),
+            clock: Box::new(SystemClock::new()),
+            frames: FramePacer::new(FRAMES_PER_SECOND),
+            redraws: FramePacer::new(FRAMES_PER_SECOND),
+            frame_pending: true,
+            pixels: Vec::new(),
+        }
+    }
+
+    /// Redraw at most `rate` times a second, e.g. 30 for a slow terminal.
+    pub fn set_redraw_rate(&mut self, rate: u32) {
+        self.redraws = FramePacer::new(rate);
+    }
+
+    pub fn emulator(&self) -> &Emulator<D> {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator<D> {
+        &mut self.emulator
+    }
+
+    pub fn into_emulator(self) -> Emulator<D> {
+        self.emulator
+    }
+
+    pub fn set_input(&mut self, input: Box<dyn Input>) {
+        self.input = input;
+    }
+
+    /// Go by `clock` instead of the system's, e.g. a
+    /// [`ManualClock`](crate::clock::ManualClock) in tests of [`Runner::run`].
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Redraw on the next [`Runner::update`] that's due for one even if the display hasn't
+    /// changed, e.g. after changing the palette.
+    pub fn request_redraw(&mut self) {
+        self.frame_pending = true;
+    }
+
+    /// Account for `elapsed` time, running the frames that are now due and presenting the
+    /// display if it's time to redraw and it has changed. Nothing runs while the emulator is
+    /// paused.
+    pub fn update(&mut self, elapsed: Duration) -> Result<(), Error> {
+        let due_frames = self.frames.advance(elapsed);
+        for _ in 0..due_frames.min(MAX_CATCH_UP_FRAMES) {
+            if self.emulator.is_paused() {
+                break;
+            }
+
+            let frame = self.emulator.run_frame(self.input.as_ref())?;
+            self.frame_pending |= frame.is_dirty;
+        }
+
+        if self.redraws.advance(elapsed) > 0 && self.frame_pending {
+            self.emulator.display().write_rgba(&mut self.pixels);
+            (self.present)(&self.pixels);
+            self.frame_pending = false;
+        }
+
+        Ok(())
+    }
+
+    /// How long until [`Runner::update`] has something to do, useful for sleeping.
+    pub fn time_until_next_frame(&self) -> Duration {
+        self.frames
+            .time_until_next_frame()
+            .min(self.redraws.time_until_next_frame())
+    }
+
+    /// Drop any partial frame, typically after resuming from a pause.
+    pub fn reset_timing(&mut self) {
+        self.frames.reset();
+        self.redraws.reset();
+    }
+
+    /// Update and sleep in a loop for as long as `is_running` returns true or until the ROM
+    /// does something invalid.
+    pub fn run(&mut self, mut is_running: impl FnMut(&Emulator<D>) -> bool) -> Result<(), Error> {
+        let mut last_update = self.clock.now();
+
+        while is_running(&self.emulator) {
+            let now = self.clock.now();
+            self.update(now - last_update)?;
+            last_update = now;
+
+            self.clock.sleep(self.time_until_next_frame());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::{Runner, FRAMES_PER_SECOND, MAX_CATCH_UP_FRAMES};
+    use crate::batch::NullInput;
+    use crate::clock::ManualClock;
+    use crate::Clock;
+    use crate::{Emulator, FramebufferDisplay, Quirks};
+
+    const FRAME: Duration = Duration::from_micros(1_000_000 / FRAMES_PER_SECOND as u64 + 1);
+
+    /// A runner for `rom` and the number of times it has presented.
+    fn runner(rom: Vec<u8>) -> (Runner<FramebufferDisplay>, Rc<RefCell<usize>>) {
+        let emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let presented = Rc::new(RefCell::new(0));
+        let counter = presented.clone();
+        let runner = Runner::new(emulator, Box::new(NullInput), move |pixels| {
+            assert_eq!(pixels.len(), 64 * 32);
+            *counter.borrow_mut() += 1;
+        });
+
+        (runner, presented)
+    }
+
+    #[test]
+    fn test_update() {
+        // A050: LD I, 0x050, D005: DRW V0, V0, 5, 1202: JP 0x202
+        let (mut runner, presented) = runner(vec![0xA0, 0x50, 0xD0, 0x05, 0x12, 0x02]);
+
+        runner.update(Duration::from_millis(1)).unwrap();
+        assert_eq!(runner.emulator().cycle_count(), 0);
+        assert_eq!(*presented.borrow(), 0);
+
+        runner.update(FRAME).unwrap();
+        assert!(runner.emulator().cycle_count() > 0);
+        assert_eq!(*presented.borrow(), 1);
+
+        // Drawing the same sprite again keeps changing the display
+        runner.update(FRAME).unwrap();
+        assert_eq!(*presented.borrow(), 2);
+    }
+
+    #[test]
+    fn test_update_only_presents_changes() {
+        // 1200: JP 0x200
+        let (mut runner, presented) = runner(vec![0x12, 0x00]);
+
+        for _ in 0..3 {
+            runner.update(FRAME).unwrap();
+        }
+        assert_eq!(*presented.borrow(), 1);
+
+        runner.request_redraw();
+        runner.update(FRAME).unwrap();
+        assert_eq!(*presented.borrow(), 2);
+    }
+
+    #[test]
+    fn test_update_catches_up_a_little() {
+        // 1200: JP 0x200
+        let (mut runner, _) = runner(vec![0x12, 0x00]);
+        let cycles_per_frame = u64::from(runner.emulator().cycles_per_frame());
+
+        runner.update(Duration::from_secs(10)).unwrap();
+
+        assert!(runner.emulator().cycle_count() <= MAX_CATCH_UP_FRAMES as u64 * cycles_per_frame);
+    }
+
+    #[test]
+    fn test_update_paused() {
+        // 1200: JP 0x200
+        let (mut runner, _) = runner(vec![0x12, 0x00]);
+
+        runner.emulator_mut().pause();
+        runner.update(FRAME).unwrap();
+
+        assert_eq!(runner.emulator().cycle_count(), 0);
+    }
+
+    #[test]
+    fn test_run_by_clock() {
+        // A050: LD I, 0x050, D005: DRW V0, V0, 5, 1202: JP 0x202
+        let (mut runner, presented) = runner(vec![0xA0, 0x50, 0xD0, 0x05, 0x12, 0x02]);
+        let clock = ManualClock::new();
+        runner.set_clock(Box::new(clock.clone()));
+        let cycles_per_frame = u64::from(runner.emulator().cycles_per_frame());
+
+        // Sleeping advances the clock a frame at a time, the first update is at 0
+        let mut updates = 0;
+        runner
+            .run(|_| {
+                updates += 1;
+                updates <= FRAMES_PER_SECOND + 1
+            })
+            .unwrap();
+
+        assert!(clock.now() > Duration::from_secs(1));
+        assert_eq!(runner.emulator().cycle_count(), 60 * cycles_per_frame);
+        assert_eq!(*presented.borrow(), 60);
+    }
+
+    #[test]
+    fn test_update_error() {
+        // 00EE: RET with an empty stack
+        let (mut runner, _) = runner(vec![0x00, 0xEE]);
+
+        assert!(runner.update(FRAME).is_err());
+    }
+}
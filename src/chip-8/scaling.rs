@@ -0,0 +1,145 @@
+//! Fitting the picture into a window of any size, centered with bars on the sides that don't
+//! fit the aspect ratio.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// As large as fits, with nearest-neighbor sampling some pixels end up a row or column
+    /// larger than others.
+    Fit,
+    /// The largest whole multiple that fits so that every pixel is the same size. Falls back
+    /// to [`ScaleMode::Fit`] when the window is smaller than the picture.
+    Integer,
+}
+
+impl ScaleMode {
+    /// The size of a `width` by `height` picture scaled into a `target_width` by
+    /// `target_height` window, at least one pixel.
+    pub fn dimensions(
+        self,
+        width: usize,
+        height: usize,
+        target_width: usize,
+        target_height: usize,
+    ) -> (usize, usize) {
+        let integer_factor = (target_width / width).min(target_height / height);
+        if self == ScaleMode::Integer && integer_factor > 0 {
+            return (width * integer_factor, height * integer_factor);
+        }
+
+        // Compare target_width / width with target_height / height without dividing
+        if target_width * height <= target_height * width {
+            (target_width.max(1), (height * target_width / width).max(1))
+        } else {
+            (
+                (width * target_height / height).max(1),
+                target_height.max(1),
+            )
+        }
+    }
+
+    /// Scale a row major `width` by `height` buffer to fill a `target_width` by `target_height`
+    /// buffer, filling what's left over with `background`.
+    pub fn apply(
+        self,
+        buffer: &[u32],
+        width: usize,
+        height: usize,
+        target_width: usize,
+        target_height: usize,
+        background: u32,
+    ) -> Vec<u32> {
+        assert_eq!(
+            buffer.len(),
+            width * height,
+            "Buffer doesn't match {}x{}",
+            width,
+            height
+        );
+        let mut scaled = vec![background; target_width * target_height];
+        if target_width == 0 || target_height == 0 {
+            return scaled;
+        }
+
+        let (scaled_width, scaled_height) =
+            self.dimensions(width, height, target_width, target_height);
+        let left = (target_width - scaled_width) / 2;
+        let top = (target_height - scaled_height) / 2;
+
+        for y in 0..scaled_height {
+            let source_row = &buffer[y * height / scaled_height * width..][..width];
+            let row = &mut scaled[(top + y) * target_width + left..][..scaled_width];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = source_row[x * width / scaled_width];
+            }
+        }
+
+        scaled
+    }
+}
+
+impl FromStr for ScaleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "fit" => Ok(ScaleMode::Fit),
+            "integer" => Ok(ScaleMode::Integer),
+            _ => Err(format!(
+                "Unknown scale mode `{}`, expected fit or integer",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScaleMode::Fit => write!(f, "fit"),
+            ScaleMode::Integer => write!(f, "integer"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScaleMode;
+
+    #[test]
+    fn test_dimensions() {
+        assert_eq!(ScaleMode::Fit.dimensions(64, 32, 640, 480), (640, 320));
+        assert_eq!(ScaleMode::Fit.dimensions(64, 32, 100, 500), (100, 50));
+        assert_eq!(ScaleMode::Fit.dimensions(64, 32, 1000, 100), (200, 100));
+        assert_eq!(ScaleMode::Integer.dimensions(64, 32, 640, 480), (640, 320));
+        assert_eq!(ScaleMode::Integer.dimensions(64, 32, 200, 500), (192, 96));
+        // Too small for any whole multiple
+        assert_eq!(ScaleMode::Integer.dimensions(64, 32, 32, 32), (32, 16));
+    }
+
+    #[test]
+    fn test_apply() {
+        // 2x1 to 6x4: the picture is 6x3 and the odd row of background ends up below it
+        let scaled = ScaleMode::Fit.apply(&[1, 2], 2, 1, 6, 4, 0);
+        assert_eq!(
+            scaled,
+            vec![
+                1, 1, 1, 2, 2, 2, //
+                1, 1, 1, 2, 2, 2, //
+                1, 1, 1, 2, 2, 2, //
+                0, 0, 0, 0, 0, 0, //
+            ]
+        );
+
+        // Integer scaling leaves a column on each side of a 2x1 picture scaled 2 times in 6x2
+        let scaled = ScaleMode::Integer.apply(&[1, 2], 2, 1, 6, 2, 9);
+        assert_eq!(
+            scaled,
+            vec![
+                9, 1, 1, 2, 2, 9, //
+                9, 1, 1, 2, 2, 9, //
+            ]
+        );
+    }
+}
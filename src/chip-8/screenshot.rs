@@ -0,0 +1,105 @@
+//! Pictures of the display, e.g. for bug reports and documentation, saved as PNG.
+
+/// A picture of the display in the same XRGB format as [`Display::rgba_framebuffer`].
+///
+/// [`Display::rgba_framebuffer`]: crate::Display::rgba_framebuffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Screenshot {
+    pub fn new(pixels: Vec<u32>, width: usize, height: usize) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "Buffer doesn't match {}x{}",
+            width,
+            height
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// The same picture with every pixel `factor` by `factor` pixels large.
+    pub fn scaled(&self, factor: usize) -> Self {
+        assert!(factor > 0, "Can't scale by 0");
+
+        let mut pixels = Vec::with_capacity(self.pixels.len() * factor * factor);
+        for row in self.pixels.chunks(self.width) {
+            let mut scaled_row = Vec::with_capacity(row.len() * factor);
+            for &pixel in row {
+                scaled_row.resize(scaled_row.len() + factor, pixel);
+            }
+            for _ in 0..factor {
+                pixels.extend_from_slice(&scaled_row);
+            }
+        }
+
+        Self::new(pixels, self.width * factor, self.height * factor)
+    }
+
+    /// The picture as an 8 bit RGB PNG file. Panics if the picture is empty, which PNG
+    /// doesn't allow.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(&self.rgb())?;
+                writer.finish()
+            })
+            .expect("Only empty pictures can't be encoded");
+
+        png
+    }
+
+    /// The pixels as 8 bit RGB, row by row.
+    pub(crate) fn rgb(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            rgb.extend_from_slice(&pixel.to_be_bytes()[1..]);
+        }
+
+        rgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Screenshot;
+
+    #[test]
+    fn test_scaled() {
+        let screenshot = Screenshot::new(vec![1, 2, 3, 4], 2, 2);
+
+        assert_eq!(
+            screenshot.scaled(2),
+            Screenshot::new(vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4], 4, 4)
+        );
+    }
+
+    #[test]
+    fn test_to_png() {
+        let png = Screenshot::new(vec![0x00ff_8000, 0x0000_00ff], 2, 1).to_png();
+
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(
+            (info.color_type, info.bit_depth),
+            (png::ColorType::Rgb, png::BitDepth::Eight)
+        );
+        assert_eq!(pixels, [0xff, 0x80, 0x00, 0x00, 0x00, 0xff]);
+    }
+}
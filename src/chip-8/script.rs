@@ -0,0 +1,222 @@
+//! Lua scripts that run after every frame with access to the registers and memory, for cheats,
+//! trainers, and automated tests. Built with `--features scripting`.
+//!
+//! A script registers its callbacks with `on_frame`, each is called with the emulator:
+//!
+//! ```lua
+//! -- Infinite lives
+//! on_frame(function(emu)
+//!     emu:poke(0x2F3, 3)
+//! end)
+//! ```
+//!
+//! The emulator has `peek(address)`, `poke(address, value)`, `register(x)`,
+//! `set_register(x, value)`, `i()`, `set_i(value)`, `pc()`, `set_pc(address)`, `cycles()`,
+//! `frames()`, and `pause()`.
+use std::error::Error as StdError;
+use std::fmt;
+
+use mlua::{Function, Lua, Table, UserData, UserDataMethods};
+
+use crate::batch::NullInput;
+use crate::{Display, Emulator, Error, Poke};
+
+/// Where the callbacks are kept in the Lua registry, out of reach of the script.
+const CALLBACKS: &str = "chip_8_on_frame";
+
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script doesn't compile, or it or one of its callbacks failed.
+    Lua(mlua::Error),
+    /// The emulator stopped.
+    Emulator(Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Lua(error) => write!(f, "{}", error),
+            ScriptError::Emulator(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl StdError for ScriptError {}
+
+impl From<mlua::Error> for ScriptError {
+    fn from(error: mlua::Error) -> Self {
+        ScriptError::Lua(error)
+    }
+}
+
+impl From<Error> for ScriptError {
+    fn from(error: Error) -> Self {
+        ScriptError::Emulator(error)
+    }
+}
+
+pub struct Script {
+    lua: Lua,
+}
+
+impl Script {
+    /// Run `source` to register its callbacks, `name` is what errors call it, e.g. the path.
+    pub fn new(source: &str, name: &str) -> Result<Self, ScriptError> {
+        let lua = Lua::new();
+        lua.set_named_registry_value(CALLBACKS, lua.create_table()?)?;
+        let on_frame = lua.create_function(|lua, callback: Function| {
+            let callbacks: Table = lua.named_registry_value(CALLBACKS)?;
+            callbacks.raw_set(callbacks.raw_len() + 1, callback)
+        })?;
+        lua.globals().set("on_frame", on_frame)?;
+        lua.load(source).set_name(name).exec()?;
+
+        Ok(Self { lua })
+    }
+
+    /// Call the `on_frame` callbacks in the order they were registered, stopping at the first
+    /// that fails.
+    pub fn run_frame<D: Display + 'static>(
+        &self,
+        emulator: &mut Emulator<D>,
+    ) -> Result<(), ScriptError> {
+        let callbacks: Table = self.lua.named_registry_value(CALLBACKS)?;
+        self.lua.scope(|scope| {
+            let emulator = scope.create_userdata_ref_mut(emulator)?;
+            for callback in callbacks.sequence_values::<Function>() {
+                callback?.call::<_, ()>(emulator.clone())?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    /// Run frames without input, calling the callbacks after each, until at least `cycles`
+    /// instructions have run or a callback pauses the emulator. For headless runs.
+    pub fn run_cycles<D: Display + 'static>(
+        &self,
+        emulator: &mut Emulator<D>,
+        cycles: u64,
+    ) -> Result<(), ScriptError> {
+        let end = emulator.cycle_count() + cycles;
+        while emulator.cycle_count() < end && !emulator.is_paused() {
+            emulator.run_frame(&NullInput)?;
+            self.run_frame(emulator)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<D: Display + 'static> UserData for Emulator<D> {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("peek", |_, emulator, address: u16| {
+            let bytes = emulator
+                .peek_slice(address, 1)
+                .map_err(mlua::Error::external)?;
+            Ok(bytes[0])
+        });
+        methods.add_method_mut("poke", |_, emulator, (address, value): (u16, u8)| {
+            let poke = Poke {
+                address,
+                bytes: vec![value],
+            };
+            emulator.poke(&poke).map_err(mlua::Error::external)
+        });
+        methods.add_method("register", |_, emulator, register: u8| {
+            check_register(register)?;
+            Ok(emulator.register(register))
+        });
+        methods.add_method_mut(
+            "set_register",
+            |_, emulator, (register, value): (u8, u8)| {
+                check_register(register)?;
+                emulator.set_register(register, value);
+                Ok(())
+            },
+        );
+        methods.add_method("i", |_, emulator, ()| Ok(emulator.i()));
+        methods.add_method_mut("set_i", |_, emulator, value: u16| {
+            emulator.set_i(value);
+            Ok(())
+        });
+        methods.add_method("pc", |_, emulator, ()| Ok(emulator.pc()));
+        methods.add_method_mut("set_pc", |_, emulator, address: u16| {
+            emulator.set_pc(address).map_err(mlua::Error::external)
+        });
+        methods.add_method("cycles", |_, emulator, ()| Ok(emulator.cycle_count()));
+        methods.add_method("frames", |_, emulator, ()| Ok(emulator.stats().frames));
+        methods.add_method_mut("pause", |_, emulator, ()| {
+            emulator.pause();
+            Ok(())
+        });
+    }
+}
+
+fn check_register(register: u8) -> mlua::Result<()> {
+    if register > 0xF {
+        return Err(mlua::Error::RuntimeError(format!(
+            "There's no register V{}, they go from V0 to VF",
+            register
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Script;
+    use crate::{Emulator, FramebufferDisplay, Quirks};
+
+    fn emulator() -> Emulator<FramebufferDisplay> {
+        // 1200: JP 0x200
+        Emulator::new(
+            FramebufferDisplay::default(),
+            vec![0x12, 0x00],
+            Quirks::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_on_frame() {
+        let script = Script::new(
+            "on_frame(function(emu)
+                emu:set_register(0, emu:register(0) + 1)
+                emu:poke(0x300, emu:peek(0x300) + 2)
+            end)",
+            "test",
+        )
+        .unwrap();
+        let mut emulator = emulator();
+
+        script.run_frame(&mut emulator).unwrap();
+        script.run_frame(&mut emulator).unwrap();
+        assert_eq!(emulator.register(0), 2);
+        assert_eq!(emulator.peek(0x300), 4);
+    }
+
+    #[test]
+    fn test_run_cycles_until_paused() {
+        let script = Script::new(
+            "on_frame(function(emu) if emu:frames() == 3 then emu:pause() end end)",
+            "test",
+        )
+        .unwrap();
+        let mut emulator = emulator();
+
+        script.run_cycles(&mut emulator, 1_000_000).unwrap();
+        assert_eq!(emulator.stats().frames, 3);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Script::new("on_frame(", "test").is_err());
+
+        let script = Script::new("on_frame(function(emu) emu:register(16) end)", "test").unwrap();
+        assert!(script.run_frame(&mut emulator()).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! The settings last used for each ROM and the ROMs that were run recently, remembered between
+//! sessions.
+//!
+//! Both are kept in a [`Storage`], which replaces the stored files atomically. The settings are
+//! stored under [`SETTINGS_KEY`] as a [`Config`] with a `[rom.<sha1>]` table for each ROM, the
+//! recent ROMs under [`RECENT_KEY`] as one path per line, most recent first.
+use std::io;
+
+use crate::config::{Config, Settings};
+use crate::rom_db;
+use crate::storage::Storage;
+
+pub const SETTINGS_KEY: &str = "settings.toml";
+pub const RECENT_KEY: &str = "recent.txt";
+
+/// How many recent ROMs are remembered.
+pub const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    /// Settings by the SHA-1 of the ROM, see [`rom_db::rom_id`].
+    pub settings: Config,
+    /// Paths of the ROMs that were run, most recent first.
+    pub recent: Vec<String>,
+}
+
+impl History {
+    /// Load the history from `storage`, empty if nothing has been stored yet.
+    pub fn load(storage: &dyn Storage) -> io::Result<Self> {
+        let settings = match storage.load(SETTINGS_KEY)? {
+            Some(data) => Config::parse(&String::from_utf8_lossy(&data)).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", SETTINGS_KEY, error),
+                )
+            })?,
+            None => Config::default(),
+        };
+        let recent = match storage.load(RECENT_KEY)? {
+            Some(data) => String::from_utf8_lossy(&data)
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(Self { settings, recent })
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage.store(SETTINGS_KEY, self.settings.to_string().as_bytes())?;
+
+        let mut recent = String::new();
+        for path in &self.recent {
+            recent.push_str(path);
+            recent.push('\n');
+        }
+        storage.store(RECENT_KEY, recent.as_bytes())
+    }
+
+    /// The settings remembered for `rom`, none are set if it hasn't been run before.
+    pub fn settings_for(&self, rom: &[u8]) -> Settings {
+        self.settings
+            .roms
+            .get(&rom_db::rom_id(rom))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Merge `settings` into the ones remembered for `rom`, replacing the settings it sets and
+    /// keeping the others.
+    pub fn remember(&mut self, rom: &[u8], settings: &Settings) {
+        if settings.is_empty() {
+            return;
+        }
+
+        self.settings
+            .roms
+            .entry(rom_db::rom_id(rom))
+            .or_default()
+            .apply(settings);
+    }
+
+    /// Move `path` to the front of the recent ROMs, forgetting the oldest beyond [`MAX_RECENT`].
+    pub fn add_recent(&mut self, path: &str) {
+        // One path per line, a path that spans lines can't be read back
+        if path.contains('\n') || path.contains('\r') {
+            return;
+        }
+
+        self.recent.retain(|recent| recent != path);
+        self.recent.insert(0, path.to_owned());
+        self.recent.truncate(MAX_RECENT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, MAX_RECENT, SETTINGS_KEY};
+    use crate::config::Settings;
+    use crate::storage::{MemoryStorage, Storage};
+
+    #[test]
+    fn test_remember() {
+        let rom = [0x12, 0x00];
+        let mut history = History::default();
+
+        history.remember(
+            &rom,
+            &Settings {
+                scale: Some(8),
+                quirks: Some("vip".to_owned()),
+                ..Settings::default()
+            },
+        );
+        history.remember(
+            &rom,
+            &Settings {
+                scale: Some(4),
+                speed: Some(700),
+                ..Settings::default()
+            },
+        );
+        history.remember(&[0x00], &Settings::default());
+
+        let settings = history.settings_for(&rom);
+        assert_eq!(settings.scale, Some(4));
+        assert_eq!(settings.speed, Some(700));
+        assert_eq!(settings.quirks.as_deref(), Some("vip"));
+        assert_eq!(history.settings.roms.len(), 1);
+        assert!(history.settings_for(&[0x00]).is_empty());
+    }
+
+    #[test]
+    fn test_add_recent() {
+        let mut history = History::default();
+        for index in 0..MAX_RECENT + 2 {
+            history.add_recent(&format!("{}.ch8", index));
+        }
+        history.add_recent("5.ch8");
+        history.add_recent("bad\nname.ch8");
+
+        assert_eq!(history.recent.len(), MAX_RECENT);
+        assert_eq!(history.recent[0], "5.ch8");
+        assert_eq!(history.recent[1], "11.ch8");
+        assert_eq!(history.recent.last().unwrap(), "2.ch8");
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(History::load(&storage).unwrap(), History::default());
+
+        let mut history = History::default();
+        history.add_recent("roms/pong.ch8");
+        history.add_recent("roms/tetris.ch8");
+        history.remember(
+            &[0x12, 0x00],
+            &Settings {
+                palette: Some("amber".to_owned()),
+                ..Settings::default()
+            },
+        );
+        history.save(&mut storage).unwrap();
+
+        assert_eq!(History::load(&storage).unwrap(), history);
+
+        storage.store(SETTINGS_KEY, b"scale = big").unwrap();
+        assert!(History::load(&storage).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use super::Sound;
+
+/// Sound that plays nothing, the default until [`Emulator::set_sound`] is called.
+///
+/// [`Emulator::set_sound`]: crate::Emulator::set_sound
+#[derive(Debug, Default)]
+pub struct NopSound;
+
+impl Sound for NopSound {
+    fn beep_active(&mut self, _active: bool) {}
+}
+
+/// Several sounds at once, e.g. a speaker and a visual bell. Every sink is told about every
+/// change, in the order they were added.
+#[derive(Default)]
+pub struct SoundSinks {
+    sinks: Vec<Box<dyn Sound>>,
+}
+
+impl SoundSinks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sink: Box<dyn Sound>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+impl Sound for SoundSinks {
+    fn beep_active(&mut self, active: bool) {
+        for sink in &mut self.sinks {
+            sink.beep_active(active);
+        }
+    }
+
+    fn set_audio_buffer(&mut self, buffer: Option<AudioBuffer>) {
+        for sink in &mut self.sinks {
+            sink.set_audio_buffer(buffer);
+        }
+    }
+}
+
+/// The number of bytes in an XO-CHIP audio pattern, 128 1-bit samples.
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// The pitch register's initial value, playing the pattern at 4000 samples per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The XO-CHIP audio pattern loaded by F002 and the pitch set by FX3A. While the sound timer is
+/// active the pattern's samples play in a loop, most significant bit first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioBuffer {
+    pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
+}
+
+impl AudioBuffer {
+    pub fn new(pattern: [u8; AUDIO_PATTERN_SIZE], pitch: u8) -> Self {
+        Self { pattern, pitch }
+    }
+
+    pub fn pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.pattern
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Samples per second, `4000 * 2 ^ ((pitch - 64) / 48)`.
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((f64::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    /// Whether sample `index` is set, wrapping around after the 128th.
+    pub fn sample(&self, index: usize) -> bool {
+        let index = index % (AUDIO_PATTERN_SIZE * 8);
+
+        self.pattern[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{AudioBuffer, SoundSinks, DEFAULT_PITCH};
+    use crate::Sound;
+
+    struct RecordingSound(Arc<Mutex<Vec<bool>>>);
+
+    impl Sound for RecordingSound {
+        fn beep_active(&mut self, active: bool) {
+            self.0.lock().unwrap().push(active);
+        }
+    }
+
+    #[test]
+    fn test_sound_sinks() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let mut sinks = SoundSinks::new();
+        sinks.push(Box::new(RecordingSound(Arc::clone(&first))));
+        sinks.push(Box::new(RecordingSound(Arc::clone(&second))));
+
+        sinks.beep_active(true);
+        sinks.beep_active(false);
+
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(*first.lock().unwrap(), vec![true, false]);
+        assert_eq!(*second.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn test_audio_buffer() {
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1000_0001;
+        let buffer = AudioBuffer::new(pattern, DEFAULT_PITCH);
+
+        assert!(buffer.sample(0));
+        assert!(!buffer.sample(1));
+        assert!(buffer.sample(7));
+        assert!(buffer.sample(128));
+        assert_eq!(buffer.playback_rate(), 4000.0);
+        assert_eq!(AudioBuffer::new(pattern, 112).playback_rate(), 8000.0);
+    }
+}
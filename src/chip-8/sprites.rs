@@ -0,0 +1,259 @@
+//! Finding and drawing the sprites in memory, for browsing the graphics in a ROM.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::assembler::ROM_BASE_ADDRESS;
+use crate::disassembler::{self, Instruction, Line};
+
+/// Sprite data that the ROM draws, found by following `LD I` to the `DRW` after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub address: u16,
+    /// 8, or 16 for the 16x16 SCHIP sprites drawn with `DRW VX, VY, 0`.
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Sprite {
+    /// How many bytes of memory the sprite takes up.
+    pub fn byte_count(&self) -> usize {
+        self.width / 8 * self.height
+    }
+}
+
+/// The sprites drawn by the code in `memory`, which starts at address 0, ordered by address.
+///
+/// Only the code reached from the start of the ROM is followed, the same as with
+/// [`disassembler::symbolize`]. I has to be loaded before the `DRW` in the same straight line of
+/// code, sprites whose address is calculated at run time aren't found.
+pub fn find_sprites(memory: &[u8]) -> Vec<Sprite> {
+    let start = ROM_BASE_ADDRESS as usize;
+    if memory.len() <= start {
+        return Vec::new();
+    }
+
+    let mut sprites = BTreeMap::<u16, Sprite>::new();
+    let mut i = None;
+    for line in disassembler::symbolize(&memory[start..]) {
+        let instruction = match line {
+            Line::Instruction { instruction, .. } => instruction,
+            Line::Label(_) | Line::Data { .. } => continue,
+        };
+
+        match instruction {
+            Instruction::LdI(address) | Instruction::LdILong(address) => i = Some(address),
+            Instruction::AddI(_)
+            | Instruction::LdFont(_)
+            | Instruction::Store(_)
+            | Instruction::Load(_) => i = None,
+            Instruction::Drw(_, _, rows) => {
+                let address = match i {
+                    Some(address) => address,
+                    None => continue,
+                };
+                let sprite = if rows == 0 {
+                    Sprite {
+                        address,
+                        width: 16,
+                        height: 16,
+                    }
+                } else {
+                    Sprite {
+                        address,
+                        width: 8,
+                        height: rows as usize,
+                    }
+                };
+                if address as usize + sprite.byte_count() > memory.len() {
+                    continue;
+                }
+
+                // The same sprite can be drawn with fewer rows, e.g. to clip it
+                let known = sprites.entry(address).or_insert(sprite);
+                if sprite.byte_count() > known.byte_count() {
+                    *known = sprite;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    sprites.into_values().collect()
+}
+
+/// Memory drawn as a grid of 8 pixel wide sprites, one byte per row, with a pixel of space
+/// between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteSheet {
+    width: usize,
+    height: usize,
+    /// Whether each pixel is on, `None` between the sprites and past the end of the memory.
+    pixels: Vec<Option<bool>>,
+}
+
+impl SpriteSheet {
+    /// `bytes` cut into sprites `sprite_height` rows tall, laid out `columns` to a row.
+    pub fn new(bytes: &[u8], sprite_height: usize, columns: usize) -> Self {
+        assert!(
+            sprite_height > 0 && columns > 0,
+            "Sprites need at least one row and column"
+        );
+
+        let sprite_count = bytes.chunks(sprite_height).count();
+        let rows = (0..sprite_count).step_by(columns).count();
+        let columns = columns.min(sprite_count);
+        let width = (columns * 9).saturating_sub(1);
+        let height = (rows * (sprite_height + 1)).saturating_sub(1);
+        let mut pixels = vec![None; width * height];
+
+        for (index, sprite) in bytes.chunks(sprite_height).enumerate() {
+            let left = index % columns * 9;
+            let top = index / columns * (sprite_height + 1);
+            for (row, byte) in sprite.iter().enumerate() {
+                for column in 0..8 {
+                    let is_on = byte & (0x80 >> column) != 0;
+                    pixels[(top + row) * width + left + column] = Some(is_on);
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether the pixel is part of a sprite and on.
+    pub fn is_on(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x] == Some(true)
+    }
+
+    /// The sheet in XRGB, with the `between` color between the sprites.
+    pub fn rgba(&self, on: u32, off: u32, between: u32) -> Vec<u32> {
+        self.pixels
+            .iter()
+            .map(|pixel| match pixel {
+                Some(true) => on,
+                Some(false) => off,
+                None => between,
+            })
+            .collect()
+    }
+}
+
+/// The sheet as text, `#` for the pixels that are on and `.` for the ones that are off.
+impl fmt::Display for SpriteSheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self
+            .pixels
+            .chunks(self.width.max(1))
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .map(|pixel| match pixel {
+                        Some(true) => '#',
+                        Some(false) => '.',
+                        None => ' ',
+                    })
+                    .collect::<String>();
+
+                line.trim_end().to_owned()
+            })
+            .collect::<Vec<_>>();
+
+        // The last sprite can be cut short by the end of the memory
+        write!(f, "{}", lines.join("\n").trim_end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_sprites, Sprite, SpriteSheet};
+
+    /// 4K of memory with `rom` at 0x200.
+    fn memory(rom: &[u8]) -> Vec<u8> {
+        let mut memory = vec![0; 4096];
+        memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+        memory
+    }
+
+    #[test]
+    fn test_find_sprites() {
+        let memory = memory(&[
+            0xA2, 0x0E, // LD I, 0x20E
+            0xD0, 0x13, // DRW V0, V1, 3
+            0xA2, 0x0E, // LD I, 0x20E
+            0xD0, 0x12, // DRW V0, V1, 2
+            0xA2, 0x11, // LD I, 0x211
+            0xD0, 0x10, // DRW V0, V1, 0
+            0x12, 0x0C, // JP 0x20C
+            0xFF, 0x81, 0xFF, // 8x3 sprite
+        ]);
+
+        assert_eq!(
+            find_sprites(&memory),
+            vec![
+                Sprite {
+                    address: 0x20E,
+                    width: 8,
+                    height: 3
+                },
+                Sprite {
+                    address: 0x211,
+                    width: 16,
+                    height: 16
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_sprites_unknown_i() {
+        let memory = memory(&[
+            0xA2, 0x08, // LD I, 0x208
+            0xF0, 0x1E, // ADD I, V0
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x12, 0x06, // JP 0x206
+        ]);
+
+        assert_eq!(find_sprites(&memory), vec![]);
+        assert_eq!(find_sprites(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_sprite_sheet() {
+        let sheet = SpriteSheet::new(&[0xF0, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x18], 3, 2);
+
+        assert_eq!((sheet.width(), sheet.height()), (17, 7));
+        assert!(sheet.is_on(0, 0));
+        assert!(!sheet.is_on(1, 1));
+        assert!(!sheet.is_on(8, 0));
+        assert_eq!(
+            sheet.to_string(),
+            "####.... ..#.....\n\
+             #..#.... .##.....\n\
+             ####.... ..#.....\n\
+             \n\
+             ...##..."
+        );
+        assert_eq!(sheet.rgba(1, 0, 2)[..10], [1, 1, 1, 1, 0, 0, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_empty_sprite_sheet() {
+        let sheet = SpriteSheet::new(&[], 8, 4);
+
+        assert_eq!((sheet.width(), sheet.height()), (0, 0));
+        assert_eq!(sheet.to_string(), "");
+    }
+}
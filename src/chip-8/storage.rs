@@ -0,0 +1,256 @@
+//! Persistence for things that outlive a session, such as RPL flags and the
+//! [remembered settings](crate::settings).
+//!
+//! Features that persist data do so through the [`Storage`] trait so that they work the same
+//! regardless of where the data ends up. [`FileStorage`] is used by native frontends,
+//! `LocalStorage` by the browser with the `wasm` feature, and [`MemoryStorage`] by tests. The
+//! config file isn't persisted by the emulator, it's written by hand and
+//! [`Config::load`](crate::config::Config::load) reads it from its path.
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A key-value store of byte blobs.
+///
+/// Keys are limited to ASCII alphanumerics, `-`, `_`, and `.`, not starting with `.`, so that
+/// every backend can store them verbatim.
+pub trait Storage: Send {
+    /// Load the value for `key`, `None` if nothing has been stored for it.
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Store `data` for `key`, replacing any previous value.
+    fn store(&mut self, key: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Remove the value for `key`, removing a key that doesn't exist is not an error.
+    fn remove(&mut self, key: &str) -> io::Result<()>;
+}
+
+fn validate_key(key: &str) -> io::Result<()> {
+    let is_valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid storage key `{}`", key),
+        ))
+    }
+}
+
+/// Stores each key as a file in a directory, the directory is created on first write.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        validate_key(key)?;
+
+        Ok(self.root.join(key))
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        fs::create_dir_all(&self.root)?;
+
+        // Write to a temporary file first so that a crash never leaves a half written value.
+        let temporary_path = self.root.join(format!(".{}.tmp", key));
+        fs::write(&temporary_path, data)?;
+        fs::rename(&temporary_path, &path)
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Keeps everything in memory, nothing survives the process.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        validate_key(key)?;
+
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        validate_key(key)?;
+        self.values.insert(key.to_string(), data.to_vec());
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        validate_key(key)?;
+        self.values.remove(key);
+
+        Ok(())
+    }
+}
+
+/// Stores each key in the browser's `localStorage`, hex encoded since only strings can be
+/// stored. Keys are prefixed with `chip-8.` to keep them apart from the page's own.
+///
+/// Nothing is kept between calls, so it fails rather than panics when `localStorage` is
+/// unavailable, e.g. in a worker or with storage disabled.
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, Default)]
+pub struct LocalStorage;
+
+#[cfg(feature = "wasm")]
+impl LocalStorage {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn storage() -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "localStorage is unavailable"))
+    }
+
+    fn item(key: &str) -> io::Result<String> {
+        validate_key(key)?;
+
+        Ok(format!("chip-8.{}", key))
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn to_io_error(error: wasm_bindgen::JsValue) -> io::Error {
+    let message = error
+        .as_string()
+        .unwrap_or_else(|| "localStorage failed".to_owned());
+
+    io::Error::other(message)
+}
+
+#[cfg(feature = "wasm")]
+impl Storage for LocalStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let item = Self::item(key)?;
+        let hex = match Self::storage()?.get_item(&item).map_err(to_io_error)? {
+            Some(hex) => hex,
+            None => return Ok(None),
+        };
+
+        decode_hex(&hex).map(Some).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{}` in localStorage isn't hex", item),
+            )
+        })
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        let item = Self::item(key)?;
+        let hex = data
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        Self::storage()?.set_item(&item, &hex).map_err(to_io_error)
+    }
+
+    fn remove(&mut self, key: &str) -> io::Result<()> {
+        let item = Self::item(key)?;
+
+        Self::storage()?.remove_item(&item).map_err(to_io_error)
+    }
+}
+
+#[cfg(feature = "wasm")]
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(&hex[start..start + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileStorage, MemoryStorage, Storage};
+    use std::io;
+
+    fn exercise(storage: &mut dyn Storage) {
+        assert_eq!(storage.load("flags.bin").unwrap(), None);
+
+        storage.store("flags.bin", &[1, 2, 3]).unwrap();
+        assert_eq!(storage.load("flags.bin").unwrap(), Some(vec![1, 2, 3]));
+
+        storage.store("flags.bin", &[4]).unwrap();
+        assert_eq!(storage.load("flags.bin").unwrap(), Some(vec![4]));
+
+        storage.remove("flags.bin").unwrap();
+        storage.remove("flags.bin").unwrap();
+        assert_eq!(storage.load("flags.bin").unwrap(), None);
+
+        for key in &["", "../escape", "a/b", ".hidden"] {
+            let error = storage.store(key, &[0]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        exercise(&mut MemoryStorage::new());
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(super::decode_hex("00ff7A"), Some(vec![0x00, 0xFF, 0x7A]));
+        assert_eq!(super::decode_hex(""), Some(vec![]));
+        assert_eq!(super::decode_hex("abc"), None);
+        assert_eq!(super::decode_hex("zz"), None);
+        assert_eq!(super::decode_hex("é0"), None);
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let root = std::env::temp_dir().join(format!("chip-8-storage-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root);
+
+        exercise(&mut storage);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+//! Running test ROMs headlessly and comparing what they leave on the display against snapshots
+//! of an earlier run, so that regressions in the CPU show up as failing tests.
+//!
+//! Snapshots only show that the output hasn't changed, not that it's correct, they are recorded
+//! from this emulator. They are [PBM](crate::batch::framebuffer_pbm) files. Set
+//! `UPDATE_SNAPSHOTS=1` to write the current output as the new snapshot instead of comparing
+//! against it, then check the images by eye before committing them.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::batch;
+use crate::{Emulator, FramebufferDisplay, Quirks, Variant};
+
+/// The environment variable that turns comparing into updating.
+const UPDATE_VARIABLE: &str = "UPDATE_SNAPSHOTS";
+/// Test ROMs that use CXNN draw the same numbers on every run.
+const RNG_SEED: u32 = 0xC8;
+
+/// Run `rom` for `cycles` cycles without input and return the display as a PBM image.
+pub fn run(
+    rom: &[u8],
+    variant: Variant,
+    quirks: Quirks,
+    cycles: usize,
+) -> Result<String, Box<dyn Error>> {
+    let display = FramebufferDisplay::for_variant(variant);
+    let mut emulator =
+        Emulator::with_variant(display, rom.to_vec(), variant, quirks)?.with_rng_seed(RNG_SEED);
+
+    batch::run_cycles(&mut emulator, cycles)?;
+
+    Ok(batch::framebuffer_pbm(emulator.display()))
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// There's no snapshot at `path` yet.
+    Missing {
+        path: PathBuf,
+    },
+    /// The display differs from the snapshot at `path`.
+    Mismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Missing { path } => write!(
+                f,
+                "No snapshot at {}, run with {}=1 to create it",
+                path.display(),
+                UPDATE_VARIABLE
+            ),
+            SnapshotError::Mismatch {
+                path,
+                expected,
+                actual,
+            } => {
+                writeln!(f, "The display differs from {}", path.display())?;
+                writeln!(f, "expected | actual")?;
+                for (expected, actual) in pbm_rows(expected).zip(pbm_rows(actual)) {
+                    writeln!(f, "{} | {}", expected, actual)?;
+                }
+
+                Ok(())
+            }
+            SnapshotError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+/// Compare the PBM image `actual` against the snapshot at `path`, or replace the snapshot
+/// when `UPDATE_SNAPSHOTS` is set.
+pub fn check_snapshot(path: &Path, actual: &str) -> Result<(), SnapshotError> {
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, actual)?;
+
+        return Ok(());
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(SnapshotError::Missing {
+                path: path.to_owned(),
+            })
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    // Tolerate line ending changes from checkouts on Windows
+    if expected.lines().eq(actual.lines()) {
+        Ok(())
+    } else {
+        Err(SnapshotError::Mismatch {
+            path: path.to_owned(),
+            expected,
+            actual: actual.to_owned(),
+        })
+    }
+}
+
+/// The pixel rows of a PBM image drawn with `#` and `.`.
+fn pbm_rows(pbm: &str) -> impl Iterator<Item = String> + '_ {
+    pbm.lines().skip(2).map(|row| {
+        row.split_whitespace()
+            .map(|pixel| if pixel == "1" { '#' } else { '.' })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_snapshot, run, SnapshotError};
+    use crate::{Quirks, Variant};
+
+    #[test]
+    fn test_check_snapshot() {
+        let dir = std::env::temp_dir().join(format!("chip-8-snapshots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("blank.pbm");
+        // 1200: JP 0x200
+        let blank = run(&[0x12, 0x00], Variant::Chip8, Quirks::default(), 10).unwrap();
+        // 00E0: CLS, F029: LD F, V0, D005: DRW V0, V0, 5, 1206: JP 0x206
+        let zero = run(
+            &[0x00, 0xE0, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06],
+            Variant::Chip8,
+            Quirks::default(),
+            10,
+        )
+        .unwrap();
+
+        match check_snapshot(&path, &blank) {
+            Err(SnapshotError::Missing { .. }) => {}
+            result => panic!("Expected a missing snapshot, got {:?}", result),
+        }
+
+        std::fs::write(&path, &blank).unwrap();
+        assert!(check_snapshot(&path, &blank).is_ok());
+
+        let error = check_snapshot(&path, &zero).unwrap_err();
+        assert!(
+            error.to_string().contains("........ | ####...."),
+            "{}",
+            error
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 /// A timer that ticks down. This is used for the 60hz sound and delay timers by [`CPU`].
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Timer {
     value: u8,
 }
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::cpu::CpuState;
+use crate::Opcode;
+
+/// Called with a [`TraceEvent`] after every executed instruction, see
+/// [`Emulator::set_trace`](crate::Emulator::set_trace).
+pub type Trace = Box<dyn FnMut(TraceEvent) + Send>;
+
+/// A register that an instruction changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDelta {
+    pub register: u8,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// An executed instruction and what it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Where the instruction was.
+    pub pc: u16,
+    pub instruction: Opcode,
+    /// The changed registers, in register order.
+    pub registers: Vec<RegisterDelta>,
+    /// The old and new value of I, if it changed.
+    pub i: Option<(u16, u16)>,
+}
+
+impl TraceEvent {
+    pub(crate) fn new(instruction: Opcode, before: &CpuState, after: &CpuState) -> Self {
+        let registers = (0..16)
+            .filter(|&register| before.v[register] != after.v[register])
+            .map(|register| RegisterDelta {
+                register: register as u8,
+                old: before.v[register],
+                new: after.v[register],
+            })
+            .collect();
+
+        Self {
+            pc: before.pc,
+            instruction,
+            registers,
+            i: Some((before.i, after.i)).filter(|(old, new)| old != new),
+        }
+    }
+}
+
+/// One line, e.g. `0x200  ADD V3, 0x01  V3: 41 -> 42`.
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut changes = self
+            .registers
+            .iter()
+            .map(|delta| {
+                format!(
+                    "V{:X}: {:02x} -> {:02x}",
+                    delta.register, delta.old, delta.new
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Some((old, new)) = self.i {
+            changes.push(format!("I: {:#05x} -> {:#05x}", old, new));
+        }
+
+        let line = format!(
+            "{:#05x}  {:<20}  {}",
+            self.pc,
+            self.instruction.to_string(),
+            changes.join(", ")
+        );
+
+        write!(f, "{}", line.trim_end())
+    }
+}
@@ -0,0 +1,50 @@
+//! The recently executed instructions, for stepping backwards in a debugger.
+use std::collections::VecDeque;
+
+use crate::cpu::Undo;
+use crate::CycleStatus;
+
+/// An executed instruction and the emulator state that isn't part of the CPU from before it.
+pub struct Step {
+    pub undo: Undo,
+    pub cycle_status: CycleStatus,
+}
+
+/// Up to `limit` of the last executed instructions, the oldest are dropped first.
+pub struct History {
+    steps: VecDeque<Step>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            steps: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn push(&mut self, step: Step) {
+        if self.steps.len() == self.limit {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    /// The most recent instruction.
+    pub fn pop(&mut self) -> Option<Step> {
+        self.steps.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
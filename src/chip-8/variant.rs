@@ -0,0 +1,182 @@
+//! The CHIP-8 variants, the historical ones mostly differ in the size of their display.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{RomError, RomWarning};
+
+/// 1260: JP 0x260, the first instruction of every hi-res ROM.
+const HIRES_JUMP: [u8; 2] = [0x12, 0x60];
+const HIRES_START_ADDRESS: u16 = 0x2C0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// The original COSMAC VIP interpreter, 64x32.
+    Chip8,
+    /// The ETI-660 interpreter, 64x48.
+    Eti660,
+    /// Hi-res CHIP-8 for the COSMAC VIP, 64x64. Its ROMs start with the hi-res interpreter
+    /// patches, which this emulates, see [`Variant::start_address`].
+    HiRes,
+    /// XO-CHIP, the modern extension from Octo with 64KiB of memory and two drawing planes.
+    XoChip,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 4] = [
+        Variant::Chip8,
+        Variant::Eti660,
+        Variant::HiRes,
+        Variant::XoChip,
+    ];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Chip8 => "chip-8",
+            Variant::Eti660 => "eti-660",
+            Variant::HiRes => "hires",
+            Variant::XoChip => "xo-chip",
+        }
+    }
+
+    /// Width and height of the display in pixels.
+    pub fn resolution(self) -> (usize, usize) {
+        match self {
+            Variant::Chip8 => (64, 32),
+            Variant::Eti660 => (64, 48),
+            Variant::HiRes => (64, 64),
+            Variant::XoChip => (64, 32),
+        }
+    }
+
+    /// Size of memory in bytes.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Variant::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    /// Where execution starts for `rom`. Hi-res ROMs start with `1260`, a jump to the patches
+    /// to the VIP interpreter that come with them, and the program itself starts at 0x2C0
+    /// after the patches.
+    pub fn start_address(self, rom: &[u8]) -> u16 {
+        if self == Variant::HiRes && rom.starts_with(&HIRES_JUMP) {
+            HIRES_START_ADDRESS
+        } else {
+            0x200
+        }
+    }
+
+    /// The variant `rom` was written for if it can be told from the ROM alone, only hi-res
+    /// ROMs can.
+    pub fn detect(rom: &[u8]) -> Option<Variant> {
+        if rom.starts_with(&HIRES_JUMP) {
+            Some(Variant::HiRes)
+        } else {
+            None
+        }
+    }
+
+    /// The largest ROM that fits in memory after 0x200, 3584 bytes for CHIP-8.
+    pub fn max_rom_size(self) -> usize {
+        self.memory_size() - 0x200
+    }
+
+    /// Check that `rom` can be run on this variant, returning anything odd about it.
+    pub fn validate_rom(self, rom: &[u8]) -> Result<Vec<RomWarning>, RomError> {
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if rom.len() > self.max_rom_size() {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max: self.max_rom_size(),
+            });
+        }
+
+        let mut warnings = Vec::new();
+        if !rom.len().is_multiple_of(2) {
+            warnings.push(RomWarning::OddLength { size: rom.len() });
+        }
+
+        Ok(warnings)
+    }
+
+    /// Whether the XO-CHIP instructions are available.
+    pub fn is_xo_chip(self) -> bool {
+        self == Variant::XoChip
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Variant::ALL
+            .iter()
+            .find(|variant| variant.name() == s.trim())
+            .cloned()
+            .ok_or_else(|| {
+                let names = Variant::ALL
+                    .iter()
+                    .map(|variant| variant.name())
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "Unknown variant `{}`, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Variant;
+    use crate::{RomError, RomWarning};
+
+    #[test]
+    fn test_parse() {
+        for variant in &Variant::ALL {
+            assert_eq!(variant.to_string().parse(), Ok(*variant));
+        }
+        assert!("chip-48".parse::<Variant>().is_err());
+    }
+
+    #[test]
+    fn test_validate_rom() {
+        assert_eq!(Variant::Chip8.validate_rom(&[0x00, 0xE0]), Ok(vec![]));
+        assert_eq!(Variant::Chip8.validate_rom(&[]), Err(RomError::Empty));
+        assert_eq!(
+            Variant::Chip8.validate_rom(&[0x00, 0xE0, 0x12]),
+            Ok(vec![RomWarning::OddLength { size: 3 }])
+        );
+        assert_eq!(
+            Variant::Chip8.validate_rom(&[0; 0x1000]),
+            Err(RomError::TooLarge {
+                size: 0x1000,
+                max: 3584
+            })
+        );
+        assert_eq!(Variant::XoChip.validate_rom(&[0; 0x1000]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_hires() {
+        let rom = [0x12, 0x60, 0x00, 0xE0];
+
+        assert_eq!(Variant::detect(&rom), Some(Variant::HiRes));
+        assert_eq!(Variant::detect(&[0x00, 0xE0]), None);
+        assert_eq!(Variant::HiRes.start_address(&rom), 0x2C0);
+        assert_eq!(Variant::HiRes.start_address(&[0x00, 0xE0]), 0x200);
+        assert_eq!(Variant::Chip8.start_address(&rom), 0x200);
+    }
+}
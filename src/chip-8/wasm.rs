@@ -0,0 +1,104 @@
+//! JavaScript bindings for running the emulator in a browser, built with `wasm-pack build
+//! --features wasm`.
+//!
+//! Nothing here keeps time, call [`WasmEmulator::run_frame`] 60 times a second, e.g. from
+//! `requestAnimationFrame`, and draw [`WasmEmulator::framebuffer`] after each frame.
+use js_sys::Uint32Array;
+use wasm_bindgen::prelude::*;
+
+use crate::storage::LocalStorage;
+use crate::{Display, Emulator, FramebufferDisplay, Input, Variant};
+
+/// The keys held in the browser, updated from key events.
+struct Keypad {
+    keys: [bool; 16],
+}
+
+impl Input for Keypad {
+    fn is_key_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+}
+
+#[wasm_bindgen]
+pub struct WasmEmulator {
+    emulator: Emulator<FramebufferDisplay>,
+    keypad: Keypad,
+}
+
+#[wasm_bindgen]
+impl WasmEmulator {
+    /// Throws if the ROM doesn't fit in memory.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<WasmEmulator, JsValue> {
+        let display = FramebufferDisplay::default();
+        let emulator = Emulator::for_rom(display, rom.to_vec(), Variant::Chip8)
+            .map_err(|error| JsValue::from_str(&error.to_string()))?;
+
+        Ok(Self {
+            emulator,
+            keypad: Keypad { keys: [false; 16] },
+        })
+    }
+
+    /// Execute a single instruction, throws if the ROM does something invalid.
+    pub fn cycle(&mut self) -> Result<(), JsValue> {
+        self.emulator.cycle(&self.keypad).map_err(to_js_error)
+    }
+
+    /// Tick the delay and sound timers, 60 times a second.
+    pub fn tick_timers(&mut self) {
+        self.emulator.tick_timers();
+    }
+
+    /// Run 1/60th of a second worth of instructions and tick the timers, returns whether the
+    /// display changed.
+    pub fn run_frame(&mut self) -> Result<bool, JsValue> {
+        self.emulator
+            .run_frame(&self.keypad)
+            .map(|frame| frame.is_dirty)
+            .map_err(to_js_error)
+    }
+
+    /// The display as `width * height` XRGB pixels, row by row.
+    pub fn framebuffer(&self) -> Uint32Array {
+        Uint32Array::from(&self.emulator.display().rgba_framebuffer()[..])
+    }
+
+    pub fn width(&self) -> usize {
+        self.emulator.display().width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.emulator.display().height()
+    }
+
+    /// Whether the tone should be playing.
+    pub fn is_sound_active(&self) -> bool {
+        self.emulator.is_sound_active()
+    }
+
+    /// Keep the flags saved by FX75 in `localStorage`, so that games keep e.g. high scores
+    /// across page loads. Throws if `localStorage` is unavailable.
+    pub fn persist_flags(&mut self) -> Result<(), JsValue> {
+        self.emulator
+            .set_flag_storage(Box::new(LocalStorage::new()))
+            .map_err(|error| JsValue::from_str(&error.to_string()))
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        if let Some(held) = self.keypad.keys.get_mut(key as usize) {
+            *held = true;
+        }
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        if let Some(held) = self.keypad.keys.get_mut(key as usize) {
+            *held = false;
+        }
+    }
+}
+
+fn to_js_error(error: crate::Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
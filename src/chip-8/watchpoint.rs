@@ -0,0 +1,365 @@
+//! Watchpoints, for finding the instruction that reads or changes a register or some memory.
+//!
+//! A watchpoint is written as a register or an address range, optionally followed by the kind
+//! of access to watch for, reads and writes by default:
+//!
+//! ```text
+//! V3 w
+//! 0x300-0x30F r
+//! 0x2F0
+//! ```
+//!
+//! Before every instruction the registers and memory it's about to access are worked out from
+//! the instruction and the current state, so execution stops with the responsible instruction
+//! at the program counter and nothing changed yet.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::breakpoint::{parse_number, BreakpointError, Hit, Operand};
+use crate::{Display, Emulator};
+
+/// The registers and memory an instruction reads and writes. Fetching the instruction itself
+/// isn't counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Accesses {
+    /// Bit N is set when VN is read.
+    pub registers_read: u16,
+    /// Bit N is set when VN is written.
+    pub registers_written: u16,
+    /// The range read, if any.
+    pub memory_read: Option<Range>,
+    /// The range written, if any.
+    pub memory_written: Option<Range>,
+}
+
+impl Accesses {
+    pub(crate) fn read_registers(&mut self, registers: impl IntoIterator<Item = u8>) {
+        for register in registers {
+            self.registers_read |= 1 << register;
+        }
+    }
+
+    pub(crate) fn write_registers(&mut self, registers: impl IntoIterator<Item = u8>) {
+        for register in registers {
+            self.registers_written |= 1 << register;
+        }
+    }
+}
+
+/// An inclusive range of addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Range {
+    /// `length` bytes from `start`, `None` if `length` is 0. Ranges past the end of the
+    /// address space are cut short.
+    pub fn with_length(start: u16, length: u16) -> Option<Self> {
+        if length == 0 {
+            return None;
+        }
+
+        Some(Self {
+            start,
+            end: start.saturating_add(length - 1),
+        })
+    }
+
+    pub fn overlaps(self, other: Range) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{:#05x}", self.start)
+        } else {
+            write!(f, "{:#05x}-{:#05x}", self.start, self.end)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// `VX`
+    Register(u8),
+    /// `ADDR` or `START-END`
+    Memory(Range),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Register(register) => write!(f, "V{:X}", register),
+            Target::Memory(range) => write!(f, "{}", range),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || BreakpointError {
+            message: format!("Invalid watchpoint target `{}`", s),
+        };
+
+        if let Ok(Operand::Register(register)) = s.parse() {
+            return Ok(Target::Register(register));
+        }
+
+        let mut parts = s.splitn(2, '-');
+        let start = parts.next().and_then(parse_number).ok_or_else(invalid)?;
+        let end = match parts.next() {
+            Some(end) => parse_number(end).ok_or_else(invalid)?,
+            None => start,
+        };
+        if end < start {
+            return Err(invalid());
+        }
+
+        Ok(Target::Memory(Range { start, end }))
+    }
+}
+
+/// The kinds of access that trigger a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `r`
+    Read,
+    /// `w`
+    Write,
+    /// `rw`
+    ReadWrite,
+}
+
+impl Kind {
+    fn includes_reads(self) -> bool {
+        self != Kind::Write
+    }
+
+    fn includes_writes(self) -> bool {
+        self != Kind::Read
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub target: Target,
+    pub kind: Kind,
+}
+
+impl Watchpoint {
+    /// Watch `target` for reads and writes.
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            kind: Kind::ReadWrite,
+        }
+    }
+
+    /// How the instruction with `accesses` triggers this watchpoint, if it does.
+    fn triggered_by(&self, accesses: &Accesses) -> Option<&'static str> {
+        let (is_read, is_written) = match self.target {
+            Target::Register(register) => (
+                accesses.registers_read & (1 << register) != 0,
+                accesses.registers_written & (1 << register) != 0,
+            ),
+            Target::Memory(range) => (
+                matches!(accesses.memory_read, Some(read) if read.overlaps(range)),
+                matches!(accesses.memory_written, Some(written) if written.overlaps(range)),
+            ),
+        };
+        let is_read = is_read && self.kind.includes_reads();
+        let is_written = is_written && self.kind.includes_writes();
+
+        match (is_read, is_written) {
+            (true, true) => Some("read and written"),
+            (true, false) => Some("read"),
+            (false, true) => Some("written"),
+            (false, false) => None,
+        }
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = BreakpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let (target, kind) = match words.as_slice() {
+            [target] => (target, Kind::ReadWrite),
+            [target, "r"] => (target, Kind::Read),
+            [target, "w"] => (target, Kind::Write),
+            [target, "rw"] => (target, Kind::ReadWrite),
+            _ => {
+                return Err(BreakpointError {
+                    message: format!("Invalid watchpoint `{}`", s),
+                })
+            }
+        };
+
+        Ok(Self {
+            target: target.parse()?,
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Read => "r",
+            Kind::Write => "w",
+            Kind::ReadWrite => "rw",
+        };
+
+        write!(f, "{} {}", self.target, kind)
+    }
+}
+
+/// A set of watchpoints checked before each instruction.
+#[derive(Debug, Clone, Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    /// The address execution last stopped at, so resuming doesn't immediately stop again.
+    stopped_at: Option<u16>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    /// Add `watchpoint`, replacing any existing watchpoint on the same target.
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.remove(watchpoint.target);
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove(&mut self, target: Target) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.target != target);
+
+        count != self.watchpoints.len()
+    }
+
+    /// Check whether the instruction about to execute triggers a watchpoint, reporting the
+    /// program counter and the instruction. Should be called before each
+    /// [`Emulator::cycle`], the returned hit always stops execution.
+    pub fn check<D: Display>(&mut self, emulator: &Emulator<D>) -> Option<Hit> {
+        let pc = emulator.pc();
+        if self.stopped_at.take() == Some(pc) || self.watchpoints.is_empty() {
+            return None;
+        }
+
+        // An instruction that can't be decoded fails when executed, there's nothing to watch
+        let instruction = emulator.current_instruction().ok()?;
+        let accesses = emulator.accesses(instruction);
+        let log = self
+            .watchpoints
+            .iter()
+            .filter_map(|watchpoint| {
+                let access = watchpoint.triggered_by(&accesses)?;
+
+                Some(format!(
+                    "{:#05x}: {} {} by {}",
+                    pc, watchpoint.target, access, instruction
+                ))
+            })
+            .collect::<Vec<_>>();
+        if log.is_empty() {
+            return None;
+        }
+
+        self.stopped_at = Some(pc);
+        Some(Hit {
+            address: pc,
+            log,
+            should_stop: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Kind, Range, Target, Watchpoint, Watchpoints};
+    use crate::batch::NullInput;
+    use crate::{Emulator, FramebufferDisplay, Quirks};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "v3 w".parse(),
+            Ok(Watchpoint {
+                target: Target::Register(3),
+                kind: Kind::Write
+            })
+        );
+        assert_eq!(
+            "0x300-0x30F".parse(),
+            Ok(Watchpoint::new(Target::Memory(Range {
+                start: 0x300,
+                end: 0x30F
+            })))
+        );
+        assert_eq!(
+            "300 r"
+                .parse::<Watchpoint>()
+                .map(|watchpoint| watchpoint.to_string()),
+            Ok("0x300 r".to_owned())
+        );
+        for watchpoint in &["", "VG", "0x30F-0x300", "V3 x", "V3 r w"] {
+            assert!(
+                watchpoint.parse::<Watchpoint>().is_err(),
+                "{} should not parse",
+                watchpoint
+            );
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        // A300: LD I, 0x300, 6305: LD V3, 0x05, F333: LD B, V3, F265: LD V0..V2, [I],
+        // 1208: JP 0x208
+        let rom = vec![0xA3, 0x00, 0x63, 0x05, 0xF3, 0x33, 0xF2, 0x65, 0x12, 0x08];
+        let mut emulator =
+            Emulator::new(FramebufferDisplay::default(), rom, Quirks::default()).unwrap();
+        let mut watchpoints = Watchpoints::new();
+        watchpoints.add("V3 w".parse().unwrap());
+        watchpoints.add("0x302 r".parse().unwrap());
+
+        assert_eq!(watchpoints.check(&emulator), None);
+        emulator.cycle(&NullInput).unwrap();
+
+        let hit = watchpoints.check(&emulator).unwrap();
+        assert_eq!(hit.address, 0x202);
+        assert_eq!(hit.log, vec!["0x202: V3 written by LD V3, 0x05"]);
+        assert!(hit.should_stop);
+        // Resuming executes the instruction that triggered the watchpoint
+        assert_eq!(watchpoints.check(&emulator), None);
+        emulator.cycle(&NullInput).unwrap();
+
+        // Reading V3 and writing 0x300-0x302 triggers neither
+        assert_eq!(watchpoints.check(&emulator), None);
+        emulator.cycle(&NullInput).unwrap();
+
+        let hit = watchpoints.check(&emulator).unwrap();
+        assert_eq!(hit.log, vec!["0x206: 0x302 read by LD V2, [I]"]);
+
+        assert!(watchpoints.remove(Target::Register(3)));
+        assert!(!watchpoints.remove(Target::Register(3)));
+    }
+}
@@ -0,0 +1,57 @@
+//! Runs the test ROMs in `tests/roms` and compares the display against the snapshots in
+//! `tests/snapshots`, see [`chip_8::test_harness`].
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip_8::assembler::assemble;
+use chip_8::test_harness::{check_snapshot, run};
+use chip_8::{Quirks, Variant};
+
+/// Every test ROM ends in an endless loop well within this.
+const CYCLES: usize = 2000;
+
+fn test_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn check_rom(name: &str, quirks: &str, snapshot: &str) {
+    let source_path = test_dir().join("roms").join(format!("{}.asm", name));
+    let source = fs::read_to_string(&source_path).unwrap();
+    let rom = assemble(&source).unwrap_or_else(|error| panic!("{}: {}", name, error));
+    let quirks = quirks.parse::<Quirks>().unwrap();
+
+    let display = run(&rom, Variant::Chip8, quirks, CYCLES).unwrap();
+    let snapshot_path = test_dir()
+        .join("snapshots")
+        .join(format!("{}.pbm", snapshot));
+    if let Err(error) = check_snapshot(&snapshot_path, &display) {
+        panic!("{}", error);
+    }
+}
+
+#[test]
+fn logo() {
+    check_rom("logo", "none", "logo");
+}
+
+#[test]
+fn opcodes() {
+    check_rom("opcodes", "none", "opcodes");
+}
+
+#[test]
+fn flags() {
+    check_rom("flags", "none", "flags");
+}
+
+#[test]
+fn quirks() {
+    check_rom("quirks", "none", "quirks-none");
+    check_rom("quirks", "vip", "quirks-vip");
+    check_rom("quirks", "schip", "quirks-schip");
+}
+
+#[test]
+fn pong() {
+    check_rom("pong", "none", "pong");
+}
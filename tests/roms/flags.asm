@@ -0,0 +1,194 @@
+; Checks how instructions set VF, drawing the number of every test that passes. All 15 digits
+; from 0 to E show up when everything works.
+    CLS
+    LD VC, 2
+    LD VD, 0
+    LD VE, 2
+
+    ; 0: ADD VX, VY sets VF on carry
+    LD V1, 0xFF
+    LD V2, 0x01
+    ADD V1, V2
+    LD V0, 0
+    SE VF, 1
+    JP t0
+    LD V0, 1
+t0:
+    CALL report
+
+    ; 1: ADD VX, VY clears VF without carry
+    LD V1, 0x01
+    LD V2, 0x01
+    ADD V1, V2
+    LD V0, 0
+    SE VF, 0
+    JP t1
+    LD V0, 1
+t1:
+    CALL report
+
+    ; 2: SUB sets VF without borrow
+    LD V1, 5
+    LD V2, 3
+    SUB V1, V2
+    LD V0, 0
+    SE VF, 1
+    JP t2
+    LD V0, 1
+t2:
+    CALL report
+
+    ; 3: SUB clears VF on borrow
+    LD V1, 3
+    LD V2, 5
+    SUB V1, V2
+    LD V0, 0
+    SE VF, 0
+    JP t3
+    LD V0, 1
+t3:
+    CALL report
+
+    ; 4: SUB of equal values doesn't borrow
+    LD V1, 5
+    LD V2, 5
+    SUB V1, V2
+    LD V0, 0
+    SE VF, 1
+    JP t4
+    LD V0, 1
+t4:
+    CALL report
+
+    ; 5: SUBN sets VF without borrow
+    LD V1, 3
+    LD V2, 5
+    SUBN V1, V2
+    LD V0, 0
+    SE VF, 1
+    JP t5
+    LD V0, 1
+t5:
+    CALL report
+
+    ; 6: SUBN clears VF on borrow
+    LD V1, 5
+    LD V2, 3
+    SUBN V1, V2
+    LD V0, 0
+    SE VF, 0
+    JP t6
+    LD V0, 1
+t6:
+    CALL report
+
+    ; 7: SHR shifts the low bit into VF
+    LD V1, 0x03
+    SHR V1
+    LD V0, 0
+    SE VF, 1
+    JP t7
+    LD V0, 1
+t7:
+    CALL report
+
+    ; 8: SHR clears VF
+    LD V1, 0x02
+    SHR V1
+    LD V0, 0
+    SE VF, 0
+    JP t8
+    LD V0, 1
+t8:
+    CALL report
+
+    ; 9: SHL shifts the high bit into VF
+    LD V1, 0x81
+    SHL V1
+    LD V0, 0
+    SE VF, 1
+    JP t9
+    LD V0, 1
+t9:
+    CALL report
+
+    ; A: SHL clears VF
+    LD V1, 0x01
+    SHL V1
+    LD V0, 0
+    SE VF, 0
+    JP tA
+    LD V0, 1
+tA:
+    CALL report
+
+    ; B: VF holds the flag, not the result, when it's also VX
+    LD VF, 0xFF
+    LD V1, 1
+    ADD VF, V1
+    LD V0, 0
+    SE VF, 1
+    JP tB
+    LD V0, 1
+tB:
+    CALL report
+
+    ; C: DRW sets VF on collision
+    LD I, dot
+    LD V1, 60
+    LD V2, 28
+    DRW V1, V2, 1
+    DRW V1, V2, 1
+    LD V0, 0
+    SE VF, 1
+    JP tC
+    LD V0, 1
+tC:
+    CALL report
+
+    ; D: DRW clears VF without collision
+    LD I, dot
+    LD V1, 60
+    LD V2, 28
+    DRW V1, V2, 1
+    LD V3, VF
+    DRW V1, V2, 1
+    LD VF, V3
+    LD V0, 0
+    SE VF, 0
+    JP tD
+    LD V0, 1
+tD:
+    CALL report
+
+    ; E: ADD VX, NN leaves VF alone
+    LD VF, 0x55
+    LD V1, 0xFF
+    ADD V1, 2
+    LD V0, 0
+    SE VF, 0x55
+    JP tE
+    LD V0, 1
+tE:
+    CALL report
+
+end:
+    JP end
+
+; Draw the number of the test in VD when V0 is 1, leave a gap otherwise. Digits go 8 to a row
+; starting at (VC, VE).
+report:
+    SE V0, 1
+    JP report_next
+    LD F, VD
+    DRW VC, VE, 5
+report_next:
+    ADD VD, 1
+    ADD VC, 6
+    SE VC, 50
+    RET
+    LD VC, 2
+    ADD VE, 7
+    RET
+
+dot: DB 0x80
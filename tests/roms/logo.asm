@@ -0,0 +1,33 @@
+; Draws CHIP-8 in the middle of the screen, like the IBM logo ROM. Exercises CLS, LD I, LD F,
+; and DRW with both font and ROM sprites.
+    CLS
+    LD V1, 10       ; x
+    LD V2, 13       ; y
+
+    LD V0, 0xC
+    LD F, V0
+    DRW V1, V2, 5
+    ADD V1, 5
+    LD I, letter_h
+    DRW V1, V2, 5
+    ADD V1, 5
+    LD I, letter_i
+    DRW V1, V2, 5
+    ADD V1, 4
+    LD I, letter_p
+    DRW V1, V2, 5
+    ADD V1, 5
+    LD I, dash
+    DRW V1, V2, 5
+    ADD V1, 5
+    LD V0, 8
+    LD F, V0
+    DRW V1, V2, 5
+
+end:
+    JP end
+
+letter_h: DB 0x90, 0x90, 0xF0, 0x90, 0x90
+letter_i: DB 0xE0, 0x40, 0x40, 0x40, 0xE0
+letter_p: DB 0xF0, 0x90, 0xF0, 0x80, 0x80
+dash:     DB 0x00, 0x00, 0xF0, 0x00, 0x00
@@ -0,0 +1,203 @@
+; Checks the basic instructions, drawing the number of every test that passes. All 16 digits
+; from 0 to F show up when everything works. CALL and RET are checked by drawing at all.
+    CLS
+    LD VC, 2
+    LD VD, 0
+    LD VE, 2
+
+    ; 0: SE VX, NN
+    LD V1, 0x42
+    LD V0, 0
+    SE V1, 0x42
+    JP t0
+    LD V0, 1
+t0:
+    CALL report
+
+    ; 1: SNE VX, NN
+    LD V1, 0x42
+    LD V0, 1
+    SNE V1, 0x43
+    LD V0, 0
+    CALL report
+
+    ; 2: SE VX, VY
+    LD V1, 7
+    LD V2, 7
+    LD V0, 0
+    SE V1, V2
+    JP t2
+    LD V0, 1
+t2:
+    CALL report
+
+    ; 3: SNE VX, VY
+    LD V1, 7
+    LD V2, 8
+    LD V0, 1
+    SNE V1, V2
+    LD V0, 0
+    CALL report
+
+    ; 4: ADD VX, NN wraps around
+    LD V1, 0xFF
+    ADD V1, 2
+    LD V0, 0
+    SE V1, 1
+    JP t4
+    LD V0, 1
+t4:
+    CALL report
+
+    ; 5: LD VX, VY
+    LD V2, 0x5A
+    LD V1, V2
+    LD V0, 0
+    SE V1, 0x5A
+    JP t5
+    LD V0, 1
+t5:
+    CALL report
+
+    ; 6: OR
+    LD V1, 0xF0
+    LD V2, 0x0F
+    OR V1, V2
+    LD V0, 0
+    SE V1, 0xFF
+    JP t6
+    LD V0, 1
+t6:
+    CALL report
+
+    ; 7: AND
+    LD V1, 0xFC
+    LD V2, 0x3F
+    AND V1, V2
+    LD V0, 0
+    SE V1, 0x3C
+    JP t7
+    LD V0, 1
+t7:
+    CALL report
+
+    ; 8: XOR
+    LD V1, 0xFF
+    LD V2, 0x0F
+    XOR V1, V2
+    LD V0, 0
+    SE V1, 0xF0
+    JP t8
+    LD V0, 1
+t8:
+    CALL report
+
+    ; 9: ADD VX, VY
+    LD V1, 0x20
+    LD V2, 0x22
+    ADD V1, V2
+    LD V0, 0
+    SE V1, 0x42
+    JP t9
+    LD V0, 1
+t9:
+    CALL report
+
+    ; A: SUB
+    LD V1, 0x50
+    LD V2, 0x0E
+    SUB V1, V2
+    LD V0, 0
+    SE V1, 0x42
+    JP tA
+    LD V0, 1
+tA:
+    CALL report
+
+    ; B: SUBN
+    LD V1, 0x0E
+    LD V2, 0x50
+    SUBN V1, V2
+    LD V0, 0
+    SE V1, 0x42
+    JP tB
+    LD V0, 1
+tB:
+    CALL report
+
+    ; C: SHR
+    LD V1, 0x84
+    SHR V1
+    LD V0, 0
+    SE V1, 0x42
+    JP tC
+    LD V0, 1
+tC:
+    CALL report
+
+    ; D: SHL
+    LD V1, 0x21
+    SHL V1
+    LD V0, 0
+    SE V1, 0x42
+    JP tD
+    LD V0, 1
+tD:
+    CALL report
+
+    ; E: LD B, VX
+    LD V1, 142
+    LD I, scratch
+    LD B, V1
+    LD I, scratch
+    LD V2, [I]
+    LD V3, 0
+    SE V0, 1
+    JP tE
+    SE V1, 4
+    JP tE
+    SE V2, 2
+    JP tE
+    LD V3, 1
+tE:
+    LD V0, V3
+    CALL report
+
+    ; F: LD [I], VX, LD VX, [I], and ADD I, VX
+    LD V0, 0x11
+    LD V1, 0x22
+    LD V2, 0x33
+    LD I, scratch
+    LD [I], V2
+    LD I, scratch
+    LD V1, 1
+    ADD I, V1
+    LD V0, [I]
+    LD V1, V0
+    LD V0, 0
+    SE V1, 0x22
+    JP tF
+    LD V0, 1
+tF:
+    CALL report
+
+end:
+    JP end
+
+; Draw the number of the test in VD when V0 is 1, leave a gap otherwise. Digits go 8 to a row
+; starting at (VC, VE).
+report:
+    SE V0, 1
+    JP report_next
+    LD F, VD
+    DRW VC, VE, 5
+report_next:
+    ADD VD, 1
+    ADD VC, 6
+    SE VC, 50
+    RET
+    LD VC, 2
+    ADD VE, 7
+    RET
+
+scratch: DB 0, 0, 0, 0
@@ -0,0 +1,127 @@
+; Two player Pong. 1 and 4 move the left paddle, C and D the right one, and the first to 9
+; points starts a new game. Exercises SKNP, the delay timer, LD F, and subroutines.
+start:
+    LD V6, 0        ; left score
+    LD V7, 0        ; right score
+    LD V4, 1        ; ball x direction, 1 or 0xFF
+    LD V0, 13       ; left paddle y
+    LD V1, 13       ; right paddle y
+
+serve:
+    LD V2, 32       ; ball x
+    LD V3, 16       ; ball y
+    LD V5, 1        ; ball y direction, 1 or 0xFF
+
+loop:
+    CLS
+    LD I, paddle
+    LD VA, 2
+    DRW VA, V0, 6
+    LD VA, 61
+    DRW VA, V1, 6
+    LD I, ball
+    DRW V2, V3, 1
+    LD F, V6
+    LD VA, 24
+    LD VB, 1
+    DRW VA, VB, 5
+    LD F, V7
+    LD VA, 36
+    DRW VA, VB, 5
+
+    LD VA, 3
+    LD DT, VA
+wait:
+    LD VA, DT
+    SE VA, 0
+    JP wait
+
+    LD VA, 0x1
+    SKNP VA
+    CALL left_up
+    LD VA, 0x4
+    SKNP VA
+    CALL left_down
+    LD VA, 0xC
+    SKNP VA
+    CALL right_up
+    LD VA, 0xD
+    SKNP VA
+    CALL right_down
+
+    ; Bounce off the top and bottom
+    SNE V3, 0
+    LD V5, 1
+    SNE V3, 31
+    LD V5, 0xFF
+
+    ; Bounce off the left paddle when the ball is next to it, within its 6 rows
+    SE V2, 3
+    JP right_paddle
+    LD VB, V3
+    SUB VB, V0      ; VF is 0 when the ball is above the paddle
+    SE VF, 1
+    JP right_paddle
+    LD VA, 6
+    SUB VB, VA      ; VF is 1 when the ball is below the paddle
+    SNE VF, 0
+    LD V4, 1
+
+right_paddle:
+    SE V2, 60
+    JP score
+    LD VB, V3
+    SUB VB, V1
+    SE VF, 1
+    JP score
+    LD VA, 6
+    SUB VB, VA
+    SNE VF, 0
+    LD V4, 0xFF
+
+score:
+    SNE V2, 0
+    JP right_scores
+    SNE V2, 63
+    JP left_scores
+
+    ADD V2, V4
+    ADD V3, V5
+    JP loop
+
+right_scores:
+    ADD V7, 1
+    LD V4, 1
+    SNE V7, 10
+    JP start
+    JP serve
+
+left_scores:
+    ADD V6, 1
+    LD V4, 0xFF
+    SNE V6, 10
+    JP start
+    JP serve
+
+left_up:
+    SE V0, 0
+    ADD V0, 0xFF
+    RET
+
+left_down:
+    SE V0, 26
+    ADD V0, 1
+    RET
+
+right_up:
+    SE V1, 0
+    ADD V1, 0xFF
+    RET
+
+right_down:
+    SE V1, 26
+    ADD V1, 1
+    RET
+
+paddle: DB 0x80, 0x80, 0x80, 0x80, 0x80, 0x80
+ball: DB 0x80
@@ -0,0 +1,82 @@
+; Detects which quirks are on and draws a digit for each of them in order, 1 when the quirk
+; is on and 0 when it's off: shift-vy, load-store-i, jump-vx, clip, and vf-reset.
+    CLS
+    LD VC, 2
+    LD VE, 2
+
+    ; shift-vy: 8XY6 shifts VY into VX rather than VX in place
+    LD V1, 0x10
+    LD V2, 0x04
+    SHR V1, V2
+    LD V0, 0
+    SNE V1, 0x02
+    LD V0, 1
+    CALL report
+
+    ; load-store-i: FX55 leaves I after the stored registers, so the second store doesn't
+    ; overwrite the first
+    LD I, scratch
+    LD V0, 0xAA
+    LD [I], V0
+    LD V0, 0xBB
+    LD [I], V0
+    LD I, scratch
+    LD V0, [I]
+    LD V1, V0
+    LD V0, 0
+    SNE V1, 0xAA
+    LD V0, 1
+    CALL report
+
+    ; jump-vx: BNNN is BXNN and adds V2 rather than V0, since the table is at 0x2XX
+    LD V0, 0
+    LD V2, 2
+    JP V0, jump_table
+jump_table:
+    JP jump_plain
+    JP jump_vx
+jump_plain:
+    LD V0, 0
+    JP jump_done
+jump_vx:
+    LD V0, 1
+jump_done:
+    CALL report
+
+    ; clip: a sprite drawn over the right edge doesn't wrap around to the left edge, so
+    ; drawing at the left edge afterwards doesn't collide
+    LD I, bar
+    LD V1, 60
+    LD V2, 28
+    DRW V1, V2, 1
+    LD V3, 0
+    DRW V3, V2, 1
+    LD V0, 0
+    SNE VF, 0
+    LD V0, 1
+    ; Erase both sprites again
+    DRW V1, V2, 1
+    DRW V3, V2, 1
+    CALL report
+
+    ; vf-reset: 8XY1 resets VF
+    LD VF, 5
+    LD V1, 0
+    OR V1, V1
+    LD V0, 0
+    SNE VF, 0
+    LD V0, 1
+    CALL report
+
+end:
+    JP end
+
+; Draw V0 at (VC, VE) and move along
+report:
+    LD F, V0
+    DRW VC, VE, 5
+    ADD VC, 6
+    RET
+
+bar: DB 0xFF
+scratch: DB 0, 0